@@ -1,21 +1,35 @@
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::doc_markdown, clippy::similar_names)]
 
-use std::{env, fs::File, path::PathBuf, sync::Arc};
+use std::{
+    env,
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::{format_err, Result};
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use figment::{
+    providers::{Env, Format, Yaml},
+    Figment,
+};
+use serde::Deserialize;
 
 use clowarden_core::{
-    cfg::Legacy,
+    cfg::{DependabotConfig, GitHubClient, Legacy, Organization},
     directory,
-    github::{GHApi, Source},
-    multierror,
+    github::{check_source_ref, GHApi, Source, SourceLocation},
+    multierror, sarif,
     services::{
         self,
         github::{
             self,
+            graph::AccessGraph,
             service::{Ctx, SvcApi},
+            state::{OrgSnapshot, Role},
             State,
         },
         Change,
@@ -25,6 +39,10 @@ use clowarden_core::{
 /// Environment variable containing Github token.
 const GITHUB_TOKEN: &str = "GITHUB_TOKEN";
 
+/// Environment variable containing the path of the GitHub Actions job summary
+/// file.
+const GITHUB_STEP_SUMMARY: &str = "GITHUB_STEP_SUMMARY";
+
 #[derive(Parser)]
 #[command(
     version,
@@ -32,39 +50,147 @@ const GITHUB_TOKEN: &str = "GITHUB_TOKEN";
 
 This tool uses the GitHub API, which requires authentication. Please make sure
 you provide a GitHub token (with repo and read:org scopes) by setting the
-GITHUB_TOKEN environment variable."
+GITHUB_TOKEN environment variable.
+
+The organization, configuration repository and branch can be provided using
+the command line flags, the CLOWARDEN_CLI_ORG, CLOWARDEN_CLI_REPO and
+CLOWARDEN_CLI_BRANCH environment variables or a config file (in that order of
+precedence)."
 )]
 struct Cli {
     #[command(subcommand)]
     command: Command,
+
+    /// Config file with default values for some of the arguments.
+    #[arg(long, global = true, default_value = ".clowarden-cli.yaml")]
+    config: PathBuf,
+}
+
+/// CLI configuration, used to provide default values for some arguments.
+#[derive(Debug, Default, Deserialize)]
+struct Config {
+    org: Option<String>,
+    repo: Option<String>,
+    branch: Option<String>,
+}
+
+impl Config {
+    /// Create a new Config instance from the config file and the environment.
+    /// Missing config files are ignored.
+    fn new(config_file: &Path) -> Result<Self> {
+        Figment::new()
+            .merge(Yaml::file(config_file))
+            .merge(Env::prefixed("CLOWARDEN_CLI_"))
+            .extract()
+            .map_err(Into::into)
+    }
 }
 
 #[derive(Subcommand)]
 enum Command {
     /// Display changes between the actual state (as defined in the services)
     /// and the desired state (as defined in the configuration).
-    Diff(BaseArgs),
+    Diff(DiffArgs),
+
+    /// Check if the changes between the actual state and the desired state
+    /// could be applied, without applying them.
+    DryApply(BaseArgs),
 
     /// Generate configuration file from the actual state (experimental).
     Generate(GenerateArgs),
 
+    /// Export the access relationships (users -> teams -> repositories, with
+    /// their roles) as a graph, i.e. to render an access map.
+    Graph(GraphArgs),
+
+    /// Suggest improvements to the configuration, using the actual state as
+    /// reference.
+    Lint(LintArgs),
+
+    /// Compare the changes CLOWarden would apply with the ones expected from
+    /// the Sheriff configuration (useful when migrating from Sheriff).
+    Shadow(ShadowArgs),
+
+    /// Take a snapshot of the actual state of the organization, that can be
+    /// used later to validate the configuration offline.
+    Snapshot(SnapshotArgs),
+
     /// Validate the configuration in the repository provided.
-    Validate(BaseArgs),
+    Validate(ValidateArgs),
+
+    /// Validate only the people file in the repository provided.
+    ValidatePeople(PeopleArgs),
 }
 
 #[derive(Args)]
-struct BaseArgs {
+struct TargetArgs {
     /// GitHub organization.
     #[arg(long)]
-    org: String,
+    org: Option<String>,
 
     /// Configuration repository.
     #[arg(long)]
-    repo: String,
+    repo: Option<String>,
 
     /// Configuration repository branch.
     #[arg(long)]
+    branch: Option<String>,
+
+    /// Local directory to read the configuration files from (instead of the
+    /// configuration repository).
+    #[arg(long, conflicts_with = "config_url")]
+    config_path: Option<PathBuf>,
+
+    /// Base url to read the configuration files from (instead of the
+    /// configuration repository), i.e. the raw files url of a GitLab project.
+    #[arg(long)]
+    config_url: Option<String>,
+}
+
+impl TargetArgs {
+    /// Set the values not provided from the command line using the ones
+    /// available in the config.
+    fn apply_config(&mut self, cfg: &Config) {
+        self.org = self.org.take().or_else(|| cfg.org.clone());
+        self.repo = self.repo.take().or_else(|| cfg.repo.clone());
+        self.branch = self.branch.take().or_else(|| cfg.branch.clone());
+    }
+
+    /// Return the target, making sure all the values have been provided.
+    fn target(&self) -> Result<Target> {
+        let required = |value: Option<&str>, name: &str| {
+            value.map(ToString::to_string).ok_or_else(|| {
+                format_err!(
+                    "{name} not provided (use --{name}, CLOWARDEN_CLI_{} or the config file)",
+                    name.to_uppercase()
+                )
+            })
+        };
+        Ok(Target {
+            org: required(self.org.as_deref(), "org")?,
+            repo: required(self.repo.as_deref(), "repo")?,
+            branch: required(self.branch.as_deref(), "branch")?,
+            location: match (&self.config_path, &self.config_url) {
+                (Some(path), _) => Some(SourceLocation::LocalPath(path.clone())),
+                (None, Some(url)) => Some(SourceLocation::Url(url.clone())),
+                (None, None) => None,
+            },
+        })
+    }
+}
+
+/// Organization and configuration repository the command operates on.
+struct Target {
+    org: String,
+    repo: String,
     branch: String,
+    location: Option<SourceLocation>,
+}
+
+#[derive(Args)]
+struct BaseArgs {
+    #[command(flatten)]
+    target: TargetArgs,
 
     /// Permissions file.
     #[arg(long, default_value = "config.yaml")]
@@ -73,13 +199,156 @@ struct BaseArgs {
     /// People file.
     #[arg(long)]
     people_file: Option<String>,
+
+    /// Role assigned to teams listed in repositories without an explicit role.
+    #[arg(long)]
+    default_team_role: Option<Role>,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    #[command(flatten)]
+    base: BaseArgs,
+
+    /// Write the diff to the GitHub Actions job summary as well (only when
+    /// running in GitHub Actions).
+    #[arg(long)]
+    github_summary: bool,
+
+    /// Report branches merged into the default branch that haven't been
+    /// updated in this number of days (branches are never deleted).
+    #[arg(long)]
+    stale_branches_max_age_days: Option<u32>,
+
+    /// Report the repositories missing a Dependabot configuration file (as
+    /// the changes needed to add a default one to them).
+    #[arg(long)]
+    dependabot_config: bool,
+
+    /// Display only the changes of this kind (i.e. repository-visibility-updated).
+    /// It can be used multiple times to display changes of several kinds.
+    #[arg(long)]
+    kind: Vec<String>,
+
+    /// Display one line per change (kind and entities involved) instead of
+    /// the detailed description of each change.
+    #[arg(long)]
+    compact: bool,
+
+    /// Display the values compared to compute each change (i.e. to find out
+    /// why a change keeps being detected).
+    #[arg(long)]
+    explain: bool,
+
+    /// Write the high risk changes found as a SARIF report to this file as
+    /// well (i.e. to upload them to GitHub code scanning).
+    #[arg(long)]
+    sarif: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ValidateArgs {
+    #[command(flatten)]
+    base: BaseArgs,
+
+    /// Write the configuration errors found as a SARIF report to this file as
+    /// well (i.e. to upload them to GitHub code scanning).
+    #[arg(long)]
+    sarif: Option<PathBuf>,
+
+    /// Organization snapshot file (taken with the snapshot command) to use
+    /// instead of getting the actual state from GitHub. When the configuration
+    /// is read from a local directory as well, no GitHub token is required.
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct LintArgs {
+    #[command(flatten)]
+    base: BaseArgs,
+
+    /// Exit with an error when some suggestions are found.
+    #[arg(long)]
+    strict: bool,
+}
+
+#[derive(Args)]
+struct ShadowArgs {
+    #[command(flatten)]
+    base: BaseArgs,
+
+    /// Exit with an error when some divergences are found.
+    #[arg(long)]
+    strict: bool,
+}
+
+#[derive(Args)]
+struct PeopleArgs {
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// People file.
+    #[arg(long)]
+    people_file: String,
+
+    /// Check that the users' GitHub accounts exist (one API call per user).
+    #[arg(long)]
+    check_accounts: bool,
+}
+
+#[derive(Args)]
+struct GraphArgs {
+    #[command(flatten)]
+    base: BaseArgs,
+
+    /// State the graph is built from.
+    #[arg(long, value_enum, default_value_t = GraphState::Desired)]
+    state: GraphState,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+    format: GraphFormat,
+
+    /// Output file.
+    #[arg(long)]
+    output_file: PathBuf,
+}
+
+/// State the access graph is built from.
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphState {
+    /// As defined in the services.
+    Actual,
+    /// As defined in the configuration.
+    Desired,
+}
+
+/// Format of the access graph.
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    /// Graphviz DOT graph.
+    Dot,
+    /// JSON list of nodes and edges.
+    Json,
+}
+
+#[derive(Args)]
+struct SnapshotArgs {
+    /// GitHub organization.
+    #[arg(long)]
+    org: Option<String>,
+
+    /// Output file.
+    #[arg(long)]
+    output_file: PathBuf,
 }
 
 #[derive(Args)]
 struct GenerateArgs {
     /// GitHub organization.
     #[arg(long)]
-    org: String,
+    org: Option<String>,
 
     /// Output file.
     #[arg(long)]
@@ -88,7 +357,7 @@ struct GenerateArgs {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
     // Setup logging
     if std::env::var_os("RUST_LOG").is_none() {
@@ -96,50 +365,179 @@ async fn main() -> Result<()> {
     }
     tracing_subscriber::fmt::init();
 
-    // Check if required Github token is present in environment
+    // Check if required Github token is present in environment (it is not
+    // needed when validating a local configuration against a snapshot)
+    let offline = matches!(
+        &cli.command,
+        Command::Validate(args) if args.snapshot.is_some() && args.base.target.config_path.is_some()
+    );
     let github_token = match env::var(GITHUB_TOKEN) {
+        Err(_) if offline => String::new(),
         Err(_) => return Err(format_err!("{} not found in environment", GITHUB_TOKEN)),
         Ok(token) => token,
     };
 
+    // Apply default values from the config file and the environment
+    let cfg = Config::new(&cli.config)?;
+    match &mut cli.command {
+        Command::Diff(args) => args.base.target.apply_config(&cfg),
+        Command::Lint(args) => args.base.target.apply_config(&cfg),
+        Command::Shadow(args) => args.base.target.apply_config(&cfg),
+        Command::Snapshot(args) => args.org = args.org.take().or_else(|| cfg.org.clone()),
+        Command::Validate(args) => args.base.target.apply_config(&cfg),
+        Command::DryApply(args) => args.target.apply_config(&cfg),
+        Command::Generate(args) => args.org = args.org.take().or_else(|| cfg.org.clone()),
+        Command::Graph(args) => args.base.target.apply_config(&cfg),
+        Command::ValidatePeople(args) => args.target.apply_config(&cfg),
+    }
+
     // Run command
     match cli.command {
         Command::Diff(args) => diff(args, github_token).await?,
+        Command::DryApply(args) => dry_apply(args, github_token).await?,
         Command::Validate(args) => validate(args, github_token).await?,
         Command::Generate(args) => generate(args, github_token).await?,
+        Command::Graph(args) => graph(args, github_token).await?,
+        Command::Lint(args) => lint(args, github_token).await?,
+        Command::Shadow(args) => shadow(args, github_token).await?,
+        Command::Snapshot(args) => snapshot(args, github_token).await?,
+        Command::ValidatePeople(args) => validate_people(args, github_token).await?,
     }
 
     Ok(())
 }
 
 /// Get changes between the actual state (service) and desired state (config).
-async fn diff(args: BaseArgs, github_token: String) -> Result<()> {
+async fn diff(args: DiffArgs, github_token: String) -> Result<()> {
     // GitHub
 
     // Setup services
-    let (gh, svc) = setup_services(github_token);
-    let legacy = setup_legacy(&args);
-    let ctx = setup_context(&args.org);
-    let src = setup_source(&args);
+    let (gh, svc) = setup_services(github_token)?;
+    let target = args.base.target.target()?;
+    let mut org = setup_organization(&args.base, &target);
+    if args.dependabot_config {
+        org.dependabot_config = Some(DependabotConfig { enforce: true });
+    }
+    let ctx = setup_context(&target.org);
+    let src = setup_source(&gh, &target).await?;
 
     // Get changes from the actual state to the desired state
     println!("Calculating diff between the actual state and the desired state...");
-    let actual_state = State::new_from_service(svc.clone(), &ctx).await?;
-    let desired_state = State::new_from_config(gh, svc, &legacy, &ctx, &src).await?;
-    let changes = actual_state.diff(&desired_state);
+    let handler = github::Handler::new(gh.clone(), svc.clone());
+    let (_, _, changes) = handler.get_explained_changes(&org, &ctx, &src, None).await?;
+
+    // Display changes (only the ones of the kinds requested, if any)
+    let mut output = String::new();
+    writeln!(output, "\n# GitHub")?;
+    writeln!(output, "\n## Directory changes\n")?;
+    for (change, explanation) in changes.directory.iter().filter(|(c, _)| kind_selected(&args.kind, c)) {
+        writeln!(output, "{}", format_change(change, args.compact)?)?;
+        if args.explain {
+            writeln!(output, "\t- {explanation}")?;
+        }
+    }
+    writeln!(output, "\n## Repositories changes\n")?;
+    for (change, explanation) in changes.repositories.iter().filter(|(c, _)| kind_selected(&args.kind, c)) {
+        writeln!(output, "{}", format_change(change, args.compact)?)?;
+        if args.explain {
+            writeln!(output, "\t- {explanation}")?;
+        }
+    }
+    if let Some(max_age_days) = args.stale_branches_max_age_days {
+        writeln!(output, "\n## Stale branches\n")?;
+        let desired_state = State::new_from_config(gh, svc.clone(), &org, &ctx, &src).await?;
+        for warning in desired_state.stale_branches_warnings(svc, &ctx, max_age_days).await? {
+            writeln!(output, "- {warning}")?;
+        }
+    }
+    println!("{output}");
+
+    // Write changes to the GitHub Actions job summary if requested
+    if args.github_summary {
+        write_github_summary(&output)?;
+    }
 
-    // Display changes
-    println!("\n# GitHub");
-    println!("\n## Directory changes\n");
-    for change in changes.directory {
-        println!("{}", change.template_format()?);
+    // Write high risk changes to the SARIF report if requested
+    if let Some(path) = &args.sarif {
+        let mut report = sarif::Report::new(Some(args.base.permissions_file.clone()));
+        for (change, _) in changes.directory.iter().filter(|(c, _)| kind_selected(&args.kind, c)) {
+            report.add_change(change)?;
+        }
+        for (change, _) in changes.repositories.iter().filter(|(c, _)| kind_selected(&args.kind, c)) {
+            report.add_change(change)?;
+        }
+        write_sarif_report(path, &report)?;
     }
-    println!("\n## Repositories changes\n");
-    for change in changes.repositories {
-        println!("{}", change.template_format()?);
+
+    Ok(())
+}
+
+/// Check if the change provided is of one of the kinds selected. All changes
+/// are selected when no kinds are provided.
+fn kind_selected(kinds: &[String], change: &dyn Change) -> bool {
+    kinds.is_empty() || kinds.contains(&change.details().kind)
+}
+
+/// Format the change provided for display. In compact mode, changes are
+/// displayed in a single line including their kind and the entities involved
+/// (the keywords of the change that are not part of the kind).
+fn format_change(change: &dyn Change, compact: bool) -> Result<String> {
+    if !compact {
+        return change.template_format();
+    }
+    let kind = change.details().kind;
+    let kind_words: Vec<&str> = kind.split('-').collect();
+    let entities: Vec<&str> = change.keywords().into_iter().filter(|k| !kind_words.contains(k)).collect();
+    Ok(format!("- {kind} {}", entities.join(" ")).trim_end().to_string())
+}
+
+/// Append the content provided to the GitHub Actions job summary. Nothing is
+/// written when not running in GitHub Actions.
+fn write_github_summary(content: &str) -> Result<()> {
+    let Some(path) = env::var_os(GITHUB_STEP_SUMMARY) else {
+        println!("{GITHUB_STEP_SUMMARY} not found in environment, skipping GitHub job summary");
+        return Ok(());
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{content}")?;
+    Ok(())
+}
+
+/// Check if the changes between the actual state (service) and the desired
+/// state (config) could be applied, without applying any of them.
+async fn dry_apply(args: BaseArgs, github_token: String) -> Result<()> {
+    // GitHub
+
+    // Setup services
+    let (gh, svc) = setup_services(github_token)?;
+    let target = args.target.target()?;
+    let org = setup_organization(&args, &target);
+    let ctx = setup_context(&target.org);
+    let src = setup_source(&gh, &target).await?;
+    let handler = github::Handler::new(gh, svc);
+
+    // Check changes (nothing will be applied)
+    println!("Checking changes between the actual state and the desired state (dry apply)...");
+    let changes_checked = handler.dry_apply(&org, &ctx, &src).await?;
+
+    // Display results
+    println!("\n# GitHub (dry apply, no changes have been applied)\n");
+    let mut errors_found = false;
+    for entry in changes_checked {
+        println!("{}", entry.change.template_format()?);
+        match entry.error {
+            Some(err) => {
+                errors_found = true;
+                println!("\t- dry apply: *would fail* ({err})");
+            }
+            None => println!("\t- dry apply: *ok*"),
+        }
     }
     println!();
 
+    if errors_found {
+        return Err(format_err!("Some changes could not be applied"));
+    }
     Ok(())
 }
 
@@ -155,8 +553,13 @@ async fn generate(args: GenerateArgs, github_token: String) -> Result<()> {
     }
 
     println!("Getting actual state from GitHub...");
-    let (_, svc) = setup_services(github_token);
-    let ctx = setup_context(&args.org);
+    let Some(org) = args.org else {
+        return Err(format_err!(
+            "org not provided (use --org, CLOWARDEN_CLI_ORG or the config file)"
+        ));
+    };
+    let (_, svc) = setup_services(github_token)?;
+    let ctx = setup_context(&org);
     let actual_state = github::State::new_from_service(svc.clone(), &ctx).await?;
 
     println!("Generating configuration file and writing it to the output file provided...");
@@ -171,43 +574,256 @@ async fn generate(args: GenerateArgs, github_token: String) -> Result<()> {
     Ok(())
 }
 
+/// Export the access relationships defined in the actual or desired state as
+/// a graph. Nothing is modified in the services.
+async fn graph(args: GraphArgs, github_token: String) -> Result<()> {
+    // Setup services
+    let (gh, svc) = setup_services(github_token)?;
+    let target = args.base.target.target()?;
+    let org = setup_organization(&args.base, &target);
+    let ctx = setup_context(&target.org);
+
+    // Get the state requested
+    let state = match args.state {
+        GraphState::Actual => {
+            println!("Getting actual state from GitHub...");
+            State::new_from_service(svc, &ctx).await?
+        }
+        GraphState::Desired => {
+            println!("Getting desired state from the configuration...");
+            let src = setup_source(&gh, &target).await?;
+            State::new_from_config(gh, svc, &org, &ctx, &src).await?
+        }
+    };
+
+    // Build the access graph and write it to the output file provided
+    println!("Exporting access graph and writing it to the output file provided...");
+    let roles_ordering = org.roles_ordering.clone().unwrap_or_default();
+    let graph = AccessGraph::new(&state, &roles_ordering);
+    let output = match args.format {
+        GraphFormat::Dot => graph.to_dot()?,
+        GraphFormat::Json => serde_json::to_string_pretty(&graph)?,
+    };
+    fs::write(&args.output_file, output)?;
+
+    println!("done!");
+    Ok(())
+}
+
+/// Write the SARIF report provided to the file given.
+fn write_sarif_report(path: &Path, report: &sarif::Report) -> Result<()> {
+    fs::write(path, report.render()?)?;
+    println!("SARIF report written to {}", path.display());
+    Ok(())
+}
+
 /// Validate configuration.
-async fn validate(args: BaseArgs, github_token: String) -> Result<()> {
+async fn validate(args: ValidateArgs, github_token: String) -> Result<()> {
     // GitHub
 
     // Setup services
-    let (gh, svc) = setup_services(github_token);
-    let legacy = setup_legacy(&args);
-    let ctx = setup_context(&args.org);
-    let src = setup_source(&args);
-
-    // Validate configuration and display results
+    let (gh, svc) = setup_services(github_token)?;
+    let target = args.base.target.target()?;
+    let org = setup_organization(&args.base, &target);
+    let ctx = setup_context(&target.org);
+    let src = setup_source(&gh, &target).await?;
+    let snapshot = args.snapshot.as_deref().map(read_snapshot).transpose()?;
+
+    // Validate configuration and display results (a SARIF report is written
+    // even when no errors are found, so that previous findings are cleared).
+    // When a snapshot is provided, it is used instead of the actual state.
     println!("Validating configuration...");
-    match github::State::new_from_config(gh, svc, &legacy, &ctx, &src).await {
-        Ok(_) => println!("Configuration is valid!"),
+    let mut report = sarif::Report::new(Some(args.base.permissions_file.clone()));
+    let state = match &snapshot {
+        Some(snapshot) => State::new_from_config_offline(gh, &org, &src, snapshot).await,
+        None => State::new_from_config(gh, svc.clone(), &org, &ctx, &src).await,
+    };
+    let result = match state {
+        Ok(state) => {
+            println!("Configuration is valid!");
+            let warnings = match &snapshot {
+                Some(snapshot) => state.warnings_offline(snapshot),
+                None => state.warnings(svc, &ctx).await?,
+            };
+            for warning in warnings {
+                println!("Warning: {warning}");
+            }
+            Ok(())
+        }
         Err(err) => {
             println!("{}\n", multierror::format_error(&err)?);
-            return Err(format_err!("Invalid configuration"));
+            report.add_error(&err);
+            Err(format_err!("Invalid configuration"))
         }
+    };
+    if let Some(path) = &args.sarif {
+        write_sarif_report(path, &report)?;
     }
 
+    result
+}
+
+/// Suggest improvements to the configuration, using the actual state as
+/// reference.
+async fn lint(args: LintArgs, github_token: String) -> Result<()> {
+    // Setup services
+    let (gh, svc) = setup_services(github_token)?;
+    let target = args.base.target.target()?;
+    let org = setup_organization(&args.base, &target);
+    let ctx = setup_context(&target.org);
+    let src = setup_source(&gh, &target).await?;
+
+    // Get suggestions from the desired state using the actual one as reference
+    println!("Linting configuration...");
+    let actual_state = State::new_from_service(svc.clone(), &ctx).await?;
+    let desired_state = State::new_from_config(gh, svc, &org, &ctx, &src).await?;
+    let roles_ordering = org.roles_ordering.clone().unwrap_or_default();
+    let suggestions = desired_state.lint(&actual_state, &roles_ordering);
+
+    // Display suggestions
+    if suggestions.is_empty() {
+        println!("No suggestions found!");
+        return Ok(());
+    }
+    for (category, category_suggestions) in &suggestions {
+        println!("\n## {category}\n");
+        for suggestion in category_suggestions {
+            println!("- {suggestion}");
+        }
+    }
+    println!();
+
+    if args.strict {
+        return Err(format_err!("Some suggestions were found"));
+    }
     Ok(())
 }
 
-/// Helper function to setup some services from the arguments provided.
-fn setup_services(github_token: String) -> (Arc<GHApi>, Arc<SvcApi>) {
-    let gh = GHApi::new_with_token(github_token.clone());
-    let svc = services::github::service::SvcApi::new_with_token(github_token);
+/// Compare the changes CLOWarden would apply with the ones Sheriff would
+/// apply from the same configuration, reporting where they diverge.
+async fn shadow(args: ShadowArgs, github_token: String) -> Result<()> {
+    // Setup services
+    let (gh, svc) = setup_services(github_token)?;
+    let target = args.base.target.target()?;
+    let org = setup_organization(&args.base, &target);
+    let ctx = setup_context(&target.org);
+    let src = setup_source(&gh, &target).await?;
+
+    // Get changes from the actual state to the desired state, as computed by
+    // CLOWarden and as expected from the Sheriff configuration
+    println!("Comparing CLOWarden changes with the ones expected from the Sheriff configuration...");
+    let actual_state = State::new_from_service(svc.clone(), &ctx).await?;
+    let desired_state = State::new_from_config(gh.clone(), svc.clone(), &org, &ctx, &src).await?;
+    let sheriff_state = State::new_from_sheriff_config(gh, svc, &org, &ctx, &src).await?;
+    let changes = actual_state.diff(&desired_state);
+    let sheriff_changes = actual_state.diff(&sheriff_state);
+    let clowarden_only = changes.difference(&sheriff_changes);
+    let sheriff_only = sheriff_changes.difference(&changes);
+
+    // Display divergences
+    if clowarden_only.is_empty() && sheriff_only.is_empty() {
+        println!("No divergences found!");
+        return Ok(());
+    }
+    let mut output = String::new();
+    for (title, divergences) in [
+        ("Changes only applied by CLOWarden", clowarden_only),
+        ("Changes only expected by Sheriff", sheriff_only),
+    ] {
+        writeln!(output, "\n## {title}\n")?;
+        for change in divergences.directory {
+            writeln!(output, "{}", change.template_format()?)?;
+        }
+        for change in divergences.repositories {
+            writeln!(output, "{}", change.template_format()?)?;
+        }
+    }
+    println!("{output}");
+
+    if args.strict {
+        return Err(format_err!("Some divergences were found"));
+    }
+    Ok(())
+}
+
+/// Take a snapshot of the actual state of the organization and write it to
+/// the output file provided.
+async fn snapshot(args: SnapshotArgs, github_token: String) -> Result<()> {
+    println!("Getting actual state from GitHub...");
+    let Some(org) = args.org else {
+        return Err(format_err!(
+            "org not provided (use --org, CLOWARDEN_CLI_ORG or the config file)"
+        ));
+    };
+    let (_, svc) = setup_services(github_token)?;
+    let ctx = setup_context(&org);
+    let snapshot = OrgSnapshot::new(svc, &ctx).await?;
+
+    println!("Writing snapshot to the output file provided...");
+    let file = File::create(&args.output_file)?;
+    serde_json::to_writer_pretty(file, &snapshot)?;
+
+    println!("done!");
+    Ok(())
+}
 
-    (Arc::new(gh), Arc::new(svc))
+/// Read the organization snapshot from the file provided.
+fn read_snapshot(path: &Path) -> Result<OrgSnapshot> {
+    let file = File::open(path).map_err(|err| format_err!("error opening snapshot file: {err}"))?;
+    let snapshot =
+        serde_json::from_reader(file).map_err(|err| format_err!("error parsing snapshot file: {err}"))?;
+    Ok(snapshot)
 }
 
-/// Helper function to create a legacy config instance from the arguments.
-fn setup_legacy(args: &BaseArgs) -> Legacy {
-    Legacy {
+/// Validate people file.
+async fn validate_people(args: PeopleArgs, github_token: String) -> Result<()> {
+    // Setup services
+    let (gh, _) = setup_services(github_token)?;
+    let target = args.target.target()?;
+    let legacy = Legacy {
         enabled: true,
-        sheriff_permissions_path: args.permissions_file.clone(),
-        cncf_people_path: args.people_file.clone(),
+        cncf_people_path: Some(args.people_file.clone()),
+        ..Default::default()
+    };
+    let src = setup_source(&gh, &target).await?;
+
+    // Validate people file and display results
+    println!("Validating people file...");
+    match directory::Directory::validate_people(gh, &legacy, &src, args.check_accounts).await {
+        Ok(()) => println!("People file is valid!"),
+        Err(err) => {
+            println!("{}\n", multierror::format_error(&err)?);
+            return Err(format_err!("Invalid people file"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Helper function to setup some services from the arguments provided.
+fn setup_services(github_token: String) -> Result<(Arc<GHApi>, Arc<SvcApi>)> {
+    let gh_client = GitHubClient::default();
+    let gh = GHApi::new_with_token(github_token.clone(), &gh_client)?;
+    let svc = services::github::service::SvcApi::new_with_token(github_token, &gh_client)?;
+
+    Ok((Arc::new(gh), Arc::new(svc)))
+}
+
+/// Helper function to create an organization config instance from the
+/// arguments.
+fn setup_organization(args: &BaseArgs, target: &Target) -> Organization {
+    Organization {
+        name: target.org.clone(),
+        repository: target.repo.clone(),
+        branch: target.branch.clone(),
+        legacy: Legacy {
+            enabled: true,
+            sheriff_permissions_path: args.permissions_file.clone(),
+            cncf_people_path: args.people_file.clone(),
+            ..Default::default()
+        },
+        default_team_role: args.default_team_role.clone(),
+        ..Default::default()
     }
 }
 
@@ -219,12 +835,16 @@ fn setup_context(org: &str) -> Ctx {
     }
 }
 
-/// Helper function to create a source instance from the arguments.
-fn setup_source(args: &BaseArgs) -> Source {
-    Source {
+/// Helper function to create a source instance from the target provided,
+/// checking that the configured branch exists.
+async fn setup_source(gh: &GHApi, target: &Target) -> Result<Source> {
+    let src = Source {
         inst_id: None,
-        owner: args.org.clone(),
-        repo: args.repo.clone(),
-        ref_: args.branch.clone(),
-    }
+        owner: target.org.clone(),
+        repo: target.repo.clone(),
+        ref_: target.branch.clone(),
+        location: target.location.clone(),
+    };
+    check_source_ref(gh, &src).await?;
+    Ok(src)
 }
@@ -1,6 +1,12 @@
 //! This module defines some types that represent parts of the configuration.
 
+use std::{collections::HashMap, fmt, path::PathBuf};
+
+use anyhow::{format_err, Result};
 use serde::{Deserialize, Serialize};
+use time::{macros::format_description, OffsetDateTime, Time, UtcOffset};
+
+use crate::services::github::state::{BranchProtection, Role, RolesOrdering};
 
 /// GitHub application configuration.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
@@ -12,6 +18,49 @@ pub struct GitHubApp {
     pub webhook_secret_fallback: Option<String>,
 }
 
+/// GitHub API client configuration (timeouts are expressed in seconds).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all(deserialize = "camelCase"))]
+pub struct GitHubClient {
+    pub timeout: u64,
+    pub pool_idle_timeout: u64,
+    pub pool_max_idle_per_host: usize,
+    pub rate_limit_warning_threshold: i64,
+    pub creation_max_wait: u64,
+    /// Maximum number of jobs processed concurrently across all organizations
+    /// (they all share the same GitHub API rate limit). Unlimited when not set.
+    pub max_concurrent_jobs: Option<usize>,
+    /// Maximum number of services whose changes are summarized concurrently
+    /// when validating configuration changes proposed in a pull request.
+    pub validate_services_concurrency: usize,
+    /// Maximum number of times the validation of the configuration changes
+    /// proposed in a pull request is retried when it fails due to transient
+    /// GitHub errors.
+    pub validate_job_max_retries: usize,
+    /// Delay before the first validation retry (doubled on each retry).
+    pub validate_job_retry_backoff: u64,
+    /// Whether the services caches should be warmed up as the organizations
+    /// workers start.
+    pub warm_cache: bool,
+}
+
+impl Default for GitHubClient {
+    fn default() -> Self {
+        Self {
+            timeout: 30,
+            pool_idle_timeout: 90,
+            pool_max_idle_per_host: 10,
+            rate_limit_warning_threshold: 500,
+            creation_max_wait: 10,
+            max_concurrent_jobs: None,
+            validate_services_concurrency: 4,
+            validate_job_max_retries: 3,
+            validate_job_retry_backoff: 5,
+            warm_cache: false,
+        }
+    }
+}
+
 /// Organization configuration.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
@@ -20,7 +69,483 @@ pub struct Organization {
     pub installation_id: i64,
     pub repository: String,
     pub branch: String,
+    pub config_source: Option<ConfigSource>,
     pub legacy: Legacy,
+    pub default_team_role: Option<Role>,
+    pub max_team_members: Option<usize>,
+    pub max_team_members_exempt_teams: Option<Vec<String>>,
+    pub roles_ordering: Option<RolesOrdering>,
+    #[serde(default)]
+    pub disable_admins_promotion: bool,
+    pub high_risk_changes_reviewers: Option<Vec<String>>,
+    #[serde(default)]
+    pub revoke_unexpected_apps: bool,
+    #[serde(default)]
+    pub incremental_reconcile: bool,
+    #[serde(default)]
+    pub reparent_child_teams: bool,
+    pub retired_teams_prefix: Option<String>,
+    pub reconcile_window: Option<ReconcileWindow>,
+    #[serde(default)]
+    pub strip_default_labels: bool,
+    pub webhooks: Option<Vec<Webhook>>,
+    #[serde(default)]
+    pub remove_undeclared_webhooks: bool,
+    pub community_health_files: Option<Vec<CommunityHealthFile>>,
+    pub reconciliation_webhook: Option<ReconciliationWebhook>,
+    pub settings: Option<OrganizationSettings>,
+    #[serde(default)]
+    pub validate_codeowners: bool,
+    /// Whether the changes the pull request author may not be entitled to
+    /// make (like granting themselves more access) should make the
+    /// validation fail. They are only reported as warnings by default.
+    #[serde(default)]
+    pub enforce_pr_author_scope: bool,
+    pub required_workflows: Option<Vec<RequiredWorkflow>>,
+    pub secrets: Option<Vec<OrganizationSecret>>,
+    pub runner_groups: Option<Vec<OrganizationRunnerGroup>>,
+    pub stale_branches_max_age_days: Option<u32>,
+    /// Number of times a collaborator can decline a repository invitation
+    /// before CLOWarden stops inviting them again (a warning is logged
+    /// instead). Declined invitations are tracked in the state snapshot.
+    pub max_declined_invitations: Option<u32>,
+    /// Number of hours team members and repositories collaborators removals
+    /// are deferred (they are only applied if they are still needed once the
+    /// grace period has elapsed). Removals are applied immediately when zero.
+    #[serde(default)]
+    pub removals_grace_period_hours: u32,
+    pub forbidden_repository_names: Option<Vec<String>>,
+    /// Users that can be added as outside collaborators to the organization's
+    /// repositories. When set, adding collaborators that are not members of
+    /// the organization nor listed here makes the validation fail.
+    pub approved_outside_collaborators: Option<Vec<String>>,
+    #[serde(default)]
+    pub observe_only: bool,
+    pub default_branch_protection: Option<BranchProtection>,
+    pub service_accounts: Option<Vec<ServiceAccount>>,
+    pub verified_domains_gate: Option<VerifiedDomainsGate>,
+    pub blocked_users: Option<Vec<String>>,
+    #[serde(default)]
+    pub unblock_unlisted_users: bool,
+    pub confirmed_blocked_members: Option<Vec<String>>,
+    /// Teams that must have the security manager role in the organization
+    /// (read access to all repositories and their security alerts). When not
+    /// set, the security manager teams are not managed.
+    pub security_manager_teams: Option<Vec<String>>,
+    pub dependabot_config: Option<DependabotConfig>,
+    /// Pull request labels that exempt the changes proposed from some
+    /// validation checks. When a pull request has one of these labels, the
+    /// corresponding check failure is reported as a warning instead.
+    pub validation_exemptions: Option<Vec<ValidationExemption>>,
+}
+
+impl Organization {
+    /// Return the validation exemptions that apply to a pull request with
+    /// the labels provided.
+    #[must_use]
+    pub fn active_validation_exemptions(&self, pr_labels: &[String]) -> Vec<ValidationExemption> {
+        self.validation_exemptions
+            .iter()
+            .flatten()
+            .filter(|exemption| pr_labels.contains(&exemption.label))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Alternative location of the organization's configuration files. When not
+/// provided, they are read from the configuration repository's branch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub enum ConfigSource {
+    /// Release tag of the configuration repository to read the files from.
+    ReleaseTag(String),
+    /// Url of a tarball (.tar.gz) containing the configuration files. Paths
+    /// are relative to the root of the tarball (a single top level directory,
+    /// like the one in GitHub's source code archives, is supported as well).
+    TarballUrl(String),
+    /// Local directory containing the configuration files.
+    LocalPath(PathBuf),
+    /// Base url the configuration files paths are appended to (i.e. the raw
+    /// files url of a repository hosted outside GitHub).
+    Url(String),
+}
+
+/// Organization settings managed by CLOWarden. Only the settings provided are
+/// reconciled, the rest are left untouched.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct OrganizationSettings {
+    pub members_can_create_pages: Option<bool>,
+    pub members_can_create_private_repositories: Option<bool>,
+    pub members_can_create_public_repositories: Option<bool>,
+    pub members_can_fork_private_repositories: Option<bool>,
+    pub team_creation_permission: Option<TeamCreationPermission>,
+}
+
+impl OrganizationSettings {
+    /// Get the value of the setting provided (if set).
+    #[must_use]
+    pub fn get(&self, setting: OrganizationSetting) -> Option<bool> {
+        match setting {
+            OrganizationSetting::MembersCanCreatePages => self.members_can_create_pages,
+            OrganizationSetting::MembersCanCreatePrivateRepositories => {
+                self.members_can_create_private_repositories
+            }
+            OrganizationSetting::MembersCanCreatePublicRepositories => {
+                self.members_can_create_public_repositories
+            }
+            OrganizationSetting::MembersCanForkPrivateRepositories => {
+                self.members_can_fork_private_repositories
+            }
+        }
+    }
+
+    /// Set the value of the setting provided.
+    pub fn set(&mut self, setting: OrganizationSetting, value: Option<bool>) {
+        match setting {
+            OrganizationSetting::MembersCanCreatePages => self.members_can_create_pages = value,
+            OrganizationSetting::MembersCanCreatePrivateRepositories => {
+                self.members_can_create_private_repositories = value;
+            }
+            OrganizationSetting::MembersCanCreatePublicRepositories => {
+                self.members_can_create_public_repositories = value;
+            }
+            OrganizationSetting::MembersCanForkPrivateRepositories => {
+                self.members_can_fork_private_repositories = value;
+            }
+        }
+    }
+}
+
+/// Organization setting that can be managed by CLOWarden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrganizationSetting {
+    MembersCanCreatePages,
+    MembersCanCreatePrivateRepositories,
+    MembersCanCreatePublicRepositories,
+    MembersCanForkPrivateRepositories,
+}
+
+impl OrganizationSetting {
+    /// All the organization settings that can be managed.
+    pub const ALL: [OrganizationSetting; 4] = [
+        OrganizationSetting::MembersCanCreatePages,
+        OrganizationSetting::MembersCanCreatePrivateRepositories,
+        OrganizationSetting::MembersCanCreatePublicRepositories,
+        OrganizationSetting::MembersCanForkPrivateRepositories,
+    ];
+
+    /// Name of the setting in the GitHub API.
+    #[must_use]
+    pub fn api_name(self) -> &'static str {
+        match self {
+            OrganizationSetting::MembersCanCreatePages => "members_can_create_pages",
+            OrganizationSetting::MembersCanCreatePrivateRepositories => {
+                "members_can_create_private_repositories"
+            }
+            OrganizationSetting::MembersCanCreatePublicRepositories => {
+                "members_can_create_public_repositories"
+            }
+            OrganizationSetting::MembersCanForkPrivateRepositories => "members_can_fork_private_repositories",
+        }
+    }
+
+    /// Description of the setting, as displayed in the changes.
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            OrganizationSetting::MembersCanCreatePages => "members can create pages",
+            OrganizationSetting::MembersCanCreatePrivateRepositories => {
+                "members can create private repositories"
+            }
+            OrganizationSetting::MembersCanCreatePublicRepositories => {
+                "members can create public repositories"
+            }
+            OrganizationSetting::MembersCanForkPrivateRepositories => "members can fork private repositories",
+        }
+    }
+}
+
+/// Who can create teams in the organization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TeamCreationPermission {
+    AllMembers,
+    Owners,
+}
+
+impl TeamCreationPermission {
+    /// Check if organization members (and not only owners) can create teams.
+    #[must_use]
+    pub fn members_can_create_teams(self) -> bool {
+        self == TeamCreationPermission::AllMembers
+    }
+}
+
+impl From<bool> for TeamCreationPermission {
+    fn from(members_can_create_teams: bool) -> Self {
+        if members_can_create_teams {
+            TeamCreationPermission::AllMembers
+        } else {
+            TeamCreationPermission::Owners
+        }
+    }
+}
+
+impl fmt::Display for TeamCreationPermission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TeamCreationPermission::AllMembers => write!(f, "all members"),
+            TeamCreationPermission::Owners => write!(f, "owners only"),
+        }
+    }
+}
+
+/// Community health file kept in the organization's .github repository.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct CommunityHealthFile {
+    /// Path of the file in the .github repository.
+    pub path: String,
+    /// Path of the file with the expected content in the configuration
+    /// repository.
+    pub source: String,
+}
+
+/// Window of time during which periodic reconciliations are allowed to run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ReconcileWindow {
+    /// Start time (HH:MM, inclusive).
+    pub start: String,
+    /// End time (HH:MM, exclusive). When it's earlier than the start time,
+    /// the window spans midnight.
+    pub end: String,
+    /// UTC offset start and end times are expressed in (i.e. +02:00). UTC is
+    /// used when not provided.
+    pub utc_offset: Option<String>,
+}
+
+impl ReconcileWindow {
+    /// Check if the moment provided is within the window.
+    pub fn contains(&self, moment: OffsetDateTime) -> Result<bool> {
+        let time_format = format_description!("[hour]:[minute]");
+        let utc_offset_format = format_description!("[offset_hour sign:mandatory]:[offset_minute]");
+        let start = Time::parse(&self.start, time_format)
+            .map_err(|err| format_err!("invalid reconcile window start time: {err}"))?;
+        let end = Time::parse(&self.end, time_format)
+            .map_err(|err| format_err!("invalid reconcile window end time: {err}"))?;
+        let utc_offset = match &self.utc_offset {
+            Some(utc_offset) => UtcOffset::parse(utc_offset, utc_offset_format)
+                .map_err(|err| format_err!("invalid reconcile window utc offset: {err}"))?,
+            None => UtcOffset::UTC,
+        };
+
+        let time = moment.to_offset(utc_offset).time();
+        if start <= end {
+            Ok(time >= start && time < end)
+        } else {
+            Ok(time >= start || time < end)
+        }
+    }
+}
+
+/// Organization webhook configuration. Webhooks are identified by their url.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct Webhook {
+    pub url: String,
+    pub events: Vec<String>,
+    /// Payloads content type (json | form). Defaults to json.
+    pub content_type: Option<String>,
+    pub secret: Option<String>,
+    /// Whether the webhook is active or not. Defaults to true.
+    pub active: Option<bool>,
+}
+
+impl Webhook {
+    /// Return the webhook's payloads content type.
+    #[must_use]
+    pub fn content_type(&self) -> &str {
+        self.content_type.as_deref().unwrap_or("json")
+    }
+
+    /// Check if the webhook is active.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.active.unwrap_or(true)
+    }
+}
+
+// The webhook's secret must never be logged.
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("url", &self.url)
+            .field("events", &self.events)
+            .field("content_type", &self.content_type)
+            .field("secret", &self.secret.as_ref().map(|_| "********"))
+            .field("active", &self.active)
+            .finish()
+    }
+}
+
+/// Outbound webhook notified after each reconciliation with the changes
+/// applied. Payloads are signed using the secret provided (if any).
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ReconciliationWebhook {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+// The webhook's secret must never be logged.
+impl fmt::Debug for ReconciliationWebhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReconciliationWebhook")
+            .field("url", &self.url)
+            .field("secret", &self.secret.as_ref().map(|_| "********"))
+            .finish()
+    }
+}
+
+/// Workflow that must run in the organization's repositories. Required
+/// workflows are enforced using organization rulesets, and are identified by
+/// the repository and path of the workflow file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct RequiredWorkflow {
+    /// Repository of the organization where the workflow file is located.
+    pub repository: String,
+    /// Path of the workflow file (i.e. .github/workflows/ci.yml).
+    pub path: String,
+    /// Branch, tag or commit of the workflow file to use. Defaults to the
+    /// repository's default branch.
+    #[serde(rename = "ref")]
+    pub ref_: Option<String>,
+    /// Repositories where the workflow is required. Defaults to all.
+    pub repositories: Option<Vec<String>>,
+}
+
+impl RequiredWorkflow {
+    /// Return the name that identifies the required workflow.
+    #[must_use]
+    pub fn name(&self) -> String {
+        format!("{}/{}", self.repository, self.path)
+    }
+
+    /// Check if the required workflow provided is equivalent to this one (the
+    /// order of the repositories is not relevant).
+    #[must_use]
+    pub fn is_equivalent(&self, other: &RequiredWorkflow) -> bool {
+        let sorted = |repositories: &Option<Vec<String>>| {
+            repositories.clone().map(|mut repositories| {
+                repositories.sort();
+                repositories.dedup();
+                repositories
+            })
+        };
+        self.repository == other.repository
+            && self.path == other.path
+            && self.ref_ == other.ref_
+            && sorted(&self.repositories) == sorted(&other.repositories)
+    }
+}
+
+/// Organization Actions secret whose selected repositories are managed by
+/// CLOWarden. Secrets values are never handled, so the secret must already
+/// exist in the organization with the selected repositories visibility.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct OrganizationSecret {
+    pub name: String,
+    /// Repositories that can access the secret.
+    #[serde(default)]
+    pub repositories: Vec<String>,
+}
+
+/// Organization self-hosted runner group whose repositories access is managed
+/// by CLOWarden. Runner groups that do not exist yet are created, using the
+/// selected repositories visibility. Runners (and their registration tokens)
+/// are never handled, so they must be registered in the group separately.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct OrganizationRunnerGroup {
+    pub name: String,
+    /// Repositories that can use the runners in the group.
+    #[serde(default)]
+    pub repositories: Vec<String>,
+}
+
+/// Service account (i.e. bot) managed by CLOWarden. Service accounts are
+/// always regular members of the organization (never admins) and belong to
+/// the teams listed. They are not part of the people directory, so they don't
+/// need a profile in the people file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ServiceAccount {
+    /// GitHub login of the service account.
+    pub login: String,
+    /// Teams the service account must be a member of.
+    #[serde(default)]
+    pub teams: Vec<String>,
+    /// Token of the service account. It's only needed to manage the
+    /// repositories it watches, as subscriptions can only be updated by the
+    /// users themselves.
+    pub token: Option<String>,
+}
+
+/// Organization's verified domains gate. When enabled, the organization's
+/// email notifications (team ones included) are only delivered to members
+/// using an email address in one of the organization's verified domains.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct VerifiedDomainsGate {
+    pub enabled: bool,
+    /// Domains that must be verified in the organization before enabling
+    /// the gate.
+    #[serde(default)]
+    pub required_domains: Vec<String>,
+}
+
+/// Organization policy about the Dependabot configuration file of the
+/// repositories. Repositories missing it are reported, and a default one is
+/// added to them when the policy is enforced.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct DependabotConfig {
+    #[serde(default)]
+    pub enforce: bool,
+}
+
+/// Pull request label that exempts the changes proposed from a validation
+/// check.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct ValidationExemption {
+    pub label: String,
+    pub check: ValidationCheck,
+}
+
+/// Validation checks that can be exempted using a pull request label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValidationCheck {
+    /// Outside collaborators not approved in the organization added.
+    OutsideCollaboratorNotApproved,
+    /// Changes the pull request author may not be entitled to make.
+    PrAuthorScope,
+}
+
+impl fmt::Display for ValidationCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationCheck::OutsideCollaboratorNotApproved => write!(f, "outside-collaborator-not-approved"),
+            ValidationCheck::PrAuthorScope => write!(f, "pr-author-scope"),
+        }
+    }
 }
 
 /// Organization legacy configuration.
@@ -30,8 +555,42 @@ pub struct Legacy {
     pub enabled: bool,
     pub sheriff_permissions_path: String,
     pub cncf_people_path: Option<String>,
+    /// Whether the people file is optional. When enabled, errors getting or
+    /// parsing it are logged as warnings and teams are loaded without users.
+    #[serde(default)]
+    pub cncf_people_optional: bool,
+    /// Custom keys used in the people file, mapped by the user profile field
+    /// they represent (i.e. full_name: name). Fields not listed here must use
+    /// the key names defined in the CNCF people file format.
+    #[serde(default)]
+    pub cncf_people_fields: HashMap<String, String>,
+    /// Custom role names used in the Sheriff's permissions file (i.e. push),
+    /// mapped to the role they represent.
+    #[serde(default)]
+    pub role_aliases: HashMap<String, Role>,
+    /// Whether the notice recommending migrating from the legacy
+    /// configuration format should be silenced.
+    #[serde(default)]
+    pub silence_deprecation_notice: bool,
 }
 
+impl Legacy {
+    /// Returns the notice recommending migrating from the legacy
+    /// configuration format, when it is used and it has not been silenced.
+    #[must_use]
+    pub fn deprecation_notice(&self) -> Option<&'static str> {
+        if self.enabled && !self.silence_deprecation_notice {
+            return Some(LEGACY_FORMAT_DEPRECATION_NOTICE);
+        }
+        None
+    }
+}
+
+/// Notice displayed when the legacy configuration format is used.
+pub const LEGACY_FORMAT_DEPRECATION_NOTICE: &str = "This organization uses the legacy (Sheriff) \
+    configuration format, which is deprecated. Please consider migrating to the native format (see the \
+    [documentation](https://github.com/cncf/clowarden) for more details).";
+
 /// Services configuration.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Services {
@@ -43,3 +602,88 @@ pub struct Services {
 pub struct Service {
     pub enabled: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn window(start: &str, end: &str, utc_offset: Option<&str>) -> ReconcileWindow {
+        ReconcileWindow {
+            start: start.to_string(),
+            end: end.to_string(),
+            utc_offset: utc_offset.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn reconcile_window_boundaries() {
+        let window = window("22:00", "23:30", None);
+        assert!(!window.contains(datetime!(2024-01-01 21:59 UTC)).unwrap());
+        assert!(window.contains(datetime!(2024-01-01 22:00 UTC)).unwrap());
+        assert!(window.contains(datetime!(2024-01-01 23:29 UTC)).unwrap());
+        assert!(!window.contains(datetime!(2024-01-01 23:30 UTC)).unwrap());
+    }
+
+    #[test]
+    fn reconcile_window_spanning_midnight() {
+        let window = window("22:00", "06:00", None);
+        assert!(window.contains(datetime!(2024-01-01 23:00 UTC)).unwrap());
+        assert!(window.contains(datetime!(2024-01-02 00:00 UTC)).unwrap());
+        assert!(window.contains(datetime!(2024-01-02 05:59 UTC)).unwrap());
+        assert!(!window.contains(datetime!(2024-01-02 06:00 UTC)).unwrap());
+        assert!(!window.contains(datetime!(2024-01-02 12:00 UTC)).unwrap());
+    }
+
+    #[test]
+    fn reconcile_window_with_utc_offset() {
+        let window = window("22:00", "06:00", Some("+02:00"));
+        assert!(!window.contains(datetime!(2024-01-01 19:59 UTC)).unwrap());
+        assert!(window.contains(datetime!(2024-01-01 20:00 UTC)).unwrap());
+        assert!(window.contains(datetime!(2024-01-02 03:59 UTC)).unwrap());
+        assert!(!window.contains(datetime!(2024-01-02 04:00 UTC)).unwrap());
+    }
+
+    #[test]
+    fn reconcile_window_invalid_time() {
+        let window = window("25:00", "06:00", None);
+        assert!(window.contains(datetime!(2024-01-01 00:00 UTC)).is_err());
+    }
+
+    #[test]
+    fn legacy_deprecation_notice() {
+        let legacy = Legacy {
+            enabled: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            legacy.deprecation_notice(),
+            Some(LEGACY_FORMAT_DEPRECATION_NOTICE)
+        );
+
+        let legacy = Legacy {
+            enabled: true,
+            silence_deprecation_notice: true,
+            ..Default::default()
+        };
+        assert_eq!(legacy.deprecation_notice(), None);
+
+        assert_eq!(Legacy::default().deprecation_notice(), None);
+    }
+
+    #[test]
+    fn organization_settings_team_creation_permission() {
+        let settings: OrganizationSettings = serde_yaml::from_str("teamCreationPermission: owners").unwrap();
+        assert_eq!(
+            settings.team_creation_permission,
+            Some(TeamCreationPermission::Owners)
+        );
+    }
+
+    #[test]
+    fn organization_settings_team_creation_permission_invalid() {
+        let err = serde_yaml::from_str::<OrganizationSettings>("teamCreationPermission: admins").unwrap_err();
+        assert!(err.to_string().contains("expected `all_members` or `owners`"));
+    }
+}
@@ -2,10 +2,12 @@
 //! format (Sheriff's and CNCF's users). The directory module relies on this
 //! module to create new directory instances from the legacy configuration.
 
-use anyhow::Result;
+use anyhow::{format_err, Context, Error, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use tracing::warn;
 
 use crate::{
     cfg::Legacy,
@@ -16,6 +18,66 @@ use crate::{
 lazy_static! {
     pub(crate) static ref VALID_TEAM_NAME: Regex =
         Regex::new(r"^[a-z0-9\-]+$").expect("expr in VALID_TEAM_NAME to be valid");
+
+    /// Regular expression to match top level sections in a YAML file.
+    static ref YAML_SECTION: Regex =
+        Regex::new(r"^(?P<section>[a-zA-Z_]+):").expect("expr in YAML_SECTION to be valid");
+
+    /// Regular expression to match the name of list entries in a YAML file.
+    static ref YAML_ENTRY_NAME: Regex = Regex::new(r#"^(?P<indent>\s*)-\s+name:\s*["']?(?P<name>[^"'\s#]+)"#)
+        .expect("expr in YAML_ENTRY_NAME to be valid");
+}
+
+/// Configuration version assumed when the permissions file does not define
+/// one explicitly.
+pub(crate) const LEGACY_CONFIG_VERSION: &str = "legacy";
+
+/// Configuration versions supported by this version of CLOWarden.
+pub(crate) const SUPPORTED_CONFIG_VERSIONS: &[&str] = &[LEGACY_CONFIG_VERSION];
+
+/// Check that the configuration version defined in the top level `version`
+/// field of the permissions file provided is supported. Files that do not
+/// define a version are considered to use the legacy format. This check must
+/// be done before parsing the rest of the file, so that configurations
+/// written for a different format are not silently misinterpreted.
+pub(crate) fn validate_config_version(content: &str) -> Result<()> {
+    let value: Value = serde_yaml::from_str(content)
+        .map_err(Error::new)
+        .context("error parsing permissions file")?;
+    let version = match value.get("version") {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::String(version)) => version.clone(),
+        Some(Value::Number(version)) => version.to_string(),
+        Some(_) => return Err(format_err!("invalid configuration version: it must be a string")),
+    };
+    if SUPPORTED_CONFIG_VERSIONS.contains(&version.as_str()) {
+        return Ok(());
+    }
+    Err(format_err!(
+        "unsupported configuration version {version} (supported versions: {}): this configuration may \
+         have been written for a newer version of CLOWarden, please upgrade it or migrate the \
+         configuration to a supported version (the legacy format is used when no version is set)",
+        SUPPORTED_CONFIG_VERSIONS.join(", ")
+    ))
+}
+
+/// Check that the permissions file provided is not empty, defining at least
+/// some teams or repositories (or including other files). An empty permissions file (i.e. after a bad
+/// merge) must never be processed as an empty desired state, as that would
+/// remove all the resources managed by CLOWarden.
+pub(crate) fn validate_permissions_not_empty(content: &str) -> Result<()> {
+    let value: Value = serde_yaml::from_str(content)
+        .map_err(Error::new)
+        .context("error parsing permissions file")?;
+    let has_entries = |section: &str| {
+        value.get(section).and_then(Value::as_sequence).is_some_and(|entries| !entries.is_empty())
+    };
+    if !has_entries("teams") && !has_entries("repositories") && !has_entries("include") {
+        return Err(format_err!(
+            "permissions file is empty: it must define some teams or repositories"
+        ));
+    }
+    Ok(())
 }
 
 /// Legacy configuration.
@@ -40,8 +102,13 @@ impl Cfg {
         };
 
         // Get CNCF people configuration
-        let cncf = match cncf::Cfg::get(gh, src, legacy.cncf_people_path.as_deref()).await {
+        let people_path = legacy.cncf_people_path.as_deref();
+        let cncf = match cncf::Cfg::get(gh, src, people_path, &legacy.cncf_people_fields).await {
             Ok(cfg) => cfg,
+            Err(err) if legacy.cncf_people_optional => {
+                warn!(?err, "error getting optional people file, ignoring it");
+                None
+            }
             Err(err) => {
                 merr.push(err);
                 None
@@ -59,13 +126,19 @@ impl Cfg {
 }
 
 pub mod sheriff {
-    use super::VALID_TEAM_NAME;
+    use std::collections::{HashMap, HashSet};
+
+    use super::{
+        validate_config_version, validate_permissions_not_empty, VALID_TEAM_NAME, YAML_ENTRY_NAME,
+        YAML_SECTION,
+    };
     use crate::{
-        directory::{TeamName, UserName},
+        directory::{TeamName, TeamNotifications, UserName},
         github::{DynGH, Source},
         multierror::MultiError,
     };
     use anyhow::{format_err, Context, Error, Result};
+    use futures::future::{BoxFuture, FutureExt};
     use serde::{Deserialize, Serialize};
 
     /// Sheriff configuration.
@@ -80,11 +153,15 @@ pub mod sheriff {
         pub(crate) async fn get(gh: DynGH, src: &Source, path: &str) -> Result<Self> {
             // Fetch configuration file and parse it
             let content = gh.get_file_content(src, path).await.context("error getting permissions file")?;
+            validate_config_version(&content)?;
+            validate_permissions_not_empty(&content)?;
             let mut cfg: Cfg = serde_yaml::from_str(&content)
                 .map_err(Error::new)
                 .context("error parsing permissions file")?;
 
             // Process and validate configuration
+            cfg.process_includes(gh.clone(), src, path, &content).await?;
+            cfg.load_rosters(gh, src).await?;
             cfg.process_composite_teams();
             cfg.remove_duplicates();
             cfg.validate()?;
@@ -92,6 +169,63 @@ pub mod sheriff {
             Ok(cfg)
         }
 
+        /// Merge into the configuration the teams defined in the permissions
+        /// files included by it (recursively). Definitions in later files
+        /// override the ones of the teams with the same name defined earlier,
+        /// and the teams defined in the including file override all of them.
+        async fn process_includes(
+            &mut self,
+            gh: DynGH,
+            src: &Source,
+            path: &str,
+            content: &str,
+        ) -> Result<()> {
+            let mut teams = vec![];
+            let mut includes_path = vec![path.to_string()];
+            for include in parse_includes(content)? {
+                let included_teams =
+                    get_included_teams(gh.clone(), src, &include, &mut includes_path).await?;
+                override_teams(&mut teams, included_teams);
+            }
+            if !teams.is_empty() {
+                override_teams(&mut teams, std::mem::take(&mut self.teams));
+                self.teams = teams;
+            }
+            Ok(())
+        }
+
+        /// Extend team's members with the users listed in the roster file of
+        /// the team (if any). Roster files are read from the same source as
+        /// the permissions file.
+        async fn load_rosters(&mut self, gh: DynGH, src: &Source) -> Result<()> {
+            let mut merr = MultiError::new(None);
+
+            for team in &mut self.teams {
+                let Some(roster) = &team.roster else {
+                    continue;
+                };
+                let members = match gh.get_file_content(src, &roster.path).await {
+                    Ok(content) => parse_roster(&content, &roster.column),
+                    Err(err) => Err(err.context("error getting roster file")),
+                };
+                match members {
+                    Ok(members) => team.members.get_or_insert_with(Vec::new).extend(members),
+                    Err(err) => {
+                        merr.push(format_err!(
+                            "team[{}]: roster {}: {err:#}",
+                            team.name,
+                            roster.path
+                        ));
+                    }
+                }
+            }
+
+            if merr.contains_errors() {
+                return Err(merr.into());
+            }
+            Ok(())
+        }
+
         /// Extend team's maintainers and members with the maintainers and
         /// members of the teams listed in the formation field.
         fn process_composite_teams(&mut self) {
@@ -192,11 +326,214 @@ pub mod sheriff {
                 }
             }
 
+            // Teams formation must not contain cycles
+            for cycle in self.find_formation_cycles() {
+                merr.push(format_err!(
+                    "team[{}]: circular formation detected: {}",
+                    cycle[0],
+                    cycle.join(" -> ")
+                ));
+            }
+
             if merr.contains_errors() {
                 return Err(merr.into());
             }
             Ok(())
         }
+
+        /// Find the cycles in the graph defined by the teams' formation field.
+        /// Each cycle is returned as the path of teams that compose it, where
+        /// the first and last entries are the same team.
+        pub(crate) fn find_formation_cycles(&self) -> Vec<Vec<TeamName>> {
+            let graph: HashMap<&TeamName, &Vec<TeamName>> = self
+                .teams
+                .iter()
+                .filter_map(|t| t.formation.as_ref().map(|formation| (&t.name, formation)))
+                .collect();
+
+            let mut cycles = vec![];
+            let mut cycles_seen = HashSet::new();
+            let mut visited = HashSet::new();
+            for team in &self.teams {
+                let mut path = vec![];
+                find_cycles(
+                    &team.name,
+                    &graph,
+                    &mut path,
+                    &mut visited,
+                    &mut cycles,
+                    &mut cycles_seen,
+                );
+            }
+            cycles
+        }
+    }
+
+    /// Permissions files included by a permissions file (paths are relative
+    /// to the root of the configuration repository).
+    #[derive(Debug, Clone, Default, Deserialize)]
+    struct Includes {
+        #[serde(default)]
+        include: Vec<String>,
+    }
+
+    /// Parse the paths of the files included by the permissions file content
+    /// provided.
+    fn parse_includes(content: &str) -> Result<Vec<String>> {
+        let includes: Includes = serde_yaml::from_str(content)
+            .map_err(Error::new)
+            .context("error parsing include directive")?;
+        Ok(includes.include)
+    }
+
+    /// Get the teams defined in the included permissions file provided, as
+    /// well as the ones defined in the files it includes. The path of files
+    /// being included is used to detect circular includes.
+    fn get_included_teams<'a>(
+        gh: DynGH,
+        src: &'a Source,
+        path: &'a str,
+        includes_path: &'a mut Vec<String>,
+    ) -> BoxFuture<'a, Result<Vec<Team>>> {
+        async move {
+            if let Some(pos) = includes_path.iter().position(|p| p == path) {
+                let mut cycle = includes_path[pos..].to_vec();
+                cycle.push(path.to_string());
+                return Err(format_err!("circular include detected: {}", cycle.join(" -> ")));
+            }
+
+            // Fetch included file and parse it
+            let content = gh
+                .get_file_content(src, path)
+                .await
+                .context(format!("error getting included file {path}"))?;
+            validate_config_version(&content).context(format!("included file {path}"))?;
+            let cfg: Cfg = serde_yaml::from_str(&content)
+                .map_err(Error::new)
+                .context(format!("error parsing included file {path}"))?;
+
+            // Merge the teams defined in the files it includes
+            let mut teams = vec![];
+            includes_path.push(path.to_string());
+            for include in parse_includes(&content).context(format!("included file {path}"))? {
+                let included_teams = get_included_teams(gh.clone(), src, &include, includes_path).await?;
+                override_teams(&mut teams, included_teams);
+            }
+            includes_path.pop();
+            override_teams(&mut teams, cfg.teams);
+
+            Ok(teams)
+        }
+        .boxed()
+    }
+
+    /// Extend the teams provided with the overriding ones, replacing the
+    /// existing teams with the same name.
+    fn override_teams(teams: &mut Vec<Team>, overriding_teams: Vec<Team>) {
+        teams.retain(|t| !overriding_teams.iter().any(|ot| ot.name == t.name));
+        teams.extend(overriding_teams);
+    }
+
+    /// Depth-first traversal of the formation graph starting at the team
+    /// provided, collecting the cycles found along the way.
+    fn find_cycles<'a>(
+        team_name: &'a TeamName,
+        graph: &HashMap<&'a TeamName, &'a Vec<TeamName>>,
+        path: &mut Vec<&'a TeamName>,
+        visited: &mut HashSet<&'a TeamName>,
+        cycles: &mut Vec<Vec<TeamName>>,
+        cycles_seen: &mut HashSet<Vec<TeamName>>,
+    ) {
+        // Cycle found, register it unless it's been seen already (starting at
+        // a different team)
+        if let Some(pos) = path.iter().position(|t| *t == team_name) {
+            let mut cycle: Vec<TeamName> = path[pos..].iter().map(|t| (*t).clone()).collect();
+            let mut key = cycle.clone();
+            let min_pos = key.iter().enumerate().min_by_key(|(_, t)| *t).map_or(0, |(i, _)| i);
+            key.rotate_left(min_pos);
+            if cycles_seen.insert(key) {
+                cycle.push(team_name.clone());
+                cycles.push(cycle);
+            }
+            return;
+        }
+        if !visited.insert(team_name) {
+            return;
+        }
+
+        path.push(team_name);
+        if let Some(formation) = graph.get(team_name) {
+            for subteam_name in *formation {
+                find_cycles(subteam_name, graph, path, visited, cycles, cycles_seen);
+            }
+        }
+        path.pop();
+    }
+
+    /// Lines where the teams and repositories are defined in the permissions
+    /// file. Entries are located on a best effort basis, so some of them may
+    /// not be found (i.e. when using an unusual formatting).
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub(crate) struct Locations {
+        path: String,
+        teams: HashMap<String, usize>,
+        repositories: HashMap<String, usize>,
+    }
+
+    impl Locations {
+        /// Get the locations of the entries in the permissions file. As they
+        /// are only informative, no locations are returned when the file
+        /// cannot be read.
+        pub(crate) async fn get(gh: DynGH, src: &Source, path: &str) -> Self {
+            match gh.get_file_content(src, path).await {
+                Ok(content) => Self::new(path, &content),
+                Err(_) => Self::default(),
+            }
+        }
+
+        /// Create a new Locations instance from the permissions file content.
+        fn new(path: &str, content: &str) -> Self {
+            let mut locations = Locations {
+                path: path.to_string(),
+                ..Default::default()
+            };
+            let mut section = None;
+            let mut entries_indent = None;
+            for (i, line) in content.lines().enumerate() {
+                if let Some(captures) = YAML_SECTION.captures(line) {
+                    section = Some(captures["section"].to_string());
+                    entries_indent = None;
+                    continue;
+                }
+                let Some(captures) = YAML_ENTRY_NAME.captures(line) else {
+                    continue;
+                };
+                let entries = match section.as_deref() {
+                    Some("teams") => &mut locations.teams,
+                    Some("repositories") => &mut locations.repositories,
+                    _ => continue,
+                };
+
+                // Only the entries at the top level of the section are
+                // considered (the first one sets the indentation)
+                let indent = captures["indent"].len();
+                if *entries_indent.get_or_insert(indent) != indent {
+                    continue;
+                }
+                entries.entry(captures["name"].to_string()).or_insert(i + 1);
+            }
+            locations
+        }
+
+        /// Get the location (path:line) of the team provided.
+        pub(crate) fn team(&self, team_name: &str) -> Option<String> {
+            self.teams.get(team_name).map(|line| format!("{}:{line}", self.path))
+        }
+
+        /// Get the location (path:line) of the repository provided.
+        pub(crate) fn repository(&self, repo_name: &str) -> Option<String> {
+            self.repositories.get(repo_name).map(|line| format!("{}:{line}", self.path))
+        }
     }
 
     /// Team configuration.
@@ -212,6 +549,79 @@ pub mod sheriff {
 
         #[serde(skip_serializing_if = "Option::is_none")]
         pub formation: Option<Vec<TeamName>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub roster: Option<Roster>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub notifications: Option<TeamNotifications>,
+    }
+
+    /// External roster (CSV file) listing some of the members of a team. The
+    /// file must have a header row, and the users' GitHub usernames must be
+    /// available in the column provided.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Roster {
+        pub path: String,
+
+        #[serde(default = "default_roster_column")]
+        pub column: String,
+    }
+
+    /// Default name of the roster column containing the GitHub usernames.
+    fn default_roster_column() -> String {
+        "username".to_string()
+    }
+
+    /// Parse the roster CSV file content provided, returning the usernames
+    /// found in the column given.
+    pub(crate) fn parse_roster(content: &str, column: &str) -> Result<Vec<UserName>> {
+        let mut rows = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+
+        // Locate the usernames column in the header row
+        let Some((_, header)) = rows.next() else {
+            return Err(format_err!("roster file is empty"));
+        };
+        let Some(column_idx) = parse_csv_row(header)?.iter().position(|field| field == column) else {
+            return Err(format_err!("column {column} not found in header row"));
+        };
+
+        // Extract usernames from the remaining rows
+        let mut user_names = vec![];
+        for (i, row) in rows {
+            let line_number = i + 1;
+            let fields = parse_csv_row(row).context(format!("line {line_number}"))?;
+            match fields.get(column_idx).map(String::as_str) {
+                Some(user_name) if !user_name.is_empty() => user_names.push(user_name.to_string()),
+                _ => return Err(format_err!("line {line_number}: username must be provided")),
+            }
+        }
+        Ok(user_names)
+    }
+
+    /// Split the CSV row provided into its fields (which are trimmed).
+    /// Quoted fields can contain commas and escaped quotes ("").
+    fn parse_csv_row(row: &str) -> Result<Vec<String>> {
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut chars = row.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if in_quotes && chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = !in_quotes,
+                ',' if !in_quotes => fields.push(std::mem::take(&mut field).trim().to_string()),
+                _ => field.push(c),
+            }
+        }
+        if in_quotes {
+            return Err(format_err!("unterminated quoted field"));
+        }
+        fields.push(field.trim().to_string());
+        Ok(fields)
     }
 
     impl From<crate::directory::Team> for Team {
@@ -220,6 +630,7 @@ pub mod sheriff {
                 name: team.name,
                 maintainers: Some(team.maintainers),
                 members: Some(team.members),
+                notifications: team.notifications,
                 ..Default::default()
             }
         }
@@ -227,12 +638,44 @@ pub mod sheriff {
 }
 
 pub(crate) mod cncf {
+    use std::collections::HashMap;
+
     use crate::{
+        directory::GITHUB_URL,
         github::{DynGH, Source},
         multierror::MultiError,
     };
     use anyhow::{format_err, Context, Error, Result};
+    use futures::stream::{self, StreamExt};
     use serde::{Deserialize, Serialize};
+    use serde_json::{Map, Value};
+
+    /// Maximum number of GitHub accounts checked concurrently.
+    const ACCOUNTS_CHECK_CONCURRENCY: usize = 10;
+
+    /// User profile fields, as named in the CNCF people file format.
+    const USER_FIELDS: &[&str] = &[
+        "name",
+        "bio",
+        "company",
+        "pronouns",
+        "location",
+        "linkedin",
+        "twitter",
+        "github",
+        "wechat",
+        "website",
+        "youtube",
+        "languages",
+        "projects",
+        "category",
+        "email",
+        "slack_id",
+        "image",
+    ];
+
+    /// User profile fields that must be present in all users.
+    const REQUIRED_USER_FIELDS: &[&str] = &["name"];
 
     /// CNCF people configuration.
     /// https://github.com/cncf/people/tree/main#listing-format
@@ -243,15 +686,22 @@ pub(crate) mod cncf {
     }
 
     impl Cfg {
-        /// Get CNCF people configuration.
-        pub(crate) async fn get(gh: DynGH, src: &Source, path: Option<&str>) -> Result<Option<Self>> {
+        /// Get CNCF people configuration. Fields found in the fields mapping
+        /// provided will be read from the custom keys they map to.
+        pub(crate) async fn get(
+            gh: DynGH,
+            src: &Source,
+            path: Option<&str>,
+            fields: &HashMap<String, String>,
+        ) -> Result<Option<Self>> {
             match path {
                 Some(path) => {
                     let content =
                         gh.get_file_content(src, path).await.context("error getting cncf people file")?;
-                    let cfg: Cfg = serde_json::from_str(&content)
-                        .map_err(Error::new)
-                        .context("error parsing cncf people file")?;
+                    if content.trim().is_empty() {
+                        return Err(format_err!("cncf people file is empty"));
+                    }
+                    let cfg = Cfg::from_str(&content, fields)?;
                     cfg.validate()?;
                     Ok(Some(cfg))
                 }
@@ -259,6 +709,51 @@ pub(crate) mod cncf {
             }
         }
 
+        /// Parse CNCF people configuration from the content provided. The
+        /// keys of each user are mapped to the user profile fields using the
+        /// fields mapping provided (fields not listed use the CNCF key names).
+        fn from_str(content: &str, fields: &HashMap<String, String>) -> Result<Self> {
+            let mut merr = MultiError::new(Some("invalid people file".to_string()));
+            for field in fields.keys() {
+                if !USER_FIELDS.contains(&field.as_str()) {
+                    merr.push(format_err!("fields mapping: unknown user field {field}"));
+                }
+            }
+            if merr.contains_errors() {
+                return Err(merr.into());
+            }
+
+            let mut value: Value = serde_json::from_str(content)
+                .map_err(Error::new)
+                .context("error parsing cncf people file")?;
+            if let Some(people) = value.as_array_mut() {
+                for (i, user) in people.iter_mut().enumerate() {
+                    let Some(entries) = user.as_object() else {
+                        continue;
+                    };
+                    let mut mapped_entries = Map::new();
+                    for field in USER_FIELDS {
+                        let key = fields.get(*field).map_or(*field, String::as_str);
+                        if let Some(entry) = entries.get(key) {
+                            mapped_entries.insert((*field).to_string(), entry.clone());
+                        } else if REQUIRED_USER_FIELDS.contains(field) {
+                            merr.push(format_err!(
+                                "user[{i}]: required field {field} not found (key: {key})"
+                            ));
+                        }
+                    }
+                    *user = Value::Object(mapped_entries);
+                }
+            }
+            if merr.contains_errors() {
+                return Err(merr.into());
+            }
+            let cfg: Cfg = serde_json::from_value(value)
+                .map_err(Error::new)
+                .context("error parsing cncf people file")?;
+            Ok(cfg)
+        }
+
         /// Validate configuration.
         fn validate(&self) -> Result<()> {
             let mut merr = MultiError::new(None);
@@ -275,6 +770,107 @@ pub(crate) mod cncf {
             }
             Ok(())
         }
+
+        /// Validate users' profiles in depth (GitHub handle, image url, etc).
+        pub(crate) fn validate_profiles(&self) -> Result<()> {
+            let mut merr = MultiError::new(Some("invalid people file".to_string()));
+
+            let mut handles_seen: HashMap<String, String> = HashMap::new();
+            for (i, user) in self.people.iter().enumerate() {
+                // Define id to be used in subsequent error messages. When
+                // available, it'll be the user name. Otherwise we'll use its
+                // index on the list.
+                let id = if user.name.is_empty() {
+                    format!("{i}")
+                } else {
+                    user.name.clone()
+                };
+
+                // Name must be provided
+                if user.name.is_empty() {
+                    merr.push(format_err!("user[{id}]: name must be provided"));
+                }
+
+                // GitHub profile url must be valid and not used by other users
+                if let Some(github) = &user.github {
+                    if let Some(captures) = GITHUB_URL.captures(github) {
+                        let handle = captures["handle"].to_lowercase();
+                        if let Some(other_user) = handles_seen.get(&handle) {
+                            merr.push(format_err!(
+                                "user[{id}]: github handle {} already used by user {other_user}",
+                                &captures["handle"]
+                            ));
+                        } else {
+                            handles_seen.insert(handle, id.clone());
+                        }
+                    } else {
+                        merr.push(format_err!(
+                            "user[{id}]: github must be a valid profile url (https://github.com/<handle>)"
+                        ));
+                    }
+                }
+
+                // Image must be a file name or an https url
+                if let Some(image) = &user.image {
+                    if image.is_empty() {
+                        merr.push(format_err!("user[{id}]: image cannot be empty"));
+                    } else if image.contains("://") && !image.starts_with("https://") {
+                        merr.push(format_err!("user[{id}]: image url must use https"));
+                    }
+                }
+            }
+
+            if merr.contains_errors() {
+                return Err(merr.into());
+            }
+            Ok(())
+        }
+
+        /// Validate that the GitHub accounts of the users exist. Users without
+        /// a valid GitHub profile url are ignored (they are reported when
+        /// validating the profiles).
+        pub(crate) async fn validate_accounts(&self, gh: DynGH, inst_id: Option<i64>) -> Result<()> {
+            let mut merr = MultiError::new(Some("invalid people file".to_string()));
+
+            // Check accounts concurrently (results are kept in the same order
+            // as the users in the people file)
+            let results: Vec<(&String, String, Result<bool>)> = stream::iter(&self.people)
+                .filter_map(|user| async move {
+                    let captures = GITHUB_URL.captures(user.github.as_ref()?)?;
+                    Some((&user.name, captures["handle"].to_string()))
+                })
+                .map(|(user_name, handle)| {
+                    let gh = gh.clone();
+                    async move {
+                        let exists = gh.user_exists(inst_id, &handle).await;
+                        (user_name, handle, exists)
+                    }
+                })
+                .buffered(ACCOUNTS_CHECK_CONCURRENCY)
+                .collect()
+                .await;
+
+            for (user_name, handle, exists) in results {
+                match exists {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        merr.push(format_err!(
+                            "user[{user_name}]: github account {handle} does not exist"
+                        ));
+                    }
+                    Err(err) => {
+                        merr.push(format_err!(
+                            "user[{user_name}]: error checking github account {handle}: {err}"
+                        ));
+                    }
+                }
+            }
+
+            if merr.contains_errors() {
+                return Err(merr.into());
+            }
+            Ok(())
+        }
     }
 
     /// User profile.
@@ -299,3 +895,589 @@ pub(crate) mod cncf {
         pub image: Option<String>,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use anyhow::format_err;
+
+    use super::cncf::{Cfg, User};
+    use super::sheriff;
+    use crate::{
+        cfg::Legacy,
+        directory::{self, Directory},
+        github::{MockGH, Source},
+        multierror::MultiError,
+    };
+
+    const PERMISSIONS_FILE: &str = r"
+teams:
+  - name: team1
+    maintainers:
+      - user1
+";
+
+    fn legacy(people_optional: bool) -> Legacy {
+        Legacy {
+            enabled: true,
+            sheriff_permissions_path: "config.yaml".to_string(),
+            cncf_people_path: Some("people.json".to_string()),
+            cncf_people_optional: people_optional,
+            ..Default::default()
+        }
+    }
+
+    fn src() -> Source {
+        Source {
+            inst_id: None,
+            owner: "org".to_string(),
+            repo: ".clowarden".to_string(),
+            ref_: "main".to_string(),
+            location: None,
+        }
+    }
+
+    #[test]
+    fn find_formation_cycles_direct_cycle() {
+        let cfg = sheriff::Cfg {
+            teams: vec![
+                sheriff::Team {
+                    name: "team1".to_string(),
+                    formation: Some(vec!["team2".to_string()]),
+                    ..Default::default()
+                },
+                sheriff::Team {
+                    name: "team2".to_string(),
+                    formation: Some(vec!["team1".to_string()]),
+                    ..Default::default()
+                },
+            ],
+        };
+        assert_eq!(
+            cfg.find_formation_cycles(),
+            vec![vec![
+                "team1".to_string(),
+                "team2".to_string(),
+                "team1".to_string()
+            ]]
+        );
+    }
+
+    #[test]
+    fn find_formation_cycles_self_reference() {
+        let cfg = sheriff::Cfg {
+            teams: vec![sheriff::Team {
+                name: "team1".to_string(),
+                formation: Some(vec!["team1".to_string()]),
+                ..Default::default()
+            }],
+        };
+        assert_eq!(
+            cfg.find_formation_cycles(),
+            vec![vec!["team1".to_string(), "team1".to_string()]]
+        );
+    }
+
+    #[test]
+    fn find_formation_cycles_indirect_cycle() {
+        let cfg = sheriff::Cfg {
+            teams: vec![
+                sheriff::Team {
+                    name: "team1".to_string(),
+                    formation: Some(vec!["team2".to_string()]),
+                    ..Default::default()
+                },
+                sheriff::Team {
+                    name: "team2".to_string(),
+                    formation: Some(vec!["team3".to_string(), "team4".to_string()]),
+                    ..Default::default()
+                },
+                sheriff::Team {
+                    name: "team3".to_string(),
+                    formation: Some(vec!["team1".to_string()]),
+                    ..Default::default()
+                },
+                sheriff::Team {
+                    name: "team4".to_string(),
+                    ..Default::default()
+                },
+            ],
+        };
+        assert_eq!(
+            cfg.find_formation_cycles(),
+            vec![vec![
+                "team1".to_string(),
+                "team2".to_string(),
+                "team3".to_string(),
+                "team1".to_string()
+            ]]
+        );
+    }
+
+    #[test]
+    fn find_formation_cycles_no_cycles() {
+        let cfg = sheriff::Cfg {
+            teams: vec![
+                sheriff::Team {
+                    name: "team1".to_string(),
+                    formation: Some(vec!["team2".to_string(), "team3".to_string()]),
+                    ..Default::default()
+                },
+                sheriff::Team {
+                    name: "team2".to_string(),
+                    formation: Some(vec!["team3".to_string()]),
+                    ..Default::default()
+                },
+                sheriff::Team {
+                    name: "team3".to_string(),
+                    ..Default::default()
+                },
+            ],
+        };
+        assert!(cfg.find_formation_cycles().is_empty());
+    }
+
+    #[test]
+    fn validate_profiles_valid() {
+        let cfg = Cfg {
+            people: vec![User {
+                name: "User 1".to_string(),
+                github: Some("https://github.com/user1".to_string()),
+                image: Some("user1.jpg".to_string()),
+                ..Default::default()
+            }],
+        };
+        assert!(cfg.validate_profiles().is_ok());
+    }
+
+    #[test]
+    fn validate_profiles_invalid_github_url() {
+        let cfg = Cfg {
+            people: vec![User {
+                name: "User 1".to_string(),
+                github: Some("https://gitlab.com/user1".to_string()),
+                ..Default::default()
+            }],
+        };
+        assert!(cfg.validate_profiles().is_err());
+    }
+
+    #[test]
+    fn validate_profiles_duplicate_github_handle() {
+        let cfg = Cfg {
+            people: vec![
+                User {
+                    name: "User 1".to_string(),
+                    github: Some("https://github.com/user1".to_string()),
+                    ..Default::default()
+                },
+                User {
+                    name: "User 2".to_string(),
+                    github: Some("https://github.com/User1/".to_string()),
+                    ..Default::default()
+                },
+            ],
+        };
+        assert!(cfg.validate_profiles().is_err());
+    }
+
+    #[test]
+    fn validate_profiles_insecure_image_url() {
+        let cfg = Cfg {
+            people: vec![User {
+                name: "User 1".to_string(),
+                image: Some("http://example.com/user1.jpg".to_string()),
+                ..Default::default()
+            }],
+        };
+        assert!(cfg.validate_profiles().is_err());
+    }
+
+    #[tokio::test]
+    async fn validate_accounts_not_found() {
+        let mut gh = MockGH::new();
+        gh.expect_user_exists().times(2).returning(|_, handle| Ok(handle == "user1"));
+
+        let cfg = Cfg {
+            people: vec![
+                User {
+                    name: "User 1".to_string(),
+                    github: Some("https://github.com/user1".to_string()),
+                    ..Default::default()
+                },
+                User {
+                    name: "User 2".to_string(),
+                    github: Some("https://github.com/user2".to_string()),
+                    ..Default::default()
+                },
+                User {
+                    name: "User 3".to_string(),
+                    ..Default::default()
+                },
+            ],
+        };
+        let err = cfg.validate_accounts(Arc::new(gh), None).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["user[User 2]: github account user2 does not exist".to_string()]
+        );
+    }
+
+    #[test]
+    fn locations_from_permissions_file() {
+        let content = r"
+teams:
+  - name: team1
+    maintainers:
+      - user1
+  - name: 'team2'
+    formation:
+      - team1
+repositories:
+  - name: repo1
+    teams:
+      team1: write
+  - name: repo2 # comment
+";
+        let locations = sheriff::Locations::new("config.yaml", content);
+        assert_eq!(locations.team("team1"), Some("config.yaml:3".to_string()));
+        assert_eq!(locations.team("team2"), Some("config.yaml:6".to_string()));
+        assert_eq!(locations.team("team3"), None);
+        assert_eq!(locations.repository("repo1"), Some("config.yaml:10".to_string()));
+        assert_eq!(locations.repository("repo2"), Some("config.yaml:13".to_string()));
+        assert_eq!(locations.repository("team1"), None);
+    }
+
+    #[tokio::test]
+    async fn get_cfg_optional_people_file_missing() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(PERMISSIONS_FILE.to_string()),
+            _ => Err(format_err!("404 Not Found")),
+        });
+
+        let cfg = super::Cfg::get(Arc::new(gh), &legacy(true), &src()).await.unwrap();
+        assert_eq!(cfg.sheriff.teams.len(), 1);
+        assert_eq!(cfg.cncf, None);
+    }
+
+    #[tokio::test]
+    async fn get_cfg_optional_people_file_malformed() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(PERMISSIONS_FILE.to_string()),
+            _ => Ok("[{".to_string()),
+        });
+
+        let cfg = super::Cfg::get(Arc::new(gh), &legacy(true), &src()).await.unwrap();
+        assert_eq!(cfg.sheriff.teams.len(), 1);
+        assert_eq!(cfg.cncf, None);
+    }
+
+    #[tokio::test]
+    async fn get_cfg_people_file_with_fields_mapping() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(PERMISSIONS_FILE.to_string()),
+            _ => Ok(r#"[{
+                "full_name": "User 1",
+                "github_profile": "https://github.com/user1",
+                "slack": "U123",
+                "email": "user1@example.com"
+            }]"#
+            .to_string()),
+        });
+        let legacy = Legacy {
+            cncf_people_fields: HashMap::from([
+                ("name".to_string(), "full_name".to_string()),
+                ("github".to_string(), "github_profile".to_string()),
+                ("slack_id".to_string(), "slack".to_string()),
+            ]),
+            ..legacy(false)
+        };
+
+        let cfg = super::Cfg::get(Arc::new(gh), &legacy, &src()).await.unwrap();
+        assert_eq!(
+            Directory::from(cfg).users,
+            vec![directory::User {
+                full_name: "User 1".to_string(),
+                user_name: Some("user1".to_string()),
+                email: Some("user1@example.com".to_string()),
+                slack_id: Some("U123".to_string()),
+                github_url: Some("https://github.com/user1".to_string()),
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_cncf_cfg_required_field_not_mapped() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content()
+            .returning(|_, _| Ok(r#"[{"full_name": "User 1"}, {"name": "User 2"}]"#.to_string()));
+        let fields = HashMap::from([("github".to_string(), "github_profile".to_string())]);
+
+        let err = Cfg::get(Arc::new(gh), &src(), Some("people.json"), &fields).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["user[0]: required field name not found (key: name)".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_cncf_cfg_unknown_mapped_field() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, _| Ok("[]".to_string()));
+        let fields = HashMap::from([("nickname".to_string(), "nick".to_string())]);
+
+        let err = Cfg::get(Arc::new(gh), &src(), Some("people.json"), &fields).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["fields mapping: unknown user field nickname".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_sheriff_cfg_empty_permissions_file() {
+        for content in ["", "  \n", "# teams:\n", "teams: []\n"] {
+            let mut gh = MockGH::new();
+            gh.expect_get_file_content().returning(move |_, _| Ok(content.to_string()));
+
+            let err = sheriff::Cfg::get(Arc::new(gh), &src(), "config.yaml").await.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "permissions file is empty: it must define some teams or repositories"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn get_sheriff_cfg_permissions_file_missing() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, _| Err(format_err!("404 Not Found")));
+
+        let err = sheriff::Cfg::get(Arc::new(gh), &src(), "config.yaml").await.unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "error getting permissions file: 404 Not Found"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_cfg_required_people_file_empty() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(PERMISSIONS_FILE.to_string()),
+            _ => Ok("\n".to_string()),
+        });
+
+        let err = super::Cfg::get(Arc::new(gh), &legacy(false), &src()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(merr.errors()[0].to_string(), "cncf people file is empty");
+    }
+
+    #[tokio::test]
+    async fn get_sheriff_cfg_with_roster() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(r"
+teams:
+  - name: team1
+    maintainers:
+      - user1
+    members:
+      - user2
+    roster:
+      path: rosters/team1.csv
+      column: github
+"
+            .to_string()),
+            "rosters/team1.csv" => Ok("name,github\n\"Doe, Jane\",user3\nJohn Doe, user2 \n".to_string()),
+            _ => Err(format_err!("404 Not Found")),
+        });
+
+        let cfg = sheriff::Cfg::get(Arc::new(gh), &src(), "config.yaml").await.unwrap();
+        assert_eq!(
+            cfg.teams[0].members,
+            Some(vec!["user2".to_string(), "user3".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn get_sheriff_cfg_with_invalid_rosters() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(r"
+teams:
+  - name: team1
+    maintainers:
+      - user1
+    roster:
+      path: team1.csv
+  - name: team2
+    maintainers:
+      - user1
+    roster:
+      path: team2.csv
+  - name: team3
+    maintainers:
+      - user1
+    roster:
+      path: team3.csv
+"
+            .to_string()),
+            "team1.csv" => Ok("username\nuser2\n,\n".to_string()),
+            "team2.csv" => Ok("login\nuser2\n".to_string()),
+            _ => Err(format_err!("404 Not Found")),
+        });
+
+        let err = sheriff::Cfg::get(Arc::new(gh), &src(), "config.yaml").await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "team[team1]: roster team1.csv: line 3: username must be provided".to_string(),
+                "team[team2]: roster team2.csv: column username not found in header row".to_string(),
+                "team[team3]: roster team3.csv: error getting roster file: 404 Not Found".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_sheriff_cfg_with_includes() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(r"
+include:
+  - base.yaml
+  - sigs.yaml
+teams:
+  - name: team3
+    maintainers:
+      - user3
+"
+            .to_string()),
+            "base.yaml" => Ok(r"
+teams:
+  - name: staff
+    maintainers:
+      - user1
+  - name: team3
+    maintainers:
+      - user1
+"
+            .to_string()),
+            "sigs.yaml" => Ok(r"
+include:
+  - base.yaml
+teams:
+  - name: staff
+    maintainers:
+      - user2
+"
+            .to_string()),
+            _ => Err(format_err!("404 Not Found")),
+        });
+
+        let cfg = sheriff::Cfg::get(Arc::new(gh), &src(), "config.yaml").await.unwrap();
+        assert_eq!(
+            cfg.teams,
+            vec![
+                sheriff::Team {
+                    name: "staff".to_string(),
+                    maintainers: Some(vec!["user2".to_string()]),
+                    ..Default::default()
+                },
+                sheriff::Team {
+                    name: "team3".to_string(),
+                    maintainers: Some(vec!["user3".to_string()]),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_sheriff_cfg_with_circular_includes() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok("include:\n  - base.yaml\n".to_string()),
+            "base.yaml" => Ok("include:\n  - staff.yaml\n".to_string()),
+            "staff.yaml" => Ok("include:\n  - config.yaml\n".to_string()),
+            _ => Err(format_err!("404 Not Found")),
+        });
+
+        let err = sheriff::Cfg::get(Arc::new(gh), &src(), "config.yaml").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "circular include detected: config.yaml -> base.yaml -> staff.yaml -> config.yaml"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_sheriff_cfg_included_file_missing() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(format!("include:\n  - base.yaml\n{PERMISSIONS_FILE}")),
+            _ => Err(format_err!("404 Not Found")),
+        });
+
+        let err = sheriff::Cfg::get(Arc::new(gh), &src(), "config.yaml").await.unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "error getting included file base.yaml: 404 Not Found"
+        );
+    }
+
+    #[test]
+    fn parse_roster_unterminated_quoted_field() {
+        let err = sheriff::parse_roster("username\n\"user1\n", "username").unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: unterminated quoted field");
+    }
+
+    #[test]
+    fn validate_config_version_supported() {
+        assert!(super::validate_config_version(&format!("version: legacy\n{PERMISSIONS_FILE}")).is_ok());
+    }
+
+    #[test]
+    fn validate_config_version_missing() {
+        assert!(super::validate_config_version(PERMISSIONS_FILE).is_ok());
+    }
+
+    #[test]
+    fn validate_config_version_unknown() {
+        let err = super::validate_config_version(&format!("version: 2\n{PERMISSIONS_FILE}")).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("unsupported configuration version 2 (supported versions: legacy)"));
+    }
+
+    #[tokio::test]
+    async fn get_cfg_unknown_config_version() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(format!("version: v2\n{PERMISSIONS_FILE}")),
+            _ => Ok("[]".to_string()),
+        });
+
+        let err = super::Cfg::get(Arc::new(gh), &legacy(false), &src()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert!(merr.errors()[0].to_string().starts_with("unsupported configuration version v2"));
+    }
+
+    #[tokio::test]
+    async fn get_cfg_required_people_file_missing() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| match path {
+            "config.yaml" => Ok(PERMISSIONS_FILE.to_string()),
+            _ => Err(format_err!("404 Not Found")),
+        });
+
+        assert!(super::Cfg::get(Arc::new(gh), &legacy(false), &src()).await.is_err());
+    }
+}
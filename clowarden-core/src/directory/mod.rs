@@ -3,7 +3,7 @@
 
 use std::{
     collections::{HashMap, HashSet},
-    fmt::Write,
+    fmt::{self, Write},
 };
 
 use anyhow::{format_err, Context, Result};
@@ -15,7 +15,11 @@ use serde_json::json;
 use crate::{
     cfg::{Legacy, Organization},
     github::{DynGH, Source},
-    services::{BaseRefConfigStatus, Change, ChangeDetails, ChangesSummary, DynChange},
+    multierror::MultiError,
+    services::{
+        BaseRefConfigStatus, Change, ChangeDetails, ChangeExplanation, ChangeRisk, ChangesSummary,
+        LocatedChange,
+    },
 };
 
 pub mod legacy;
@@ -54,10 +58,40 @@ impl Directory {
         ))
     }
 
+    /// Validate the users defined in the people file of the configuration
+    /// source provided, without loading the rest of the configuration. When
+    /// requested, the existence of the users' GitHub accounts will be checked
+    /// as well (this requires one API call per user).
+    pub async fn validate_people(
+        gh: DynGH,
+        legacy: &Legacy,
+        src: &Source,
+        check_accounts: bool,
+    ) -> Result<()> {
+        let Some(people_path) = legacy.cncf_people_path.as_deref() else {
+            return Err(format_err!("people file not provided"));
+        };
+        let fields = &legacy.cncf_people_fields;
+        if let Some(cfg) = legacy::cncf::Cfg::get(gh.clone(), src, Some(people_path), fields).await? {
+            cfg.validate_profiles()?;
+            if check_accounts {
+                cfg.validate_accounts(gh, src.inst_id).await?;
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the changes detected between this directory instance and the
     /// new one provided.
     #[must_use]
     pub fn diff(&self, new: &Directory) -> Vec<DirectoryChange> {
+        self.diff_explained(new).into_iter().map(|(change, _)| change).collect()
+    }
+
+    /// Returns the changes detected between this directory instance and the
+    /// new one provided, along with the values compared to compute them.
+    #[must_use]
+    pub fn diff_explained(&self, new: &Directory) -> Vec<(DirectoryChange, ChangeExplanation)> {
         let mut changes = vec![];
 
         // Teams
@@ -68,10 +102,16 @@ impl Directory {
         let teams_names_old: HashSet<&TeamName> = teams_old.keys().copied().collect();
         let teams_names_new: HashSet<&TeamName> = teams_new.keys().copied().collect();
         for team_name in teams_names_old.difference(&teams_names_new) {
-            changes.push(DirectoryChange::TeamRemoved((*team_name).to_string()));
+            changes.push((
+                DirectoryChange::TeamRemoved((*team_name).to_string()),
+                ChangeExplanation::removed("team", team_name),
+            ));
         }
         for team_name in teams_names_new.difference(&teams_names_old) {
-            changes.push(DirectoryChange::TeamAdded(teams_new[*team_name].clone()));
+            changes.push((
+                DirectoryChange::TeamAdded(teams_new[*team_name].clone()),
+                ChangeExplanation::added("team", team_name),
+            ));
         }
 
         // Teams maintainers and members added/removed
@@ -85,30 +125,73 @@ impl Directory {
             let maintainers_new: HashSet<&UserName> = teams_new[team_name].maintainers.iter().collect();
             let members_old: HashSet<&UserName> = teams_old[team_name].members.iter().collect();
             let members_new: HashSet<&UserName> = teams_new[team_name].members.iter().collect();
+
+            // Helper closure to explain the change in the role of a user in
+            // the team (the user may have been moved from one list to another)
+            let explanation = |user_name: &UserName| {
+                let role = |maintainers: &HashSet<&UserName>, members: &HashSet<&UserName>| {
+                    if maintainers.contains(user_name) {
+                        Some("maintainer".to_string())
+                    } else if members.contains(user_name) {
+                        Some("member".to_string())
+                    } else {
+                        None
+                    }
+                };
+                ChangeExplanation {
+                    field: "team role",
+                    old: role(&maintainers_old, &members_old),
+                    new: role(&maintainers_new, &members_new),
+                }
+            };
+
             for user_name in maintainers_old.difference(&maintainers_new) {
-                changes.push(DirectoryChange::TeamMaintainerRemoved(
-                    (*team_name).to_string(),
-                    (*user_name).to_string(),
+                changes.push((
+                    DirectoryChange::TeamMaintainerRemoved(
+                        (*team_name).to_string(),
+                        (*user_name).to_string(),
+                    ),
+                    explanation(*user_name),
                 ));
             }
             for user_name in members_old.difference(&members_new) {
-                changes.push(DirectoryChange::TeamMemberRemoved(
-                    (*team_name).to_string(),
-                    (*user_name).to_string(),
+                changes.push((
+                    DirectoryChange::TeamMemberRemoved((*team_name).to_string(), (*user_name).to_string()),
+                    explanation(*user_name),
                 ));
             }
             for user_name in maintainers_new.difference(&maintainers_old) {
-                changes.push(DirectoryChange::TeamMaintainerAdded(
-                    (*team_name).to_string(),
-                    (*user_name).to_string(),
+                changes.push((
+                    DirectoryChange::TeamMaintainerAdded((*team_name).to_string(), (*user_name).to_string()),
+                    explanation(*user_name),
                 ));
             }
             for user_name in members_new.difference(&members_old) {
-                changes.push(DirectoryChange::TeamMemberAdded(
-                    (*team_name).to_string(),
-                    (*user_name).to_string(),
+                changes.push((
+                    DirectoryChange::TeamMemberAdded((*team_name).to_string(), (*user_name).to_string()),
+                    explanation(*user_name),
                 ));
             }
+
+            // Notifications (only checked when they have been defined)
+            if let (Some(notifications_new), Some(notifications_old)) = (
+                teams_new[team_name].notifications,
+                teams_old[team_name].notifications,
+            ) {
+                if notifications_new != notifications_old {
+                    changes.push((
+                        DirectoryChange::TeamNotificationsUpdated(
+                            (*team_name).to_string(),
+                            notifications_new,
+                        ),
+                        ChangeExplanation::updated(
+                            "team notifications",
+                            notifications_old,
+                            notifications_new,
+                        ),
+                    ));
+                }
+            }
         }
 
         // Users
@@ -120,10 +203,16 @@ impl Directory {
         let users_fullnames_new: HashSet<&UserFullName> = users_new.keys().copied().collect();
         let mut users_added: Vec<&UserFullName> = vec![];
         for full_name in users_fullnames_old.difference(&users_fullnames_new) {
-            changes.push(DirectoryChange::UserRemoved((*full_name).to_string()));
+            changes.push((
+                DirectoryChange::UserRemoved((*full_name).to_string()),
+                ChangeExplanation::removed("user", full_name),
+            ));
         }
         for full_name in users_fullnames_new.difference(&users_fullnames_old) {
-            changes.push(DirectoryChange::UserAdded((*full_name).to_string()));
+            changes.push((
+                DirectoryChange::UserAdded((*full_name).to_string()),
+                ChangeExplanation::added("user", full_name),
+            ));
             users_added.push(full_name);
         }
 
@@ -137,7 +226,10 @@ impl Directory {
 
             let user_old = &users_old[full_name];
             if user_new != user_old {
-                changes.push(DirectoryChange::UserUpdated((*full_name).to_string()));
+                changes.push((
+                    DirectoryChange::UserUpdated((*full_name).to_string()),
+                    ChangeExplanation::updated("user", format!("{user_old:?}"), format!("{user_new:?}")),
+                ));
             }
         }
 
@@ -153,13 +245,20 @@ impl Directory {
     ) -> Result<ChangesSummary> {
         let base_src = Source::from(org);
         let directory_head = Directory::new_from_config(gh.clone(), &org.legacy, head_src).await?;
+        directory_head.validate_teams_size(org)?;
         let (changes, base_ref_config_status) =
-            match Directory::new_from_config(gh, &org.legacy, &base_src).await {
+            match Directory::new_from_config(gh.clone(), &org.legacy, &base_src).await {
                 Ok(directory_base) => {
+                    let locations =
+                        legacy::sheriff::Locations::get(gh, head_src, &org.legacy.sheriff_permissions_path)
+                            .await;
                     let changes = directory_base
                         .diff(&directory_head)
                         .into_iter()
-                        .map(|change| Box::new(change) as DynChange)
+                        .map(|change| {
+                            let location = change.team_name().and_then(|team_name| locations.team(team_name));
+                            LocatedChange::wrap(Box::new(change), location)
+                        })
                         .collect();
                     (changes, BaseRefConfigStatus::Valid)
                 }
@@ -169,9 +268,42 @@ impl Directory {
         Ok(ChangesSummary {
             changes,
             base_ref_config_status,
+            warnings: vec![],
+            users_impact: vec![],
         })
     }
 
+    /// Check that teams do not exceed the maximum number of members (including
+    /// maintainers) allowed in the organization, if any. Teams listed as
+    /// exempt in the organization's configuration are not checked.
+    pub fn validate_teams_size(&self, org: &Organization) -> Result<()> {
+        let Some(max_team_members) = org.max_team_members else {
+            return Ok(());
+        };
+        let exempt_teams = org.max_team_members_exempt_teams.as_deref().unwrap_or_default();
+
+        let mut merr = MultiError::new(Some("invalid directory configuration".to_string()));
+        for team in &self.teams {
+            if exempt_teams.contains(&team.name) {
+                continue;
+            }
+            let team_members: HashSet<&UserName> =
+                team.maintainers.iter().chain(team.members.iter()).collect();
+            if team_members.len() > max_team_members {
+                merr.push(format_err!(
+                    "team[{}]: too many members ({}, maximum allowed is {max_team_members})",
+                    team.name,
+                    team_members.len()
+                ));
+            }
+        }
+
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
+        Ok(())
+    }
+
     /// Get team identified by the team name provided.
     #[must_use]
     pub fn get_team(&self, team_name: &str) -> Option<&Team> {
@@ -256,6 +388,9 @@ pub struct Team {
 
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     pub annotations: HashMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<TeamNotifications>,
 }
 
 impl From<legacy::sheriff::Team> for Team {
@@ -264,11 +399,30 @@ impl From<legacy::sheriff::Team> for Team {
             name: team.name.clone(),
             maintainers: team.maintainers.clone().unwrap_or_default(),
             members: team.members.clone().unwrap_or_default(),
+            notifications: team.notifications,
             ..Default::default()
         }
     }
 }
 
+/// Team notifications setting. When enabled, the team members are notified
+/// when the team is @mentioned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TeamNotifications {
+    Enabled,
+    Disabled,
+}
+
+impl fmt::Display for TeamNotifications {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TeamNotifications::Enabled => write!(f, "enabled"),
+            TeamNotifications::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
 /// User profile.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct User {
@@ -301,11 +455,31 @@ pub enum DirectoryChange {
     TeamMaintainerRemoved(TeamName, UserName),
     TeamMemberAdded(TeamName, UserName),
     TeamMemberRemoved(TeamName, UserName),
+    TeamNotificationsUpdated(TeamName, TeamNotifications),
     UserAdded(UserFullName),
     UserRemoved(UserFullName),
     UserUpdated(UserFullName),
 }
 
+impl DirectoryChange {
+    /// Returns the name of the team affected by the change, if any.
+    #[must_use]
+    pub fn team_name(&self) -> Option<&TeamName> {
+        match self {
+            DirectoryChange::TeamAdded(team) => Some(&team.name),
+            DirectoryChange::TeamRemoved(team_name)
+            | DirectoryChange::TeamMaintainerAdded(team_name, _)
+            | DirectoryChange::TeamMaintainerRemoved(team_name, _)
+            | DirectoryChange::TeamMemberAdded(team_name, _)
+            | DirectoryChange::TeamMemberRemoved(team_name, _)
+            | DirectoryChange::TeamNotificationsUpdated(team_name, _) => Some(team_name),
+            DirectoryChange::UserAdded(_)
+            | DirectoryChange::UserRemoved(_)
+            | DirectoryChange::UserUpdated(_) => None,
+        }
+    }
+}
+
 impl Change for DirectoryChange {
     /// [Change::details]
     fn details(&self) -> ChangeDetails {
@@ -334,6 +508,10 @@ impl Change for DirectoryChange {
                 kind: "team-member-removed".to_string(),
                 extra: json!({ "team_name": team_name, "user_name": user_name }),
             },
+            DirectoryChange::TeamNotificationsUpdated(team_name, notifications) => ChangeDetails {
+                kind: "team-notifications-updated".to_string(),
+                extra: json!({ "team_name": team_name, "notifications": notifications }),
+            },
             DirectoryChange::UserAdded(full_name) => ChangeDetails {
                 kind: "user-added".to_string(),
                 extra: json!({ "full_name": full_name }),
@@ -377,6 +555,9 @@ impl Change for DirectoryChange {
             DirectoryChange::TeamMemberRemoved(team_name, user_name) => {
                 vec!["team", "member", "removed", team_name, user_name]
             }
+            DirectoryChange::TeamNotificationsUpdated(team_name, _) => {
+                vec!["team", "notifications", "updated", team_name]
+            }
             DirectoryChange::UserAdded(full_name) => {
                 vec!["user", "added", full_name]
             }
@@ -389,6 +570,14 @@ impl Change for DirectoryChange {
         }
     }
 
+    /// [Change::risk]
+    fn risk(&self) -> ChangeRisk {
+        match self {
+            DirectoryChange::TeamRemoved(_) => ChangeRisk::High,
+            _ => ChangeRisk::Low,
+        }
+    }
+
     /// [Change::template_format]
     fn template_format(&self) -> Result<String> {
         let mut s = String::new();
@@ -408,6 +597,9 @@ impl Change for DirectoryChange {
                         write!(s, "\n\t\t- **{user_name}**")?;
                     }
                 }
+                if let Some(notifications) = &team.notifications {
+                    write!(s, "\n\t- Notifications: **{notifications}**")?;
+                }
             }
             DirectoryChange::TeamRemoved(team_name) => {
                 write!(s, "- team **{team_name}** has been *removed*")?;
@@ -430,6 +622,12 @@ impl Change for DirectoryChange {
                     "- **{user_name}** is no longer a member of team **{team_name}**",
                 )?;
             }
+            DirectoryChange::TeamNotificationsUpdated(team_name, notifications) => {
+                write!(
+                    s,
+                    "- team **{team_name}** notifications have been *{notifications}*"
+                )?;
+            }
             DirectoryChange::UserAdded(full_name) => {
                 write!(s, "- user **{full_name}** has been *added*")?;
             }
@@ -449,6 +647,42 @@ impl Change for DirectoryChange {
 mod tests {
     use super::*;
 
+    fn team_with_three_members() -> Directory {
+        Directory {
+            teams: vec![Team {
+                name: "team1".to_string(),
+                maintainers: vec!["user1".to_string()],
+                members: vec!["user2".to_string(), "user3".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn validate_teams_size_team_too_big() {
+        let org = Organization {
+            max_team_members: Some(2),
+            ..Default::default()
+        };
+        let err = team_with_three_members().validate_teams_size(&org).unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["team[team1]: too many members (3, maximum allowed is 2)".to_string()]
+        );
+    }
+
+    #[test]
+    fn validate_teams_size_exempt_team() {
+        let org = Organization {
+            max_team_members: Some(2),
+            max_team_members_exempt_teams: Some(vec!["team1".to_string()]),
+            ..Default::default()
+        };
+        team_with_three_members().validate_teams_size(&org).unwrap();
+    }
+
     #[test]
     fn diff_team_added() {
         let team1 = Team {
@@ -480,6 +714,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn diff_explained_team_member_promoted() {
+        let team1 = Team {
+            name: "team1".to_string(),
+            members: vec!["user1".to_string()],
+            ..Default::default()
+        };
+        let team1_promoting_member = Team {
+            maintainers: vec!["user1".to_string()],
+            members: vec![],
+            ..team1.clone()
+        };
+        let dir1 = Directory {
+            teams: vec![team1],
+            ..Default::default()
+        };
+        let dir2 = Directory {
+            teams: vec![team1_promoting_member],
+            ..Default::default()
+        };
+        let explanation = ChangeExplanation {
+            field: "team role",
+            old: Some("member".to_string()),
+            new: Some("maintainer".to_string()),
+        };
+        assert_eq!(
+            dir1.diff_explained(&dir2),
+            vec![
+                (
+                    DirectoryChange::TeamMemberRemoved("team1".to_string(), "user1".to_string()),
+                    explanation.clone()
+                ),
+                (
+                    DirectoryChange::TeamMaintainerAdded("team1".to_string(), "user1".to_string()),
+                    explanation
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn diff_team_maintainer_added() {
         let team1 = Team {
@@ -590,6 +864,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn diff_team_notifications_updated() {
+        let team1 = Team {
+            name: "team1".to_string(),
+            notifications: Some(TeamNotifications::Enabled),
+            ..Default::default()
+        };
+        let team1_disabling_notifications = Team {
+            notifications: Some(TeamNotifications::Disabled),
+            ..team1.clone()
+        };
+        let dir1 = Directory {
+            teams: vec![team1],
+            ..Default::default()
+        };
+        let dir2 = Directory {
+            teams: vec![team1_disabling_notifications],
+            ..Default::default()
+        };
+        assert_eq!(
+            dir1.diff(&dir2),
+            vec![DirectoryChange::TeamNotificationsUpdated(
+                "team1".to_string(),
+                TeamNotifications::Disabled
+            )]
+        );
+    }
+
+    #[test]
+    fn diff_team_notifications_not_defined() {
+        let team1 = Team {
+            name: "team1".to_string(),
+            ..Default::default()
+        };
+        let team1_defining_notifications = Team {
+            notifications: Some(TeamNotifications::Disabled),
+            ..team1.clone()
+        };
+        let dir1 = Directory {
+            teams: vec![team1],
+            ..Default::default()
+        };
+        let dir2 = Directory {
+            teams: vec![team1_defining_notifications],
+            ..Default::default()
+        };
+        assert!(dir1.diff(&dir2).is_empty());
+    }
+
     #[test]
     fn diff_user_added() {
         let user1 = User {
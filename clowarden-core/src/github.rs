@@ -1,59 +1,145 @@
 //! This module defines an abstraction layer over the GitHub API.
 
-use std::sync::Arc;
+use std::{io::Read, path::PathBuf, sync::Arc, time::Duration};
 
-use anyhow::{format_err, Context, Result};
+use anyhow::{format_err, Context, Error, Result};
 use async_trait::async_trait;
 use base64::{engine::general_purpose::STANDARD as b64, Engine as _};
-#[cfg(test)]
+use cached::proc_macro::cached;
+use flate2::read::GzDecoder;
+#[cfg(any(test, feature = "mocks"))]
 use mockall::automock;
 use octorust::{
     auth::{Credentials, InstallationTokenGenerator, JWTCredentials},
+    types::AppsCreateInstallationAccessTokenRequest,
     Client,
 };
+use reqwest::{
+    header::{ACCEPT, USER_AGENT},
+    Method, Request, Response, StatusCode,
+};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
+use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use task_local_extensions::Extensions;
+
+use crate::{
+    cfg::{ConfigSource, GitHubApp, GitHubClient, Organization},
+    multierror::MultiError,
+};
+
+/// GitHub API base url.
+pub(crate) const GITHUB_API_URL: &str = "https://api.github.com";
 
-use crate::cfg::{GitHubApp, Organization};
+/// Fragments of the error messages returned when the GitHub API fails due to
+/// a transient issue (lowercased).
+const TRANSIENT_ERRORS_MARKERS: &[&str] = &[
+    "500 internal server error",
+    "502 bad gateway",
+    "503 service unavailable",
+    "504 gateway timeout",
+    "429 too many requests",
+    "rate limit",
+];
 
 /// Trait that defines some operations a GH implementation must support.
 #[async_trait]
 #[allow(clippy::ref_option_ref)]
-#[cfg_attr(test, automock)]
+#[cfg_attr(any(test, feature = "mocks"), automock)]
 pub trait GH {
     /// Get file content.
     async fn get_file_content(&self, src: &Source, path: &str) -> Result<String>;
+
+    /// Check if the reference (branch or tag) of the source provided exists.
+    async fn ref_exists(&self, src: &Source) -> Result<bool>;
+
+    /// Check if a GitHub account with the handle provided exists.
+    async fn user_exists(&self, inst_id: Option<i64>, handle: &str) -> Result<bool>;
 }
 
 /// Type alias to represent a GH trait object.
 pub type DynGH = Arc<dyn GH + Send + Sync>;
 
+/// Trait that defines the operations needed to fetch remote resources that
+/// are not served by the GitHub API (i.e. configuration tarballs or files
+/// hosted in other services).
+#[async_trait]
+#[cfg_attr(test, automock)]
+pub trait Fetcher {
+    /// Fetch the content of the resource at the url provided.
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>>;
+}
+
+/// Type alias to represent a Fetcher trait object.
+pub type DynFetcher = Arc<dyn Fetcher + Send + Sync>;
+
+/// Fetcher implementation backed by an HTTP client.
+pub struct HttpFetcher {
+    http_client: ClientWithMiddleware,
+}
+
+impl HttpFetcher {
+    /// Create a new HttpFetcher instance.
+    #[must_use]
+    pub fn new(http_client: ClientWithMiddleware) -> Self {
+        Self { http_client }
+    }
+}
+
+#[async_trait]
+impl Fetcher for HttpFetcher {
+    /// [Fetcher::fetch]
+    async fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        // Resources are cached for a short period of time, as multiple files
+        // are usually read from the same tarball in a row
+        #[cached(
+            time = 60,
+            sync_writes = true,
+            result = true,
+            key = "String",
+            convert = r#"{ url.to_string() }"#
+        )]
+        async fn inner(http_client: &ClientWithMiddleware, url: &str) -> Result<Vec<u8>> {
+            let response = http_client.get(url).send().await?.error_for_status()?;
+            Ok(response.bytes().await?.to_vec())
+        }
+        inner(&self.http_client, url).await
+    }
+}
+
 /// GH implementation backed by the GitHub API.
-#[derive(Default)]
 pub struct GHApi {
     app_credentials: Option<JWTCredentials>,
     token: Option<String>,
+    http_client: ClientWithMiddleware,
+    fetcher: DynFetcher,
 }
 
 impl GHApi {
     /// Create a new GHApi instance using the token provided.
-    #[must_use]
-    pub fn new_with_token(token: String) -> Self {
-        Self {
+    pub fn new_with_token(token: String, gh_client: &GitHubClient) -> Result<Self> {
+        let http_client = new_http_client(gh_client)?;
+        Ok(Self {
+            app_credentials: None,
             token: Some(token),
-            ..Default::default()
-        }
+            fetcher: Arc::new(HttpFetcher::new(http_client.clone())),
+            http_client,
+        })
     }
 
     /// Create a new GHApi instance using the app credentials provided in the
     /// configuration.
-    pub fn new_with_app_creds(gh_app: &GitHubApp) -> Result<Self> {
+    pub fn new_with_app_creds(gh_app: &GitHubApp, gh_client: &GitHubClient) -> Result<Self> {
         // Setup GitHub app credentials
         let private_key = pem::parse(&gh_app.private_key)?.contents().to_owned();
         let jwt_credentials =
             JWTCredentials::new(gh_app.app_id, private_key).context("error setting up credentials")?;
 
+        let http_client = new_http_client(gh_client)?;
         Ok(Self {
             app_credentials: Some(jwt_credentials),
-            ..Default::default()
+            token: None,
+            fetcher: Arc::new(HttpFetcher::new(http_client.clone())),
+            http_client,
         })
     }
 
@@ -75,7 +161,19 @@ impl GHApi {
             Credentials::Token(token)
         };
 
-        Ok(Client::new(user_agent, credentials)?)
+        Ok(Client::custom(user_agent, credentials, self.http_client.clone()))
+    }
+
+    /// Get a token to call the GitHub API directly for the installation id
+    /// provided (if any).
+    async fn get_token(&self, inst_id: Option<i64>) -> Result<String> {
+        get_token(
+            &self.http_client,
+            self.app_credentials.as_ref(),
+            self.token.as_deref(),
+            inst_id,
+        )
+        .await
     }
 }
 
@@ -83,6 +181,25 @@ impl GHApi {
 impl GH for GHApi {
     /// [GH::get_file_content]
     async fn get_file_content(&self, src: &Source, path: &str) -> Result<String> {
+        match &src.location {
+            Some(SourceLocation::Tarball(tarball_url)) => {
+                let tarball = self.fetcher.fetch(tarball_url).await.context("error fetching tarball")?;
+                return extract_file_from_tarball(&tarball, path);
+            }
+            Some(SourceLocation::LocalPath(dir)) => {
+                let content = tokio::fs::read_to_string(dir.join(path))
+                    .await
+                    .context(format!("error reading file {path}"))?;
+                return Ok(content);
+            }
+            Some(SourceLocation::Url(base_url)) => {
+                let url = format!("{}/{path}", base_url.trim_end_matches('/'));
+                let content = self.fetcher.fetch(&url).await.context("error fetching file")?;
+                return Ok(String::from_utf8(content)?);
+            }
+            None => {}
+        }
+
         let client = self.setup_client(src.inst_id)?;
         let mut content = client
             .repos()
@@ -95,23 +212,544 @@ impl GH for GHApi {
         let decoded_content = String::from_utf8(b64.decode(content)?)?;
         Ok(decoded_content)
     }
+
+    /// [GH::ref_exists]
+    async fn ref_exists(&self, src: &Source) -> Result<bool> {
+        // The GitHub API client does not expose the status code of the
+        // responses, so we call the API directly
+        let user_agent = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        let token = self.get_token(src.inst_id).await?;
+        for kind in ["heads", "tags"] {
+            let url = format!(
+                "{GITHUB_API_URL}/repos/{}/{}/git/ref/{kind}/{}",
+                src.owner, src.repo, src.ref_
+            );
+            let response = self
+                .http_client
+                .get(url)
+                .bearer_auth(&token)
+                .header(ACCEPT, "application/vnd.github+json")
+                .header(USER_AGENT, &user_agent)
+                .send()
+                .await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                continue;
+            }
+            response.error_for_status()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// [GH::user_exists]
+    async fn user_exists(&self, inst_id: Option<i64>, handle: &str) -> Result<bool> {
+        #[cached(
+            time = 3600,
+            sync_writes = true,
+            result = true,
+            key = "String",
+            convert = r#"{ handle.to_lowercase() }"#
+        )]
+        async fn inner(client: &Client, handle: &str) -> Result<bool> {
+            match client.users().get_by_username_public_user(handle).await {
+                Ok(_) => Ok(true),
+                Err(err) if err.to_string().contains("404") => Ok(false),
+                Err(err) => Err(err),
+            }
+        }
+        let client = self.setup_client(inst_id)?;
+        inner(&client, handle).await
+    }
 }
 
 /// Information about the origin of a file located in a GitHub repository.
+/// When an alternative location is provided, files are read from it instead.
 pub struct Source {
     pub inst_id: Option<i64>,
     pub owner: String,
     pub repo: String,
     pub ref_: String,
+    pub location: Option<SourceLocation>,
 }
 
 impl From<&Organization> for Source {
     fn from(org: &Organization) -> Self {
+        let ref_ = match &org.config_source {
+            Some(ConfigSource::ReleaseTag(tag)) => tag.clone(),
+            _ => org.branch.clone(),
+        };
+        let location = match &org.config_source {
+            Some(ConfigSource::TarballUrl(url)) => Some(SourceLocation::Tarball(url.clone())),
+            Some(ConfigSource::LocalPath(path)) => Some(SourceLocation::LocalPath(path.clone())),
+            Some(ConfigSource::Url(url)) => Some(SourceLocation::Url(url.clone())),
+            Some(ConfigSource::ReleaseTag(_)) | None => None,
+        };
         Source {
             inst_id: Some(org.installation_id),
             owner: org.name.clone(),
             repo: org.repository.clone(),
-            ref_: org.branch.clone(),
+            ref_,
+            location,
+        }
+    }
+}
+
+/// Check the reference of the configuration source provided exists, so that
+/// misconfigured organizations can be detected before any job is run. Sources
+/// with an alternative location are not read from the repository, so they
+/// are not checked.
+pub async fn check_source_ref(gh: &(dyn GH + Send + Sync), src: &Source) -> Result<()> {
+    if src.location.is_some() {
+        return Ok(());
+    }
+    let exists = gh.ref_exists(src).await.context("error checking configured branch")?;
+    if !exists {
+        return Err(format_err!(
+            "configured branch not found: {} (repository {}/{})",
+            src.ref_,
+            src.owner,
+            src.repo
+        ));
+    }
+    Ok(())
+}
+
+/// Alternative location files can be read from (instead of from a GitHub
+/// repository).
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceLocation {
+    /// Url of a gzipped tarball containing the files.
+    Tarball(String),
+    /// Local directory containing the files.
+    LocalPath(PathBuf),
+    /// Base url the files paths are appended to.
+    Url(String),
+}
+
+/// Extract the content of the file at the path provided from the gzipped
+/// tarball provided. The path can be relative to the root of the tarball or
+/// to its top level directory.
+fn extract_file_from_tarball(tarball: &[u8], path: &str) -> Result<String> {
+    let mut archive = tar::Archive::new(GzDecoder::new(tarball));
+    for entry in archive.entries().context("error reading tarball")? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let entry_path = entry.path()?.to_string_lossy().to_string();
+        let entry_path = entry_path.trim_start_matches("./");
+        if entry_path == path || entry_path.split_once('/').is_some_and(|(_, p)| p == path) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            return Ok(content);
+        }
+    }
+    Err(format_err!("file {path} not found in tarball"))
+}
+
+/// Create a new HTTP client to be used by the GitHub API clients, setup using
+/// the configuration provided. The client returned is meant to be shared, so
+/// that connections can be reused across requests.
+pub fn new_http_client(gh_client: &GitHubClient) -> Result<ClientWithMiddleware> {
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(gh_client.timeout))
+        .pool_idle_timeout(Duration::from_secs(gh_client.pool_idle_timeout))
+        .pool_max_idle_per_host(gh_client.pool_max_idle_per_host)
+        .build()
+        .context("error setting up http client")?;
+    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+
+    Ok(ClientBuilder::new(http_client)
+        .with(RetryIdempotentMiddleware(
+            RetryTransientMiddleware::new_with_policy(retry_policy),
+        ))
+        .build())
+}
+
+/// Get a token to call the GitHub API directly for the installation id
+/// provided, using the app credentials provided. When no installation id is
+/// provided, the token provided is returned.
+pub(crate) async fn get_token(
+    http_client: &ClientWithMiddleware,
+    app_credentials: Option<&JWTCredentials>,
+    token: Option<&str>,
+    inst_id: Option<i64>,
+) -> Result<String> {
+    #[cached(
+        time = 3000,
+        sync_writes = true,
+        result = true,
+        key = "i64",
+        convert = r#"{ inst_id }"#
+    )]
+    async fn inner(client: &Client, inst_id: i64) -> Result<String> {
+        let body = AppsCreateInstallationAccessTokenRequest {
+            permissions: None,
+            repositories: vec![],
+            repository_ids: vec![],
+        };
+        let token = client.apps().create_installation_access_token(inst_id, &body).await?;
+        Ok(token.token)
+    }
+
+    if let Some(inst_id) = inst_id {
+        let Some(app_creds) = app_credentials.cloned() else {
+            return Err(format_err!("error getting token: app credentials not provided"));
+        };
+        let user_agent = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        let client = Client::custom(user_agent, Credentials::JWT(app_creds), http_client.clone());
+        inner(&client, inst_id).await
+    } else {
+        let Some(token) = token else {
+            return Err(format_err!("error getting token: token not provided"));
+        };
+        Ok(token.to_string())
+    }
+}
+
+/// Middleware that retries the requests that fail due to a transient error,
+/// as long as they are idempotent. Other requests may have been processed by
+/// GitHub already when the error is returned (i.e. on timeouts), so retrying
+/// them could apply the same change twice (i.e. sending an invitation).
+struct RetryIdempotentMiddleware(RetryTransientMiddleware<ExponentialBackoff>);
+
+#[async_trait]
+impl Middleware for RetryIdempotentMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::PUT | Method::DELETE
+        ) {
+            self.0.handle(req, extensions, next).await
+        } else {
+            next.run(req, extensions).await
+        }
+    }
+}
+
+/// Check if the error provided was caused by a transient GitHub API failure
+/// (i.e. server errors, rate limits or timeouts), in which case the operation
+/// that failed may succeed if retried. Errors aggregated in a MultiError are
+/// only considered transient when all of them are.
+#[must_use]
+pub fn is_transient_error(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(merr) = cause.downcast_ref::<MultiError>() {
+            let errors = merr.errors();
+            return !errors.is_empty() && errors.into_iter().all(is_transient_error);
+        }
+        let reqwest_err = cause.downcast_ref::<reqwest::Error>().or_else(|| match cause
+            .downcast_ref::<reqwest_middleware::Error>(
+        ) {
+            Some(reqwest_middleware::Error::Reqwest(err)) => Some(err),
+            _ => None,
+        });
+        if let Some(err) = reqwest_err {
+            if err.is_timeout() || err.is_connect() {
+                return true;
+            }
+            if let Some(status) = err.status() {
+                return status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+            }
         }
+        let msg = cause.to_string().to_lowercase();
+        TRANSIENT_ERRORS_MARKERS.iter().any(|marker| msg.contains(marker))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+    use crate::{
+        directory::legacy::sheriff,
+        testutil::{local_server, RedirectTo},
+    };
+
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn gh(fetcher: MockFetcher) -> GHApi {
+        let http_client = new_http_client(&GitHubClient::default()).unwrap();
+        GHApi {
+            app_credentials: None,
+            token: Some("token".to_string()),
+            http_client,
+            fetcher: Arc::new(fetcher),
+        }
+    }
+
+    /// Setup a GH client whose requests are sent to a local server, which
+    /// responds to each of them with the status returned by the function
+    /// provided for the request path.
+    fn gh_with_server(status: fn(&str) -> &'static str) -> GHApi {
+        let (addr, _) = local_server(status, "{}");
+        GHApi {
+            app_credentials: None,
+            token: Some("token".to_string()),
+            http_client: ClientBuilder::new(reqwest::Client::new()).with(RedirectTo(addr)).build(),
+            fetcher: Arc::new(MockFetcher::new()),
+        }
+    }
+
+    fn repository_src() -> Source {
+        Source {
+            inst_id: None,
+            owner: "org".to_string(),
+            repo: "config".to_string(),
+            ref_: "main".to_string(),
+            location: None,
+        }
+    }
+
+    fn src(location: SourceLocation) -> Source {
+        Source {
+            location: Some(location),
+            ..repository_src()
+        }
+    }
+
+    fn tarball_src(tarball_url: &str) -> Source {
+        src(SourceLocation::Tarball(tarball_url.to_string()))
+    }
+
+    #[tokio::test]
+    async fn get_file_content_from_tarball() {
+        let mut fetcher = MockFetcher::new();
+        fetcher
+            .expect_fetch()
+            .withf(|url| url == "https://releases.url/config.tar.gz")
+            .returning(|_| Ok(tarball(&[("config/sheriff.yaml", "repositories: []")])));
+
+        let content = gh(fetcher)
+            .get_file_content(
+                &tarball_src("https://releases.url/config.tar.gz"),
+                "config/sheriff.yaml",
+            )
+            .await
+            .unwrap();
+        assert_eq!(content, "repositories: []");
+    }
+
+    #[tokio::test]
+    async fn get_file_content_from_tarball_with_top_level_directory() {
+        let mut fetcher = MockFetcher::new();
+        fetcher.expect_fetch().returning(|_| {
+            Ok(tarball(&[(
+                "org-config-1a2b3c/config/sheriff.yaml",
+                "repositories: []",
+            )]))
+        });
+
+        let content = gh(fetcher)
+            .get_file_content(
+                &tarball_src("https://releases.url/config.tar.gz"),
+                "config/sheriff.yaml",
+            )
+            .await
+            .unwrap();
+        assert_eq!(content, "repositories: []");
+    }
+
+    #[tokio::test]
+    async fn get_file_content_from_tarball_file_not_found() {
+        let mut fetcher = MockFetcher::new();
+        fetcher.expect_fetch().returning(|_| Ok(tarball(&[("config/people.json", "[]")])));
+
+        let err = gh(fetcher)
+            .get_file_content(
+                &tarball_src("https://releases.url/config.tar.gz"),
+                "config/sheriff.yaml",
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "file config/sheriff.yaml not found in tarball");
+    }
+
+    #[test]
+    fn source_from_organization_release_tag() {
+        let org = Organization {
+            branch: "main".to_string(),
+            config_source: Some(ConfigSource::ReleaseTag("v1.2.0".to_string())),
+            ..Default::default()
+        };
+        let src = Source::from(&org);
+        assert_eq!(src.ref_, "v1.2.0");
+        assert!(src.location.is_none());
+    }
+
+    #[test]
+    fn source_from_organization_local_path() {
+        let org = Organization {
+            branch: "main".to_string(),
+            config_source: Some(ConfigSource::LocalPath(PathBuf::from("/config"))),
+            ..Default::default()
+        };
+        let src = Source::from(&org);
+        assert_eq!(src.ref_, "main");
+        assert_eq!(
+            src.location,
+            Some(SourceLocation::LocalPath(PathBuf::from("/config")))
+        );
+    }
+
+    #[tokio::test]
+    async fn sheriff_cfg_from_local_path() {
+        let gh: DynGH = Arc::new(gh(MockFetcher::new()));
+        let src = src(SourceLocation::LocalPath(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/testdata"),
+        ));
+
+        let cfg = sheriff::Cfg::get(gh, &src, "config.yaml").await.unwrap();
+        assert_eq!(cfg.teams.len(), 1);
+        assert_eq!(cfg.teams[0].name, "team1");
+        assert_eq!(cfg.teams[0].maintainers, Some(vec!["user1".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn sheriff_cfg_from_local_path_file_not_found() {
+        let gh: DynGH = Arc::new(gh(MockFetcher::new()));
+        let src = src(SourceLocation::LocalPath(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/testdata"),
+        ));
+
+        let err = sheriff::Cfg::get(gh, &src, "missing.yaml").await.unwrap_err();
+        assert_eq!(err.to_string(), "error getting permissions file");
+    }
+
+    #[tokio::test]
+    async fn sheriff_cfg_from_url() {
+        let mut fetcher = MockFetcher::new();
+        fetcher
+            .expect_fetch()
+            .withf(|url| url == "https://gitlab.url/org/config/-/raw/main/config.yaml")
+            .times(1)
+            .returning(|_| Ok(include_bytes!("testdata/config.yaml").to_vec()));
+        let gh: DynGH = Arc::new(gh(fetcher));
+        let src = src(SourceLocation::Url(
+            "https://gitlab.url/org/config/-/raw/main/".to_string(),
+        ));
+
+        let cfg = sheriff::Cfg::get(gh, &src, "config.yaml").await.unwrap();
+        assert_eq!(cfg.teams.len(), 1);
+        assert_eq!(cfg.teams[0].name, "team1");
+    }
+
+    #[tokio::test]
+    async fn check_source_ref_found() {
+        let mut gh = MockGH::new();
+        gh.expect_ref_exists().times(1).returning(|_| Ok(true));
+
+        check_source_ref(&gh, &repository_src()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_source_ref_not_found() {
+        let mut gh = MockGH::new();
+        gh.expect_ref_exists().withf(|src| src.ref_ == "main").times(1).returning(|_| Ok(false));
+
+        let err = check_source_ref(&gh, &repository_src()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "configured branch not found: main (repository org/config)"
+        );
+    }
+
+    #[tokio::test]
+    async fn check_source_ref_alternative_location() {
+        let mut gh = MockGH::new();
+        gh.expect_ref_exists().never();
+
+        check_source_ref(&gh, &tarball_src("https://releases.url/config.tar.gz")).await.unwrap();
+    }
+
+    #[test]
+    fn is_transient_error_server_error() {
+        let err = format_err!("code: 502 Bad Gateway, error: upstream unavailable")
+            .context("error getting actual state from service");
+        assert!(is_transient_error(&err));
+    }
+
+    #[test]
+    fn is_transient_error_rate_limit() {
+        let err = format_err!("You have exceeded a secondary rate limit");
+        assert!(is_transient_error(&err));
+    }
+
+    #[test]
+    fn is_transient_error_invalid_config() {
+        let mut merr = MultiError::new(Some("invalid github service configuration".to_string()));
+        merr.push(format_err!(
+            "team[team1]: user1 must be a member of the organization"
+        ));
+        let err = Error::from(merr);
+        assert!(!is_transient_error(&err));
+    }
+
+    #[test]
+    fn is_transient_error_some_errors_not_transient() {
+        let mut merr = MultiError::new(None);
+        merr.push(format_err!("code: 503 Service Unavailable"));
+        merr.push(format_err!("repo[repo1]: invalid topic Cloud_Native"));
+        let err = Error::from(merr);
+        assert!(!is_transient_error(&err));
+    }
+
+    #[tokio::test]
+    async fn http_client_does_not_retry_non_idempotent_requests() {
+        // Setup a server that always fails with a transient error
+        let (addr, requests) = local_server(|_| "503 Service Unavailable", "");
+
+        let http_client = new_http_client(&GitHubClient::default()).unwrap();
+        let resp = http_client.post(format!("http://{addr}/orgs/org/repos")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn ref_exists_branch() {
+        let gh = gh_with_server(|_| "200 OK");
+        assert!(gh.ref_exists(&repository_src()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn ref_exists_tag() {
+        let gh = gh_with_server(|path| {
+            if path == "/repos/org/config/git/ref/tags/main" {
+                "200 OK"
+            } else {
+                "404 Not Found"
+            }
+        });
+        assert!(gh.ref_exists(&repository_src()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn ref_exists_not_found() {
+        let gh = gh_with_server(|_| "404 Not Found");
+        assert!(!gh.ref_exists(&repository_src()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn ref_exists_error() {
+        let gh = gh_with_server(|_| "403 Forbidden");
+        assert!(gh.ref_exists(&repository_src()).await.is_err());
     }
 }
@@ -10,4 +10,7 @@ pub mod cfg;
 pub mod directory;
 pub mod github;
 pub mod multierror;
+pub mod sarif;
 pub mod services;
+#[cfg(test)]
+mod testutil;
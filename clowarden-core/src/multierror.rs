@@ -4,6 +4,8 @@ use std::fmt::{self, Write};
 
 use anyhow::{Error, Result};
 
+use crate::cfg::{ValidationCheck, ValidationExemption};
+
 /// MultiError represents an error that aggregates a collection of errors.
 #[derive(Debug, Default)]
 pub struct MultiError {
@@ -59,6 +61,65 @@ impl fmt::Display for MultiError {
 
 impl std::error::Error for MultiError {}
 
+/// Error returned when a validation check that can be exempted fails.
+#[derive(Debug)]
+pub struct CheckError {
+    pub check: ValidationCheck,
+    pub msg: String,
+}
+
+impl CheckError {
+    /// Create a new CheckError instance.
+    #[must_use]
+    pub fn new(check: ValidationCheck, msg: String) -> Self {
+        Self { check, msg }
+    }
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+/// Remove from the error provided (recursively) the checks errors exempted,
+/// returning the remaining error (if any) and a warning for each of the
+/// errors exempted.
+#[must_use]
+pub fn exempt_errors(err: Error, exemptions: &[ValidationExemption]) -> (Option<Error>, Vec<String>) {
+    fn exempt_errors(
+        err: Error,
+        exemptions: &[ValidationExemption],
+        warnings: &mut Vec<String>,
+    ) -> Option<Error> {
+        let err = match err.downcast::<MultiError>() {
+            Ok(merr) => {
+                let mut remaining = MultiError::new(merr.context);
+                for err in merr.errors {
+                    if let Some(err) = exempt_errors(err, exemptions, warnings) {
+                        remaining.push(err);
+                    }
+                }
+                return remaining.contains_errors().then(|| remaining.into());
+            }
+            Err(err) => err,
+        };
+        if let Some(check_err) = err.downcast_ref::<CheckError>() {
+            if let Some(exemption) = exemptions.iter().find(|e| e.check == check_err.check) {
+                warnings.push(format!("{check_err} (exempted by label {})", exemption.label));
+                return None;
+            }
+        }
+        Some(err)
+    }
+
+    let mut warnings = vec![];
+    let err = exempt_errors(err, exemptions, &mut warnings);
+    (err, warnings)
+}
+
 /// Format the error provided recursively.
 #[allow(clippy::missing_errors_doc)]
 pub fn format_error(err: &Error) -> Result<String> {
@@ -89,3 +150,68 @@ pub fn format_error(err: &Error) -> Result<String> {
     format_error(err, 0, &mut s)?;
     Ok(s)
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::format_err;
+
+    use super::*;
+
+    fn errors() -> Error {
+        let mut merr = MultiError::new(Some("invalid changes".to_string()));
+        merr.push(format_err!("unexpected error"));
+        merr.push(
+            CheckError::new(
+                ValidationCheck::OutsideCollaboratorNotApproved,
+                "user[user1]: not approved".to_string(),
+            )
+            .into(),
+        );
+        merr.into()
+    }
+
+    #[test]
+    fn exempt_errors_exempted() {
+        let exemptions = vec![ValidationExemption {
+            label: "clowarden/allow-collaborator".to_string(),
+            check: ValidationCheck::OutsideCollaboratorNotApproved,
+        }];
+        let (err, warnings) = exempt_errors(errors(), &exemptions);
+        assert_eq!(
+            format_error(&err.unwrap()).unwrap(),
+            "\n- invalid changes\n\t- unexpected error"
+        );
+        assert_eq!(
+            warnings,
+            vec!["user[user1]: not approved (exempted by label clowarden/allow-collaborator)"]
+        );
+    }
+
+    #[test]
+    fn exempt_errors_not_exempted() {
+        let exemptions = vec![ValidationExemption {
+            label: "clowarden/allow-pr-author".to_string(),
+            check: ValidationCheck::PrAuthorScope,
+        }];
+        let (err, warnings) = exempt_errors(errors(), &exemptions);
+        let merr = err.unwrap().downcast::<MultiError>().unwrap();
+        assert_eq!(merr.errors().len(), 2);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn exempt_errors_all_exempted() {
+        let err: Error = CheckError::new(
+            ValidationCheck::PrAuthorScope,
+            "team[team1]: not allowed".to_string(),
+        )
+        .into();
+        let exemptions = vec![ValidationExemption {
+            label: "clowarden/allow-pr-author".to_string(),
+            check: ValidationCheck::PrAuthorScope,
+        }];
+        let (err, warnings) = exempt_errors(err, &exemptions);
+        assert!(err.is_none());
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,218 @@
+//! This module provides the functionality needed to render CLOWarden findings
+//! (high risk changes and configuration errors) as a SARIF report, so that
+//! they can be ingested by security tooling (i.e. code scanning dashboards).
+
+use std::collections::BTreeMap;
+
+use anyhow::{Error, Result};
+use as_any::Downcast;
+use serde_json::{json, Value};
+
+use crate::{
+    multierror::MultiError,
+    services::{Change, ChangeRisk, LocatedChange},
+};
+
+/// SARIF version used in the reports.
+const SARIF_VERSION: &str = "2.1.0";
+
+/// SARIF schema used in the reports.
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Id of the rule used to report configuration errors.
+pub const INVALID_CONFIGURATION_RULE_ID: &str = "invalid-configuration";
+
+/// SARIF report builder.
+#[derive(Debug, Default)]
+pub struct Report {
+    default_location: Option<String>,
+    rules: BTreeMap<String, String>,
+    results: Vec<Value>,
+}
+
+impl Report {
+    /// Create a new report instance. Findings without a location will be
+    /// reported at the default location provided (i.e. the configuration
+    /// file path), as some tools require all results to have one.
+    #[must_use]
+    pub fn new(default_location: Option<String>) -> Self {
+        Self {
+            default_location,
+            ..Default::default()
+        }
+    }
+
+    /// Add the change provided to the report. Only high risk changes are
+    /// reported, using the change kind as the rule id.
+    pub fn add_change(&mut self, change: &dyn Change) -> Result<()> {
+        if change.risk() != ChangeRisk::High {
+            return Ok(());
+        }
+        let kind = change.details().kind;
+        let (message, location) = match change.downcast_ref::<LocatedChange>() {
+            Some(located) => (located.change.template_format()?, Some(located.location.clone())),
+            None => (change.template_format()?, None),
+        };
+        self.rules.entry(kind.clone()).or_insert_with(|| format!("High risk change: {kind}"));
+        self.push_result(&kind, "warning", &message, location);
+        Ok(())
+    }
+
+    /// Add the error provided to the report. Each of the errors aggregated
+    /// in a MultiError is reported as a separate result.
+    pub fn add_error(&mut self, err: &Error) {
+        self.rules
+            .entry(INVALID_CONFIGURATION_RULE_ID.to_string())
+            .or_insert_with(|| "Invalid configuration".to_string());
+        if let Some(merr) = err.downcast_ref::<MultiError>() {
+            for err in merr.errors() {
+                self.add_error(err);
+            }
+        } else {
+            self.push_result(INVALID_CONFIGURATION_RULE_ID, "error", &format!("{err:#}"), None);
+        }
+    }
+
+    /// Render the report as a SARIF JSON document.
+    pub fn render(&self) -> Result<String> {
+        let rules: Vec<Value> = self
+            .rules
+            .iter()
+            .map(|(id, description)| json!({ "id": id, "shortDescription": { "text": description } }))
+            .collect();
+        let report = json!({
+            "$schema": SARIF_SCHEMA,
+            "version": SARIF_VERSION,
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "CLOWarden",
+                        "informationUri": "https://github.com/cncf/clowarden",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    }
+                },
+                "results": self.results,
+            }],
+        });
+        Ok(serde_json::to_string_pretty(&report)?)
+    }
+
+    /// Add a result to the report. Locations are expected to be in the
+    /// path:line format (the line is optional).
+    fn push_result(&mut self, rule_id: &str, level: &str, message: &str, location: Option<String>) {
+        let message = message.trim_start_matches("- ");
+        let mut result = json!({
+            "ruleId": rule_id,
+            "level": level,
+            "message": { "text": message.replace('*', "") },
+        });
+        let location = location.or_else(|| self.default_location.clone()).filter(|l| !l.is_empty());
+        if let Some(location) = location {
+            let path_and_line =
+                location.rsplit_once(':').and_then(|(path, line)| Some((path, line.parse::<u64>().ok()?)));
+            let physical_location = match path_and_line {
+                Some((path, line)) => json!({
+                    "artifactLocation": { "uri": path },
+                    "region": { "startLine": line },
+                }),
+                None => json!({ "artifactLocation": { "uri": location } }),
+            };
+            result["locations"] = json!([{ "physicalLocation": physical_location }]);
+        }
+        self.results.push(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::format_err;
+
+    use super::*;
+    use crate::services::github::state::{RepositoryChange, Visibility};
+
+    /// Check that the SARIF report provided contains all the properties
+    /// required by the SARIF 2.1.0 schema for the objects used.
+    fn assert_valid_sarif(report: &Value) {
+        assert_eq!(report["version"], SARIF_VERSION);
+        assert!(report["$schema"].is_string());
+        let runs = report["runs"].as_array().unwrap();
+        assert_eq!(runs.len(), 1);
+        let driver = &runs[0]["tool"]["driver"];
+        assert!(driver["name"].is_string());
+        let rules_ids: Vec<&str> = driver["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| rule["id"].as_str().unwrap())
+            .collect();
+        for result in runs[0]["results"].as_array().unwrap() {
+            assert!(result["message"]["text"].is_string());
+            assert!(rules_ids.contains(&result["ruleId"].as_str().unwrap()));
+            assert!(["none", "note", "warning", "error"].contains(&result["level"].as_str().unwrap()));
+            for location in result["locations"].as_array().into_iter().flatten() {
+                let physical_location = &location["physicalLocation"];
+                assert!(physical_location["artifactLocation"]["uri"].is_string());
+                if let Some(region) = physical_location.get("region") {
+                    assert!(region["startLine"].as_u64().unwrap() >= 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn report_high_risk_changes_and_errors() {
+        let mut report = Report::new(Some("config.yaml".to_string()));
+        report
+            .add_change(&RepositoryChange::VisibilityUpdated(
+                "repo1".to_string(),
+                Visibility::Public,
+            ))
+            .unwrap();
+        report
+            .add_change(&LocatedChange {
+                change: Box::new(RepositoryChange::VisibilityUpdated(
+                    "repo2".to_string(),
+                    Visibility::Public,
+                )),
+                location: "config.yaml:10".to_string(),
+            })
+            .unwrap();
+        let mut merr = MultiError::new(Some("invalid github service configuration".to_string()));
+        merr.push(format_err!("repo[repo1]: error1"));
+        merr.push(format_err!("repo[repo2]: error2"));
+        report.add_error(&merr.into());
+
+        let report: Value = serde_json::from_str(&report.render().unwrap()).unwrap();
+        assert_valid_sarif(&report);
+        let results = report["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0]["ruleId"], "repository-visibility-updated");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "config.yaml"
+        );
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            10
+        );
+        assert_eq!(results[2]["ruleId"], INVALID_CONFIGURATION_RULE_ID);
+        assert_eq!(results[2]["level"], "error");
+        assert_eq!(results[3]["message"]["text"], "repo[repo2]: error2");
+    }
+
+    #[test]
+    fn report_low_risk_changes_are_skipped() {
+        let mut report = Report::new(None);
+        report
+            .add_change(&RepositoryChange::TopicsUpdated(
+                "repo1".to_string(),
+                vec!["topic1".to_string()],
+            ))
+            .unwrap();
+
+        let report: Value = serde_json::from_str(&report.render().unwrap()).unwrap();
+        assert_valid_sarif(&report);
+        assert!(report["runs"][0]["results"].as_array().unwrap().is_empty());
+    }
+}
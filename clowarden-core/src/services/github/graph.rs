@@ -0,0 +1,314 @@
+//! This module provides the functionality needed to export the access model
+//! defined in a state as a graph (users -> teams -> repositories), so that it
+//! can be rendered (i.e. using Graphviz) to visualize who has access to what.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Write},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    state::{RepositoryName, Role, RolesOrdering},
+    State,
+};
+use crate::directory::{TeamName, UserName};
+
+/// Access relationships defined in a state, as a list of nodes and edges.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccessGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl AccessGraph {
+    /// Create a new access graph from the state provided. In addition to the
+    /// relationships defined explicitly, the effective role of each user in
+    /// each repository (computed using the roles ordering provided) is
+    /// included as well.
+    #[must_use]
+    pub fn new(state: &State, roles_ordering: &RolesOrdering) -> Self {
+        let mut users: BTreeSet<UserName> = BTreeSet::new();
+        let mut teams: BTreeSet<TeamName> = BTreeSet::new();
+        let mut repos: BTreeSet<RepositoryName> = BTreeSet::new();
+        let mut edges = vec![];
+
+        // Teams maintainers and members
+        for team in &state.directory.teams {
+            teams.insert(team.name.clone());
+            for (users_names, kind) in [
+                (&team.maintainers, EdgeKind::Maintainer),
+                (&team.members, EdgeKind::Member),
+            ] {
+                for user_name in users_names {
+                    users.insert(user_name.clone());
+                    edges.push(Edge::new(
+                        node_id(NodeKind::User, user_name),
+                        node_id(NodeKind::Team, &team.name),
+                        kind,
+                        None,
+                    ));
+                }
+            }
+        }
+
+        // Teams and collaborators with access to the repositories
+        for repo in &state.repositories {
+            repos.insert(repo.name.clone());
+            for (team_name, role) in repo.teams.iter().chain(repo.flattened_teams.iter()).flatten() {
+                teams.insert(team_name.clone());
+                edges.push(Edge::new(
+                    node_id(NodeKind::Team, team_name),
+                    node_id(NodeKind::Repository, &repo.name),
+                    EdgeKind::TeamAccess,
+                    Some(role.clone()),
+                ));
+            }
+            for (user_name, role) in repo.collaborators.iter().flatten() {
+                users.insert(user_name.clone());
+                edges.push(Edge::new(
+                    node_id(NodeKind::User, user_name),
+                    node_id(NodeKind::Repository, &repo.name),
+                    EdgeKind::Collaborator,
+                    Some(role.clone()),
+                ));
+            }
+        }
+
+        // Users effective roles in the repositories
+        for (user_name, repos_roles) in state.effective_roles(roles_ordering) {
+            for (repo_name, role) in repos_roles {
+                edges.push(Edge::new(
+                    node_id(NodeKind::User, &user_name),
+                    node_id(NodeKind::Repository, &repo_name),
+                    EdgeKind::EffectiveAccess,
+                    Some(role),
+                ));
+            }
+            users.insert(user_name);
+        }
+
+        let nodes = [
+            (NodeKind::User, users),
+            (NodeKind::Team, teams),
+            (NodeKind::Repository, repos),
+        ]
+        .into_iter()
+        .flat_map(|(kind, names)| names.into_iter().map(move |name| Node::new(kind, name)))
+        .collect();
+
+        AccessGraph { nodes, edges }
+    }
+
+    /// Render the access graph in the DOT format. Effective access edges are
+    /// displayed dashed, to distinguish them from the ones defined explicitly.
+    pub fn to_dot(&self) -> Result<String> {
+        let mut s = String::new();
+        writeln!(s, "digraph access {{")?;
+        writeln!(s, "  rankdir=LR;")?;
+        for node in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::User => "ellipse",
+                NodeKind::Team => "box",
+                NodeKind::Repository => "folder",
+            };
+            writeln!(
+                s,
+                "  \"{}\" [label=\"{}\", shape={shape}];",
+                escape(&node.id),
+                escape(&node.name)
+            )?;
+        }
+        for edge in &self.edges {
+            let label = match &edge.role {
+                Some(role) => role.to_string(),
+                None => edge.kind.to_string(),
+            };
+            let style = if edge.kind == EdgeKind::EffectiveAccess {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            writeln!(
+                s,
+                "  \"{}\" -> \"{}\" [label=\"{label}\"{style}];",
+                escape(&edge.from),
+                escape(&edge.to)
+            )?;
+        }
+        writeln!(s, "}}")?;
+        Ok(s)
+    }
+}
+
+/// Node of the access graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub id: String,
+    pub kind: NodeKind,
+    pub name: String,
+}
+
+impl Node {
+    /// Create a new node of the kind provided.
+    fn new(kind: NodeKind, name: String) -> Self {
+        Node {
+            id: node_id(kind, &name),
+            kind,
+            name,
+        }
+    }
+}
+
+/// Kind of entity a node represents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    User,
+    Team,
+    Repository,
+}
+
+/// Edge of the access graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+}
+
+impl Edge {
+    /// Create a new edge of the kind provided.
+    fn new(from: String, to: String, kind: EdgeKind, role: Option<Role>) -> Self {
+        Edge { from, to, kind, role }
+    }
+}
+
+/// Kind of relationship an edge represents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// User is a maintainer of the team.
+    Maintainer,
+    /// User is a member of the team.
+    Member,
+    /// Team has access to the repository.
+    TeamAccess,
+    /// User is a collaborator of the repository.
+    Collaborator,
+    /// Effective role of the user in the repository, considering both the
+    /// teams and the collaborators with access to it.
+    EffectiveAccess,
+}
+
+impl fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdgeKind::Maintainer => write!(f, "maintainer"),
+            EdgeKind::Member => write!(f, "member"),
+            EdgeKind::TeamAccess => write!(f, "team_access"),
+            EdgeKind::Collaborator => write!(f, "collaborator"),
+            EdgeKind::EffectiveAccess => write!(f, "effective_access"),
+        }
+    }
+}
+
+/// Build the id of the node of the kind and name provided. Ids are prefixed
+/// with the kind, as entities of different kinds may have the same name.
+fn node_id(kind: NodeKind, name: &str) -> String {
+    let prefix = match kind {
+        NodeKind::User => "user",
+        NodeKind::Team => "team",
+        NodeKind::Repository => "repo",
+    };
+    format!("{prefix}:{name}")
+}
+
+/// Escape the value provided so that it can be used in a DOT quoted string.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        directory::{Directory, Team},
+        services::github::state::Repository,
+    };
+
+    fn state() -> State {
+        State {
+            directory: Directory {
+                teams: vec![Team {
+                    name: "team1".to_string(),
+                    maintainers: vec!["user1".to_string()],
+                    members: vec!["user2".to_string()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Admin)])),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn new_access_graph() {
+        let graph = AccessGraph::new(&state(), &RolesOrdering::default());
+        assert_eq!(
+            serde_json::to_value(&graph).unwrap(),
+            json!({
+                "nodes": [
+                    { "id": "user:user1", "kind": "user", "name": "user1" },
+                    { "id": "user:user2", "kind": "user", "name": "user2" },
+                    { "id": "team:team1", "kind": "team", "name": "team1" },
+                    { "id": "repo:repo1", "kind": "repository", "name": "repo1" },
+                ],
+                "edges": [
+                    { "from": "user:user1", "to": "team:team1", "kind": "maintainer" },
+                    { "from": "user:user2", "to": "team:team1", "kind": "member" },
+                    { "from": "team:team1", "to": "repo:repo1", "kind": "team_access", "role": "write" },
+                    { "from": "user:user1", "to": "repo:repo1", "kind": "collaborator", "role": "admin" },
+                    { "from": "user:user1", "to": "repo:repo1", "kind": "effective_access", "role": "admin" },
+                    { "from": "user:user2", "to": "repo:repo1", "kind": "effective_access", "role": "write" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn access_graph_to_dot() {
+        let graph = AccessGraph::new(&state(), &RolesOrdering::default());
+        assert_eq!(
+            graph.to_dot().unwrap(),
+            r#"digraph access {
+  rankdir=LR;
+  "user:user1" [label="user1", shape=ellipse];
+  "user:user2" [label="user2", shape=ellipse];
+  "team:team1" [label="team1", shape=box];
+  "repo:repo1" [label="repo1", shape=folder];
+  "user:user1" -> "team:team1" [label="maintainer"];
+  "user:user2" -> "team:team1" [label="member"];
+  "team:team1" -> "repo:repo1" [label="write"];
+  "user:user1" -> "repo:repo1" [label="admin"];
+  "user:user1" -> "repo:repo1" [label="admin", style=dashed];
+  "user:user2" -> "repo:repo1" [label="write", style=dashed];
+}
+"#
+        );
+    }
+}
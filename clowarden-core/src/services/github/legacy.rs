@@ -3,14 +3,18 @@
 //! state instances from the legacy configuration.
 
 pub(crate) mod sheriff {
+    use std::collections::HashMap;
+
     use anyhow::{format_err, Context, Error, Result};
     use serde::{Deserialize, Serialize};
+    use serde_yaml::Value;
 
     use crate::{
-        directory::legacy::VALID_TEAM_NAME,
+        cfg::Legacy,
+        directory::legacy::{validate_permissions_not_empty, VALID_TEAM_NAME},
         github::{DynGH, Source},
         multierror::MultiError,
-        services::github::state::Repository,
+        services::github::state::{Repository, Role},
     };
 
     /// Sheriff configuration.
@@ -22,13 +26,79 @@ pub(crate) mod sheriff {
 
     impl Cfg {
         /// Get sheriff configuration.
-        pub(crate) async fn get(gh: DynGH, src: &Source, path: &str) -> Result<Self> {
-            let content =
-                gh.get_file_content(src, path).await.context("error getting sheriff permissions file")?;
-            let cfg: Cfg = serde_yaml::from_str(&content)
+        pub(crate) async fn get(
+            gh: DynGH,
+            src: &Source,
+            legacy: &Legacy,
+            default_team_role: &Role,
+        ) -> Result<Self> {
+            let content = gh
+                .get_file_content(src, &legacy.sheriff_permissions_path)
+                .await
+                .context("error getting sheriff permissions file")?;
+            let cfg = Cfg::from_str(&content, default_team_role, &legacy.role_aliases)?;
+            cfg.validate()?;
+            Ok(cfg)
+        }
+
+        /// Parse sheriff configuration from the content provided. Teams listed
+        /// in repositories without an explicit role will be assigned the
+        /// default team role provided. Roles found in the role aliases provided
+        /// will be replaced by the role they map to.
+        fn from_str(
+            content: &str,
+            default_team_role: &Role,
+            role_aliases: &HashMap<String, Role>,
+        ) -> Result<Self> {
+            validate_permissions_not_empty(content)?;
+            let mut value: Value = serde_yaml::from_str(content)
+                .map_err(Error::new)
+                .context("error parsing permissions file")?;
+            let mut merr = MultiError::new(Some("invalid github service configuration".to_string()));
+            if let Some(repositories) = value.get_mut("repositories").and_then(Value::as_sequence_mut) {
+                for (i, repo) in repositories.iter_mut().enumerate() {
+                    let id = match repo.get("name").and_then(Value::as_str) {
+                        Some(name) if !name.is_empty() => name.to_string(),
+                        _ => format!("{i}"),
+                    };
+                    if let Some(teams) = repo.get_mut("teams").and_then(Value::as_mapping_mut) {
+                        for (_, role) in teams.iter_mut() {
+                            if role.is_null() {
+                                *role = Value::String(default_team_role.to_string());
+                            }
+                        }
+                    }
+                    for (field, kind) in [
+                        ("teams", "team"),
+                        ("flattened_teams", "team"),
+                        ("collaborators", "user"),
+                        ("external_collaborators", "user"),
+                    ] {
+                        let Some(entries) = repo.get_mut(field).and_then(Value::as_mapping_mut) else {
+                            continue;
+                        };
+                        for (name, role) in entries.iter_mut() {
+                            let Some(role_name) = role.as_str() else {
+                                continue;
+                            };
+                            if let Some(aliased_role) = role_aliases.get(role_name) {
+                                *role = Value::String(aliased_role.to_string());
+                            } else if role_name.parse::<Role>().is_err() {
+                                merr.push(format_err!(
+                                    "repo[{id}]: {kind}[{}]: invalid role {role_name} (no alias defined for it)",
+                                    name.as_str().unwrap_or_default()
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            if merr.contains_errors() {
+                return Err(merr.into());
+            }
+            let cfg: Cfg = serde_yaml::from_value(value)
                 .map_err(Error::new)
                 .context("error parsing permissions file")?;
-            cfg.validate()?;
             Ok(cfg)
         }
 
@@ -82,4 +152,104 @@ pub(crate) mod sheriff {
             Ok(())
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::BTreeMap;
+
+        use super::*;
+
+        #[test]
+        fn from_str_team_without_role_gets_default_role() {
+            let content = r"
+repositories:
+  - name: repo1
+    teams:
+      team1:
+      team2: admin
+";
+            let cfg = Cfg::from_str(content, &Role::Write, &HashMap::new()).unwrap();
+            assert_eq!(
+                cfg.repositories[0].teams,
+                Some(BTreeMap::from([
+                    ("team1".to_string(), Role::Write),
+                    ("team2".to_string(), Role::Admin),
+                ]))
+            );
+        }
+
+        #[test]
+        fn from_str_team_without_role_gets_read_role_by_default() {
+            let content = r"
+repositories:
+  - name: repo1
+    teams:
+      team1:
+";
+            let cfg = Cfg::from_str(content, &Role::default(), &HashMap::new()).unwrap();
+            assert_eq!(
+                cfg.repositories[0].teams,
+                Some(BTreeMap::from([("team1".to_string(), Role::Read)]))
+            );
+        }
+
+        #[test]
+        fn from_str_aliased_roles_are_resolved() {
+            let content = r"
+repositories:
+  - name: repo1
+    teams:
+      team1: push
+      team2: admin
+    external_collaborators:
+      user1: pull
+";
+            let role_aliases = HashMap::from([
+                ("push".to_string(), Role::Write),
+                ("pull".to_string(), Role::Read),
+            ]);
+            let cfg = Cfg::from_str(content, &Role::default(), &role_aliases).unwrap();
+            assert_eq!(
+                cfg.repositories[0].teams,
+                Some(BTreeMap::from([
+                    ("team1".to_string(), Role::Write),
+                    ("team2".to_string(), Role::Admin),
+                ]))
+            );
+            assert_eq!(
+                cfg.repositories[0].collaborators,
+                Some(BTreeMap::from([("user1".to_string(), Role::Read)]))
+            );
+        }
+
+        #[test]
+        fn from_str_unmapped_roles_are_reported() {
+            let content = r"
+repositories:
+  - name: repo1
+    teams:
+      team1: push
+    external_collaborators:
+      user1: pull
+";
+            let role_aliases = HashMap::from([("push".to_string(), Role::Write)]);
+            let err = Cfg::from_str(content, &Role::default(), &role_aliases).unwrap_err();
+            let merr = err.downcast_ref::<MultiError>().unwrap();
+            assert_eq!(
+                merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+                vec!["repo[repo1]: user[user1]: invalid role pull (no alias defined for it)".to_string()]
+            );
+        }
+
+        #[test]
+        fn from_str_empty_permissions_file() {
+            for content in ["", "# comment\n", "repositories: []\n"] {
+                let err = Cfg::from_str(content, &Role::default(), &HashMap::new()).unwrap_err();
+                assert_eq!(
+                    err.to_string(),
+                    "permissions file is empty: it must define some teams or repositories"
+                );
+            }
+        }
+    }
 }
@@ -1,29 +1,45 @@
 //! This module contains the implementation of the GitHub service handler.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use anyhow::{format_err, Context, Result};
 use as_any::Downcast;
 use async_trait::async_trait;
+use futures::{
+    stream::{self, StreamExt},
+    try_join,
+};
 use state::Changes;
-use tracing::debug;
+use tracing::{debug, info, warn};
 
 use crate::{
-    cfg::Organization,
-    directory::{DirectoryChange, UserName},
+    cfg::{Organization, OrganizationRunnerGroup, OrganizationSetting, ValidationCheck, ValidationExemption},
+    directory::{legacy::sheriff::Locations, DirectoryChange, TeamName, UserName},
     github::{DynGH, Source},
-    multierror::MultiError,
-    services::ChangeApplied,
+    multierror::{exempt_errors, CheckError, MultiError},
+    services::{ChangeApplied, ChangeChecked},
 };
 
-use super::{BaseRefConfigStatus, ChangesApplied, ChangesSummary, DynChange, ServiceHandler};
+use super::{
+    BaseRefConfigStatus, Change, ChangeDetails, ChangeExplanation, ChangesApplied, ChangesChecked,
+    ChangesSummary, DynChange, DynReconcileCheckpoint, LocatedChange, ReconcileOutput, ServiceHandler,
+    StateSnapshot,
+};
 
 use self::{
-    service::{Ctx, DynSvc},
-    state::{RepositoryChange, RepositoryInvitationId, RepositoryName},
+    service::{Ctx, DynSvc, OrgWebhook},
+    state::{
+        codeowners_content, codeowners_owners, CollaboratorInvitation, DiscussionSettings, ExplainedChanges,
+        OrganizationChange, PendingRemoval, RepositoriesSnapshot, Repository, RepositoryArchivedChange,
+        RepositoryChange, RepositoryInvitationId, RepositoryName, Role, TeamHierarchyChange,
+        TeamRetirementChange, DEFAULT_DEPENDABOT_CONFIG, DEPENDABOT_CONFIG_PATHS, VALID_USER_NAME,
+    },
 };
 
+pub mod graph;
 mod legacy;
+#[cfg(test)]
+mod recording;
 pub mod service;
 pub mod state;
 pub use state::State;
@@ -31,65 +47,399 @@ pub use state::State;
 /// GitHub's service name.
 pub const SERVICE_NAME: &str = "github";
 
+/// Maximum number of changes to the teams access to repositories applied
+/// concurrently.
+const TEAMS_ACCESS_CONCURRENCY: usize = 5;
+
 /// GitHub's service handler.
 pub struct Handler {
     gh: DynGH,
     svc: DynSvc,
+    checkpoint: Option<DynReconcileCheckpoint>,
 }
 
 impl Handler {
     /// Create a new Handler instance.
     pub fn new(gh: DynGH, svc: DynSvc) -> Self {
-        Self { gh, svc }
+        Self {
+            gh,
+            svc,
+            checkpoint: None,
+        }
     }
 
-    /// Helper function to get the invitation id for a given user in a
-    /// repository (when available).
-    async fn get_repository_invitation(
+    /// Register the changes applied in the checkpoint provided as they are
+    /// applied, so that reconciliations can be resumed if interrupted.
+    #[must_use]
+    pub fn with_checkpoint(mut self, checkpoint: DynReconcileCheckpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Check if the changes needed so that the actual state matches the
+    /// desired state could be applied, without applying them (dry apply).
+    /// Each change is verified against the service (i.e. target resources
+    /// exist and we have permissions to modify them), which allows detecting
+    /// some problems that cannot be found by just comparing both states.
+    pub async fn dry_apply(&self, org: &Organization, ctx: &Ctx, src: &Source) -> Result<ChangesChecked> {
+        // Get changes between the actual and the desired state
+        let (_, _, changes) = self.get_changes(org, ctx, src, None).await?;
+
+        // Get some resources from the service used to check the changes
+        let resources = DryApplyResources {
+            teams: self.svc.list_teams(ctx).await?.into_iter().map(|t| t.slug).collect(),
+            repositories: self
+                .svc
+                .list_repositories(ctx)
+                .await?
+                .into_iter()
+                .map(|r| (r.name, r.archived))
+                .collect(),
+        };
+
+        // Check changes
+        let mut changes_checked = vec![];
+        for change in changes.directory {
+            let err = self.check_directory_change(ctx, &resources, &change).await;
+            changes_checked.push(ChangeChecked {
+                change: Box::new(change),
+                error: err,
+            });
+        }
+        for change in changes.repositories {
+            let err = self.check_repository_change(ctx, &resources, &change).await;
+            changes_checked.push(ChangeChecked {
+                change: Box::new(change),
+                error: err,
+            });
+        }
+
+        Ok(changes_checked)
+    }
+
+    /// Get changes between the actual and the desired state. The actual and
+    /// the desired states are returned as well.
+    async fn get_changes(
         &self,
+        org: &Organization,
         ctx: &Ctx,
-        repo_name: &RepositoryName,
-        user_name: &UserName,
-    ) -> Result<Option<RepositoryInvitationId>> {
-        let invitation_id =
-            self.svc.list_repository_invitations(ctx, repo_name).await?.iter().find_map(|i| {
-                if i.invitee.is_some() && &i.invitee.as_ref().unwrap().login == user_name {
-                    return Some(i.id);
+        src: &Source,
+        snapshot: Option<&RepositoriesSnapshot>,
+    ) -> Result<(State, State, Changes)> {
+        let (actual_state, desired_state, changes) =
+            self.get_explained_changes(org, ctx, src, snapshot).await?;
+        Ok((actual_state, desired_state, changes.into()))
+    }
+
+    /// Get changes between the actual and the desired state, along with the
+    /// values compared to compute each of them. The actual and the desired
+    /// states are returned as well.
+    pub async fn get_explained_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        src: &Source,
+        snapshot: Option<&RepositoriesSnapshot>,
+    ) -> Result<(State, State, ExplainedChanges)> {
+        let mut actual_state = State::new_from_service_incremental(self.svc.clone(), ctx, snapshot)
+            .await
+            .context("error getting actual state from service")?;
+        let desired_state = State::new_from_config(self.gh.clone(), self.svc.clone(), org, ctx, src)
+            .await
+            .context("error getting desired state from configuration")?;
+        actual_state
+            .fetch_opt_in_settings(self.svc.clone(), ctx, org, &desired_state)
+            .await
+            .context("error getting actual state from service")?;
+        let mut changes = actual_state.diff_explained(&desired_state);
+        changes.repositories.extend(
+            self.get_codeowners_changes(ctx, &actual_state, &desired_state)
+                .await
+                .context("error getting CODEOWNERS files changes")?,
+        );
+        changes.repositories.extend(
+            self.get_dependabot_config_changes(org, ctx, &actual_state, &desired_state)
+                .await
+                .context("error getting Dependabot configuration files changes")?,
+        );
+        debug!(?changes, "changes between the actual and the desired state");
+        Ok((actual_state, desired_state, changes))
+    }
+
+    /// Update the collaborators invitations tracked provided with the ones
+    /// declined since they were sent. Invitations pending that cannot be found
+    /// in the actual state anymore have been declined, whereas the accepted
+    /// ones are not tracked anymore. When the organization limits the number
+    /// of declined invitations, collaborators that have reached it are not
+    /// invited again.
+    async fn track_invitations(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        actual_state: &State,
+        changes: &mut Changes,
+        invitations: Vec<CollaboratorInvitation>,
+    ) -> Result<Vec<CollaboratorInvitation>> {
+        let mut invitations_tracked = vec![];
+        for mut invitation in invitations {
+            // Invitations to repositories that no longer exist are discarded
+            let repo = actual_state.repositories.iter().find(|r| r.name == invitation.repository);
+            let Some(repo) = repo else {
+                continue;
+            };
+            let is_collaborator =
+                repo.collaborators.as_ref().is_some_and(|c| c.contains_key(&invitation.user_name));
+            if is_collaborator {
+                // Collaborators include pending invitations
+                if !invitation.pending
+                    || self
+                        .get_repository_invitation(ctx, &invitation.repository, &invitation.user_name)
+                        .await?
+                        .is_none()
+                {
+                    continue;
                 }
-                None
+            } else if invitation.pending {
+                invitation.pending = false;
+                invitation.declines += 1;
+            }
+            invitations_tracked.push(invitation);
+        }
+
+        // Do not invite again collaborators that have declined too many times
+        if let Some(max_declined_invitations) = org.max_declined_invitations {
+            changes.repositories.retain(|change| {
+                let RepositoryChange::CollaboratorAdded(repo_name, user_name, _) = change else {
+                    return true;
+                };
+                let declines = invitations_tracked
+                    .iter()
+                    .find(|i| &i.repository == repo_name && &i.user_name == user_name)
+                    .map_or(0, |i| i.declines);
+                if declines >= max_declined_invitations {
+                    warn!(
+                        repo = repo_name,
+                        user = user_name,
+                        declines,
+                        "collaborator declined the invitation too many times, not inviting again"
+                    );
+                    return false;
+                }
+                true
             });
-        Ok(invitation_id)
+        }
+
+        Ok(invitations_tracked)
     }
 
-    /// Validate users found in some of the changes provided.
-    async fn validate_users(&self, ctx: &Ctx, changes: &Changes) -> Result<()> {
-        let mut merr = MultiError::new(Some("invalid github service configuration".to_string()));
+    /// Get the changes needed so that the organization in the service matches
+    /// the organization settings defined in the configuration. The webhooks
+    /// registered in the service are returned as well, as they are needed to
+    /// apply the changes.
+    async fn get_organization_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        src: &Source,
+    ) -> Result<(Vec<OrgWebhook>, Vec<OrganizationChange>)> {
+        let (webhooks_in_svc, mut changes) = self.get_webhooks_changes(org, ctx).await?;
+        changes.extend(self.get_community_health_files_changes(org, ctx, src).await?);
+        changes.extend(self.get_settings_changes(org, ctx).await?);
+        changes.extend(self.get_required_workflows_changes(org, ctx).await?);
+        changes.extend(self.get_secrets_changes(org, ctx).await?);
+        changes.extend(self.get_runner_groups_changes(org, ctx).await?);
+        changes.extend(self.get_service_accounts_changes(org, ctx).await?);
+        changes.extend(self.get_verified_domains_gate_changes(org, ctx).await?);
+        changes.extend(self.get_blocked_users_changes(org, ctx).await?);
+        changes.extend(self.get_security_manager_teams_changes(org, ctx).await?);
+        Ok((webhooks_in_svc, changes))
+    }
 
-        // Collect users to validate from changes
-        let mut users_to_validate = HashSet::new();
-        for change in &changes.directory {
-            if let DirectoryChange::TeamMemberAdded(_, user_name) = change {
-                users_to_validate.insert(user_name);
+    /// Get the changes needed so that the organization webhooks in the service
+    /// match the ones defined in the configuration (if any). Webhooks are
+    /// identified by their url. The webhooks registered in the service are
+    /// returned as well, as they are needed to apply the changes.
+    async fn get_webhooks_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+    ) -> Result<(Vec<OrgWebhook>, Vec<OrganizationChange>)> {
+        let Some(webhooks) = &org.webhooks else {
+            return Ok((vec![], vec![]));
+        };
+        let webhooks_in_svc =
+            self.svc.list_org_webhooks(ctx).await.context("error listing organization webhooks")?;
+
+        // Webhooks added or updated
+        let mut changes = vec![];
+        for webhook in webhooks {
+            match webhooks_in_svc.iter().find(|w| w.url == webhook.url) {
+                Some(webhook_in_svc) => {
+                    let events: HashSet<&String> = webhook.events.iter().collect();
+                    let events_in_svc: HashSet<&String> = webhook_in_svc.events.iter().collect();
+                    if events != events_in_svc
+                        || webhook.content_type() != webhook_in_svc.content_type
+                        || webhook.is_active() != webhook_in_svc.active
+                    {
+                        changes.push(OrganizationChange::WebhookUpdated(webhook.clone()));
+                    }
+                }
+                None => changes.push(OrganizationChange::WebhookAdded(webhook.clone())),
             }
         }
-        for change in &changes.repositories {
-            if let RepositoryChange::CollaboratorAdded(_, user_name, _) = change {
-                users_to_validate.insert(user_name);
+
+        // Webhooks removed (only when explicitly enabled for the organization)
+        if org.remove_undeclared_webhooks {
+            let mut urls_removed = HashSet::new();
+            for webhook_in_svc in &webhooks_in_svc {
+                if !webhooks.iter().any(|w| w.url == webhook_in_svc.url)
+                    && urls_removed.insert(&webhook_in_svc.url)
+                {
+                    changes.push(OrganizationChange::WebhookRemoved(webhook_in_svc.url.clone()));
+                }
             }
         }
 
-        // Validate users collected
-        for user_name in users_to_validate {
-            match self.svc.get_user_login(ctx, user_name).await {
-                Ok(valid_user_name) => {
-                    if user_name != &valid_user_name {
-                        merr.push(format_err!(
-                            "user[{user_name}]: invalid username, should be {valid_user_name}",
-                        ));
-                    }
+        Ok((webhooks_in_svc, changes))
+    }
+
+    /// Get the changes needed so that the CODEOWNERS file of the repositories
+    /// that have it managed by CLOWarden matches the one generated from the
+    /// teams with write access to them. Repositories that don't exist yet are
+    /// skipped, their CODEOWNERS file will be set up in the next reconcile.
+    async fn get_codeowners_changes(
+        &self,
+        ctx: &Ctx,
+        actual_state: &State,
+        desired_state: &State,
+    ) -> Result<Vec<(RepositoryChange, ChangeExplanation)>> {
+        let mut changes = vec![];
+        for repo in &desired_state.repositories {
+            let Some(codeowners) = &repo.codeowners else {
+                continue;
+            };
+            if !actual_state.repositories.iter().any(|r| r.name == repo.name) {
+                continue;
+            }
+            let owners = codeowners_owners(repo, &ctx.org);
+            let content = self
+                .svc
+                .get_repository_codeowners(ctx, &repo.name)
+                .await
+                .context(format!("error getting repository {} CODEOWNERS file", repo.name))?;
+            if content.as_deref() != Some(codeowners_content(&owners).as_str()) {
+                let explanation = match content {
+                    Some(_) => ChangeExplanation::updated("codeowners", "outdated", owners.join(" ")),
+                    None => ChangeExplanation::added("codeowners", owners.join(" ")),
+                };
+                changes.push((
+                    RepositoryChange::CodeownersUpdated(repo.name.clone(), owners, codeowners.update_mode),
+                    explanation,
+                ));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Get the changes needed so that the repositories defined in the
+    /// configuration have a Dependabot configuration file, when required by
+    /// the organization. Repositories missing it are only reported unless
+    /// the policy is enforced, in which case a default one is added to them.
+    /// Repositories that don't exist yet are skipped (they will be checked in
+    /// the next reconcile).
+    async fn get_dependabot_config_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        actual_state: &State,
+        desired_state: &State,
+    ) -> Result<Vec<(RepositoryChange, ChangeExplanation)>> {
+        let Some(dependabot_config) = &org.dependabot_config else {
+            return Ok(vec![]);
+        };
+
+        let mut changes = vec![];
+        for repo in &desired_state.repositories {
+            if !actual_state.repositories.iter().any(|r| r.name == repo.name) {
+                continue;
+            }
+            let mut found = false;
+            for path in DEPENDABOT_CONFIG_PATHS {
+                found = self.svc.repository_file_exists(ctx, &repo.name, path, None).await.context(
+                    format!("error checking repository {} Dependabot config file", repo.name),
+                )?;
+                if found {
+                    break;
                 }
+            }
+            if found {
+                continue;
+            }
+            if dependabot_config.enforce {
+                changes.push((
+                    RepositoryChange::DependabotConfigAdded(repo.name.clone()),
+                    ChangeExplanation::added("dependabot config", DEPENDABOT_CONFIG_PATHS[0]),
+                ));
+            } else {
+                warn!(
+                    repo = repo.name,
+                    "repository does not have a Dependabot configuration file"
+                );
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Get the changes needed so that the community health files in the
+    /// organization's .github repository match the ones defined in the
+    /// configuration (if any). The expected content of each file is read from
+    /// the configuration repository. Other files in the .github repository are
+    /// not modified.
+    async fn get_community_health_files_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        src: &Source,
+    ) -> Result<Vec<OrganizationChange>> {
+        let Some(files) = &org.community_health_files else {
+            return Ok(vec![]);
+        };
+
+        let mut merr = MultiError::new(Some("invalid community health files".to_string()));
+        let mut changes = vec![];
+        for file in files {
+            let content = match self.gh.get_file_content(src, &file.source).await {
+                Ok(content) => content,
                 Err(err) => {
-                    merr.push(format_err!("user[{user_name}]: error validating username: {err}"));
+                    merr.push(format_err!(
+                        "file[{}]: error getting source file: {err}",
+                        file.path
+                    ));
+                    continue;
+                }
+            };
+            if let Err(err) = validate_community_health_file(&file.path, &content) {
+                merr.push(format_err!("file[{}]: {err}", file.path));
+                continue;
+            }
+            match self
+                .svc
+                .get_org_community_health_file(ctx, &file.path)
+                .await
+                .context(format!("error getting community health file {}", file.path))?
+            {
+                Some(content_in_svc) if content_in_svc == content => {}
+                Some(_) => {
+                    changes.push(OrganizationChange::CommunityHealthFileUpdated(
+                        file.path.clone(),
+                        content,
+                    ));
+                }
+                None => {
+                    changes.push(OrganizationChange::CommunityHealthFileAdded(
+                        file.path.clone(),
+                        content,
+                    ));
                 }
             }
         }
@@ -97,152 +447,3647 @@ impl Handler {
         if merr.contains_errors() {
             return Err(merr.into());
         }
-        Ok(())
+        Ok(changes)
     }
-}
 
-#[async_trait]
-impl ServiceHandler for Handler {
-    /// [ServiceHandler::get_changes_summary]
-    async fn get_changes_summary(&self, org: &Organization, head_src: &Source) -> Result<ChangesSummary> {
-        let ctx = Ctx::from(org);
-        let base_src = Source::from(org);
-        let head_state =
-            State::new_from_config(self.gh.clone(), self.svc.clone(), &org.legacy, &ctx, head_src).await?;
-        let (changes, base_ref_config_status) =
-            match State::new_from_config(self.gh.clone(), self.svc.clone(), &org.legacy, &ctx, &base_src)
-                .await
-            {
-                Ok(base_state) => {
-                    let changes = base_state.diff(&head_state);
-                    self.validate_users(&ctx, &changes).await?;
-                    let repositories_changes = changes
-                        .repositories
-                        .into_iter()
-                        .map(|change| Box::new(change) as DynChange)
-                        .collect();
-                    (repositories_changes, BaseRefConfigStatus::Valid)
-                }
-                Err(_) => (vec![], BaseRefConfigStatus::Invalid),
-            };
+    /// Get the changes needed so that the organization settings match the ones
+    /// defined in the organization's configuration (if any). Settings not
+    /// defined are not managed.
+    async fn get_settings_changes(&self, org: &Organization, ctx: &Ctx) -> Result<Vec<OrganizationChange>> {
+        let Some(settings) = &org.settings else {
+            return Ok(vec![]);
+        };
+        let settings_in_svc =
+            self.svc.get_org_settings(ctx).await.context("error getting organization settings")?;
 
-        Ok(ChangesSummary {
-            changes,
-            base_ref_config_status,
-        })
+        let mut changes = vec![];
+        for setting in OrganizationSetting::ALL {
+            if let Some(value) = settings.get(setting) {
+                if settings_in_svc.get(setting) != Some(value) {
+                    changes.push(OrganizationChange::SettingUpdated(setting, value));
+                }
+            }
+        }
+        if let Some(permission) = settings.team_creation_permission {
+            if settings_in_svc.team_creation_permission != Some(permission) {
+                changes.push(OrganizationChange::TeamCreationPermissionUpdated(permission));
+            }
+        }
+        Ok(changes)
     }
 
-    /// [ServiceHandler::reconcile]
-    async fn reconcile(&self, org: &Organization) -> Result<ChangesApplied> {
-        // Get changes between the actual and the desired state
-        let ctx = Ctx::from(org);
-        let src = Source::from(org);
-        let actual_state = State::new_from_service(self.svc.clone(), &ctx)
-            .await
-            .context("error getting actual state from service")?;
-        let desired_state =
-            State::new_from_config(self.gh.clone(), self.svc.clone(), &org.legacy, &ctx, &src)
-                .await
-                .context("error getting desired state from configuration")?;
-        let changes = actual_state.diff(&desired_state);
-        debug!(?changes, "changes between the actual and the desired state");
+    /// Get the changes needed so that the required workflows enforced in the
+    /// organization match the ones defined in its configuration (if any). Only
+    /// the required workflows managed by CLOWarden are considered. The
+    /// workflow files referenced must exist in their repositories.
+    async fn get_required_workflows_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+    ) -> Result<Vec<OrganizationChange>> {
+        let Some(workflows) = &org.required_workflows else {
+            return Ok(vec![]);
+        };
 
-        // Apply changes needed to match desired state
-        let mut changes_applied = vec![];
+        // Check the workflow files referenced exist
+        let mut merr = MultiError::new(Some("invalid required workflows".to_string()));
+        for workflow in workflows {
+            let exists = self
+                .svc
+                .repository_file_exists(ctx, &workflow.repository, &workflow.path, workflow.ref_.clone())
+                .await
+                .context(format!(
+                    "error checking required workflow {} file",
+                    workflow.name()
+                ))?;
+            if !exists {
+                merr.push(format_err!(
+                    "workflow[{}]: workflow file not found in repository {}",
+                    workflow.name(),
+                    workflow.repository
+                ));
+            }
+        }
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
 
-        // Apply directory changes
-        let ctx = Ctx::from(org);
-        for change in changes.directory {
-            let err = match &change {
-                DirectoryChange::TeamAdded(team) => self.svc.add_team(&ctx, team).await.err(),
-                DirectoryChange::TeamRemoved(team_name) => self.svc.remove_team(&ctx, team_name).await.err(),
-                DirectoryChange::TeamMaintainerAdded(team_name, user_name) => {
-                    self.svc.add_team_maintainer(&ctx, team_name, user_name).await.err()
-                }
-                DirectoryChange::TeamMaintainerRemoved(team_name, user_name) => {
-                    self.svc.remove_team_maintainer(&ctx, team_name, user_name).await.err()
-                }
-                DirectoryChange::TeamMemberAdded(team_name, user_name) => {
-                    self.svc.add_team_member(&ctx, team_name, user_name).await.err()
-                }
-                DirectoryChange::TeamMemberRemoved(team_name, user_name) => {
-                    self.svc.remove_team_member(&ctx, team_name, user_name).await.err()
+        // Required workflows added or updated
+        let workflows_in_svc = self
+            .svc
+            .list_org_required_workflows(ctx)
+            .await
+            .context("error listing organization required workflows")?;
+        let mut changes = vec![];
+        for workflow in workflows {
+            match workflows_in_svc.iter().find(|w| w.workflow.name() == workflow.name()) {
+                Some(workflow_in_svc) => {
+                    if !workflow.is_equivalent(&workflow_in_svc.workflow) {
+                        changes.push(OrganizationChange::RequiredWorkflowUpdated(
+                            workflow_in_svc.id,
+                            workflow.clone(),
+                        ));
+                    }
                 }
-                DirectoryChange::UserAdded(_)
-                | DirectoryChange::UserRemoved(_)
-                | DirectoryChange::UserUpdated(_) => continue,
-            };
-            changes_applied.push(ChangeApplied {
-                change: Box::new(change),
-                error: err.map(|e| e.to_string()),
-                applied_at: time::OffsetDateTime::now_utc(),
-            });
+                None => changes.push(OrganizationChange::RequiredWorkflowAdded(workflow.clone())),
+            }
         }
 
-        // Apply repositories changes
-        'changes_repositories: for change in changes.repositories {
-            let err = match &change {
-                RepositoryChange::RepositoryAdded(repo) => self.svc.add_repository(&ctx, repo).await.err(),
-                RepositoryChange::TeamAdded(repo_name, team_name, role) => {
-                    self.svc.add_repository_team(&ctx, repo_name, team_name, role).await.err()
-                }
-                RepositoryChange::TeamRemoved(repo_name, team_name) => {
-                    // If the team has just been deleted from the directory in
-                    // this reconciliation, there is no need to remove it from
-                    // the repository as this will be done automatically when
-                    // the team is deleted from GitHub
-                    for entry in &changes_applied {
-                        let change = (*entry.change).downcast_ref::<DirectoryChange>();
-                        if let Some(DirectoryChange::TeamRemoved(team_removed_name)) = change {
-                            if team_name == team_removed_name {
-                                continue 'changes_repositories;
-                            }
-                        }
-                    }
+        // Required workflows removed
+        for workflow_in_svc in &workflows_in_svc {
+            if !workflows.iter().any(|w| w.name() == workflow_in_svc.workflow.name()) {
+                changes.push(OrganizationChange::RequiredWorkflowRemoved(
+                    workflow_in_svc.id,
+                    workflow_in_svc.workflow.name(),
+                ));
+            }
+        }
 
-                    self.svc.remove_repository_team(&ctx, repo_name, team_name).await.err()
-                }
-                RepositoryChange::TeamRoleUpdated(repo_name, team_name, role) => {
-                    self.svc.update_repository_team_role(&ctx, repo_name, team_name, role).await.err()
+        Ok(changes)
+    }
+
+    /// Get the changes needed so that the repositories selected to access the
+    /// organization secrets match the ones defined in its configuration (if
+    /// any). Secrets must exist and use the selected repositories visibility,
+    /// and the repositories referenced must exist in the organization.
+    async fn get_secrets_changes(&self, org: &Organization, ctx: &Ctx) -> Result<Vec<OrganizationChange>> {
+        let Some(secrets) = &org.secrets else {
+            return Ok(vec![]);
+        };
+
+        let mut merr = MultiError::new(Some("invalid organization secrets".to_string()));
+        let mut changes = vec![];
+        for secret in secrets {
+            // Check secret is valid
+            let visibility = self
+                .svc
+                .get_org_secret_visibility(ctx, &secret.name)
+                .await
+                .context(format!("error getting organization secret {}", secret.name))?;
+            match visibility.as_deref() {
+                Some("selected") => {}
+                Some(visibility) => {
+                    merr.push(format_err!(
+                        "secret[{}]: visibility must be selected (found {visibility})",
+                        secret.name
+                    ));
+                    continue;
                 }
-                RepositoryChange::CollaboratorAdded(repo_name, user_name, role) => {
-                    self.svc.add_repository_collaborator(&ctx, repo_name, user_name, role).await.err()
+                None => {
+                    merr.push(format_err!("secret[{}]: secret does not exist", secret.name));
+                    continue;
                 }
-                RepositoryChange::CollaboratorRemoved(repo_name, user_name) => {
-                    if let Some(invitation_id) =
-                        self.get_repository_invitation(&ctx, repo_name, user_name).await?
-                    {
-                        self.svc.remove_repository_invitation(&ctx, repo_name, invitation_id).await.err()
-                    } else {
-                        self.svc.remove_repository_collaborator(&ctx, repo_name, user_name).await.err()
-                    }
+            }
+            let mut repos_found = true;
+            for repo_name in &secret.repositories {
+                if !self.svc.repository_exists(ctx, repo_name).await? {
+                    merr.push(format_err!(
+                        "secret[{}]: repository {repo_name} does not exist",
+                        secret.name
+                    ));
+                    repos_found = false;
                 }
-                RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, role) => {
-                    if let Some(invitation_id) =
-                        self.get_repository_invitation(&ctx, repo_name, user_name).await?
-                    {
-                        self.svc
-                            .update_repository_invitation(&ctx, repo_name, invitation_id, role)
-                            .await
-                            .err()
-                    } else {
-                        self.svc
-                            .update_repository_collaborator_role(&ctx, repo_name, user_name, role)
-                            .await
-                            .err()
-                    }
+            }
+            if !repos_found {
+                continue;
+            }
+
+            // Check if the selected repositories need to be updated
+            let repos_in_svc: HashSet<RepositoryName> = self
+                .svc
+                .list_org_secret_repositories(ctx, &secret.name)
+                .await
+                .context(format!(
+                    "error listing organization secret {} repositories",
+                    secret.name
+                ))?
+                .into_iter()
+                .collect();
+            let repos: HashSet<RepositoryName> = secret.repositories.iter().cloned().collect();
+            if repos != repos_in_svc {
+                let mut repos_names: Vec<RepositoryName> = repos.into_iter().collect();
+                repos_names.sort();
+                changes.push(OrganizationChange::SecretRepositoriesUpdated(
+                    secret.name.clone(),
+                    repos_names,
+                ));
+            }
+        }
+
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
+        Ok(changes)
+    }
+
+    /// Get the changes needed so that the organization's self-hosted runner
+    /// groups defined in its configuration (if any) exist and can be used by
+    /// the repositories listed. Runner groups not defined are left untouched.
+    /// Existing runner groups must use the selected repositories visibility,
+    /// and the repositories referenced must exist in the organization.
+    async fn get_runner_groups_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+    ) -> Result<Vec<OrganizationChange>> {
+        let Some(runner_groups) = &org.runner_groups else {
+            return Ok(vec![]);
+        };
+
+        let runner_groups_in_svc = self
+            .svc
+            .list_org_runner_groups(ctx)
+            .await
+            .context("error listing organization runner groups")?;
+        let mut merr = MultiError::new(Some("invalid organization runner groups".to_string()));
+        let mut changes = vec![];
+        for runner_group in runner_groups {
+            // Check runner group is valid
+            let runner_group_in_svc = runner_groups_in_svc.iter().find(|g| g.name == runner_group.name);
+            if let Some(runner_group_in_svc) = runner_group_in_svc {
+                if runner_group_in_svc.visibility != "selected" {
+                    merr.push(format_err!(
+                        "runner_group[{}]: visibility must be selected (found {})",
+                        runner_group.name,
+                        runner_group_in_svc.visibility
+                    ));
+                    continue;
                 }
-                RepositoryChange::VisibilityUpdated(repo_name, visibility) => {
-                    self.svc.update_repository_visibility(&ctx, repo_name, visibility).await.err()
+            }
+            let mut repos_found = true;
+            for repo_name in &runner_group.repositories {
+                if !self.svc.repository_exists(ctx, repo_name).await? {
+                    merr.push(format_err!(
+                        "runner_group[{}]: repository {repo_name} does not exist",
+                        runner_group.name
+                    ));
+                    repos_found = false;
                 }
+            }
+            if !repos_found {
+                continue;
+            }
+
+            // Check if the runner group needs to be added or its repositories
+            // updated
+            let repos: HashSet<RepositoryName> = runner_group.repositories.iter().cloned().collect();
+            let mut repos_names: Vec<RepositoryName> = repos.iter().cloned().collect();
+            repos_names.sort();
+            let new_runner_group = OrganizationRunnerGroup {
+                name: runner_group.name.clone(),
+                repositories: repos_names,
             };
-            changes_applied.push(ChangeApplied {
-                change: Box::new(change),
-                error: err.map(|e| e.to_string()),
-                applied_at: time::OffsetDateTime::now_utc(),
-            });
+            let Some(runner_group_in_svc) = runner_group_in_svc else {
+                changes.push(OrganizationChange::RunnerGroupAdded(new_runner_group));
+                continue;
+            };
+            let repos_in_svc: HashSet<RepositoryName> = self
+                .svc
+                .list_org_runner_group_repositories(ctx, runner_group_in_svc.id)
+                .await
+                .context(format!(
+                    "error listing runner group {} repositories",
+                    runner_group.name
+                ))?
+                .into_iter()
+                .collect();
+            if repos != repos_in_svc {
+                changes.push(OrganizationChange::RunnerGroupRepositoriesUpdated(
+                    runner_group_in_svc.id,
+                    new_runner_group,
+                ));
+            }
         }
 
-        Ok(changes_applied)
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
+        Ok(changes)
+    }
+
+    /// Get the changes needed so that the organization's service accounts (if
+    /// any) are regular members of the organization. Service accounts that
+    /// are not members yet are invited, and the ones that are admins are
+    /// demoted. Their teams membership is handled as part of the directory.
+    async fn get_service_accounts_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+    ) -> Result<Vec<OrganizationChange>> {
+        let Some(service_accounts) = &org.service_accounts else {
+            return Ok(vec![]);
+        };
+
+        let mut changes = vec![];
+        for service_account in service_accounts {
+            let role =
+                self.svc.get_org_membership_role(ctx, &service_account.login).await.context(format!(
+                    "error getting service account {} membership",
+                    service_account.login
+                ))?;
+            let login = service_account.login.clone();
+            match role.as_deref() {
+                None => changes.push(OrganizationChange::ServiceAccountInvited(login)),
+                Some("admin") => changes.push(OrganizationChange::ServiceAccountDemoted(login)),
+                Some(_) => {}
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Get the changes needed so that the organization's verified domains
+    /// gate matches the one defined in its configuration (if any). The gate
+    /// can only be enabled when the organization has some verified domains
+    /// and all the domains required are among them.
+    async fn get_verified_domains_gate_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+    ) -> Result<Vec<OrganizationChange>> {
+        let Some(gate) = &org.verified_domains_gate else {
+            return Ok(vec![]);
+        };
+        let verified_domains = self
+            .svc
+            .get_org_verified_domains(ctx)
+            .await
+            .context("error getting organization verified domains")?;
+
+        // Check the required domains are verified before enabling the gate
+        if gate.enabled {
+            let mut merr = MultiError::new(Some("invalid verified domains gate".to_string()));
+            if verified_domains.domains.is_empty() {
+                merr.push(format_err!("the organization does not have any verified domains"));
+            }
+            for domain in &gate.required_domains {
+                if !verified_domains.domains.iter().any(|d| d.eq_ignore_ascii_case(domain)) {
+                    merr.push(format_err!(
+                        "required domain {domain} is not verified in the organization"
+                    ));
+                }
+            }
+            if merr.contains_errors() {
+                return Err(merr.into());
+            }
+        }
+
+        if verified_domains.gate_enabled != gate.enabled {
+            return Ok(vec![OrganizationChange::VerifiedDomainsGateUpdated(gate.enabled)]);
+        }
+        Ok(vec![])
+    }
+
+    /// Get the changes needed so that the users blocked from the organization
+    /// match the ones defined in its configuration (if any). Organization
+    /// members can only be blocked when they have been explicitly confirmed,
+    /// and users not listed are only unblocked when requested.
+    async fn get_blocked_users_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+    ) -> Result<Vec<OrganizationChange>> {
+        let Some(blocked_users) = &org.blocked_users else {
+            return Ok(vec![]);
+        };
+
+        // Validate blocked users
+        let members = self.svc.list_org_members(ctx).await.context("error listing organization members")?;
+        let confirmed_members = org.confirmed_blocked_members.as_deref().unwrap_or_default();
+        let mut merr = MultiError::new(Some("invalid blocked users".to_string()));
+        for user_name in blocked_users {
+            if !VALID_USER_NAME.is_match(user_name) {
+                merr.push(format_err!("{user_name} is not a valid user name"));
+                continue;
+            }
+            let is_member = members.iter().any(|m| m.login.eq_ignore_ascii_case(user_name));
+            let is_confirmed = confirmed_members.iter().any(|u| u.eq_ignore_ascii_case(user_name));
+            if is_member && !is_confirmed {
+                merr.push(format_err!(
+                    "{user_name} is an organization member and has not been confirmed to be blocked"
+                ));
+            }
+        }
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
+
+        // Block listed users not blocked yet and, when enabled, unblock the
+        // ones not listed
+        let blocked_users_in_svc = self
+            .svc
+            .list_org_blocked_users(ctx)
+            .await
+            .context("error listing organization blocked users")?;
+        let mut changes = vec![];
+        for user_name in blocked_users {
+            if !blocked_users_in_svc.iter().any(|u| u.eq_ignore_ascii_case(user_name)) {
+                changes.push(OrganizationChange::UserBlocked(user_name.clone()));
+            }
+        }
+        if org.unblock_unlisted_users {
+            for user_name in blocked_users_in_svc {
+                if !blocked_users.iter().any(|u| u.eq_ignore_ascii_case(&user_name)) {
+                    changes.push(OrganizationChange::UserUnblocked(user_name));
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Get the changes needed so that the teams granted the security manager
+    /// role in the organization match the ones defined in its configuration
+    /// (if any). All teams listed must exist in the organization.
+    async fn get_security_manager_teams_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+    ) -> Result<Vec<OrganizationChange>> {
+        let Some(security_manager_teams) = &org.security_manager_teams else {
+            return Ok(vec![]);
+        };
+
+        // Validate security manager teams
+        let mut merr = MultiError::new(Some("invalid security manager teams".to_string()));
+        for team_name in security_manager_teams {
+            if !self.svc.team_exists(ctx, team_name).await? {
+                merr.push(format_err!("team {team_name} does not exist in the organization"));
+            }
+        }
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
+
+        // Grant the role to the teams listed and revoke it from the ones not
+        let teams_in_svc = self
+            .svc
+            .list_org_security_manager_teams(ctx)
+            .await
+            .context("error listing organization security manager teams")?;
+        let mut changes = vec![];
+        for team_name in security_manager_teams {
+            if !teams_in_svc.iter().any(|t| t.eq_ignore_ascii_case(team_name)) {
+                changes.push(OrganizationChange::SecurityManagerTeamAdded(team_name.clone()));
+            }
+        }
+        for team_name in teams_in_svc {
+            if !security_manager_teams.iter().any(|t| t.eq_ignore_ascii_case(&team_name)) {
+                changes.push(OrganizationChange::SecurityManagerTeamRemoved(team_name));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Apply all the changes provided (including the organization ones). When
+    /// the organization is in observe-only mode, the changes are returned as
+    /// planned (without errors) but they are not applied.
+    async fn apply_all_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        webhooks_in_svc: &[OrgWebhook],
+        changes: Changes,
+        organization_changes: Vec<OrganizationChange>,
+    ) -> Result<ChangesApplied> {
+        if org.observe_only {
+            let planned_at = time::OffsetDateTime::now_utc();
+            let changes_planned = changes
+                .directory
+                .into_iter()
+                .map(|change| Box::new(change) as DynChange)
+                .chain(changes.repositories.into_iter().map(|change| Box::new(change) as DynChange))
+                .chain(organization_changes.into_iter().map(|change| Box::new(change) as DynChange))
+                .map(|change| ChangeApplied {
+                    change,
+                    error: None,
+                    applied_at: planned_at,
+                })
+                .collect();
+            return Ok(changes_planned);
+        }
+
+        let mut changes_applied = self.apply_changes(org, ctx, changes).await?;
+        changes_applied
+            .extend(self.apply_organization_changes(ctx, webhooks_in_svc, organization_changes).await);
+        Ok(changes_applied)
+    }
+
+    /// Apply the organization changes provided. The webhooks registered in the
+    /// service are used to locate the ones to update or remove.
+    async fn apply_organization_changes(
+        &self,
+        ctx: &Ctx,
+        webhooks_in_svc: &[OrgWebhook],
+        changes: Vec<OrganizationChange>,
+    ) -> ChangesApplied {
+        let webhook_id = |url: &str| webhooks_in_svc.iter().find(|w| w.url == url).map(|w| w.id);
+
+        let mut changes_applied = vec![];
+        for change in changes {
+            let err = match &change {
+                OrganizationChange::WebhookAdded(webhook) => {
+                    self.svc.add_org_webhook(ctx, webhook).await.err()
+                }
+                OrganizationChange::WebhookUpdated(webhook) => match webhook_id(&webhook.url) {
+                    Some(webhook_id) => self.svc.update_org_webhook(ctx, webhook_id, webhook).await.err(),
+                    None => Some(format_err!("webhook not found")),
+                },
+                OrganizationChange::WebhookRemoved(url) => match webhook_id(url) {
+                    Some(webhook_id) => self.svc.remove_org_webhook(ctx, webhook_id).await.err(),
+                    None => Some(format_err!("webhook not found")),
+                },
+                OrganizationChange::CommunityHealthFileAdded(path, content)
+                | OrganizationChange::CommunityHealthFileUpdated(path, content) => {
+                    self.svc.update_org_community_health_file(ctx, path, content).await.err()
+                }
+                OrganizationChange::SettingUpdated(setting, value) => {
+                    self.svc.update_org_setting(ctx, *setting, *value).await.err()
+                }
+                OrganizationChange::RequiredWorkflowAdded(workflow) => {
+                    self.svc.add_org_required_workflow(ctx, workflow).await.err()
+                }
+                OrganizationChange::RequiredWorkflowUpdated(workflow_id, workflow) => {
+                    self.svc.update_org_required_workflow(ctx, *workflow_id, workflow).await.err()
+                }
+                OrganizationChange::RequiredWorkflowRemoved(workflow_id, _) => {
+                    self.svc.remove_org_required_workflow(ctx, *workflow_id).await.err()
+                }
+                OrganizationChange::SecretRepositoriesUpdated(secret_name, repos_names) => {
+                    self.svc.update_org_secret_repositories(ctx, secret_name, repos_names).await.err()
+                }
+                OrganizationChange::RunnerGroupAdded(runner_group) => {
+                    self.svc.add_org_runner_group(ctx, runner_group).await.err()
+                }
+                OrganizationChange::RunnerGroupRepositoriesUpdated(runner_group_id, runner_group) => self
+                    .svc
+                    .update_org_runner_group_repositories(ctx, *runner_group_id, &runner_group.repositories)
+                    .await
+                    .err(),
+                OrganizationChange::ServiceAccountInvited(user_name)
+                | OrganizationChange::ServiceAccountDemoted(user_name) => {
+                    self.svc.update_org_membership_to_member(ctx, user_name).await.err()
+                }
+                OrganizationChange::VerifiedDomainsGateUpdated(enabled) => {
+                    self.svc.update_org_verified_domains_gate(ctx, *enabled).await.err()
+                }
+                OrganizationChange::TeamCreationPermissionUpdated(permission) => {
+                    self.svc.update_org_team_creation_permission(ctx, *permission).await.err()
+                }
+                OrganizationChange::UserBlocked(user_name) => {
+                    self.svc.block_org_user(ctx, user_name).await.err()
+                }
+                OrganizationChange::UserUnblocked(user_name) => {
+                    self.svc.unblock_org_user(ctx, user_name).await.err()
+                }
+                OrganizationChange::SecurityManagerTeamAdded(team_name) => {
+                    self.svc.add_org_security_manager_team(ctx, team_name).await.err()
+                }
+                OrganizationChange::SecurityManagerTeamRemoved(team_name) => {
+                    self.svc.remove_org_security_manager_team(ctx, team_name).await.err()
+                }
+            };
+            changes_applied.push(ChangeApplied {
+                change: Box::new(change),
+                error: err.map(|e| e.to_string()),
+                applied_at: time::OffsetDateTime::now_utc(),
+            });
+            self.register_last_change_applied(ctx, &changes_applied).await;
+        }
+        changes_applied
+    }
+
+    /// Apply the changes provided, returning the result of applying each of
+    /// them.
+    async fn apply_changes(&self, org: &Organization, ctx: &Ctx, changes: Changes) -> Result<ChangesApplied> {
+        let mut changes_applied = vec![];
+
+        // Apply directory changes
+        let teams_removed: HashSet<TeamName> = changes
+            .directory
+            .iter()
+            .filter_map(|change| match change {
+                DirectoryChange::TeamRemoved(team_name) => Some(team_name.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut teams_retired: HashMap<TeamName, TeamName> = HashMap::new();
+        for change in changes.directory {
+            let err = match &change {
+                DirectoryChange::TeamAdded(team) => self.svc.add_team(ctx, team).await.err(),
+                DirectoryChange::TeamRemoved(team_name) => {
+                    // Child teams must be handled before removing (or retiring)
+                    // their parent
+                    if let Some(change_applied) =
+                        self.prepare_child_teams(org, ctx, team_name, &teams_removed).await
+                    {
+                        changes_applied.push(change_applied);
+                        self.register_last_change_applied(ctx, &changes_applied).await;
+                    }
+
+                    // Retire the team instead of removing it when enabled
+                    if let Some(prefix) = &org.retired_teams_prefix {
+                        let (new_team_name, err) =
+                            match self.get_retired_team_name(ctx, prefix, team_name).await {
+                                Ok(new_team_name) => {
+                                    let err =
+                                        self.svc.retire_team(ctx, team_name, &new_team_name).await.err();
+                                    (new_team_name, err)
+                                }
+                                Err(err) => (format!("{prefix}{team_name}"), Some(err)),
+                            };
+                        if err.is_none() {
+                            teams_retired.insert(team_name.clone(), new_team_name.clone());
+                        }
+                        changes_applied.push(ChangeApplied {
+                            change: Box::new(TeamRetirementChange::TeamRetired(
+                                team_name.clone(),
+                                new_team_name,
+                            )),
+                            error: err.map(|e| e.to_string()),
+                            applied_at: time::OffsetDateTime::now_utc(),
+                        });
+                        self.register_last_change_applied(ctx, &changes_applied).await;
+                        continue;
+                    }
+
+                    self.svc.remove_team(ctx, team_name).await.err()
+                }
+                DirectoryChange::TeamMaintainerAdded(team_name, user_name) => {
+                    self.svc.add_team_maintainer(ctx, team_name, user_name).await.err()
+                }
+                DirectoryChange::TeamMaintainerRemoved(team_name, user_name) => {
+                    self.svc.remove_team_maintainer(ctx, team_name, user_name).await.err()
+                }
+                DirectoryChange::TeamMemberAdded(team_name, user_name) => {
+                    self.svc.add_team_member(ctx, team_name, user_name).await.err()
+                }
+                DirectoryChange::TeamMemberRemoved(team_name, user_name) => {
+                    self.svc.remove_team_member(ctx, team_name, user_name).await.err()
+                }
+                DirectoryChange::TeamNotificationsUpdated(team_name, notifications) => {
+                    self.svc.update_team_notifications(ctx, team_name, *notifications).await.err()
+                }
+                DirectoryChange::UserAdded(_)
+                | DirectoryChange::UserRemoved(_)
+                | DirectoryChange::UserUpdated(_) => continue,
+            };
+            changes_applied.push(ChangeApplied {
+                change: Box::new(change),
+                error: err.map(|e| e.to_string()),
+                applied_at: time::OffsetDateTime::now_utc(),
+            });
+            self.register_last_change_applied(ctx, &changes_applied).await;
+        }
+
+        // Apply repositories changes. Consecutive changes to the teams access
+        // to repositories are independent, so they are applied concurrently
+        // (results are recorded in the same order the changes were provided)
+        let is_team_access_change = |change: &RepositoryChange| team_access(change).is_some();
+        let mut changes_repositories = changes.repositories.into_iter().peekable();
+        'changes_repositories: while let Some(change) = changes_repositories.next() {
+            if is_team_access_change(&change) {
+                let mut batch = vec![change];
+                while let Some(change) = changes_repositories.next_if(is_team_access_change) {
+                    batch.push(change);
+                }
+                debug!(calls = batch.len(), "applying teams access changes");
+                let errs: Vec<Option<anyhow::Error>> = stream::iter(batch.iter().filter_map(team_access))
+                    .map(|access| self.apply_team_access_change(ctx, access))
+                    .buffered(TEAMS_ACCESS_CONCURRENCY)
+                    .collect()
+                    .await;
+                for (change, err) in batch.into_iter().zip(errs) {
+                    changes_applied.push(repository_change_applied(change, err));
+                    self.register_last_change_applied(ctx, &changes_applied).await;
+                }
+                continue;
+            }
+
+            let err = match &change {
+                RepositoryChange::RepositoryAdded(repo) => self.add_repository(ctx, repo).await.err(),
+                RepositoryChange::TeamAdded(..) | RepositoryChange::TeamRoleUpdated(..) => {
+                    unreachable!("teams access changes are applied in batches")
+                }
+                RepositoryChange::TeamRemoved(repo_name, team_name) => {
+                    // If the team has just been deleted from the directory in
+                    // this reconciliation, there is no need to remove it from
+                    // the repository as this will be done automatically when
+                    // the team is deleted from GitHub
+                    for entry in &changes_applied {
+                        let change = (*entry.change).downcast_ref::<DirectoryChange>();
+                        if let Some(DirectoryChange::TeamRemoved(team_removed_name)) = change {
+                            if team_name == team_removed_name {
+                                continue 'changes_repositories;
+                            }
+                        }
+                    }
+
+                    // Teams retired in this reconciliation have been renamed
+                    let team_name = teams_retired.get(team_name).unwrap_or(team_name);
+                    self.svc.remove_repository_team(ctx, repo_name, team_name).await.err()
+                }
+                RepositoryChange::CollaboratorAdded(repo_name, user_name, role) => {
+                    self.svc.add_repository_collaborator(ctx, repo_name, user_name, role).await.err()
+                }
+                RepositoryChange::CollaboratorRemoved(repo_name, user_name) => {
+                    if let Some(invitation_id) =
+                        self.get_repository_invitation(ctx, repo_name, user_name).await?
+                    {
+                        self.svc.remove_repository_invitation(ctx, repo_name, invitation_id).await.err()
+                    } else {
+                        self.svc.remove_repository_collaborator(ctx, repo_name, user_name).await.err()
+                    }
+                }
+                RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, role) => {
+                    if let Some(invitation_id) =
+                        self.get_repository_invitation(ctx, repo_name, user_name).await?
+                    {
+                        self.svc.update_repository_invitation(ctx, repo_name, invitation_id, role).await.err()
+                    } else {
+                        self.svc
+                            .update_repository_collaborator_role(ctx, repo_name, user_name, role)
+                            .await
+                            .err()
+                    }
+                }
+                RepositoryChange::VisibilityUpdated(repo_name, visibility) => {
+                    self.svc.update_repository_visibility(ctx, repo_name, visibility).await.err()
+                }
+                RepositoryChange::ForkingPolicyUpdated(repo_name, allow_forking) => {
+                    self.svc.update_repository_allow_forking(ctx, repo_name, *allow_forking).await.err()
+                }
+                RepositoryChange::TemplateStatusUpdated(repo_name, is_template) => {
+                    self.svc.update_repository_template_status(ctx, repo_name, *is_template).await.err()
+                }
+                RepositoryChange::DiscussionsSettingsUpdated(repo_name, discussions) => {
+                    self.update_repository_discussions(ctx, repo_name, discussions).await.err()
+                }
+                RepositoryChange::MergeCommitSettingsUpdated(repo_name, settings) => {
+                    self.svc.update_repository_merge_commit_settings(ctx, repo_name, settings).await.err()
+                }
+                RepositoryChange::CodeScanningUpdated(repo_name, setup) => {
+                    self.svc.update_repository_code_scanning_setup(ctx, repo_name, setup).await.err()
+                }
+                RepositoryChange::ActionsAllowListUpdated(repo_name, allow_list) => {
+                    self.svc.update_repository_actions_allow_list(ctx, repo_name, allow_list).await.err()
+                }
+                RepositoryChange::CodeownersUpdated(repo_name, owners, update_mode) => self
+                    .svc
+                    .update_repository_codeowners(ctx, repo_name, &codeowners_content(owners), *update_mode)
+                    .await
+                    .err(),
+                RepositoryChange::DependabotConfigAdded(repo_name) => self
+                    .svc
+                    .add_repository_dependabot_config(ctx, repo_name, DEFAULT_DEPENDABOT_CONFIG)
+                    .await
+                    .err(),
+                RepositoryChange::PrivateVulnerabilityReportingUpdated(repo_name, enabled) => self
+                    .svc
+                    .update_repository_private_vulnerability_reporting(ctx, repo_name, *enabled)
+                    .await
+                    .err(),
+                RepositoryChange::WatcherAdded(repo_name, user_name) => {
+                    match service_account_token(org, user_name) {
+                        Some(token) => self.svc.add_repository_watcher(ctx, repo_name, token).await.err(),
+                        None => Some(format_err!("service account token not found")),
+                    }
+                }
+                RepositoryChange::WatcherRemoved(repo_name, user_name) => {
+                    match service_account_token(org, user_name) {
+                        Some(token) => self.svc.remove_repository_watcher(ctx, repo_name, token).await.err(),
+                        None => Some(format_err!("service account token not found")),
+                    }
+                }
+                RepositoryChange::TopicsUpdated(repo_name, topics) => {
+                    self.svc.update_repository_topics(ctx, repo_name, topics).await.err()
+                }
+                RepositoryChange::CollaboratorAccessExpiring(_, _, _) => {
+                    // Informational change, the collaborator will be removed
+                    // once the access has expired
+                    continue;
+                }
+                RepositoryChange::UnexpectedAppAccess(repo_name, app_slug) => {
+                    // Revoking apps access is only done when it has been
+                    // explicitly enabled for the organization
+                    if !org.revoke_unexpected_apps {
+                        continue;
+                    }
+                    self.svc.remove_repository_app(ctx, repo_name, app_slug).await.err()
+                }
+            };
+            changes_applied.push(repository_change_applied(change, err));
+            self.register_last_change_applied(ctx, &changes_applied).await;
+        }
+
+        Ok(changes_applied)
+    }
+
+    /// Get the changes registered in the checkpoint by the previous
+    /// reconciliation of the organization, in case it was interrupted. Errors
+    /// getting them are logged but not returned, as the reconciliation can
+    /// proceed without them (all changes are detected again anyway).
+    async fn get_checkpointed_changes(&self, ctx: &Ctx) -> Vec<ChangeDetails> {
+        let Some(checkpoint) = &self.checkpoint else {
+            return vec![];
+        };
+        match checkpoint.get_changes_applied(&ctx.org, SERVICE_NAME).await {
+            Ok(changes) => changes,
+            Err(err) => {
+                warn!(?err, org = ctx.org, "error getting reconcile checkpoint");
+                vec![]
+            }
+        }
+    }
+
+    /// Register in the checkpoint the last change in the changes applied
+    /// provided, as long as it was applied successfully.
+    async fn register_last_change_applied(&self, ctx: &Ctx, changes_applied: &ChangesApplied) {
+        let Some(checkpoint) = &self.checkpoint else {
+            return;
+        };
+        let Some(change_applied) = changes_applied.last().filter(|c| c.error.is_none()) else {
+            return;
+        };
+        let change = change_applied.change.details();
+        if let Err(err) = checkpoint.register_change_applied(&ctx.org, SERVICE_NAME, &change).await {
+            warn!(
+                ?err,
+                org = ctx.org,
+                "error registering change applied in reconcile checkpoint"
+            );
+        }
+    }
+
+    /// Discard the changes registered in the checkpoint, once all the changes
+    /// have been applied.
+    async fn reset_checkpoint(&self, ctx: &Ctx) {
+        let Some(checkpoint) = &self.checkpoint else {
+            return;
+        };
+        if let Err(err) = checkpoint.reset(&ctx.org, SERVICE_NAME).await {
+            warn!(?err, org = ctx.org, "error resetting reconcile checkpoint");
+        }
+    }
+
+    /// Reconcile the organization provided, resuming the previous
+    /// reconciliation from the checkpoint if it was interrupted.
+    async fn reconcile_from_checkpoint(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        snapshot: Option<&StateSnapshot>,
+        incremental: bool,
+    ) -> Result<ReconcileOutput> {
+        let src = Source::from(org);
+
+        // Prepare snapshot of the repositories in the actual state (if any).
+        // The invitations tracked are kept even in full reconciliations.
+        let mut snapshot = snapshot.and_then(|snapshot| {
+            match serde_json::from_value::<RepositoriesSnapshot>(snapshot.clone()) {
+                Ok(snapshot) => Some(snapshot),
+                Err(err) => {
+                    warn!(?err, "invalid state snapshot, ignoring it");
+                    None
+                }
+            }
+        });
+
+        // Resume the previous reconciliation if it was interrupted. Only the
+        // invitations and pending removals tracked are carried over to full
+        // reconciliations, the repositories are always fetched again
+        let checkpointed = self.get_checkpointed_changes(ctx).await;
+        let resuming = !checkpointed.is_empty();
+        if resuming {
+            info!(
+                org = org.name,
+                changes = checkpointed.len(),
+                "resuming interrupted reconciliation"
+            );
+            resume_from_checkpoint(
+                snapshot.get_or_insert_with(RepositoriesSnapshot::default),
+                &checkpointed,
+            );
+        }
+        let invitations = snapshot.as_ref().map(|s| s.invitations.clone()).unwrap_or_default();
+        let pending_removals = snapshot.as_ref().map(|s| s.pending_removals.clone()).unwrap_or_default();
+        let snapshot = snapshot.filter(|_| incremental);
+
+        // Get changes between the actual and the desired state
+        let taken_at = time::OffsetDateTime::now_utc().unix_timestamp();
+        let (actual_state, desired_state, mut changes) =
+            self.get_changes(org, ctx, &src, snapshot.as_ref()).await?;
+        let mut invitations =
+            self.track_invitations(org, ctx, &actual_state, &mut changes, invitations).await?;
+        let pending_removals = defer_removals(org, &desired_state, &mut changes, pending_removals, taken_at);
+        let (webhooks_in_svc, organization_changes) = self.get_organization_changes(org, ctx, &src).await?;
+
+        // Apply changes needed to match desired state (only planned when the
+        // organization is in observe-only mode)
+        let changes_applied =
+            self.apply_all_changes(org, ctx, &webhooks_in_svc, changes, organization_changes).await?;
+        if !org.observe_only {
+            track_invitations_sent(&changes_applied, &mut invitations);
+        }
+
+        // Prepare a new snapshot of the repositories in the actual state, to
+        // be used in the next incremental reconciliation. Repositories that
+        // may have been modified by the changes applied (or with invitations
+        // pending) are excluded, so that they are fetched again next time.
+        let mut teams_removed = HashSet::new();
+        let mut repositories_modified = HashSet::new();
+        for entry in &changes_applied {
+            let change = &*entry.change;
+            if let Some(DirectoryChange::TeamRemoved(team_name)) = change.downcast_ref::<DirectoryChange>() {
+                teams_removed.insert(team_name);
+            }
+            if let Some(change) = change.downcast_ref::<RepositoryChange>() {
+                repositories_modified.insert(change.repository_name());
+            }
+        }
+        for invitation in invitations.iter().filter(|i| i.pending) {
+            repositories_modified.insert(&invitation.repository);
+        }
+        let team_removed = |repo: &&Repository| {
+            repo.teams
+                .iter()
+                .flat_map(BTreeMap::keys)
+                .any(|team_name| teams_removed.contains(team_name))
+        };
+        let snapshot = RepositoriesSnapshot {
+            repositories: actual_state
+                .repositories
+                .iter()
+                .filter(|repo| !repositories_modified.contains(&repo.name) && !team_removed(repo))
+                .cloned()
+                .collect(),
+            invitations,
+            pending_removals,
+            taken_at,
+        };
+
+        Ok(ReconcileOutput {
+            snapshot: Some(serde_json::to_value(snapshot)?),
+            changes_applied,
+        })
+    }
+
+    /// Apply again the changes provided that failed previously, as long as
+    /// they are still needed.
+    async fn retry_failed_changes(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        failed: &[ChangeDetails],
+    ) -> Result<ChangesApplied> {
+        // Get changes between the actual and the desired state, keeping only
+        // the ones that failed previously (the rest are already satisfied)
+        let src = Source::from(org);
+        let (_, _, changes) = self.get_changes(org, ctx, &src, None).await?;
+        let changes = Changes {
+            directory: select_failed(changes.directory, failed),
+            repositories: select_failed(changes.repositories, failed),
+        };
+        let (webhooks_in_svc, organization_changes) = self.get_organization_changes(org, ctx, &src).await?;
+        let organization_changes = select_failed(organization_changes, failed);
+
+        // Apply failed changes again
+        self.apply_all_changes(org, ctx, &webhooks_in_svc, changes, organization_changes).await
+    }
+
+    /// Set the role of the team in the repository provided, returning the
+    /// error found (if any). The same call adds the team to the repository
+    /// or updates its role when it already has access to it.
+    async fn apply_team_access_change(
+        &self,
+        ctx: &Ctx,
+        (repo_name, team_name, role): (&RepositoryName, &TeamName, &Role),
+    ) -> Option<anyhow::Error> {
+        self.svc.add_repository_team(ctx, repo_name, team_name, role).await.err()
+    }
+
+    /// Check if the directory change provided could be applied. An error
+    /// describing the problem found is returned when it could not.
+    async fn check_directory_change(
+        &self,
+        ctx: &Ctx,
+        resources: &DryApplyResources,
+        change: &DirectoryChange,
+    ) -> Option<String> {
+        match change {
+            DirectoryChange::TeamAdded(team) => {
+                if resources.teams.contains(&team.name) {
+                    return Some(format!("team {} already exists", team.name));
+                }
+                for user_name in team.maintainers.iter().chain(team.members.iter()) {
+                    if let Some(err) = self.check_user(ctx, user_name).await {
+                        return Some(err);
+                    }
+                }
+                None
+            }
+            DirectoryChange::TeamRemoved(team_name)
+            | DirectoryChange::TeamMaintainerRemoved(team_name, _)
+            | DirectoryChange::TeamMemberRemoved(team_name, _)
+            | DirectoryChange::TeamNotificationsUpdated(team_name, _) => check_team(resources, team_name),
+            DirectoryChange::TeamMaintainerAdded(team_name, user_name)
+            | DirectoryChange::TeamMemberAdded(team_name, user_name) => {
+                if let Some(err) = check_team(resources, team_name) {
+                    return Some(err);
+                }
+                self.check_user(ctx, user_name).await
+            }
+            DirectoryChange::UserAdded(_)
+            | DirectoryChange::UserRemoved(_)
+            | DirectoryChange::UserUpdated(_) => None,
+        }
+    }
+
+    /// Check if the repository change provided could be applied. An error
+    /// describing the problem found is returned when it could not.
+    async fn check_repository_change(
+        &self,
+        ctx: &Ctx,
+        resources: &DryApplyResources,
+        change: &RepositoryChange,
+    ) -> Option<String> {
+        match change {
+            RepositoryChange::RepositoryAdded(repo) => {
+                if resources.repositories.contains_key(&repo.name) {
+                    return Some(format!("repository {} already exists", repo.name));
+                }
+                for team_name in repo.teams.iter().flat_map(|teams| teams.keys()) {
+                    if let Some(err) = check_team(resources, team_name) {
+                        return Some(err);
+                    }
+                }
+                for user_name in repo.collaborators.iter().flat_map(|collaborators| collaborators.keys()) {
+                    if let Some(err) = self.check_user(ctx, user_name).await {
+                        return Some(err);
+                    }
+                }
+                None
+            }
+            RepositoryChange::TeamAdded(repo_name, team_name, _)
+            | RepositoryChange::TeamRemoved(repo_name, team_name)
+            | RepositoryChange::TeamRoleUpdated(repo_name, team_name, _) => {
+                if let Some(err) = check_team(resources, team_name) {
+                    return Some(err);
+                }
+                self.check_repository(ctx, resources, repo_name).await
+            }
+            RepositoryChange::CollaboratorAdded(repo_name, user_name, _) => {
+                if let Some(err) = self.check_user(ctx, user_name).await {
+                    return Some(err);
+                }
+                self.check_repository(ctx, resources, repo_name).await
+            }
+            RepositoryChange::CollaboratorRemoved(repo_name, _)
+            | RepositoryChange::CollaboratorRoleUpdated(repo_name, _, _)
+            | RepositoryChange::VisibilityUpdated(repo_name, _)
+            | RepositoryChange::UnexpectedAppAccess(repo_name, _)
+            | RepositoryChange::ForkingPolicyUpdated(repo_name, _)
+            | RepositoryChange::TemplateStatusUpdated(repo_name, _)
+            | RepositoryChange::DiscussionsSettingsUpdated(repo_name, _)
+            | RepositoryChange::MergeCommitSettingsUpdated(repo_name, _)
+            | RepositoryChange::CodeScanningUpdated(repo_name, _)
+            | RepositoryChange::ActionsAllowListUpdated(repo_name, _)
+            | RepositoryChange::DependabotConfigAdded(repo_name)
+            | RepositoryChange::PrivateVulnerabilityReportingUpdated(repo_name, _)
+            | RepositoryChange::WatcherAdded(repo_name, _)
+            | RepositoryChange::WatcherRemoved(repo_name, _)
+            | RepositoryChange::TopicsUpdated(repo_name, _) => {
+                self.check_repository(ctx, resources, repo_name).await
+            }
+            RepositoryChange::CodeownersUpdated(repo_name, owners, _) => {
+                // Owners are referenced as @org/team
+                for owner in owners {
+                    let team_name = owner.rsplit('/').next().unwrap_or_default().to_string();
+                    if let Some(err) = check_team(resources, &team_name) {
+                        return Some(err);
+                    }
+                }
+                self.check_repository(ctx, resources, repo_name).await
+            }
+            RepositoryChange::CollaboratorAccessExpiring(_, _, _) => None,
+        }
+    }
+
+    /// Check the repository provided exists, is not archived and that we have
+    /// admin access to it.
+    async fn check_repository(
+        &self,
+        ctx: &Ctx,
+        resources: &DryApplyResources,
+        repo_name: &RepositoryName,
+    ) -> Option<String> {
+        match resources.repositories.get(repo_name) {
+            None => return Some(format!("repository {repo_name} not found")),
+            Some(true) => return Some(format!("repository {repo_name} is archived")),
+            Some(false) => {}
+        }
+        match self.svc.get_repository_admin_access(ctx, repo_name).await {
+            Ok(Some(false)) => Some(format!("no admin access to repository {repo_name}")),
+            Ok(_) => None,
+            Err(err) => Some(format!("error checking access to repository {repo_name}: {err}")),
+        }
+    }
+
+    /// Check the user provided exists.
+    async fn check_user(&self, ctx: &Ctx, user_name: &UserName) -> Option<String> {
+        match self.svc.get_user_login(ctx, user_name).await {
+            Ok(valid_user_name) if &valid_user_name == user_name => None,
+            Ok(valid_user_name) => Some(format!(
+                "invalid username {user_name}, should be {valid_user_name}"
+            )),
+            Err(err) => Some(format!("error validating username {user_name}: {err}")),
+        }
+    }
+
+    /// Helper function to get the invitation id for a given user in a
+    /// repository (when available).
+    async fn get_repository_invitation(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_name: &UserName,
+    ) -> Result<Option<RepositoryInvitationId>> {
+        let invitation_id =
+            self.svc.list_repository_invitations(ctx, repo_name).await?.iter().find_map(|i| {
+                if i.invitee.is_some() && &i.invitee.as_ref().unwrap().login == user_name {
+                    return Some(i.id);
+                }
+                None
+            });
+        Ok(invitation_id)
+    }
+
+    /// Add the repository provided to the organization. Once it has been
+    /// created, the default labels set up by GitHub are removed if requested.
+    /// The repository already exists at that point, so failing to protect its
+    /// default branch is not considered an error adding it.
+    async fn add_repository(&self, ctx: &Ctx, repo: &Repository) -> Result<()> {
+        let default_branch = self.svc.add_repository(ctx, repo).await?;
+
+        if repo.strip_default_labels == Some(true) {
+            for label_name in self.svc.list_repository_labels(ctx, &repo.name).await? {
+                self.svc
+                    .remove_repository_label(ctx, &repo.name, &label_name)
+                    .await
+                    .context(format!("error removing default label {label_name}"))?;
+            }
+        }
+
+        if let Some(branch_protection) = &repo.branch_protection {
+            if let Err(err) = self
+                .svc
+                .update_repository_branch_protection(ctx, &repo.name, &default_branch, branch_protection)
+                .await
+            {
+                warn!(?err, repo = repo.name, "error applying default branch protection");
+            }
+        }
+
+        if let Some(topics) = repo.topics.as_ref().filter(|topics| !topics.is_empty()) {
+            self.svc
+                .update_repository_topics(ctx, &repo.name, topics)
+                .await
+                .context("error setting repository topics")?;
+        }
+
+        Ok(())
+    }
+
+    /// Update the discussions settings of the repository provided. Discussions
+    /// categories cannot be managed using the GitHub API, so an error is
+    /// returned when the ones in the repository do not match the expected ones
+    /// and they need to be updated manually.
+    async fn update_repository_discussions(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        discussions: &DiscussionSettings,
+    ) -> Result<()> {
+        self.svc.update_repository_discussions(ctx, repo_name, discussions.enabled).await?;
+
+        let Some(categories) = &discussions.categories else {
+            return Ok(());
+        };
+        let actual_categories =
+            self.svc.get_repository_discussions(ctx, repo_name).await?.categories.unwrap_or_default();
+        let missing: Vec<&str> =
+            categories.iter().filter(|c| !actual_categories.contains(c)).map(String::as_str).collect();
+        let unexpected: Vec<&str> =
+            actual_categories.iter().filter(|c| !categories.contains(c)).map(String::as_str).collect();
+        if !missing.is_empty() || !unexpected.is_empty() {
+            return Err(format_err!(
+                "discussions categories must be updated manually (missing: [{}], unexpected: [{}])",
+                missing.join(", "),
+                unexpected.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Prepare the child teams of the team provided before removing it. When
+    /// enabled for the organization, child teams will be moved to the parent
+    /// of the team being removed. Otherwise they'll be reported as orphaned.
+    /// Child teams being removed as well are not taken into account.
+    async fn prepare_child_teams(
+        &self,
+        org: &Organization,
+        ctx: &Ctx,
+        team_name: &TeamName,
+        teams_removed: &HashSet<TeamName>,
+    ) -> Option<ChangeApplied> {
+        let child_teams: Vec<TeamName> = match self.svc.list_child_teams(ctx, team_name).await {
+            Ok(child_teams) => child_teams.into_iter().filter(|t| !teams_removed.contains(t)).collect(),
+            Err(err) => {
+                return Some(ChangeApplied {
+                    change: Box::new(TeamHierarchyChange::ChildTeamsOrphaned(team_name.clone(), vec![])),
+                    error: Some(format!("error listing child teams: {err}")),
+                    applied_at: time::OffsetDateTime::now_utc(),
+                });
+            }
+        };
+        if child_teams.is_empty() {
+            return None;
+        }
+
+        let (change, err) = if org.reparent_child_teams {
+            let err = self.svc.reparent_child_teams(ctx, team_name).await.err();
+            (
+                TeamHierarchyChange::ChildTeamsReparented(team_name.clone(), child_teams),
+                err,
+            )
+        } else {
+            (
+                TeamHierarchyChange::ChildTeamsOrphaned(team_name.clone(), child_teams),
+                None,
+            )
+        };
+        Some(ChangeApplied {
+            change: Box::new(change),
+            error: err.map(|e| e.to_string()),
+            applied_at: time::OffsetDateTime::now_utc(),
+        })
+    }
+
+    /// Get the name the team provided will be renamed to when retiring it.
+    /// When there is already a team with that name in the organization (i.e.
+    /// a team with the same name was retired before), a numeric suffix is
+    /// appended to it.
+    async fn get_retired_team_name(&self, ctx: &Ctx, prefix: &str, team_name: &TeamName) -> Result<TeamName> {
+        let teams: HashSet<TeamName> = self
+            .svc
+            .list_teams(ctx)
+            .await
+            .context("error listing teams")?
+            .into_iter()
+            .map(|t| t.slug)
+            .collect();
+        let retired_team_name = format!("{prefix}{team_name}");
+        let mut new_team_name = retired_team_name.clone();
+        let mut suffix = 2;
+        while teams.contains(&new_team_name) {
+            new_team_name = format!("{retired_team_name}-{suffix}");
+            suffix += 1;
+        }
+        Ok(new_team_name)
+    }
+
+    /// Validate users found in some of the changes provided. When the
+    /// organization restricts the outside collaborators, the collaborators
+    /// added that are not members of the organization must be approved.
+    async fn validate_users(&self, org: &Organization, ctx: &Ctx, changes: &Changes) -> Result<()> {
+        let mut merr = MultiError::new(Some("invalid github service configuration".to_string()));
+
+        // Collect users to validate from changes
+        let mut users_to_validate = HashSet::new();
+        for change in &changes.directory {
+            if let DirectoryChange::TeamMemberAdded(_, user_name) = change {
+                users_to_validate.insert(user_name);
+            }
+        }
+        for change in &changes.repositories {
+            if let RepositoryChange::CollaboratorAdded(_, user_name, _) = change {
+                users_to_validate.insert(user_name);
+            }
+        }
+
+        // Validate users collected
+        for user_name in users_to_validate {
+            match self.svc.get_user_login(ctx, user_name).await {
+                Ok(valid_user_name) => {
+                    if user_name != &valid_user_name {
+                        merr.push(format_err!(
+                            "user[{user_name}]: invalid username, should be {valid_user_name}",
+                        ));
+                    }
+                }
+                Err(err) => {
+                    merr.push(format_err!("user[{user_name}]: error validating username: {err}"));
+                }
+            }
+        }
+
+        // Validate outside collaborators added are approved
+        if let Some(approved_outside_collaborators) = &org.approved_outside_collaborators {
+            let collaborators_added: BTreeSet<&UserName> = changes
+                .repositories
+                .iter()
+                .filter_map(|change| match change {
+                    RepositoryChange::CollaboratorAdded(_, user_name, _) => Some(user_name),
+                    _ => None,
+                })
+                .collect();
+            if !collaborators_added.is_empty() {
+                let org_members: HashSet<UserName> =
+                    self.svc.list_org_members(ctx).await?.into_iter().map(|m| m.login).collect();
+                for user_name in collaborators_added {
+                    if !org_members.contains(user_name) && !approved_outside_collaborators.contains(user_name)
+                    {
+                        merr.push(
+                            CheckError::new(
+                                ValidationCheck::OutsideCollaboratorNotApproved,
+                                format!(
+                                    "user[{user_name}]: outside collaborator not approved in the organization"
+                                ),
+                            )
+                            .into(),
+                        );
+                    }
+                }
+            }
+        }
+
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ServiceHandler for Handler {
+    /// [ServiceHandler::get_changes_summary]
+    async fn get_changes_summary(
+        &self,
+        org: &Organization,
+        head_src: &Source,
+        pr_author: Option<&UserName>,
+        exemptions: &[ValidationExemption],
+    ) -> Result<ChangesSummary> {
+        let ctx = Ctx::from(org);
+        let base_src = Source::from(org);
+        let head_state =
+            State::new_from_config(self.gh.clone(), self.svc.clone(), org, &ctx, head_src).await?;
+        let mut pr_author_warnings = vec![];
+        let mut exempted_warnings = vec![];
+        let (changes, base_ref_config_status, users_impact) =
+            match State::new_from_config(self.gh.clone(), self.svc.clone(), org, &ctx, &base_src).await {
+                Ok(base_state) => {
+                    let changes = base_state.diff(&head_state);
+                    let roles_ordering = org.roles_ordering.clone().unwrap_or_default();
+                    let users_impact = base_state.users_impact(&head_state, &roles_ordering);
+                    if let Some(pr_author) = pr_author {
+                        pr_author_warnings =
+                            base_state.pr_author_warnings(&head_state, pr_author, &roles_ordering);
+                    }
+                    if let Err(err) = self.validate_users(org, &ctx, &changes).await {
+                        let (err, exempted) = exempt_errors(err, exemptions);
+                        if let Some(err) = err {
+                            return Err(err);
+                        }
+                        exempted_warnings.extend(exempted);
+                    }
+                    let locations =
+                        Locations::get(self.gh.clone(), head_src, &org.legacy.sheriff_permissions_path).await;
+                    let repositories_changes = changes
+                        .repositories
+                        .into_iter()
+                        .map(|change| {
+                            let location = locations.repository(change.repository_name());
+                            LocatedChange::wrap(Box::new(change), location)
+                        })
+                        .collect();
+                    (repositories_changes, BaseRefConfigStatus::Valid, users_impact)
+                }
+                Err(_) => (vec![], BaseRefConfigStatus::Invalid, vec![]),
+            };
+        if org.enforce_pr_author_scope && !pr_author_warnings.is_empty() {
+            let mut merr = MultiError::new(Some(
+                "changes not allowed for the pull request author".to_string(),
+            ));
+            for warning in pr_author_warnings.drain(..) {
+                merr.push(CheckError::new(ValidationCheck::PrAuthorScope, warning).into());
+            }
+            let (err, exempted) = exempt_errors(merr.into(), exemptions);
+            if let Some(err) = err {
+                return Err(err);
+            }
+            exempted_warnings.extend(exempted);
+        }
+        let mut warnings = head_state.warnings(self.svc.clone(), &ctx).await?;
+        warnings.extend(pr_author_warnings);
+        warnings.extend(exempted_warnings);
+        if org.validate_codeowners {
+            warnings.extend(head_state.codeowners_warnings(self.svc.clone(), &ctx).await?);
+        }
+        if let Some(max_age_days) = org.stale_branches_max_age_days {
+            warnings.extend(head_state.stale_branches_warnings(self.svc.clone(), &ctx, max_age_days).await?);
+        }
+
+        Ok(ChangesSummary {
+            changes,
+            base_ref_config_status,
+            warnings,
+            users_impact,
+        })
+    }
+
+    /// [ServiceHandler::reconcile]
+    async fn reconcile(
+        &self,
+        org: &Organization,
+        snapshot: Option<&StateSnapshot>,
+        incremental: bool,
+    ) -> Result<ReconcileOutput> {
+        let ctx = Ctx::from(org);
+        let output = self.reconcile_from_checkpoint(org, &ctx, snapshot, incremental).await?;
+
+        // The checkpoint is only needed when the reconciliation is interrupted
+        // or fails, so that the next one can resume from where it stopped
+        self.reset_checkpoint(&ctx).await;
+        Ok(output)
+    }
+
+    /// [ServiceHandler::retry_failed]
+    async fn retry_failed(&self, org: &Organization, failed: &[ChangeDetails]) -> Result<ChangesApplied> {
+        let ctx = Ctx::from(org);
+        let changes_applied = self.retry_failed_changes(org, &ctx, failed).await;
+        self.reset_checkpoint(&ctx).await;
+        changes_applied
+    }
+
+    /// [ServiceHandler::warm_up]
+    async fn warm_up(&self, org: &Organization) -> Result<()> {
+        let ctx = Ctx::from(org);
+
+        // The organization info prefetched is kept until the first
+        // reconciliation uses it, the templates are cached long enough
+        try_join!(
+            self.svc.prefetch_org_info(&ctx),
+            self.svc.list_gitignore_templates(&ctx),
+            self.svc.list_license_templates(&ctx),
+        )
+        .context("error prefetching organization info")?;
+
+        Ok(())
+    }
+}
+
+/// Validate the content of the community health file provided. YAML files
+/// (i.e. issue forms or the issue template chooser config) must be valid.
+fn validate_community_health_file(path: &str, content: &str) -> Result<()> {
+    if content.trim().is_empty() {
+        return Err(format_err!("file is empty"));
+    }
+    let path = path.to_lowercase();
+    if path.ends_with(".yml") || path.ends_with(".yaml") {
+        serde_yaml::from_str::<serde_yaml::Value>(content).context("invalid yaml file")?;
+    }
+    Ok(())
+}
+
+/// Select the changes provided that match any of the failed ones, sorting them
+/// in the same order the failed changes were applied.
+fn select_failed<T: Change>(changes: Vec<T>, failed: &[ChangeDetails]) -> Vec<T> {
+    let mut selected: Vec<(usize, T)> = changes
+        .into_iter()
+        .filter_map(|change| {
+            let details = change.details();
+            failed.iter().position(|f| f == &details).map(|pos| (pos, change))
+        })
+        .collect();
+    selected.sort_by_key(|(pos, _)| *pos);
+    selected.into_iter().map(|(_, change)| change).collect()
+}
+
+/// Some resources available in the service, used to check the changes during
+/// a dry apply.
+struct DryApplyResources {
+    teams: HashSet<TeamName>,
+    repositories: HashMap<RepositoryName, bool>,
+}
+
+/// Returns the repository, team and role of the repository change provided
+/// when it updates the access of a team to the repository (these changes can
+/// be applied concurrently).
+fn team_access(change: &RepositoryChange) -> Option<(&RepositoryName, &TeamName, &Role)> {
+    match change {
+        RepositoryChange::TeamAdded(repo_name, team_name, role)
+        | RepositoryChange::TeamRoleUpdated(repo_name, team_name, role) => Some((repo_name, team_name, role)),
+        _ => None,
+    }
+}
+
+/// Prepare the change applied entry for the repository change provided. The
+/// repository may have been archived after the changes were calculated. No
+/// action is possible on it, so the change is recorded as skipped instead of
+/// as failed.
+fn repository_change_applied(change: RepositoryChange, err: Option<anyhow::Error>) -> ChangeApplied {
+    if err.as_ref().is_some_and(is_archived_repository_error) {
+        warn!(repo = %change.repository_name(), "repository archived, change skipped");
+        return ChangeApplied {
+            change: Box::new(RepositoryArchivedChange::ChangeSkipped(change)),
+            error: None,
+            applied_at: time::OffsetDateTime::now_utc(),
+        };
+    }
+    ChangeApplied {
+        change: Box::new(change),
+        error: err.map(|e| e.to_string()),
+        applied_at: time::OffsetDateTime::now_utc(),
+    }
+}
+
+/// Check if the error provided was returned by GitHub because the repository
+/// affected is archived (and therefore read-only).
+fn is_archived_repository_error(err: &anyhow::Error) -> bool {
+    format!("{err:#}").to_lowercase().contains("repository was archived")
+}
+
+/// Track the collaborators invitations sent successfully in the changes
+/// applied provided, so that they can be checked in the next reconciliation.
+fn track_invitations_sent(changes_applied: &ChangesApplied, invitations: &mut Vec<CollaboratorInvitation>) {
+    for change_applied in changes_applied.iter().filter(|c| c.error.is_none()) {
+        let Some(RepositoryChange::CollaboratorAdded(repo_name, user_name, _)) =
+            (*change_applied.change).downcast_ref::<RepositoryChange>()
+        else {
+            continue;
+        };
+        match invitations.iter_mut().find(|i| &i.repository == repo_name && &i.user_name == user_name) {
+            Some(invitation) => invitation.pending = true,
+            None => invitations.push(CollaboratorInvitation {
+                repository: repo_name.clone(),
+                user_name: user_name.clone(),
+                pending: true,
+                declines: 0,
+            }),
+        }
+    }
+}
+
+/// Prepare the snapshot provided to resume a reconciliation that was
+/// interrupted, using the changes it applied before stopping. Repositories
+/// that may have been modified by those changes are discarded from the
+/// snapshot, so that they are fetched again from the service and the changes
+/// already applied are not detected (and applied) again. The collaborators
+/// invitations sent are tracked as well.
+fn resume_from_checkpoint(snapshot: &mut RepositoriesSnapshot, checkpointed: &[ChangeDetails]) {
+    let mut teams_removed = HashSet::new();
+    let mut repositories_modified = HashSet::new();
+    for change in checkpointed {
+        let str_field = |field: &str| change.extra.get(field).and_then(|v| v.as_str());
+        if matches!(change.kind.as_str(), "team-removed" | "team-retired") {
+            if let Some(team_name) = str_field("team_name") {
+                teams_removed.insert(team_name.to_string());
+            }
+            continue;
+        }
+        let repo_name = str_field("repo_name").or_else(|| change.extra.pointer("/repo/name")?.as_str());
+        let Some(repo_name) = repo_name else {
+            continue;
+        };
+        repositories_modified.insert(repo_name.to_string());
+        if change.kind == "repository-collaborator-added" {
+            let Some(user_name) = str_field("user_name") else {
+                continue;
+            };
+            let invitation = snapshot
+                .invitations
+                .iter_mut()
+                .find(|i| i.repository == repo_name && i.user_name == user_name);
+            match invitation {
+                Some(invitation) => invitation.pending = true,
+                None => snapshot.invitations.push(CollaboratorInvitation {
+                    repository: repo_name.to_string(),
+                    user_name: user_name.to_string(),
+                    pending: true,
+                    declines: 0,
+                }),
+            }
+        }
+    }
+    snapshot.repositories.retain(|repo| {
+        !repositories_modified.contains(&repo.name)
+            && !repo
+                .teams
+                .iter()
+                .flat_map(BTreeMap::keys)
+                .any(|team_name| teams_removed.contains(team_name))
+    });
+}
+
+/// Defer the team members and repositories collaborators removals found in
+/// the changes provided until the organization's removals grace period has
+/// elapsed since they were first detected. Removals still pending are
+/// returned, so that they can be checked again in the next reconciliation.
+/// Pending removals that are not needed anymore are discarded. Users whose
+/// role in a team is being updated are not considered removals, and the
+/// collaborators whose access has expired are removed right away.
+fn defer_removals(
+    org: &Organization,
+    desired_state: &State,
+    changes: &mut Changes,
+    pending_removals: Vec<PendingRemoval>,
+    now: i64,
+) -> Vec<PendingRemoval> {
+    if org.removals_grace_period_hours == 0 {
+        return vec![];
+    }
+    let grace_period = i64::from(org.removals_grace_period_hours) * 3600;
+
+    // Check if the removal provided must be deferred, registering it as
+    // pending when needed
+    let mut still_pending = vec![];
+    let mut defer = |change: &dyn Change| {
+        let details = change.details();
+        let detected_at =
+            pending_removals.iter().find(|p| p.matches(&details)).map_or(now, |p| p.detected_at);
+        if now - detected_at >= grace_period {
+            return false;
+        }
+        debug!(
+            kind = details.kind,
+            "removal deferred until the grace period elapses"
+        );
+        still_pending.push(PendingRemoval {
+            kind: details.kind,
+            extra: details.extra,
+            detected_at,
+        });
+        true
+    };
+
+    let users_added: HashSet<(TeamName, UserName)> = changes
+        .directory
+        .iter()
+        .filter_map(|change| match change {
+            DirectoryChange::TeamMaintainerAdded(team_name, user_name)
+            | DirectoryChange::TeamMemberAdded(team_name, user_name) => {
+                Some((team_name.clone(), user_name.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    changes.directory.retain(|change| match change {
+        DirectoryChange::TeamMaintainerRemoved(team_name, user_name)
+        | DirectoryChange::TeamMemberRemoved(team_name, user_name) => {
+            users_added.contains(&(team_name.clone(), user_name.clone())) || !defer(change)
+        }
+        _ => true,
+    });
+    let access_expired = |repo_name: &RepositoryName, user_name: &UserName| {
+        desired_state
+            .repositories
+            .iter()
+            .find(|repo| &repo.name == repo_name)
+            .and_then(|repo| repo.expired_collaborators.as_ref())
+            .is_some_and(|expired| expired.contains(user_name))
+    };
+    changes.repositories.retain(|change| match change {
+        RepositoryChange::CollaboratorRemoved(repo_name, user_name) => {
+            access_expired(repo_name, user_name) || !defer(change)
+        }
+        _ => true,
+    });
+
+    still_pending
+}
+
+/// Get the token of the organization's service account provided (if any).
+fn service_account_token<'a>(org: &'a Organization, user_name: &UserName) -> Option<&'a str> {
+    org.service_accounts
+        .iter()
+        .flatten()
+        .find(|service_account| &service_account.login == user_name)
+        .and_then(|service_account| service_account.token.as_deref())
+}
+
+/// Check the team provided exists in the service.
+fn check_team(resources: &DryApplyResources, team_name: &TeamName) -> Option<String> {
+    if resources.teams.contains(team_name) {
+        return None;
+    }
+    Some(format!("team {team_name} not found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::recording::RecordingSvc;
+    use super::service::{MockSvc, OrgRequiredWorkflow, OrgRunnerGroup, OrgVerifiedDomains};
+    use super::state::{BranchProtection, CodeownersManagement, CodeownersUpdateMode};
+    use super::*;
+    use crate::{
+        cfg::{
+            CommunityHealthFile, DependabotConfig, OrganizationSecret, OrganizationSettings,
+            RequiredWorkflow, ServiceAccount, TeamCreationPermission, VerifiedDomainsGate, Webhook,
+        },
+        github::MockGH,
+        services::MockReconcileCheckpoint,
+    };
+
+    fn dry_apply_resources() -> DryApplyResources {
+        DryApplyResources {
+            teams: HashSet::from(["team1".to_string()]),
+            repositories: HashMap::from([("repo1".to_string(), false), ("repo2".to_string(), true)]),
+        }
+    }
+
+    fn ctx() -> Ctx {
+        Ctx {
+            inst_id: None,
+            org: "org".to_string(),
+        }
+    }
+
+    fn member(login: &str) -> octorust::types::SimpleUser {
+        serde_json::from_value(serde_json::json!({ "login": login })).unwrap()
+    }
+
+    fn team(slug: &str) -> octorust::types::Team {
+        serde_json::from_value(serde_json::json!({ "slug": slug })).unwrap()
+    }
+
+    fn src() -> Source {
+        Source {
+            inst_id: None,
+            owner: "org".to_string(),
+            repo: ".clowarden".to_string(),
+            ref_: "main".to_string(),
+            location: None,
+        }
+    }
+
+    fn webhooks_in_svc() -> Vec<OrgWebhook> {
+        vec![
+            OrgWebhook {
+                id: 1,
+                url: "https://hook1.url".to_string(),
+                events: vec!["pull_request".to_string(), "push".to_string()],
+                content_type: "json".to_string(),
+                active: true,
+            },
+            OrgWebhook {
+                id: 2,
+                url: "https://hook2.url".to_string(),
+                events: vec!["push".to_string()],
+                content_type: "json".to_string(),
+                active: true,
+            },
+            OrgWebhook {
+                id: 3,
+                url: "https://hook3.url".to_string(),
+                events: vec!["push".to_string()],
+                content_type: "json".to_string(),
+                active: true,
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn check_repository_change_ok() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_user_login().returning(|_, user_name| Ok(user_name.clone()));
+        svc.expect_get_repository_admin_access().returning(|_, _| Ok(Some(true)));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let change =
+            RepositoryChange::CollaboratorAdded("repo1".to_string(), "user1".to_string(), Role::Write);
+        let err = handler.check_repository_change(&ctx(), &dry_apply_resources(), &change).await;
+        assert_eq!(err, None);
+    }
+
+    #[tokio::test]
+    async fn validate_users_approved_outside_collaborator() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_user_login().returning(|_, user_name| Ok(user_name.clone()));
+        svc.expect_list_org_members().times(1).returning(|_| Ok(vec![member("user1")]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            approved_outside_collaborators: Some(vec!["user2".to_string()]),
+            ..Default::default()
+        };
+        let changes = Changes {
+            repositories: vec![
+                RepositoryChange::CollaboratorAdded("repo1".to_string(), "user1".to_string(), Role::Write),
+                RepositoryChange::CollaboratorAdded("repo1".to_string(), "user2".to_string(), Role::Read),
+            ],
+            ..Default::default()
+        };
+        handler.validate_users(&org, &ctx(), &changes).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn validate_users_unapproved_outside_collaborator() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_user_login().returning(|_, user_name| Ok(user_name.clone()));
+        svc.expect_list_org_members().times(1).returning(|_| Ok(vec![member("user1")]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            approved_outside_collaborators: Some(vec!["user2".to_string()]),
+            ..Default::default()
+        };
+        let changes = Changes {
+            repositories: vec![RepositoryChange::CollaboratorAdded(
+                "repo1".to_string(),
+                "user3".to_string(),
+                Role::Read,
+            )],
+            ..Default::default()
+        };
+        let err = handler.validate_users(&org, &ctx(), &changes).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["user[user3]: outside collaborator not approved in the organization"]
+        );
+    }
+
+    #[tokio::test]
+    async fn check_repository_change_codeowners_team_not_found() {
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(MockSvc::new()));
+
+        let change = RepositoryChange::CodeownersUpdated(
+            "repo1".to_string(),
+            vec!["@org/team1".to_string(), "@org/team2".to_string()],
+            CodeownersUpdateMode::Commit,
+        );
+        let err = handler.check_repository_change(&ctx(), &dry_apply_resources(), &change).await;
+        assert_eq!(err, Some("team team2 not found".to_string()));
+    }
+
+    #[tokio::test]
+    async fn check_repository_change_repository_not_found() {
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(MockSvc::new()));
+
+        let change = RepositoryChange::TeamAdded("repo3".to_string(), "team1".to_string(), Role::Write);
+        let err = handler.check_repository_change(&ctx(), &dry_apply_resources(), &change).await;
+        assert_eq!(err, Some("repository repo3 not found".to_string()));
+    }
+
+    #[tokio::test]
+    async fn check_repository_change_repository_archived() {
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(MockSvc::new()));
+
+        let change = RepositoryChange::CollaboratorRemoved("repo2".to_string(), "user1".to_string());
+        let err = handler.check_repository_change(&ctx(), &dry_apply_resources(), &change).await;
+        assert_eq!(err, Some("repository repo2 is archived".to_string()));
+    }
+
+    #[tokio::test]
+    async fn check_repository_change_no_admin_access() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_repository_admin_access().returning(|_, _| Ok(Some(false)));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let change = RepositoryChange::VisibilityUpdated("repo1".to_string(), state::Visibility::Private);
+        let err = handler.check_repository_change(&ctx(), &dry_apply_resources(), &change).await;
+        assert_eq!(err, Some("no admin access to repository repo1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn check_directory_change_team_not_found() {
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(MockSvc::new()));
+
+        let change = DirectoryChange::TeamMemberRemoved("team2".to_string(), "user1".to_string());
+        let err = handler.check_directory_change(&ctx(), &dry_apply_resources(), &change).await;
+        assert_eq!(err, Some("team team2 not found".to_string()));
+    }
+
+    #[tokio::test]
+    async fn prepare_child_teams_no_child_teams() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_child_teams().returning(|_, _| Ok(vec![]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let change_applied = handler
+            .prepare_child_teams(
+                &Organization::default(),
+                &ctx(),
+                &"team1".to_string(),
+                &HashSet::new(),
+            )
+            .await;
+        assert!(change_applied.is_none());
+    }
+
+    #[tokio::test]
+    async fn prepare_child_teams_orphaned() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_child_teams()
+            .returning(|_, _| Ok(vec!["team2".to_string(), "team3".to_string()]));
+        svc.expect_reparent_child_teams().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let teams_removed = HashSet::from(["team3".to_string()]);
+        let change_applied = handler
+            .prepare_child_teams(
+                &Organization::default(),
+                &ctx(),
+                &"team1".to_string(),
+                &teams_removed,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            (*change_applied.change).downcast_ref::<TeamHierarchyChange>(),
+            Some(&TeamHierarchyChange::ChildTeamsOrphaned(
+                "team1".to_string(),
+                vec!["team2".to_string()]
+            ))
+        );
+        assert_eq!(change_applied.error, None);
+    }
+
+    #[tokio::test]
+    async fn prepare_child_teams_reparented() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_child_teams().returning(|_, _| Ok(vec!["team2".to_string()]));
+        svc.expect_reparent_child_teams().times(1).returning(|_, _| Ok(vec!["team2".to_string()]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            reparent_child_teams: true,
+            ..Default::default()
+        };
+        let change_applied = handler
+            .prepare_child_teams(&org, &ctx(), &"team1".to_string(), &HashSet::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            (*change_applied.change).downcast_ref::<TeamHierarchyChange>(),
+            Some(&TeamHierarchyChange::ChildTeamsReparented(
+                "team1".to_string(),
+                vec!["team2".to_string()]
+            ))
+        );
+        assert_eq!(change_applied.error, None);
+    }
+
+    #[tokio::test]
+    async fn apply_changes_team_retired() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_child_teams().returning(|_, _| Ok(vec![]));
+        svc.expect_list_teams().returning(|_| Ok(vec![team("team1")]));
+        svc.expect_remove_team().never();
+        svc.expect_retire_team()
+            .withf(|_, team_name, new_team_name| team_name == "team1" && new_team_name == "archived-team1")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        svc.expect_remove_repository_team()
+            .withf(|_, repo_name, team_name| repo_name == "repo1" && team_name == "archived-team1")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            retired_teams_prefix: Some("archived-".to_string()),
+            ..Default::default()
+        };
+        let changes = Changes {
+            directory: vec![DirectoryChange::TeamRemoved("team1".to_string())],
+            repositories: vec![RepositoryChange::TeamRemoved(
+                "repo1".to_string(),
+                "team1".to_string(),
+            )],
+        };
+        let changes_applied = handler.apply_changes(&org, &ctx(), changes).await.unwrap();
+        assert_eq!(changes_applied.len(), 2);
+        assert_eq!(
+            (*changes_applied[0].change).downcast_ref::<TeamRetirementChange>(),
+            Some(&TeamRetirementChange::TeamRetired(
+                "team1".to_string(),
+                "archived-team1".to_string()
+            ))
+        );
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn apply_changes_registers_checkpoint() {
+        let mut svc = MockSvc::new();
+        svc.expect_update_repository_visibility()
+            .withf(|_, repo_name, _| repo_name == "repo1")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        svc.expect_update_repository_visibility()
+            .withf(|_, repo_name, _| repo_name == "repo2")
+            .times(1)
+            .returning(|_, _, _| Err(format_err!("unexpected error")));
+        let mut checkpoint = MockReconcileCheckpoint::new();
+        checkpoint
+            .expect_register_change_applied()
+            .withf(|org, service_name, change| {
+                org == "org"
+                    && service_name == SERVICE_NAME
+                    && change.kind == "repository-visibility-updated"
+                    && change.extra["repo_name"] == "repo1"
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let handler =
+            Handler::new(Arc::new(MockGH::new()), Arc::new(svc)).with_checkpoint(Arc::new(checkpoint));
+
+        let changes = Changes {
+            directory: vec![],
+            repositories: vec![
+                RepositoryChange::VisibilityUpdated("repo1".to_string(), state::Visibility::Private),
+                RepositoryChange::VisibilityUpdated("repo2".to_string(), state::Visibility::Private),
+            ],
+        };
+        let changes_applied = handler.apply_changes(&Organization::default(), &ctx(), changes).await.unwrap();
+        assert_eq!(changes_applied.len(), 2);
+        assert!(changes_applied[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn reconcile_keeps_checkpoint_on_error() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_teams().times(1).returning(|_| Err(format_err!("unexpected error")));
+        let mut checkpoint = MockReconcileCheckpoint::new();
+        checkpoint.expect_get_changes_applied().times(1).returning(|_, _| Ok(vec![]));
+        checkpoint.expect_reset().never();
+        let handler =
+            Handler::new(Arc::new(MockGH::new()), Arc::new(svc)).with_checkpoint(Arc::new(checkpoint));
+
+        assert!(handler.reconcile(&Organization::default(), None, false).await.is_err());
+    }
+
+    #[test]
+    fn resume_from_checkpoint_after_restart() {
+        let repo = |name: &str, team_name: &str| Repository {
+            name: name.to_string(),
+            teams: Some(BTreeMap::from([(team_name.to_string(), Role::Write)])),
+            ..Default::default()
+        };
+        let mut snapshot = RepositoriesSnapshot {
+            repositories: vec![
+                repo("repo1", "team1"),
+                repo("repo2", "team1"),
+                repo("repo3", "team2"),
+                repo("repo4", "team1"),
+            ],
+            taken_at: 1,
+            ..Default::default()
+        };
+
+        // The reconciliation was interrupted after applying these changes
+        let checkpointed: Vec<ChangeDetails> = [
+            Box::new(RepositoryChange::VisibilityUpdated(
+                "repo1".to_string(),
+                state::Visibility::Private,
+            )) as DynChange,
+            Box::new(RepositoryChange::CollaboratorAdded(
+                "repo2".to_string(),
+                "user1".to_string(),
+                Role::Read,
+            )),
+            Box::new(DirectoryChange::TeamRemoved("team2".to_string())),
+        ]
+        .iter()
+        .map(|change| change.details())
+        .collect();
+        resume_from_checkpoint(&mut snapshot, &checkpointed);
+
+        assert_eq!(snapshot.repositories, vec![repo("repo4", "team1")]);
+        assert_eq!(
+            snapshot.invitations,
+            vec![CollaboratorInvitation {
+                repository: "repo2".to_string(),
+                user_name: "user1".to_string(),
+                pending: true,
+                declines: 0,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_changes_team_retired_name_taken() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_child_teams().returning(|_, _| Ok(vec![]));
+        svc.expect_list_teams().returning(|_| {
+            Ok(vec![
+                team("team1"),
+                team("archived-team1"),
+                team("archived-team1-2"),
+            ])
+        });
+        svc.expect_retire_team()
+            .withf(|_, team_name, new_team_name| team_name == "team1" && new_team_name == "archived-team1-3")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            retired_teams_prefix: Some("archived-".to_string()),
+            ..Default::default()
+        };
+        let changes = Changes {
+            directory: vec![DirectoryChange::TeamRemoved("team1".to_string())],
+            repositories: vec![],
+        };
+        let changes_applied = handler.apply_changes(&org, &ctx(), changes).await.unwrap();
+        assert_eq!(
+            (*changes_applied[0].change).downcast_ref::<TeamRetirementChange>(),
+            Some(&TeamRetirementChange::TeamRetired(
+                "team1".to_string(),
+                "archived-team1-3".to_string()
+            ))
+        );
+        assert!(changes_applied[0].error.is_none());
+    }
+
+    #[tokio::test]
+    async fn apply_changes_calls_order() {
+        let mut reads_svc = MockSvc::new();
+        reads_svc.expect_list_child_teams().returning(|_, _| Ok(vec!["team3".to_string()]));
+        reads_svc.expect_list_teams().returning(|_| Ok(vec![team("team1"), team("team3")]));
+        let svc = Arc::new(RecordingSvc::new(Arc::new(reads_svc)));
+        let handler = Handler::new(Arc::new(MockGH::new()), svc.clone());
+
+        let org = Organization {
+            retired_teams_prefix: Some("archived-".to_string()),
+            reparent_child_teams: true,
+            ..Default::default()
+        };
+        let changes = Changes {
+            directory: vec![
+                DirectoryChange::TeamAdded(crate::directory::Team {
+                    name: "team2".to_string(),
+                    ..Default::default()
+                }),
+                DirectoryChange::TeamRemoved("team1".to_string()),
+            ],
+            repositories: vec![
+                RepositoryChange::TeamAdded("repo1".to_string(), "team2".to_string(), Role::Write),
+                RepositoryChange::TeamRemoved("repo1".to_string(), "team1".to_string()),
+            ],
+        };
+        let changes_applied = handler.apply_changes(&org, &ctx(), changes).await.unwrap();
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+        assert_eq!(
+            svc.calls(),
+            vec![
+                "add_team team2",
+                "reparent_child_teams team1",
+                "retire_team team1 archived-team1",
+                "add_repository_team repo1 team2 write",
+                "remove_repository_team repo1 archived-team1",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_changes_teams_access_concurrently() {
+        let svc = Arc::new(RecordingSvc::new(Arc::new(MockSvc::new())));
+        let handler = Handler::new(Arc::new(MockGH::new()), svc.clone());
+
+        let repositories_changes = vec![
+            RepositoryChange::TeamAdded("repo1".to_string(), "team1".to_string(), Role::Write),
+            RepositoryChange::TeamAdded("repo1".to_string(), "team2".to_string(), Role::Read),
+            RepositoryChange::TeamRoleUpdated("repo1".to_string(), "team3".to_string(), Role::Admin),
+            RepositoryChange::CollaboratorAdded("repo1".to_string(), "user1".to_string(), Role::Write),
+            RepositoryChange::TeamAdded("repo2".to_string(), "team1".to_string(), Role::Maintain),
+        ];
+        let changes = Changes {
+            directory: vec![],
+            repositories: repositories_changes.clone(),
+        };
+        let changes_applied = handler.apply_changes(&Organization::default(), &ctx(), changes).await.unwrap();
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+        assert_eq!(
+            changes_applied
+                .iter()
+                .map(|c| (*c.change).downcast_ref::<RepositoryChange>().unwrap().clone())
+                .collect::<Vec<RepositoryChange>>(),
+            repositories_changes
+        );
+
+        // One call per change, in the same order
+        assert_eq!(
+            svc.calls(),
+            vec![
+                "add_repository_team repo1 team1 write",
+                "add_repository_team repo1 team2 read",
+                "add_repository_team repo1 team3 admin",
+                "add_repository_collaborator repo1 user1 write",
+                "add_repository_team repo2 team1 maintain",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_changes_team_removed() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_child_teams().returning(|_, _| Ok(vec![]));
+        svc.expect_retire_team().never();
+        svc.expect_remove_team().times(1).returning(|_, _| Ok(()));
+        svc.expect_remove_repository_team().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = Changes {
+            directory: vec![DirectoryChange::TeamRemoved("team1".to_string())],
+            repositories: vec![RepositoryChange::TeamRemoved(
+                "repo1".to_string(),
+                "team1".to_string(),
+            )],
+        };
+        let changes_applied = handler.apply_changes(&Organization::default(), &ctx(), changes).await.unwrap();
+        assert_eq!(changes_applied.len(), 1);
+        assert_eq!(
+            (*changes_applied[0].change).downcast_ref::<DirectoryChange>(),
+            Some(&DirectoryChange::TeamRemoved("team1".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_changes_repository_archived() {
+        let mut svc = MockSvc::new();
+        svc.expect_add_repository_team().times(1).returning(|_, _, _, _| {
+            Err(format_err!(
+                "code: 403, error: Repository was archived so is read-only."
+            ))
+        });
+        svc.expect_remove_repository_collaborator()
+            .times(1)
+            .returning(|_, _, _| Err(format_err!("code: 500, error: Internal Server Error")));
+        svc.expect_list_repository_invitations().returning(|_, _| Ok(vec![]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = Changes {
+            repositories: vec![
+                RepositoryChange::TeamAdded("repo1".to_string(), "team1".to_string(), Role::Write),
+                RepositoryChange::CollaboratorRemoved("repo2".to_string(), "user1".to_string()),
+            ],
+            ..Default::default()
+        };
+        let changes_applied = handler.apply_changes(&Organization::default(), &ctx(), changes).await.unwrap();
+        assert_eq!(changes_applied.len(), 2);
+        assert_eq!(
+            (*changes_applied[0].change).downcast_ref::<RepositoryArchivedChange>(),
+            Some(&RepositoryArchivedChange::ChangeSkipped(
+                RepositoryChange::TeamAdded("repo1".to_string(), "team1".to_string(), Role::Write)
+            ))
+        );
+        assert!(changes_applied[0].error.is_none());
+        assert!(changes_applied[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn apply_changes_repository_watchers() {
+        let mut svc = MockSvc::new();
+        svc.expect_add_repository_watcher()
+            .withf(|_, repo_name, user_token| repo_name == "repo1" && *user_token == "token1")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        svc.expect_remove_repository_watcher()
+            .withf(|_, repo_name, user_token| repo_name == "repo2" && *user_token == "token1")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            service_accounts: Some(vec![
+                ServiceAccount {
+                    login: "bot1".to_string(),
+                    token: Some("token1".to_string()),
+                    ..Default::default()
+                },
+                ServiceAccount {
+                    login: "bot2".to_string(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+        let changes = Changes {
+            repositories: vec![
+                RepositoryChange::WatcherAdded("repo1".to_string(), "bot1".to_string()),
+                RepositoryChange::WatcherRemoved("repo2".to_string(), "bot1".to_string()),
+                RepositoryChange::WatcherAdded("repo3".to_string(), "bot2".to_string()),
+            ],
+            ..Default::default()
+        };
+        let changes_applied = handler.apply_changes(&org, &ctx(), changes).await.unwrap();
+        assert_eq!(changes_applied.len(), 3);
+        assert!(changes_applied[0].error.is_none());
+        assert!(changes_applied[1].error.is_none());
+        assert_eq!(
+            changes_applied[2].error.as_deref(),
+            Some("service account token not found")
+        );
+    }
+
+    #[tokio::test]
+    async fn add_repository_strip_default_labels() {
+        let mut svc = MockSvc::new();
+        svc.expect_add_repository().times(1).returning(|_, _| Ok("main".to_string()));
+        svc.expect_list_repository_labels()
+            .times(1)
+            .returning(|_, _| Ok(vec!["bug".to_string(), "documentation".to_string()]));
+        svc.expect_remove_repository_label()
+            .withf(|_, repo_name, label_name| repo_name == "repo1" && label_name == "bug")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        svc.expect_remove_repository_label()
+            .withf(|_, repo_name, label_name| repo_name == "repo1" && label_name == "documentation")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let repo = Repository {
+            name: "repo1".to_string(),
+            strip_default_labels: Some(true),
+            ..Default::default()
+        };
+        handler.add_repository(&ctx(), &repo).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn add_repository_keep_default_labels() {
+        let mut svc = MockSvc::new();
+        svc.expect_add_repository().times(1).returning(|_, _| Ok("main".to_string()));
+        svc.expect_list_repository_labels().never();
+        svc.expect_remove_repository_label().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let repo = Repository {
+            name: "repo1".to_string(),
+            ..Default::default()
+        };
+        handler.add_repository(&ctx(), &repo).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn add_repository_branch_protection() {
+        let branch_protection = BranchProtection {
+            required_approving_review_count: Some(1),
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_add_repository().times(1).returning(|_, _| Ok("main".to_string()));
+        let expected_branch_protection = branch_protection.clone();
+        svc.expect_update_repository_branch_protection()
+            .withf(move |_, repo_name, branch, protection| {
+                repo_name == "repo1" && branch == "main" && *protection == expected_branch_protection
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let repo = Repository {
+            name: "repo1".to_string(),
+            branch_protection: Some(branch_protection),
+            ..Default::default()
+        };
+        handler.add_repository(&ctx(), &repo).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn add_repository_branch_protection_error_is_not_fatal() {
+        let mut svc = MockSvc::new();
+        svc.expect_add_repository().times(1).returning(|_, _| Ok("main".to_string()));
+        svc.expect_update_repository_branch_protection()
+            .times(1)
+            .returning(|_, _, _, _| Err(format_err!("branch protection not available")));
+        svc.expect_update_repository_topics().times(1).returning(|_, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let repo = Repository {
+            name: "repo1".to_string(),
+            branch_protection: Some(BranchProtection::default()),
+            topics: Some(vec!["topic1".to_string()]),
+            ..Default::default()
+        };
+        handler.add_repository(&ctx(), &repo).await.unwrap();
+    }
+
+    #[test]
+    fn select_failed_changes_only() {
+        let change1 =
+            RepositoryChange::CollaboratorAdded("repo1".to_string(), "user1".to_string(), Role::Write);
+        let change2 = RepositoryChange::TeamAdded("repo1".to_string(), "team1".to_string(), Role::Read);
+        let change3 = RepositoryChange::CollaboratorRemoved("repo2".to_string(), "user2".to_string());
+        let change4 = RepositoryChange::CollaboratorRemoved("repo3".to_string(), "user3".to_string());
+
+        // change4 failed previously but it's already satisfied now, so it's
+        // not found in the changes detected
+        let failed = vec![change3.details(), change4.details(), change1.details()];
+        let changes = vec![change1.clone(), change2, change3.clone()];
+        assert_eq!(select_failed(changes, &failed), vec![change3, change1]);
+    }
+
+    #[tokio::test]
+    async fn track_invitations_declined_not_invited_again() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_repository_invitations().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            max_declined_invitations: Some(1),
+            ..Default::default()
+        };
+        let actual_state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut changes = Changes {
+            directory: vec![],
+            repositories: vec![RepositoryChange::CollaboratorAdded(
+                "repo1".to_string(),
+                "user1".to_string(),
+                Role::Write,
+            )],
+        };
+        let invitations = vec![CollaboratorInvitation {
+            repository: "repo1".to_string(),
+            user_name: "user1".to_string(),
+            pending: true,
+            declines: 0,
+        }];
+        let invitations = handler
+            .track_invitations(&org, &ctx(), &actual_state, &mut changes, invitations)
+            .await
+            .unwrap();
+        assert!(changes.repositories.is_empty());
+        assert_eq!(
+            invitations,
+            vec![CollaboratorInvitation {
+                repository: "repo1".to_string(),
+                user_name: "user1".to_string(),
+                pending: false,
+                declines: 1,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn track_invitations_accepted_and_sent() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_repository_invitations().times(1).returning(|_, _| Ok(vec![]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let actual_state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let change =
+            RepositoryChange::CollaboratorAdded("repo1".to_string(), "user2".to_string(), Role::Read);
+        let mut changes = Changes {
+            directory: vec![],
+            repositories: vec![change.clone()],
+        };
+        let invitations = vec![CollaboratorInvitation {
+            repository: "repo1".to_string(),
+            user_name: "user1".to_string(),
+            pending: true,
+            declines: 1,
+        }];
+        let mut invitations = handler
+            .track_invitations(
+                &Organization::default(),
+                &ctx(),
+                &actual_state,
+                &mut changes,
+                invitations,
+            )
+            .await
+            .unwrap();
+        assert!(invitations.is_empty());
+        assert_eq!(changes.repositories, vec![change.clone()]);
+
+        let changes_applied = vec![ChangeApplied {
+            change: Box::new(change),
+            error: None,
+            applied_at: time::OffsetDateTime::now_utc(),
+        }];
+        track_invitations_sent(&changes_applied, &mut invitations);
+        assert_eq!(
+            invitations,
+            vec![CollaboratorInvitation {
+                repository: "repo1".to_string(),
+                user_name: "user2".to_string(),
+                pending: true,
+                declines: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn defer_removals_immediate() {
+        let removal = RepositoryChange::CollaboratorRemoved("repo1".to_string(), "user1".to_string());
+        let mut changes = Changes {
+            directory: vec![DirectoryChange::TeamMemberRemoved(
+                "team1".to_string(),
+                "user1".to_string(),
+            )],
+            repositories: vec![removal],
+        };
+        let expected_changes = changes.clone();
+
+        let pending_removals = defer_removals(
+            &Organization::default(),
+            &State::default(),
+            &mut changes,
+            vec![],
+            0,
+        );
+        assert!(pending_removals.is_empty());
+        assert_eq!(changes, expected_changes);
+    }
+
+    #[test]
+    fn defer_removals_deferred_until_grace_period_elapses() {
+        let org = Organization {
+            removals_grace_period_hours: 24,
+            ..Default::default()
+        };
+        let member_removed = DirectoryChange::TeamMemberRemoved("team1".to_string(), "user1".to_string());
+        let changes = Changes {
+            directory: vec![
+                member_removed.clone(),
+                DirectoryChange::TeamMaintainerRemoved("team1".to_string(), "user2".to_string()),
+                DirectoryChange::TeamMemberAdded("team1".to_string(), "user2".to_string()),
+            ],
+            repositories: vec![RepositoryChange::TeamAdded(
+                "repo1".to_string(),
+                "team1".to_string(),
+                Role::Write,
+            )],
+        };
+
+        // Removal detected for the first time: it is deferred (role updates
+        // and other changes are applied immediately)
+        let mut changes_1 = changes.clone();
+        let pending_removals = defer_removals(&org, &State::default(), &mut changes_1, vec![], 1000);
+        assert_eq!(
+            pending_removals,
+            vec![PendingRemoval {
+                kind: member_removed.details().kind,
+                extra: member_removed.details().extra,
+                detected_at: 1000,
+            }]
+        );
+        assert_eq!(changes_1.directory, changes.directory[1..].to_vec());
+        assert_eq!(changes_1.repositories, changes.repositories);
+
+        // Removal still needed but grace period not elapsed yet
+        let mut changes_2 = changes.clone();
+        let pending_removals = defer_removals(
+            &org,
+            &State::default(),
+            &mut changes_2,
+            pending_removals,
+            1000 + 3600,
+        );
+        assert_eq!(pending_removals.len(), 1);
+        assert_eq!(pending_removals[0].detected_at, 1000);
+        assert_eq!(changes_2, changes_1);
+
+        // Grace period elapsed: removal applied
+        let mut changes_3 = changes.clone();
+        let pending_removals = defer_removals(
+            &org,
+            &State::default(),
+            &mut changes_3,
+            pending_removals,
+            1000 + 24 * 3600,
+        );
+        assert!(pending_removals.is_empty());
+        assert_eq!(changes_3, changes);
+    }
+
+    #[test]
+    fn defer_removals_not_needed_anymore_are_discarded() {
+        let org = Organization {
+            removals_grace_period_hours: 24,
+            ..Default::default()
+        };
+        let removal = RepositoryChange::CollaboratorRemoved("repo1".to_string(), "user1".to_string());
+        let mut changes = Changes {
+            directory: vec![],
+            repositories: vec![removal],
+        };
+        let pending_removals = defer_removals(&org, &State::default(), &mut changes, vec![], 1000);
+        assert_eq!(pending_removals.len(), 1);
+        assert!(changes.repositories.is_empty());
+
+        // The removal is not part of the changes anymore (i.e. the
+        // configuration change was reverted)
+        let mut changes = Changes::default();
+        let pending_removals = defer_removals(&org, &State::default(), &mut changes, pending_removals, 2000);
+        assert!(pending_removals.is_empty());
+    }
+
+    #[test]
+    fn defer_removals_expired_collaborators_are_not_deferred() {
+        let org = Organization {
+            removals_grace_period_hours: 24,
+            ..Default::default()
+        };
+        let desired_state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                expired_collaborators: Some(BTreeSet::from(["user1".to_string()])),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let expired = RepositoryChange::CollaboratorRemoved("repo1".to_string(), "user1".to_string());
+        let removal = RepositoryChange::CollaboratorRemoved("repo1".to_string(), "user2".to_string());
+        let mut changes = Changes {
+            directory: vec![],
+            repositories: vec![expired.clone(), removal],
+        };
+
+        let pending_removals = defer_removals(&org, &desired_state, &mut changes, vec![], 1000);
+        assert_eq!(pending_removals.len(), 1);
+        assert_eq!(pending_removals[0].extra["user_name"], "user2");
+        assert_eq!(changes.repositories, vec![expired]);
+    }
+
+    #[tokio::test]
+    async fn get_webhooks_changes_not_configured() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_webhooks().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let (_, changes) = handler.get_webhooks_changes(&Organization::default(), &ctx()).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_webhooks_changes_added_updated_removed() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_webhooks().times(2).returning(|_| Ok(webhooks_in_svc()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let webhook1 = Webhook {
+            url: "https://hook1.url".to_string(),
+            events: vec!["push".to_string(), "pull_request".to_string()],
+            ..Default::default()
+        };
+        let webhook2 = Webhook {
+            url: "https://hook2.url".to_string(),
+            events: vec!["push".to_string(), "pull_request".to_string()],
+            ..Default::default()
+        };
+        let webhook4 = Webhook {
+            url: "https://hook4.url".to_string(),
+            events: vec!["repository".to_string()],
+            secret: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let mut org = Organization {
+            webhooks: Some(vec![webhook1, webhook2.clone(), webhook4.clone()]),
+            ..Default::default()
+        };
+
+        // Undeclared webhooks are not removed by default
+        let (_, changes) = handler.get_webhooks_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::WebhookUpdated(webhook2.clone()),
+                OrganizationChange::WebhookAdded(webhook4.clone()),
+            ]
+        );
+
+        org.remove_undeclared_webhooks = true;
+        let (_, changes) = handler.get_webhooks_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::WebhookUpdated(webhook2),
+                OrganizationChange::WebhookAdded(webhook4),
+                OrganizationChange::WebhookRemoved("https://hook3.url".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_organization_changes_webhooks() {
+        let mut svc = MockSvc::new();
+        svc.expect_add_org_webhook()
+            .withf(|_, webhook| webhook.url == "https://hook4.url")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        svc.expect_update_org_webhook()
+            .withf(|_, webhook_id, webhook| *webhook_id == 2 && webhook.url == "https://hook2.url")
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        svc.expect_remove_org_webhook()
+            .withf(|_, webhook_id| *webhook_id == 3)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = vec![
+            OrganizationChange::WebhookUpdated(Webhook {
+                url: "https://hook2.url".to_string(),
+                ..Default::default()
+            }),
+            OrganizationChange::WebhookAdded(Webhook {
+                url: "https://hook4.url".to_string(),
+                ..Default::default()
+            }),
+            OrganizationChange::WebhookRemoved("https://hook3.url".to_string()),
+            OrganizationChange::WebhookRemoved("https://hook5.url".to_string()),
+        ];
+        let changes_applied = handler.apply_organization_changes(&ctx(), &webhooks_in_svc(), changes).await;
+        assert_eq!(
+            changes_applied.iter().map(|c| c.error.clone()).collect::<Vec<Option<String>>>(),
+            vec![None, None, None, Some("webhook not found".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_codeowners_changes_outdated_and_missing_files() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_repository_codeowners()
+            .times(3)
+            .returning(|_, repo_name| match repo_name.as_str() {
+                "repo1" => Ok(Some(codeowners_content(&["@org/team1".to_string()]))),
+                "repo2" => Ok(Some("* @org/team2\n".to_string())),
+                _ => Ok(None),
+            });
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let repo = |name: &str, codeowners: Option<CodeownersManagement>| Repository {
+            name: name.to_string(),
+            teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+            codeowners,
+            ..Default::default()
+        };
+        let pull_request = CodeownersManagement {
+            update_mode: CodeownersUpdateMode::PullRequest,
+        };
+        let actual_state = State {
+            repositories: vec![
+                repo("repo1", None),
+                repo("repo2", None),
+                repo("repo3", None),
+                repo("repo5", None),
+            ],
+            ..Default::default()
+        };
+        let desired_state = State {
+            repositories: vec![
+                repo("repo1", Some(CodeownersManagement::default())),
+                repo("repo2", Some(pull_request.clone())),
+                repo("repo3", Some(CodeownersManagement::default())),
+                repo("repo4", Some(CodeownersManagement::default())),
+                repo("repo5", None),
+            ],
+            ..Default::default()
+        };
+        let changes = handler.get_codeowners_changes(&ctx(), &actual_state, &desired_state).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    RepositoryChange::CodeownersUpdated(
+                        "repo2".to_string(),
+                        vec!["@org/team1".to_string()],
+                        CodeownersUpdateMode::PullRequest
+                    ),
+                    ChangeExplanation::updated("codeowners", "outdated", "@org/team1")
+                ),
+                (
+                    RepositoryChange::CodeownersUpdated(
+                        "repo3".to_string(),
+                        vec!["@org/team1".to_string()],
+                        CodeownersUpdateMode::Commit
+                    ),
+                    ChangeExplanation::added("codeowners", "@org/team1")
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_dependabot_config_changes_missing_files() {
+        let mut svc = MockSvc::new();
+        svc.expect_repository_file_exists().returning(|_, repo_name, path, _| {
+            Ok(matches!(
+                (repo_name.as_str(), path),
+                ("repo1", ".github/dependabot.yml") | ("repo2", ".github/dependabot.yaml")
+            ))
+        });
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let repo = |name: &str| Repository {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let actual_state = State {
+            repositories: vec![repo("repo1"), repo("repo2"), repo("repo3")],
+            ..Default::default()
+        };
+        let desired_state = State {
+            repositories: vec![repo("repo1"), repo("repo2"), repo("repo3"), repo("repo4")],
+            ..Default::default()
+        };
+
+        // Missing files are only reported when the policy is not enforced
+        let mut org = Organization {
+            dependabot_config: Some(DependabotConfig::default()),
+            ..Default::default()
+        };
+        let changes = handler
+            .get_dependabot_config_changes(&org, &ctx(), &actual_state, &desired_state)
+            .await
+            .unwrap();
+        assert!(changes.is_empty());
+
+        org.dependabot_config = Some(DependabotConfig { enforce: true });
+        let changes = handler
+            .get_dependabot_config_changes(&org, &ctx(), &actual_state, &desired_state)
+            .await
+            .unwrap();
+        assert_eq!(
+            changes,
+            vec![(
+                RepositoryChange::DependabotConfigAdded("repo3".to_string()),
+                ChangeExplanation::added("dependabot config", ".github/dependabot.yml")
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_community_health_files_changes_added_updated() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(|_, path| Ok(format!("content of {path}")));
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_community_health_file().returning(|_, path| match path {
+            "CODE_OF_CONDUCT.md" => Ok(Some("content of health/CODE_OF_CONDUCT.md".to_string())),
+            "CONTRIBUTING.md" => Ok(Some("outdated content".to_string())),
+            _ => Ok(None),
+        });
+        let handler = Handler::new(Arc::new(gh), Arc::new(svc));
+
+        let health_file = |path: &str| CommunityHealthFile {
+            path: path.to_string(),
+            source: format!("health/{path}"),
+        };
+        let org = Organization {
+            community_health_files: Some(vec![
+                health_file("CODE_OF_CONDUCT.md"),
+                health_file("CONTRIBUTING.md"),
+                health_file("SECURITY.md"),
+            ]),
+            ..Default::default()
+        };
+        let changes = handler.get_community_health_files_changes(&org, &ctx(), &src()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::CommunityHealthFileUpdated(
+                    "CONTRIBUTING.md".to_string(),
+                    "content of health/CONTRIBUTING.md".to_string()
+                ),
+                OrganizationChange::CommunityHealthFileAdded(
+                    "SECURITY.md".to_string(),
+                    "content of health/SECURITY.md".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_community_health_files_changes_invalid_file() {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content()
+            .returning(|_, _| Ok("blank_issues_enabled: [false".to_string()));
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_community_health_file().never();
+        let handler = Handler::new(Arc::new(gh), Arc::new(svc));
+
+        let org = Organization {
+            community_health_files: Some(vec![CommunityHealthFile {
+                path: ".github/ISSUE_TEMPLATE/config.yml".to_string(),
+                source: "health/config.yml".to_string(),
+            }]),
+            ..Default::default()
+        };
+        let err = handler.get_community_health_files_changes(&org, &ctx(), &src()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(merr.errors().len(), 1);
+        assert_eq!(
+            merr.errors()[0].to_string(),
+            "file[.github/ISSUE_TEMPLATE/config.yml]: invalid yaml file"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_settings_changes_not_configured() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_settings().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = handler.get_settings_changes(&Organization::default(), &ctx()).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_settings_changes_each_setting() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_settings().returning(|_| {
+            let mut settings = OrganizationSettings::default();
+            for setting in OrganizationSetting::ALL {
+                settings.set(setting, Some(false));
+            }
+            Ok(settings)
+        });
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        for setting in OrganizationSetting::ALL {
+            // Setting already matching the desired value
+            let mut settings = OrganizationSettings::default();
+            settings.set(setting, Some(false));
+            let org = Organization {
+                settings: Some(settings.clone()),
+                ..Default::default()
+            };
+            let changes = handler.get_settings_changes(&org, &ctx()).await.unwrap();
+            assert!(changes.is_empty());
+
+            // Setting not matching the desired value
+            settings.set(setting, Some(true));
+            let org = Organization {
+                settings: Some(settings),
+                ..Default::default()
+            };
+            let changes = handler.get_settings_changes(&org, &ctx()).await.unwrap();
+            assert_eq!(changes, vec![OrganizationChange::SettingUpdated(setting, true)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_settings_changes_team_creation_permission() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_settings().returning(|_| {
+            Ok(OrganizationSettings {
+                team_creation_permission: Some(TeamCreationPermission::AllMembers),
+                ..Default::default()
+            })
+        });
+        svc.expect_update_org_team_creation_permission()
+            .withf(|_, permission| *permission == TeamCreationPermission::Owners)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        // Permission already matching the desired value
+        let org = Organization {
+            settings: Some(OrganizationSettings {
+                team_creation_permission: Some(TeamCreationPermission::AllMembers),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let changes = handler.get_settings_changes(&org, &ctx()).await.unwrap();
+        assert!(changes.is_empty());
+
+        // Permission not matching the desired value
+        let org = Organization {
+            settings: Some(OrganizationSettings {
+                team_creation_permission: Some(TeamCreationPermission::Owners),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let changes = handler.get_settings_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![OrganizationChange::TeamCreationPermissionUpdated(
+                TeamCreationPermission::Owners
+            )]
+        );
+
+        let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn apply_organization_changes_settings() {
+        let mut svc = MockSvc::new();
+        for setting in OrganizationSetting::ALL {
+            svc.expect_update_org_setting()
+                .withf(move |_, s, value| *s == setting && *value)
+                .times(1)
+                .returning(|_, _, _| Ok(()));
+        }
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = OrganizationSetting::ALL
+            .into_iter()
+            .map(|setting| OrganizationChange::SettingUpdated(setting, true))
+            .collect();
+        let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+        assert_eq!(
+            changes_applied.iter().map(|c| c.error.clone()).collect::<Vec<Option<String>>>(),
+            vec![None, None, None, None]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_required_workflows_changes_not_configured() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_required_workflows().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = handler.get_required_workflows_changes(&Organization::default(), &ctx()).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_required_workflows_changes_added_updated_removed() {
+        let workflow = |path: &str, repositories: Option<Vec<&str>>| RequiredWorkflow {
+            repository: "workflows".to_string(),
+            path: path.to_string(),
+            ref_: None,
+            repositories: repositories.map(|r| r.into_iter().map(ToString::to_string).collect()),
+        };
+        let workflow1 = workflow("wf1.yml", Some(vec!["repo1", "repo2"]));
+        let workflow2 = workflow("wf2.yml", None);
+        let workflow3 = workflow("wf3.yml", None);
+        let workflow4 = workflow("wf4.yml", None);
+
+        let mut svc = MockSvc::new();
+        svc.expect_repository_file_exists().times(3).returning(|_, _, _, _| Ok(true));
+        let workflows_in_svc = vec![
+            OrgRequiredWorkflow {
+                id: 1,
+                workflow: workflow("wf1.yml", Some(vec!["repo2", "repo1"])),
+            },
+            OrgRequiredWorkflow {
+                id: 2,
+                workflow: workflow("wf2.yml", Some(vec!["repo1"])),
+            },
+            OrgRequiredWorkflow {
+                id: 3,
+                workflow: workflow3,
+            },
+        ];
+        svc.expect_list_org_required_workflows().returning(move |_| Ok(workflows_in_svc.clone()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            required_workflows: Some(vec![workflow1, workflow2.clone(), workflow4.clone()]),
+            ..Default::default()
+        };
+        let changes = handler.get_required_workflows_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::RequiredWorkflowUpdated(2, workflow2),
+                OrganizationChange::RequiredWorkflowAdded(workflow4),
+                OrganizationChange::RequiredWorkflowRemoved(3, "workflows/wf3.yml".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_required_workflows_changes_workflow_file_not_found() {
+        let mut svc = MockSvc::new();
+        svc.expect_repository_file_exists()
+            .withf(|_, repo_name, path, ref_| {
+                repo_name == "workflows" && path == "wf1.yml" && ref_.as_deref() == Some("v1")
+            })
+            .returning(|_, _, _, _| Ok(false));
+        svc.expect_list_org_required_workflows().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            required_workflows: Some(vec![RequiredWorkflow {
+                repository: "workflows".to_string(),
+                path: "wf1.yml".to_string(),
+                ref_: Some("v1".to_string()),
+                repositories: None,
+            }]),
+            ..Default::default()
+        };
+        let err = handler.get_required_workflows_changes(&org, &ctx()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(merr.errors().len(), 1);
+        assert_eq!(
+            merr.errors()[0].to_string(),
+            "workflow[workflows/wf1.yml]: workflow file not found in repository workflows"
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_organization_changes_required_workflows() {
+        let workflow = RequiredWorkflow {
+            repository: "workflows".to_string(),
+            path: "wf1.yml".to_string(),
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_add_org_required_workflow().times(1).returning(|_, _| Ok(()));
+        svc.expect_update_org_required_workflow()
+            .withf(|_, workflow_id, _| *workflow_id == 2)
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        svc.expect_remove_org_required_workflow()
+            .withf(|_, workflow_id| *workflow_id == 3)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = vec![
+            OrganizationChange::RequiredWorkflowAdded(workflow.clone()),
+            OrganizationChange::RequiredWorkflowUpdated(2, workflow),
+            OrganizationChange::RequiredWorkflowRemoved(3, "workflows/wf3.yml".to_string()),
+        ];
+        let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn get_service_accounts_changes_invited_and_demoted() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_membership_role().returning(|_, user_name| match user_name.as_str() {
+            "bot1" => Ok(None),
+            "bot2" => Ok(Some("admin".to_string())),
+            _ => Ok(Some("member".to_string())),
+        });
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let service_account = |login: &str| ServiceAccount {
+            login: login.to_string(),
+            ..Default::default()
+        };
+        let org = Organization {
+            service_accounts: Some(vec![
+                service_account("bot1"),
+                service_account("bot2"),
+                service_account("bot3"),
+            ]),
+            ..Default::default()
+        };
+        let changes = handler.get_service_accounts_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::ServiceAccountInvited("bot1".to_string()),
+                OrganizationChange::ServiceAccountDemoted("bot2".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_verified_domains_gate_changes_enabled_and_disabled() {
+        for (enabled_in_svc, enabled) in [(false, true), (true, false)] {
+            let mut svc = MockSvc::new();
+            svc.expect_get_org_verified_domains().returning(move |_| {
+                Ok(OrgVerifiedDomains {
+                    domains: vec!["example.com".to_string()],
+                    gate_enabled: enabled_in_svc,
+                })
+            });
+            svc.expect_update_org_verified_domains_gate()
+                .withf(move |_, value| *value == enabled)
+                .times(1)
+                .returning(|_, _| Ok(()));
+            let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+            let org = Organization {
+                verified_domains_gate: Some(VerifiedDomainsGate {
+                    enabled,
+                    required_domains: vec!["EXAMPLE.com".to_string()],
+                }),
+                ..Default::default()
+            };
+            let changes = handler.get_verified_domains_gate_changes(&org, &ctx()).await.unwrap();
+            assert_eq!(
+                changes,
+                vec![OrganizationChange::VerifiedDomainsGateUpdated(enabled)]
+            );
+
+            let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+            assert!(changes_applied.iter().all(|c| c.error.is_none()));
+        }
+    }
+
+    #[tokio::test]
+    async fn get_verified_domains_gate_changes_required_domains_not_verified() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_verified_domains().returning(|_| {
+            Ok(OrgVerifiedDomains {
+                domains: vec!["example.com".to_string()],
+                gate_enabled: false,
+            })
+        });
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            verified_domains_gate: Some(VerifiedDomainsGate {
+                enabled: true,
+                required_domains: vec!["example.com".to_string(), "example.org".to_string()],
+            }),
+            ..Default::default()
+        };
+        let err = handler.get_verified_domains_gate_changes(&org, &ctx()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["required domain example.org is not verified in the organization".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_blocked_users_changes_blocked_and_unblocked() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![member("user1")]));
+        svc.expect_list_org_blocked_users()
+            .returning(|_| Ok(vec!["user2".to_string(), "user3".to_string()]));
+        svc.expect_block_org_user()
+            .withf(|_, user_name| user_name == "user1")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        svc.expect_unblock_org_user()
+            .withf(|_, user_name| user_name == "user3")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            blocked_users: Some(vec!["user1".to_string(), "USER2".to_string()]),
+            unblock_unlisted_users: true,
+            confirmed_blocked_members: Some(vec!["user1".to_string()]),
+            ..Default::default()
+        };
+        let changes = handler.get_blocked_users_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::UserBlocked("user1".to_string()),
+                OrganizationChange::UserUnblocked("user3".to_string()),
+            ]
+        );
+
+        let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn get_blocked_users_changes_unlisted_users_not_unblocked() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+        svc.expect_list_org_blocked_users().returning(|_| Ok(vec!["user2".to_string()]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            blocked_users: Some(vec!["user1".to_string()]),
+            ..Default::default()
+        };
+        let changes = handler.get_blocked_users_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![OrganizationChange::UserBlocked("user1".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_blocked_users_changes_invalid_blocked_users() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![member("user1")]));
+        svc.expect_list_org_blocked_users().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            blocked_users: Some(vec![
+                "user1".to_string(),
+                "-user2".to_string(),
+                "user3".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let err = handler.get_blocked_users_changes(&org, &ctx()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "user1 is an organization member and has not been confirmed to be blocked".to_string(),
+                "-user2 is not a valid user name".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_security_manager_teams_changes_not_configured() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_security_manager_teams().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = handler
+            .get_security_manager_teams_changes(&Organization::default(), &ctx())
+            .await
+            .unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_security_manager_teams_changes_granted_and_revoked() {
+        let mut svc = MockSvc::new();
+        svc.expect_team_exists().returning(|_, _| Ok(true));
+        svc.expect_list_org_security_manager_teams()
+            .returning(|_| Ok(vec!["team2".to_string(), "team3".to_string()]));
+        svc.expect_add_org_security_manager_team()
+            .withf(|_, team_name| team_name == "team1")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        svc.expect_remove_org_security_manager_team()
+            .withf(|_, team_name| team_name == "team3")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            security_manager_teams: Some(vec!["team1".to_string(), "team2".to_string()]),
+            ..Default::default()
+        };
+        let changes = handler.get_security_manager_teams_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::SecurityManagerTeamAdded("team1".to_string()),
+                OrganizationChange::SecurityManagerTeamRemoved("team3".to_string()),
+            ]
+        );
+
+        let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn get_security_manager_teams_changes_team_not_found() {
+        let mut svc = MockSvc::new();
+        svc.expect_team_exists().returning(|_, team_name| Ok(team_name == "team1"));
+        svc.expect_list_org_security_manager_teams().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            security_manager_teams: Some(vec!["team1".to_string(), "team2".to_string()]),
+            ..Default::default()
+        };
+        let err = handler.get_security_manager_teams_changes(&org, &ctx()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["team team2 does not exist in the organization".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_secrets_changes_not_configured() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_secret_visibility().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = handler.get_secrets_changes(&Organization::default(), &ctx()).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_secrets_changes_repositories_updated() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_secret_visibility().returning(|_, _| Ok(Some("selected".to_string())));
+        svc.expect_repository_exists().returning(|_, _| Ok(true));
+        svc.expect_list_org_secret_repositories()
+            .withf(|_, secret_name| secret_name == "SECRET1")
+            .returning(|_, _| Ok(vec!["repo2".to_string(), "repo1".to_string()]));
+        svc.expect_list_org_secret_repositories()
+            .withf(|_, secret_name| secret_name == "SECRET2")
+            .returning(|_, _| Ok(vec!["repo1".to_string()]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            secrets: Some(vec![
+                OrganizationSecret {
+                    name: "SECRET1".to_string(),
+                    repositories: vec!["repo1".to_string(), "repo2".to_string()],
+                },
+                OrganizationSecret {
+                    name: "SECRET2".to_string(),
+                    repositories: vec!["repo3".to_string(), "repo1".to_string()],
+                },
+            ]),
+            ..Default::default()
+        };
+        let changes = handler.get_secrets_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![OrganizationChange::SecretRepositoriesUpdated(
+                "SECRET2".to_string(),
+                vec!["repo1".to_string(), "repo3".to_string()]
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_secrets_changes_invalid_secrets() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_org_secret_visibility()
+            .withf(|_, secret_name| secret_name == "SECRET1")
+            .returning(|_, _| Ok(None));
+        svc.expect_get_org_secret_visibility()
+            .withf(|_, secret_name| secret_name == "SECRET2")
+            .returning(|_, _| Ok(Some("all".to_string())));
+        svc.expect_get_org_secret_visibility()
+            .withf(|_, secret_name| secret_name == "SECRET3")
+            .returning(|_, _| Ok(Some("selected".to_string())));
+        svc.expect_repository_exists().returning(|_, _| Ok(false));
+        svc.expect_list_org_secret_repositories().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let secret = |name: &str| OrganizationSecret {
+            name: name.to_string(),
+            repositories: vec!["repo1".to_string()],
+        };
+        let org = Organization {
+            secrets: Some(vec![secret("SECRET1"), secret("SECRET2"), secret("SECRET3")]),
+            ..Default::default()
+        };
+        let err = handler.get_secrets_changes(&org, &ctx()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "secret[SECRET1]: secret does not exist",
+                "secret[SECRET2]: visibility must be selected (found all)",
+                "secret[SECRET3]: repository repo1 does not exist",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_organization_changes_secrets() {
+        let mut svc = MockSvc::new();
+        svc.expect_update_org_secret_repositories()
+            .withf(|_, secret_name, repos_names| {
+                secret_name == "SECRET1" && repos_names == ["repo1".to_string(), "repo2".to_string()]
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = vec![OrganizationChange::SecretRepositoriesUpdated(
+            "SECRET1".to_string(),
+            vec!["repo1".to_string(), "repo2".to_string()],
+        )];
+        let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+        assert!(changes_applied[0].error.is_none());
+    }
+
+    #[tokio::test]
+    async fn get_runner_groups_changes_not_configured() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_runner_groups().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = handler.get_runner_groups_changes(&Organization::default(), &ctx()).await.unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_runner_groups_changes_added_and_repositories_updated() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_runner_groups().returning(|_| {
+            Ok(vec![
+                OrgRunnerGroup {
+                    id: 1,
+                    name: "Default".to_string(),
+                    visibility: "all".to_string(),
+                },
+                OrgRunnerGroup {
+                    id: 2,
+                    name: "group1".to_string(),
+                    visibility: "selected".to_string(),
+                },
+                OrgRunnerGroup {
+                    id: 3,
+                    name: "group2".to_string(),
+                    visibility: "selected".to_string(),
+                },
+            ])
+        });
+        svc.expect_repository_exists().returning(|_, _| Ok(true));
+        svc.expect_list_org_runner_group_repositories()
+            .withf(|_, runner_group_id| *runner_group_id == 2)
+            .returning(|_, _| Ok(vec!["repo2".to_string(), "repo1".to_string()]));
+        svc.expect_list_org_runner_group_repositories()
+            .withf(|_, runner_group_id| *runner_group_id == 3)
+            .returning(|_, _| Ok(vec!["repo1".to_string()]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let runner_group = |name: &str, repositories: &[&str]| OrganizationRunnerGroup {
+            name: name.to_string(),
+            repositories: repositories.iter().map(ToString::to_string).collect(),
+        };
+        let org = Organization {
+            runner_groups: Some(vec![
+                runner_group("group1", &["repo1", "repo2"]),
+                runner_group("group2", &["repo3", "repo1"]),
+                runner_group("group3", &["repo1"]),
+            ]),
+            ..Default::default()
+        };
+        let changes = handler.get_runner_groups_changes(&org, &ctx()).await.unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                OrganizationChange::RunnerGroupRepositoriesUpdated(
+                    3,
+                    runner_group("group2", &["repo1", "repo3"])
+                ),
+                OrganizationChange::RunnerGroupAdded(runner_group("group3", &["repo1"])),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_runner_groups_changes_invalid_runner_groups() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_runner_groups().returning(|_| {
+            Ok(vec![OrgRunnerGroup {
+                id: 1,
+                name: "Default".to_string(),
+                visibility: "all".to_string(),
+            }])
+        });
+        svc.expect_repository_exists().returning(|_, _| Ok(false));
+        svc.expect_list_org_runner_group_repositories().never();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            runner_groups: Some(vec![
+                OrganizationRunnerGroup {
+                    name: "Default".to_string(),
+                    repositories: vec!["repo1".to_string()],
+                },
+                OrganizationRunnerGroup {
+                    name: "group1".to_string(),
+                    repositories: vec!["repo1".to_string()],
+                },
+            ]),
+            ..Default::default()
+        };
+        let err = handler.get_runner_groups_changes(&org, &ctx()).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "runner_group[Default]: visibility must be selected (found all)",
+                "runner_group[group1]: repository repo1 does not exist",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_organization_changes_runner_groups() {
+        let mut svc = MockSvc::new();
+        svc.expect_add_org_runner_group()
+            .withf(|_, runner_group| runner_group.name == "group1")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        svc.expect_update_org_runner_group_repositories()
+            .withf(|_, runner_group_id, repos_names| {
+                *runner_group_id == 2 && repos_names == ["repo1".to_string(), "repo2".to_string()]
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let changes = vec![
+            OrganizationChange::RunnerGroupAdded(OrganizationRunnerGroup {
+                name: "group1".to_string(),
+                repositories: vec!["repo1".to_string()],
+            }),
+            OrganizationChange::RunnerGroupRepositoriesUpdated(
+                2,
+                OrganizationRunnerGroup {
+                    name: "group2".to_string(),
+                    repositories: vec!["repo1".to_string(), "repo2".to_string()],
+                },
+            ),
+        ];
+        let changes_applied = handler.apply_organization_changes(&ctx(), &[], changes).await;
+        assert!(changes_applied.iter().all(|c| c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn apply_all_changes_observe_only() {
+        // No expectations are set, so any call to the service would fail
+        let svc = MockSvc::new();
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            observe_only: true,
+            ..Default::default()
+        };
+        let changes = Changes {
+            directory: vec![DirectoryChange::TeamRemoved("team1".to_string())],
+            repositories: vec![RepositoryChange::CollaboratorAdded(
+                "repo1".to_string(),
+                "user1".to_string(),
+                Role::Write,
+            )],
+        };
+        let organization_changes = vec![OrganizationChange::SecretRepositoriesUpdated(
+            "SECRET1".to_string(),
+            vec!["repo1".to_string()],
+        )];
+        let changes_planned =
+            handler.apply_all_changes(&org, &ctx(), &[], changes, organization_changes).await.unwrap();
+        assert_eq!(
+            changes_planned.iter().map(|c| c.change.details().kind).collect::<Vec<String>>(),
+            vec![
+                "team-removed",
+                "repository-collaborator-added",
+                "organization-secret-repositories-updated"
+            ]
+        );
+        assert!(changes_planned.iter().all(|c| c.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn warm_up_prefetches_cached_info() {
+        let mut svc = MockSvc::new();
+        svc.expect_prefetch_org_info().times(1).returning(|_| Ok(()));
+        svc.expect_list_gitignore_templates().times(1).returning(|_| Ok(vec!["Rust".to_string()]));
+        svc.expect_list_license_templates().times(1).returning(|_| Ok(vec!["mit".to_string()]));
+        let handler = Handler::new(Arc::new(MockGH::new()), Arc::new(svc));
+
+        let org = Organization {
+            name: "org".to_string(),
+            ..Default::default()
+        };
+        handler.warm_up(&org).await.unwrap();
     }
 }
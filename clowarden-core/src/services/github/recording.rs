@@ -0,0 +1,756 @@
+//! This module provides a Svc implementation for tests that records all the
+//! mutating calls made to it, in the order they were made. Read-only calls
+//! are delegated to an inner Svc (usually a MockSvc programmed only for the
+//! reads needed), so that tests can assert the exact sequence of API calls
+//! made when applying some changes.
+
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use octorust::types::{
+    Collaborator, MinimalRepository, OrganizationInvitation, RepositoryInvitation, SimpleUser, Team,
+    TeamMembership,
+};
+
+use crate::{
+    cfg::{
+        OrganizationRunnerGroup, OrganizationSetting, OrganizationSettings, RequiredWorkflow,
+        TeamCreationPermission, Webhook,
+    },
+    directory::{self, TeamName, TeamNotifications, UserName},
+};
+
+use super::{
+    service::{
+        AppInstallation, Ctx, DynSvc, MergedBranch, OrgRequiredWorkflow, OrgRunnerGroup, OrgVerifiedDomains,
+        OrgWebhook, Svc,
+    },
+    state::{
+        ActionsAllowList, AppSlug, BranchProtection, CodeScanningSetup, CodeownersUpdateMode,
+        DiscussionSettings, MergeCommitSettings, Repository, RepositoryName, Role, Visibility,
+    },
+};
+
+/// Svc implementation that records the mutating calls made to it.
+pub(crate) struct RecordingSvc {
+    inner: DynSvc,
+    calls: Mutex<Vec<String>>,
+}
+
+impl RecordingSvc {
+    /// Create a new recording svc instance. Read-only calls will be delegated
+    /// to the svc provided.
+    pub(crate) fn new(inner: DynSvc) -> Self {
+        Self {
+            inner,
+            calls: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns the mutating calls recorded so far, in the order they were
+    /// made. Each call is represented by the method name followed by its most
+    /// relevant arguments.
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.calls.lock().expect("calls lock not to be poisoned").clone()
+    }
+
+    /// Record the call provided.
+    fn record(&self, call: String) {
+        self.calls.lock().expect("calls lock not to be poisoned").push(call);
+    }
+}
+
+#[async_trait]
+impl Svc for RecordingSvc {
+    async fn add_repository(&self, _ctx: &Ctx, repo: &Repository) -> Result<String> {
+        self.record(format!("add_repository {}", repo.name));
+        Ok("main".to_string())
+    }
+
+    async fn add_org_required_workflow(&self, _ctx: &Ctx, workflow: &RequiredWorkflow) -> Result<()> {
+        self.record(format!(
+            "add_org_required_workflow {}/{}",
+            workflow.repository, workflow.path
+        ));
+        Ok(())
+    }
+
+    async fn add_org_runner_group(&self, _ctx: &Ctx, runner_group: &OrganizationRunnerGroup) -> Result<()> {
+        self.record(format!(
+            "add_org_runner_group {} {}",
+            runner_group.name,
+            runner_group.repositories.join(",")
+        ));
+        Ok(())
+    }
+
+    async fn add_org_security_manager_team(&self, _ctx: &Ctx, team_name: &TeamName) -> Result<()> {
+        self.record(format!("add_org_security_manager_team {team_name}"));
+        Ok(())
+    }
+
+    async fn add_org_webhook(&self, _ctx: &Ctx, webhook: &Webhook) -> Result<()> {
+        self.record(format!("add_org_webhook {}", webhook.url));
+        Ok(())
+    }
+
+    async fn add_repository_collaborator(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_name: &UserName,
+        role: &Role,
+    ) -> Result<()> {
+        self.record(format!(
+            "add_repository_collaborator {repo_name} {user_name} {role}"
+        ));
+        Ok(())
+    }
+
+    async fn add_repository_dependabot_config(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        _content: &str,
+    ) -> Result<()> {
+        self.record(format!("add_repository_dependabot_config {repo_name}"));
+        Ok(())
+    }
+
+    async fn add_repository_watcher(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        _user_token: &str,
+    ) -> Result<()> {
+        self.record(format!("add_repository_watcher {repo_name}"));
+        Ok(())
+    }
+
+    async fn add_repository_team(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        team_name: &TeamName,
+        role: &Role,
+    ) -> Result<()> {
+        self.record(format!("add_repository_team {repo_name} {team_name} {role}"));
+        Ok(())
+    }
+
+    async fn add_team(&self, _ctx: &Ctx, team: &directory::Team) -> Result<()> {
+        self.record(format!("add_team {}", team.name));
+        Ok(())
+    }
+
+    async fn add_team_maintainer(
+        &self,
+        _ctx: &Ctx,
+        team_name: &TeamName,
+        user_name: &UserName,
+    ) -> Result<()> {
+        self.record(format!("add_team_maintainer {team_name} {user_name}"));
+        Ok(())
+    }
+
+    async fn add_team_member(&self, _ctx: &Ctx, team_name: &TeamName, user_name: &UserName) -> Result<()> {
+        self.record(format!("add_team_member {team_name} {user_name}"));
+        Ok(())
+    }
+
+    async fn block_org_user(&self, _ctx: &Ctx, user_name: &UserName) -> Result<()> {
+        self.record(format!("block_org_user {user_name}"));
+        Ok(())
+    }
+
+    async fn get_repository_admin_access(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<bool>> {
+        self.inner.get_repository_admin_access(ctx, repo_name).await
+    }
+
+    async fn get_team_membership(
+        &self,
+        ctx: &Ctx,
+        team_name: &TeamName,
+        user_name: &UserName,
+    ) -> Result<TeamMembership> {
+        self.inner.get_team_membership(ctx, team_name, user_name).await
+    }
+
+    async fn get_team_notifications(&self, ctx: &Ctx, team_name: &TeamName) -> Result<TeamNotifications> {
+        self.inner.get_team_notifications(ctx, team_name).await
+    }
+
+    async fn get_repository_allow_forking(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<bool> {
+        self.inner.get_repository_allow_forking(ctx, repo_name).await
+    }
+
+    async fn get_org_settings(&self, ctx: &Ctx) -> Result<OrganizationSettings> {
+        self.inner.get_org_settings(ctx).await
+    }
+
+    async fn get_org_verified_domains(&self, ctx: &Ctx) -> Result<OrgVerifiedDomains> {
+        self.inner.get_org_verified_domains(ctx).await
+    }
+
+    async fn get_org_community_health_file(&self, ctx: &Ctx, path: &str) -> Result<Option<String>> {
+        self.inner.get_org_community_health_file(ctx, path).await
+    }
+
+    async fn get_org_secret_visibility(&self, ctx: &Ctx, secret_name: &str) -> Result<Option<String>> {
+        self.inner.get_org_secret_visibility(ctx, secret_name).await
+    }
+
+    async fn get_org_membership_role(&self, ctx: &Ctx, user_name: &UserName) -> Result<Option<String>> {
+        self.inner.get_org_membership_role(ctx, user_name).await
+    }
+
+    async fn get_repository_discussions(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<DiscussionSettings> {
+        self.inner.get_repository_discussions(ctx, repo_name).await
+    }
+
+    async fn get_repository_actions_allow_list(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<ActionsAllowList> {
+        self.inner.get_repository_actions_allow_list(ctx, repo_name).await
+    }
+
+    async fn get_repository_code_scanning_setup(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<CodeScanningSetup>> {
+        self.inner.get_repository_code_scanning_setup(ctx, repo_name).await
+    }
+
+    async fn get_repository_private_vulnerability_reporting(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<bool> {
+        self.inner.get_repository_private_vulnerability_reporting(ctx, repo_name).await
+    }
+
+    async fn get_repository_codeowners(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<String>> {
+        self.inner.get_repository_codeowners(ctx, repo_name).await
+    }
+
+    async fn get_repository_merge_commit_settings(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<MergeCommitSettings> {
+        self.inner.get_repository_merge_commit_settings(ctx, repo_name).await
+    }
+
+    async fn get_user_login(&self, ctx: &Ctx, user_name: &UserName) -> Result<UserName> {
+        self.inner.get_user_login(ctx, user_name).await
+    }
+
+    async fn list_apps_installations(&self, ctx: &Ctx) -> Result<Vec<AppInstallation>> {
+        self.inner.list_apps_installations(ctx).await
+    }
+
+    async fn list_child_teams(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<TeamName>> {
+        self.inner.list_child_teams(ctx, team_name).await
+    }
+
+    async fn list_gitignore_templates(&self, ctx: &Ctx) -> Result<Vec<String>> {
+        self.inner.list_gitignore_templates(ctx).await
+    }
+
+    async fn list_license_templates(&self, ctx: &Ctx) -> Result<Vec<String>> {
+        self.inner.list_license_templates(ctx).await
+    }
+
+    async fn list_org_admins(&self, ctx: &Ctx) -> Result<Vec<SimpleUser>> {
+        self.inner.list_org_admins(ctx).await
+    }
+
+    async fn list_org_blocked_users(&self, ctx: &Ctx) -> Result<Vec<UserName>> {
+        self.inner.list_org_blocked_users(ctx).await
+    }
+
+    async fn list_org_members(&self, ctx: &Ctx) -> Result<Vec<SimpleUser>> {
+        self.inner.list_org_members(ctx).await
+    }
+
+    async fn list_org_required_workflows(&self, ctx: &Ctx) -> Result<Vec<OrgRequiredWorkflow>> {
+        self.inner.list_org_required_workflows(ctx).await
+    }
+
+    async fn list_org_runner_group_repositories(
+        &self,
+        ctx: &Ctx,
+        runner_group_id: i64,
+    ) -> Result<Vec<RepositoryName>> {
+        self.inner.list_org_runner_group_repositories(ctx, runner_group_id).await
+    }
+
+    async fn list_org_runner_groups(&self, ctx: &Ctx) -> Result<Vec<OrgRunnerGroup>> {
+        self.inner.list_org_runner_groups(ctx).await
+    }
+
+    async fn list_org_secret_repositories(
+        &self,
+        ctx: &Ctx,
+        secret_name: &str,
+    ) -> Result<Vec<RepositoryName>> {
+        self.inner.list_org_secret_repositories(ctx, secret_name).await
+    }
+
+    async fn list_org_security_manager_teams(&self, ctx: &Ctx) -> Result<Vec<TeamName>> {
+        self.inner.list_org_security_manager_teams(ctx).await
+    }
+
+    async fn list_org_webhooks(&self, ctx: &Ctx) -> Result<Vec<OrgWebhook>> {
+        self.inner.list_org_webhooks(ctx).await
+    }
+
+    async fn list_repositories(&self, ctx: &Ctx) -> Result<Vec<MinimalRepository>> {
+        self.inner.list_repositories(ctx).await
+    }
+
+    async fn list_repository_collaborators(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Vec<Collaborator>> {
+        self.inner.list_repository_collaborators(ctx, repo_name).await
+    }
+
+    async fn list_repository_invitations(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Vec<RepositoryInvitation>> {
+        self.inner.list_repository_invitations(ctx, repo_name).await
+    }
+
+    async fn list_repository_labels(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<String>> {
+        self.inner.list_repository_labels(ctx, repo_name).await
+    }
+
+    async fn list_repository_merged_branches(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Vec<MergedBranch>> {
+        self.inner.list_repository_merged_branches(ctx, repo_name).await
+    }
+
+    async fn list_repository_teams(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<Team>> {
+        self.inner.list_repository_teams(ctx, repo_name).await
+    }
+
+    async fn list_team_invitations(
+        &self,
+        ctx: &Ctx,
+        team_name: &TeamName,
+    ) -> Result<Vec<OrganizationInvitation>> {
+        self.inner.list_team_invitations(ctx, team_name).await
+    }
+
+    async fn list_team_maintainers(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<SimpleUser>> {
+        self.inner.list_team_maintainers(ctx, team_name).await
+    }
+
+    async fn list_team_members(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<SimpleUser>> {
+        self.inner.list_team_members(ctx, team_name).await
+    }
+
+    async fn list_teams(&self, ctx: &Ctx) -> Result<Vec<Team>> {
+        self.inner.list_teams(ctx).await
+    }
+
+    async fn list_repository_watchers(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<UserName>> {
+        self.inner.list_repository_watchers(ctx, repo_name).await
+    }
+
+    async fn prefetch_org_info(&self, ctx: &Ctx) -> Result<()> {
+        self.inner.prefetch_org_info(ctx).await
+    }
+
+    async fn remove_repository_collaborator(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_name: &UserName,
+    ) -> Result<()> {
+        self.record(format!("remove_repository_collaborator {repo_name} {user_name}"));
+        Ok(())
+    }
+
+    async fn remove_repository_watcher(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        _user_token: &str,
+    ) -> Result<()> {
+        self.record(format!("remove_repository_watcher {repo_name}"));
+        Ok(())
+    }
+
+    async fn reparent_child_teams(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<TeamName>> {
+        // All the child teams are reported as reparented
+        self.record(format!("reparent_child_teams {team_name}"));
+        self.inner.list_child_teams(ctx, team_name).await
+    }
+
+    async fn repository_exists(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<bool> {
+        self.inner.repository_exists(ctx, repo_name).await
+    }
+
+    async fn repository_file_exists(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        path: &str,
+        ref_: Option<String>,
+    ) -> Result<bool> {
+        self.inner.repository_file_exists(ctx, repo_name, path, ref_).await
+    }
+
+    async fn retire_team(&self, _ctx: &Ctx, team_name: &TeamName, new_team_name: &TeamName) -> Result<()> {
+        self.record(format!("retire_team {team_name} {new_team_name}"));
+        Ok(())
+    }
+
+    async fn remove_org_required_workflow(&self, _ctx: &Ctx, workflow_id: i64) -> Result<()> {
+        self.record(format!("remove_org_required_workflow {workflow_id}"));
+        Ok(())
+    }
+
+    async fn remove_org_security_manager_team(&self, _ctx: &Ctx, team_name: &TeamName) -> Result<()> {
+        self.record(format!("remove_org_security_manager_team {team_name}"));
+        Ok(())
+    }
+
+    async fn remove_org_webhook(&self, _ctx: &Ctx, webhook_id: i64) -> Result<()> {
+        self.record(format!("remove_org_webhook {webhook_id}"));
+        Ok(())
+    }
+
+    async fn remove_repository_app(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        app_slug: &AppSlug,
+    ) -> Result<()> {
+        self.record(format!("remove_repository_app {repo_name} {app_slug}"));
+        Ok(())
+    }
+
+    async fn remove_repository_invitation(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        invitation_id: i64,
+    ) -> Result<()> {
+        self.record(format!(
+            "remove_repository_invitation {repo_name} {invitation_id}"
+        ));
+        Ok(())
+    }
+
+    async fn remove_repository_label(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        label_name: &str,
+    ) -> Result<()> {
+        self.record(format!("remove_repository_label {repo_name} {label_name}"));
+        Ok(())
+    }
+
+    async fn remove_repository_team(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        team_name: &TeamName,
+    ) -> Result<()> {
+        self.record(format!("remove_repository_team {repo_name} {team_name}"));
+        Ok(())
+    }
+
+    async fn remove_team(&self, _ctx: &Ctx, team_name: &TeamName) -> Result<()> {
+        self.record(format!("remove_team {team_name}"));
+        Ok(())
+    }
+
+    async fn remove_team_maintainer(
+        &self,
+        _ctx: &Ctx,
+        team_name: &TeamName,
+        user_name: &UserName,
+    ) -> Result<()> {
+        self.record(format!("remove_team_maintainer {team_name} {user_name}"));
+        Ok(())
+    }
+
+    async fn remove_team_member(&self, _ctx: &Ctx, team_name: &TeamName, user_name: &UserName) -> Result<()> {
+        self.record(format!("remove_team_member {team_name} {user_name}"));
+        Ok(())
+    }
+
+    async fn team_exists(&self, ctx: &Ctx, team_name: &TeamName) -> Result<bool> {
+        self.inner.team_exists(ctx, team_name).await
+    }
+
+    async fn unblock_org_user(&self, _ctx: &Ctx, user_name: &UserName) -> Result<()> {
+        self.record(format!("unblock_org_user {user_name}"));
+        Ok(())
+    }
+
+    async fn update_org_required_workflow(
+        &self,
+        _ctx: &Ctx,
+        workflow_id: i64,
+        workflow: &RequiredWorkflow,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_org_required_workflow {workflow_id} {}/{}",
+            workflow.repository, workflow.path
+        ));
+        Ok(())
+    }
+
+    async fn update_org_runner_group_repositories(
+        &self,
+        _ctx: &Ctx,
+        runner_group_id: i64,
+        repos_names: &[RepositoryName],
+    ) -> Result<()> {
+        self.record(format!(
+            "update_org_runner_group_repositories {runner_group_id} {}",
+            repos_names.join(",")
+        ));
+        Ok(())
+    }
+
+    async fn update_org_secret_repositories(
+        &self,
+        _ctx: &Ctx,
+        secret_name: &str,
+        repos_names: &[RepositoryName],
+    ) -> Result<()> {
+        self.record(format!(
+            "update_org_secret_repositories {secret_name} {}",
+            repos_names.join(",")
+        ));
+        Ok(())
+    }
+
+    async fn update_org_webhook(&self, _ctx: &Ctx, webhook_id: i64, webhook: &Webhook) -> Result<()> {
+        self.record(format!("update_org_webhook {webhook_id} {}", webhook.url));
+        Ok(())
+    }
+
+    async fn update_org_setting(&self, _ctx: &Ctx, setting: OrganizationSetting, value: bool) -> Result<()> {
+        self.record(format!("update_org_setting {} {value}", setting.api_name()));
+        Ok(())
+    }
+
+    async fn update_org_verified_domains_gate(&self, _ctx: &Ctx, enabled: bool) -> Result<()> {
+        self.record(format!("update_org_verified_domains_gate {enabled}"));
+        Ok(())
+    }
+
+    async fn update_org_team_creation_permission(
+        &self,
+        _ctx: &Ctx,
+        permission: TeamCreationPermission,
+    ) -> Result<()> {
+        self.record(format!("update_org_team_creation_permission {permission}"));
+        Ok(())
+    }
+
+    async fn update_org_community_health_file(&self, _ctx: &Ctx, path: &str, _content: &str) -> Result<()> {
+        self.record(format!("update_org_community_health_file {path}"));
+        Ok(())
+    }
+
+    async fn update_org_membership_to_member(&self, _ctx: &Ctx, user_name: &UserName) -> Result<()> {
+        self.record(format!("update_org_membership_to_member {user_name}"));
+        Ok(())
+    }
+
+    async fn update_repository_collaborator_role(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_name: &UserName,
+        role: &Role,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_collaborator_role {repo_name} {user_name} {role}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_invitation(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        invitation_id: i64,
+        role: &Role,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_invitation {repo_name} {invitation_id} {role}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_allow_forking(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        allow_forking: bool,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_allow_forking {repo_name} {allow_forking}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_branch_protection(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        branch: &str,
+        _protection: &BranchProtection,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_branch_protection {repo_name} {branch}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_actions_allow_list(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        allow_list: &ActionsAllowList,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_actions_allow_list {repo_name} {allow_list}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_codeowners(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        _content: &str,
+        update_mode: CodeownersUpdateMode,
+    ) -> Result<()> {
+        self.record(format!("update_repository_codeowners {repo_name} {update_mode}"));
+        Ok(())
+    }
+
+    async fn update_repository_code_scanning_setup(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        setup: &CodeScanningSetup,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_code_scanning_setup {repo_name} {setup}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_private_vulnerability_reporting(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        enabled: bool,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_private_vulnerability_reporting {repo_name} {enabled}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_discussions(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        enabled: bool,
+    ) -> Result<()> {
+        self.record(format!("update_repository_discussions {repo_name} {enabled}"));
+        Ok(())
+    }
+
+    async fn update_repository_merge_commit_settings(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        settings: &MergeCommitSettings,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_merge_commit_settings {repo_name} {settings}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_topics(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        topics: &[String],
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_topics {repo_name} {}",
+            topics.join(",")
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_template_status(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        is_template: bool,
+    ) -> Result<()> {
+        self.record(format!(
+            "update_repository_template_status {repo_name} {is_template}"
+        ));
+        Ok(())
+    }
+
+    async fn update_repository_visibility(
+        &self,
+        _ctx: &Ctx,
+        repo_name: &RepositoryName,
+        visibility: &Visibility,
+    ) -> Result<()> {
+        self.record(format!("update_repository_visibility {repo_name} {visibility}"));
+        Ok(())
+    }
+
+    async fn update_team_notifications(
+        &self,
+        _ctx: &Ctx,
+        team_name: &TeamName,
+        notifications: TeamNotifications,
+    ) -> Result<()> {
+        self.record(format!("update_team_notifications {team_name} {notifications}"));
+        Ok(())
+    }
+}
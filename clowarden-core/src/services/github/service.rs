@@ -1,39 +1,142 @@
 //! This module defines an abstraction layer over the service's (GitHub) API.
 
-use std::sync::Arc;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::{format_err, Context, Result};
 use async_trait::async_trait;
-use cached::proc_macro::cached;
+use base64::{engine::general_purpose::STANDARD as b64, Engine as _};
+use cached::{proc_macro::cached, Cached, TimedCache};
+use futures::try_join;
 #[cfg(test)]
 use mockall::automock;
 use octorust::{
     auth::{Credentials, InstallationTokenGenerator, JWTCredentials},
     types::{
-        Affiliation, Collaborator, MinimalRepository, Order, OrganizationInvitation, OrgsListMembersFilter,
-        OrgsListMembersRole, Privacy, ReposAddCollaboratorRequest, ReposCreateInOrgRequest,
-        ReposCreateInOrgRequestVisibility, ReposListOrgSort, ReposListOrgType, ReposUpdateInvitationRequest,
-        ReposUpdateRequest, RepositoryInvitation, SimpleUser, Team, TeamMembership, TeamMembershipRole,
+        ActivitySetRepoSubscriptionRequest, Affiliation, Collaborator, ContentFile, MinimalRepository, Order,
+        OrganizationInvitation, OrgsListMembersFilter, OrgsListMembersRole, Privacy,
+        ReposAddCollaboratorRequest, ReposCreateInOrgRequest, ReposCreateInOrgRequestVisibility,
+        ReposListOrgSort, ReposListOrgType, ReposUpdateInvitationRequest, ReposUpdateRequest,
+        RepositoryInvitation, SimpleUser, Team, TeamMembership, TeamMembershipRole,
         TeamsAddUpdateMembershipUserInOrgRequest, TeamsAddUpdateRepoPermissionsInOrgRequest,
         TeamsCreateRequest, TeamsListMembersInOrgRole,
     },
     Client,
 };
-use tokio::time::{sleep, Duration};
+use reqwest::{
+    header::{ACCEPT, USER_AGENT},
+    Method, StatusCode,
+};
+use reqwest_middleware::ClientWithMiddleware;
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::time::{sleep, Duration, Instant};
 
 use crate::{
-    cfg::{GitHubApp, Organization},
-    directory::{self, TeamName, UserName},
+    cfg::{
+        GitHubApp, GitHubClient, Organization, OrganizationRunnerGroup, OrganizationSetting,
+        OrganizationSettings, RequiredWorkflow, TeamCreationPermission, Webhook,
+    },
+    directory::{self, TeamName, TeamNotifications, UserName},
+    github::{get_token, new_http_client, GITHUB_API_URL},
+};
+
+use super::state::{
+    ActionsAllowList, AppSlug, BranchProtection, CodeScanningQuerySuite, CodeScanningSetup,
+    CodeownersUpdateMode, DiscussionSettings, MergeCommitSettings, Repository, RepositoryName, Role,
+    Visibility, DEPENDABOT_CONFIG_PATHS, RETIRED_TEAM_DESCRIPTION,
 };
 
-use super::state::{Repository, RepositoryName, Role, Visibility};
+/// Interval between checks when waiting for a newly created resource to be
+/// visible in GitHub.
+const VISIBILITY_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Repository where the organization's default community health files live.
+const COMMUNITY_HEALTH_FILES_REPO: &str = ".github";
+
+/// Locations where GitHub looks for the CODEOWNERS file, in order.
+const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Branch used to propose the CODEOWNERS file changes in a pull request.
+const CODEOWNERS_BRANCH: &str = "clowarden-codeowners";
+
+/// Time (in seconds) the organization information prefetched when warming up
+/// is kept waiting for the first reconciliation to use it.
+const PREFETCHED_ORG_INFO_LIFESPAN: u64 = 900;
+
+/// Prefix used in the name of the rulesets used to enforce the required
+/// workflows managed by CLOWarden. The rest of the name is the workflow name.
+const REQUIRED_WORKFLOW_RULESET_PREFIX: &str = "clowarden-required-workflow: ";
+
+/// GraphQL query used to get the discussions settings of a repository.
+const REPOSITORY_DISCUSSIONS_QUERY: &str = r"
+query($owner: String!, $name: String!) {
+    repository(owner: $owner, name: $name) {
+        hasDiscussionsEnabled
+        discussionCategories(first: 100) {
+            nodes {
+                name
+            }
+        }
+    }
+}
+";
+
+/// GraphQL query used to get all the licenses known by GitHub (the REST API
+/// only lists the commonly used ones).
+const LICENSES_QUERY: &str = r"
+query {
+    licenses {
+        key
+    }
+}
+";
+
+/// GraphQL query used to get the organization's verified domains gate.
+const ORG_VERIFIED_DOMAINS_QUERY: &str = r"
+query($login: String!) {
+    organization(login: $login) {
+        id
+        notificationDeliveryRestrictionEnabledSetting
+        domains(first: 100, isVerified: true) {
+            nodes {
+                domain
+            }
+        }
+    }
+}
+";
+
+/// GraphQL mutation used to update the organization's verified domains gate.
+const UPDATE_ORG_VERIFIED_DOMAINS_GATE_MUTATION: &str = r"
+mutation($ownerId: ID!, $settingValue: NotificationRestrictionSettingValue!) {
+    updateNotificationRestrictionSetting(input: { ownerId: $ownerId, settingValue: $settingValue }) {
+        clientMutationId
+    }
+}
+";
 
 /// Trait that defines some operations a Svc implementation must support.
 #[async_trait]
 #[cfg_attr(test, automock)]
 pub trait Svc {
-    /// Add repository to organization.
-    async fn add_repository(&self, ctx: &Ctx, repo: &Repository) -> Result<()>;
+    /// Add repository to organization, returning its default branch.
+    async fn add_repository(&self, ctx: &Ctx, repo: &Repository) -> Result<String>;
+
+    /// Add required workflow to organization.
+    async fn add_org_required_workflow(&self, ctx: &Ctx, workflow: &RequiredWorkflow) -> Result<()>;
+
+    /// Add self-hosted runner group to organization. The runner group is
+    /// created using the selected repositories visibility.
+    async fn add_org_runner_group(&self, ctx: &Ctx, runner_group: &OrganizationRunnerGroup) -> Result<()>;
+
+    /// Grant the security manager role in the organization to the team.
+    async fn add_org_security_manager_team(&self, ctx: &Ctx, team_name: &TeamName) -> Result<()>;
+
+    /// Add webhook to organization.
+    async fn add_org_webhook(&self, ctx: &Ctx, webhook: &Webhook) -> Result<()>;
 
     /// Add collaborator to repository.
     async fn add_repository_collaborator(
@@ -44,7 +147,24 @@ pub trait Svc {
         role: &Role,
     ) -> Result<()>;
 
-    /// Add team to repository.
+    /// Add the Dependabot configuration file provided to the repository's
+    /// default branch.
+    async fn add_repository_dependabot_config(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        content: &str,
+    ) -> Result<()>;
+
+    /// Subscribe the user whose token is provided to the repository.
+    async fn add_repository_watcher(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_token: &str,
+    ) -> Result<()>;
+
+    /// Add team to repository, or update its role if it has access already.
     async fn add_repository_team(
         &self,
         ctx: &Ctx,
@@ -62,6 +182,17 @@ pub trait Svc {
     /// Add member to the team.
     async fn add_team_member(&self, ctx: &Ctx, team_name: &TeamName, user_name: &UserName) -> Result<()>;
 
+    /// Block user from the organization.
+    async fn block_org_user(&self, ctx: &Ctx, user_name: &UserName) -> Result<()>;
+
+    /// Get whether we have admin access to the repository provided. None is
+    /// returned when the service does not report this information.
+    async fn get_repository_admin_access(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<bool>>;
+
     /// Get user's membership in team provided.
     async fn get_team_membership(
         &self,
@@ -70,15 +201,125 @@ pub trait Svc {
         user_name: &UserName,
     ) -> Result<TeamMembership>;
 
+    /// Get the notifications setting of the team provided.
+    async fn get_team_notifications(&self, ctx: &Ctx, team_name: &TeamName) -> Result<TeamNotifications>;
+
+    /// Check if forking is allowed in the repository provided.
+    async fn get_repository_allow_forking(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<bool>;
+
+    /// Get the current value of the organization settings that can be managed.
+    async fn get_org_settings(&self, ctx: &Ctx) -> Result<OrganizationSettings>;
+
+    /// Get the organization's verified domains and whether the verified
+    /// domains gate is enabled or not.
+    async fn get_org_verified_domains(&self, ctx: &Ctx) -> Result<OrgVerifiedDomains>;
+
+    /// Get the content of a community health file from the organization's
+    /// .github repository. None is returned when the file does not exist.
+    async fn get_org_community_health_file(&self, ctx: &Ctx, path: &str) -> Result<Option<String>>;
+
+    /// Get the visibility of an organization Actions secret. None is returned
+    /// when the secret does not exist.
+    async fn get_org_secret_visibility(&self, ctx: &Ctx, secret_name: &str) -> Result<Option<String>>;
+
+    /// Get the role of the user provided in the organization (pending
+    /// invitations included). None is returned when the user is not a member.
+    async fn get_org_membership_role(&self, ctx: &Ctx, user_name: &UserName) -> Result<Option<String>>;
+
+    /// Get repository discussions settings.
+    async fn get_repository_discussions(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<DiscussionSettings>;
+
+    /// Get the GitHub Actions allow-list of the repository provided.
+    async fn get_repository_actions_allow_list(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<ActionsAllowList>;
+
+    /// Get the code scanning default setup of the repository provided. None is
+    /// returned when the default setup is not supported in the repository.
+    async fn get_repository_code_scanning_setup(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<CodeScanningSetup>>;
+
+    /// Check if private vulnerability reporting is enabled in the repository
+    /// provided.
+    async fn get_repository_private_vulnerability_reporting(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<bool>;
+
+    /// Get the content of the CODEOWNERS file of the repository provided. The
+    /// locations supported by GitHub are checked in the same order GitHub
+    /// does. None is returned when the file does not exist.
+    async fn get_repository_codeowners(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<String>>;
+
+    /// Get repository merge commit settings.
+    async fn get_repository_merge_commit_settings(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<MergeCommitSettings>;
+
     /// Get user login.
     async fn get_user_login(&self, ctx: &Ctx, user_name: &UserName) -> Result<UserName>;
 
+    /// List apps installations in the organization.
+    async fn list_apps_installations(&self, ctx: &Ctx) -> Result<Vec<AppInstallation>>;
+
+    /// List child teams of the team provided.
+    async fn list_child_teams(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<TeamName>>;
+
+    /// List gitignore templates available.
+    async fn list_gitignore_templates(&self, ctx: &Ctx) -> Result<Vec<String>>;
+
+    /// List license templates available (keys).
+    async fn list_license_templates(&self, ctx: &Ctx) -> Result<Vec<String>>;
+
     /// List organization admins.
     async fn list_org_admins(&self, ctx: &Ctx) -> Result<Vec<SimpleUser>>;
 
+    /// List users blocked from the organization.
+    async fn list_org_blocked_users(&self, ctx: &Ctx) -> Result<Vec<UserName>>;
+
     /// List organization members.
     async fn list_org_members(&self, ctx: &Ctx) -> Result<Vec<SimpleUser>>;
 
+    /// List organization required workflows managed by CLOWarden.
+    async fn list_org_required_workflows(&self, ctx: &Ctx) -> Result<Vec<OrgRequiredWorkflow>>;
+
+    /// List the repositories that can use an organization self-hosted runner
+    /// group.
+    async fn list_org_runner_group_repositories(
+        &self,
+        ctx: &Ctx,
+        runner_group_id: i64,
+    ) -> Result<Vec<RepositoryName>>;
+
+    /// List organization self-hosted runner groups.
+    async fn list_org_runner_groups(&self, ctx: &Ctx) -> Result<Vec<OrgRunnerGroup>>;
+
+    /// List the repositories selected to access an organization Actions secret.
+    async fn list_org_secret_repositories(&self, ctx: &Ctx, secret_name: &str)
+        -> Result<Vec<RepositoryName>>;
+
+    /// List the teams with the security manager role in the organization.
+    async fn list_org_security_manager_teams(&self, ctx: &Ctx) -> Result<Vec<TeamName>>;
+
+    /// List organization webhooks.
+    async fn list_org_webhooks(&self, ctx: &Ctx) -> Result<Vec<OrgWebhook>>;
+
     /// List repositories in the organization.
     async fn list_repositories(&self, ctx: &Ctx) -> Result<Vec<MinimalRepository>>;
 
@@ -96,6 +337,17 @@ pub trait Svc {
         repo_name: &RepositoryName,
     ) -> Result<Vec<RepositoryInvitation>>;
 
+    /// List repository's labels (names).
+    async fn list_repository_labels(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<String>>;
+
+    /// List repository's branches already merged into its default branch. This
+    /// requires an extra request per branch.
+    async fn list_repository_merged_branches(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Vec<MergedBranch>>;
+
     /// List repository's teams.
     async fn list_repository_teams(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<Team>>;
 
@@ -115,6 +367,14 @@ pub trait Svc {
     /// List teams in the organization.
     async fn list_teams(&self, ctx: &Ctx) -> Result<Vec<Team>>;
 
+    /// List the users watching the repository provided.
+    async fn list_repository_watchers(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<UserName>>;
+
+    /// Prefetch the organization members, admins and repositories. The
+    /// information prefetched is returned by the next call to the
+    /// corresponding list method, provided it happens within a while.
+    async fn prefetch_org_info(&self, ctx: &Ctx) -> Result<()>;
+
     /// Remove collaborator from repository.
     async fn remove_repository_collaborator(
         &self,
@@ -123,6 +383,53 @@ pub trait Svc {
         user_name: &UserName,
     ) -> Result<()>;
 
+    /// Unsubscribe the user whose token is provided from the repository.
+    async fn remove_repository_watcher(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_token: &str,
+    ) -> Result<()>;
+
+    /// Move the child teams of the team provided to its parent (or to the top
+    /// level of the hierarchy if it has no parent). The names of the teams
+    /// moved are returned.
+    async fn reparent_child_teams(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<TeamName>>;
+
+    /// Check if the repository provided exists in the organization.
+    async fn repository_exists(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<bool>;
+
+    /// Check if a file exists in the repository provided. The repository's
+    /// default branch is used when no reference is provided.
+    async fn repository_file_exists(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        path: &str,
+        ref_: Option<String>,
+    ) -> Result<bool>;
+
+    /// Retire the team provided, renaming it and making it secret. Retired
+    /// teams are flagged using their description.
+    async fn retire_team(&self, ctx: &Ctx, team_name: &TeamName, new_team_name: &TeamName) -> Result<()>;
+
+    /// Remove required workflow from organization.
+    async fn remove_org_required_workflow(&self, ctx: &Ctx, workflow_id: i64) -> Result<()>;
+
+    /// Revoke the security manager role in the organization from the team.
+    async fn remove_org_security_manager_team(&self, ctx: &Ctx, team_name: &TeamName) -> Result<()>;
+
+    /// Remove webhook from organization.
+    async fn remove_org_webhook(&self, ctx: &Ctx, webhook_id: i64) -> Result<()>;
+
+    /// Remove app access to repository.
+    async fn remove_repository_app(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        app_slug: &AppSlug,
+    ) -> Result<()>;
+
     /// Remove repository invitation.
     async fn remove_repository_invitation(
         &self,
@@ -131,6 +438,14 @@ pub trait Svc {
         invitation_id: i64,
     ) -> Result<()>;
 
+    /// Remove label from repository.
+    async fn remove_repository_label(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        label_name: &str,
+    ) -> Result<()>;
+
     /// Remove team from repository.
     async fn remove_repository_team(
         &self,
@@ -153,6 +468,62 @@ pub trait Svc {
     /// Remove member from the team.
     async fn remove_team_member(&self, ctx: &Ctx, team_name: &TeamName, user_name: &UserName) -> Result<()>;
 
+    /// Check if the team provided exists in the organization.
+    async fn team_exists(&self, ctx: &Ctx, team_name: &TeamName) -> Result<bool>;
+
+    /// Unblock user from the organization.
+    async fn unblock_org_user(&self, ctx: &Ctx, user_name: &UserName) -> Result<()>;
+
+    /// Update organization required workflow.
+    async fn update_org_required_workflow(
+        &self,
+        ctx: &Ctx,
+        workflow_id: i64,
+        workflow: &RequiredWorkflow,
+    ) -> Result<()>;
+
+    /// Set the repositories that can use an organization self-hosted runner
+    /// group. The runners in the group are not modified.
+    async fn update_org_runner_group_repositories(
+        &self,
+        ctx: &Ctx,
+        runner_group_id: i64,
+        repos_names: &[RepositoryName],
+    ) -> Result<()>;
+
+    /// Set the repositories selected to access an organization Actions secret.
+    /// The secret's value is not modified.
+    async fn update_org_secret_repositories(
+        &self,
+        ctx: &Ctx,
+        secret_name: &str,
+        repos_names: &[RepositoryName],
+    ) -> Result<()>;
+
+    /// Update organization webhook.
+    async fn update_org_webhook(&self, ctx: &Ctx, webhook_id: i64, webhook: &Webhook) -> Result<()>;
+
+    /// Update organization setting.
+    async fn update_org_setting(&self, ctx: &Ctx, setting: OrganizationSetting, value: bool) -> Result<()>;
+
+    /// Enable or disable the organization's verified domains gate.
+    async fn update_org_verified_domains_gate(&self, ctx: &Ctx, enabled: bool) -> Result<()>;
+
+    /// Update the permission required to create teams in the organization.
+    async fn update_org_team_creation_permission(
+        &self,
+        ctx: &Ctx,
+        permission: TeamCreationPermission,
+    ) -> Result<()>;
+
+    /// Create or update a community health file in the organization's .github
+    /// repository.
+    async fn update_org_community_health_file(&self, ctx: &Ctx, path: &str, content: &str) -> Result<()>;
+
+    /// Set the role of the user provided in the organization to member. Users
+    /// who are not members yet are invited to join the organization.
+    async fn update_org_membership_to_member(&self, ctx: &Ctx, user_name: &UserName) -> Result<()>;
+
     /// Update collaborator role in repository.
     async fn update_repository_collaborator_role(
         &self,
@@ -171,13 +542,88 @@ pub trait Svc {
         role: &Role,
     ) -> Result<()>;
 
-    /// Update team role in repository.
-    async fn update_repository_team_role(
+    /// Update repository forking policy.
+    async fn update_repository_allow_forking(
         &self,
         ctx: &Ctx,
         repo_name: &RepositoryName,
-        team_name: &TeamName,
-        role: &Role,
+        allow_forking: bool,
+    ) -> Result<()>;
+
+    /// Update the protection of the repository's branch provided.
+    async fn update_repository_branch_protection(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        branch: &str,
+        protection: &BranchProtection,
+    ) -> Result<()>;
+
+    /// Update the GitHub Actions allow-list of the repository.
+    async fn update_repository_actions_allow_list(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        allow_list: &ActionsAllowList,
+    ) -> Result<()>;
+
+    /// Update the code scanning default setup of the repository.
+    async fn update_repository_code_scanning_setup(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        setup: &CodeScanningSetup,
+    ) -> Result<()>;
+
+    /// Update (or create) the CODEOWNERS file of the repository's default
+    /// branch with the content provided, committing the changes directly or
+    /// proposing them in a pull request.
+    async fn update_repository_codeowners(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        content: &str,
+        update_mode: CodeownersUpdateMode,
+    ) -> Result<()>;
+
+    /// Enable or disable private vulnerability reporting in the repository.
+    async fn update_repository_private_vulnerability_reporting(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        enabled: bool,
+    ) -> Result<()>;
+
+    /// Enable or disable discussions in the repository.
+    async fn update_repository_discussions(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        enabled: bool,
+    ) -> Result<()>;
+
+    /// Update repository merge commit settings (only the ones defined).
+    async fn update_repository_merge_commit_settings(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        settings: &MergeCommitSettings,
+    ) -> Result<()>;
+
+    /// Replace all the topics of the repository with the ones provided.
+    async fn update_repository_topics(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        topics: &[String],
+    ) -> Result<()>;
+
+    /// Update repository template status.
+    async fn update_repository_template_status(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        is_template: bool,
     ) -> Result<()>;
 
     /// Update repository visibility.
@@ -187,31 +633,43 @@ pub trait Svc {
         repo_name: &RepositoryName,
         visibility: &Visibility,
     ) -> Result<()>;
+
+    /// Update the notifications setting of the team.
+    async fn update_team_notifications(
+        &self,
+        ctx: &Ctx,
+        team_name: &TeamName,
+        notifications: TeamNotifications,
+    ) -> Result<()>;
 }
 
 /// Type alias to represent a Svc trait object.
 pub type DynSvc = Arc<dyn Svc + Send + Sync>;
 
 /// Svc implementation backed by the GitHub API.
-#[derive(Default)]
 pub struct SvcApi {
     app_credentials: Option<JWTCredentials>,
     token: Option<String>,
+    http_client: ClientWithMiddleware,
+    creation_max_wait: Duration,
+    prefetched: Mutex<TimedCache<String, PrefetchedOrgInfo>>,
 }
 
 impl SvcApi {
     /// Create a new SvcApi instance using the token provided.
-    #[must_use]
-    pub fn new_with_token(token: String) -> Self {
-        Self {
+    pub fn new_with_token(token: String, gh_client: &GitHubClient) -> Result<Self> {
+        Ok(Self {
+            app_credentials: None,
             token: Some(token),
-            ..Default::default()
-        }
+            http_client: new_http_client(gh_client)?,
+            creation_max_wait: Duration::from_secs(gh_client.creation_max_wait),
+            prefetched: new_prefetched_cache(),
+        })
     }
 
     /// Create a new SvcApi instance using the app credentials provided in the
     /// configuration.
-    pub fn new_with_app_creds(gh_app: &GitHubApp) -> Result<Self> {
+    pub fn new_with_app_creds(gh_app: &GitHubApp, gh_client: &GitHubClient) -> Result<Self> {
         // Setup GitHub app credentials
         let private_key = pem::parse(&gh_app.private_key)?.contents().to_owned();
         let jwt_credentials =
@@ -219,10 +677,24 @@ impl SvcApi {
 
         Ok(Self {
             app_credentials: Some(jwt_credentials),
-            ..Default::default()
+            token: None,
+            http_client: new_http_client(gh_client)?,
+            creation_max_wait: Duration::from_secs(gh_client.creation_max_wait),
+            prefetched: new_prefetched_cache(),
         })
     }
 
+    /// Take the piece of prefetched information selected for the organization
+    /// provided (if available), so that it's only used once.
+    fn take_prefetched<T>(
+        &self,
+        org: &str,
+        take: impl FnOnce(&mut PrefetchedOrgInfo) -> Option<T>,
+    ) -> Option<T> {
+        let mut prefetched = self.prefetched.lock().expect("prefetched lock not poisoned");
+        prefetched.cache_get_mut(org).and_then(take)
+    }
+
     /// Setup GitHub API client for the installation id provided (if any).
     fn setup_client(&self, inst_id: Option<i64>) -> Result<Client> {
         let user_agent = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
@@ -241,82 +713,284 @@ impl SvcApi {
             Credentials::Token(token)
         };
 
-        Ok(Client::new(user_agent, credentials)?)
+        Ok(Client::custom(user_agent, credentials, self.http_client.clone()))
     }
-}
-
-#[async_trait]
-impl Svc for SvcApi {
-    /// [Svc::add_repository]
-    async fn add_repository(&self, ctx: &Ctx, repo: &Repository) -> Result<()> {
-        let client = self.setup_client(ctx.inst_id)?;
 
-        // Create repository
-        let visibility = match repo.visibility {
-            Some(Visibility::Internal) => Some(ReposCreateInOrgRequestVisibility::Internal),
-            Some(Visibility::Private) => Some(ReposCreateInOrgRequestVisibility::Private),
-            Some(Visibility::Public) => Some(ReposCreateInOrgRequestVisibility::Public),
-            None => None,
-        };
-        let body = ReposCreateInOrgRequest {
-            allow_auto_merge: None,
-            allow_merge_commit: None,
-            allow_rebase_merge: None,
-            allow_squash_merge: None,
-            auto_init: None,
-            delete_branch_on_merge: None,
-            description: String::new(),
-            gitignore_template: String::new(),
-            has_issues: None,
-            has_projects: None,
-            has_wiki: None,
-            homepage: String::new(),
-            is_template: None,
-            license_template: String::new(),
-            name: repo.name.clone(),
-            private: None,
-            team_id: 0,
-            visibility,
-        };
-        client.repos().create_in_org(&ctx.org, &body).await?;
-        sleep(Duration::from_secs(1)).await;
+    /// Setup a GitHub API client that acts on behalf of the user whose token
+    /// is provided.
+    fn setup_user_client(&self, user_token: &str) -> Client {
+        let user_agent = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        Client::custom(
+            user_agent,
+            Credentials::Token(user_token.to_string()),
+            self.http_client.clone(),
+        )
+    }
 
-        // Add repository teams
-        if let Some(teams) = &repo.teams {
-            for (team_name, role) in teams {
-                self.add_repository_team(ctx, &repo.name, team_name, role).await?;
-            }
-        }
+    /// Get a token to call the GitHub API directly for the installation id
+    /// provided (if any).
+    async fn get_token(&self, inst_id: Option<i64>) -> Result<String> {
+        get_token(
+            &self.http_client,
+            self.app_credentials.as_ref(),
+            self.token.as_deref(),
+            inst_id,
+        )
+        .await
+    }
 
-        // Add repository collaborators
-        if let Some(collaborators) = &repo.collaborators {
-            for (user_name, role) in collaborators {
-                self.add_repository_collaborator(ctx, &repo.name, user_name, role).await?;
-            }
+    /// Get the ids of the repositories provided (some API endpoints expect the
+    /// ids of the repositories, not their names).
+    async fn get_repositories_ids(&self, ctx: &Ctx, repos_names: &[RepositoryName]) -> Result<Vec<i64>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let mut repos_ids = vec![];
+        for repo_name in repos_names {
+            repos_ids.push(client.repos().get(&ctx.org, repo_name).await?.id);
         }
+        Ok(repos_ids)
+    }
 
-        Ok(())
+    /// Get a file from the organization's .github repository. None is returned
+    /// when the file does not exist.
+    async fn get_community_health_file(&self, ctx: &Ctx, path: &str) -> Result<Option<ContentFile>> {
+        self.get_repository_file(ctx, COMMUNITY_HEALTH_FILES_REPO, path).await
     }
 
-    /// [Svc::add_repository_collaborator]
-    async fn add_repository_collaborator(
+    /// Get a file from the repository provided (using the default branch).
+    /// None is returned when the file does not exist.
+    async fn get_repository_file(
         &self,
         ctx: &Ctx,
-        repo_name: &RepositoryName,
-        user_name: &UserName,
-        role: &Role,
-    ) -> Result<()> {
+        repo_name: &str,
+        path: &str,
+    ) -> Result<Option<ContentFile>> {
         let client = self.setup_client(ctx.inst_id)?;
-        let body = ReposAddCollaboratorRequest {
-            permission: Some(role.into()),
-            permissions: String::new(),
-        };
-        client.repos().add_collaborator(&ctx.org, repo_name, user_name, &body).await?;
-        Ok(())
+        match client.repos().get_content_file(&ctx.org, repo_name, path, "").await {
+            Ok(file) => Ok(Some(file)),
+            Err(err) if err.to_string().contains("404") => Ok(None),
+            Err(err) => Err(err),
+        }
     }
 
-    /// [Svc::add_repository_team]
-    async fn add_repository_team(
+    /// Prepare the body of a request to create or update the ruleset used to
+    /// enforce the required workflow provided. Rulesets reference the
+    /// workflow's repository by id, so we need to look it up first.
+    async fn required_workflow_ruleset_body(&self, ctx: &Ctx, workflow: &RequiredWorkflow) -> Result<Value> {
+        let path = format!("/repos/{}/{}", ctx.org, workflow.repository);
+        let repo = self.api_request(ctx, Method::GET, &path, None).await?;
+        let Some(repository_id) = repo["id"].as_i64() else {
+            return Err(format_err!("repository {} id not found", workflow.repository));
+        };
+        let mut workflow_parameters = json!({
+            "path": workflow.path,
+            "repository_id": repository_id,
+        });
+        if let Some(ref_) = &workflow.ref_ {
+            workflow_parameters["ref"] = json!(ref_);
+        }
+        let repositories = match &workflow.repositories {
+            Some(repositories) => json!(repositories),
+            None => json!(["~ALL"]),
+        };
+        Ok(json!({
+            "name": format!("{REQUIRED_WORKFLOW_RULESET_PREFIX}{}", workflow.name()),
+            "target": "branch",
+            "enforcement": "active",
+            "conditions": {
+                "ref_name": { "include": ["~DEFAULT_BRANCH"], "exclude": [] },
+                "repository_name": { "include": repositories, "exclude": [] },
+            },
+            "rules": [{
+                "type": "workflows",
+                "parameters": { "workflows": [workflow_parameters] },
+            }],
+        }))
+    }
+
+    /// Run the organization's verified domains GraphQL query, returning the
+    /// organization node.
+    async fn query_org_verified_domains(&self, ctx: &Ctx) -> Result<Value> {
+        let body = json!({
+            "query": ORG_VERIFIED_DOMAINS_QUERY,
+            "variables": { "login": ctx.org },
+        });
+        let mut response = self.api_request(ctx, Method::POST, "/graphql", Some(body)).await?;
+        if let Some(errors) = response.get("errors") {
+            return Err(format_err!("error getting verified domains: {errors}"));
+        }
+        Ok(response["data"]["organization"].take())
+    }
+
+    /// Call the GitHub API directly. This is used for the endpoints (or
+    /// fields) not supported yet by the GitHub API client.
+    async fn api_request(&self, ctx: &Ctx, method: Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let user_agent = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        let mut request = self
+            .http_client
+            .request(method, format!("{GITHUB_API_URL}{path}"))
+            .bearer_auth(self.get_token(ctx.inst_id).await?)
+            .header(ACCEPT, "application/vnd.github+json")
+            .header(USER_AGENT, user_agent);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?.error_for_status()?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(Value::Null);
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl Svc for SvcApi {
+    /// [Svc::add_repository]
+    async fn add_repository(&self, ctx: &Ctx, repo: &Repository) -> Result<String> {
+        let client = self.setup_client(ctx.inst_id)?;
+
+        // Create repository
+        let body = new_repos_create_in_org_request(repo);
+        let default_branch = client.repos().create_in_org(&ctx.org, &body).await?.default_branch;
+        if !wait_until_visible(self.creation_max_wait, || self.repository_exists(ctx, &repo.name)).await? {
+            return Err(format_err!(
+                "repository {} not visible after creating it",
+                repo.name
+            ));
+        }
+
+        // Set forking policy (not supported when creating the repository)
+        if let Some(allow_forking) = repo.allow_forking {
+            self.update_repository_allow_forking(ctx, &repo.name, allow_forking).await?;
+        }
+
+        // Enable or disable discussions (categories cannot be set up using
+        // the API, GitHub creates some default ones when they are enabled)
+        if let Some(discussions) = &repo.discussions {
+            self.update_repository_discussions(ctx, &repo.name, discussions.enabled).await?;
+        }
+
+        // Set merge commit settings (not supported yet by the GitHub API
+        // client when creating the repository)
+        let merge_commit_settings = repo.merge_commit_settings();
+        if !merge_commit_settings.is_empty() {
+            self.update_repository_merge_commit_settings(ctx, &repo.name, &merge_commit_settings)
+                .await?;
+        }
+
+        // Enable or disable private vulnerability reporting (not supported
+        // when creating the repository)
+        if let Some(enabled) = repo.private_vulnerability_reporting {
+            self.update_repository_private_vulnerability_reporting(ctx, &repo.name, enabled).await?;
+        }
+
+        // Add repository teams
+        if let Some(teams) = &repo.teams {
+            for (team_name, role) in teams {
+                self.add_repository_team(ctx, &repo.name, team_name, role).await?;
+            }
+        }
+
+        // Add repository collaborators
+        if let Some(collaborators) = &repo.collaborators {
+            for (user_name, role) in collaborators {
+                self.add_repository_collaborator(ctx, &repo.name, user_name, role).await?;
+            }
+        }
+
+        Ok(default_branch)
+    }
+
+    /// [Svc::add_org_required_workflow]
+    async fn add_org_required_workflow(&self, ctx: &Ctx, workflow: &RequiredWorkflow) -> Result<()> {
+        let path = format!("/orgs/{}/rulesets", ctx.org);
+        let body = self.required_workflow_ruleset_body(ctx, workflow).await?;
+        self.api_request(ctx, Method::POST, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::add_org_runner_group]
+    async fn add_org_runner_group(&self, ctx: &Ctx, runner_group: &OrganizationRunnerGroup) -> Result<()> {
+        let repos_ids = self.get_repositories_ids(ctx, &runner_group.repositories).await?;
+        let path = format!("/orgs/{}/actions/runner-groups", ctx.org);
+        let body = json!({
+            "name": runner_group.name,
+            "visibility": "selected",
+            "selected_repository_ids": repos_ids,
+        });
+        self.api_request(ctx, Method::POST, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::add_org_security_manager_team]
+    async fn add_org_security_manager_team(&self, ctx: &Ctx, team_name: &TeamName) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        client.orgs().add_security_manager_team(&ctx.org, team_name).await?;
+        Ok(())
+    }
+
+    /// [Svc::add_org_webhook]
+    async fn add_org_webhook(&self, ctx: &Ctx, webhook: &Webhook) -> Result<()> {
+        let path = format!("/orgs/{}/hooks", ctx.org);
+        let mut body = webhook_request_body(webhook);
+        body["name"] = json!("web");
+        self.api_request(ctx, Method::POST, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::add_repository_collaborator]
+    async fn add_repository_collaborator(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_name: &UserName,
+        role: &Role,
+    ) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let body = ReposAddCollaboratorRequest {
+            permission: Some(role.into()),
+            permissions: String::new(),
+        };
+        client.repos().add_collaborator(&ctx.org, repo_name, user_name, &body).await?;
+        Ok(())
+    }
+
+    /// [Svc::add_repository_dependabot_config]
+    async fn add_repository_dependabot_config(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        content: &str,
+    ) -> Result<()> {
+        let path = format!(
+            "/repos/{}/{repo_name}/contents/{}",
+            ctx.org, DEPENDABOT_CONFIG_PATHS[0]
+        );
+        let body = json!({
+            "message": "Add Dependabot configuration file",
+            "content": b64.encode(content),
+        });
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::add_repository_watcher]
+    async fn add_repository_watcher(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        user_token: &str,
+    ) -> Result<()> {
+        let client = self.setup_user_client(user_token);
+        let body = ActivitySetRepoSubscriptionRequest {
+            ignored: false,
+            subscribed: true,
+        };
+        client.activity().set_repo_subscription(&ctx.org, repo_name, &body).await?;
+        Ok(())
+    }
+
+    /// [Svc::add_repository_team]
+    async fn add_repository_team(
         &self,
         ctx: &Ctx,
         repo_name: &RepositoryName,
@@ -348,13 +1022,20 @@ impl Svc for SvcApi {
             repo_names: vec![],
         };
         client.teams().create(&ctx.org, &body).await?;
-        sleep(Duration::from_secs(1)).await;
+        if !wait_until_visible(self.creation_max_wait, || self.team_exists(ctx, &team.name)).await? {
+            return Err(format_err!("team {} not visible after creating it", team.name));
+        }
 
         // Add team members
         for user_name in &team.members {
             self.add_team_member(ctx, &team.name, user_name).await?;
         }
 
+        // Set notifications (not supported when creating the team)
+        if let Some(notifications) = team.notifications {
+            self.update_team_notifications(ctx, &team.name, notifications).await?;
+        }
+
         Ok(())
     }
 
@@ -384,6 +1065,24 @@ impl Svc for SvcApi {
         Ok(())
     }
 
+    /// [Svc::block_org_user]
+    async fn block_org_user(&self, ctx: &Ctx, user_name: &UserName) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        client.orgs().block_user(&ctx.org, user_name).await?;
+        Ok(())
+    }
+
+    /// [Svc::get_repository_admin_access]
+    async fn get_repository_admin_access(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<bool>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let repo = client.repos().get(&ctx.org, repo_name).await?;
+        Ok(repo.permissions.map(|p| p.admin))
+    }
+
     /// [Svc::get_team_membership]
     async fn get_team_membership(
         &self,
@@ -395,12 +1094,301 @@ impl Svc for SvcApi {
         Ok(client.teams().get_membership_for_user_in_org(&ctx.org, team_name, user_name).await?)
     }
 
+    /// [Svc::get_team_notifications]
+    async fn get_team_notifications(&self, ctx: &Ctx, team_name: &TeamName) -> Result<TeamNotifications> {
+        // The notifications setting is not supported yet by the GitHub API
+        // client, so we call the API directly
+        let path = format!("/orgs/{}/teams/{team_name}", ctx.org);
+        let team = self.api_request(ctx, Method::GET, &path, None).await?;
+        match team["notification_setting"].as_str() {
+            Some("notifications_disabled") => Ok(TeamNotifications::Disabled),
+            _ => Ok(TeamNotifications::Enabled),
+        }
+    }
+
+    /// [Svc::get_repository_allow_forking]
+    async fn get_repository_allow_forking(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<bool> {
+        let client = self.setup_client(ctx.inst_id)?;
+        Ok(client.repos().get(&ctx.org, repo_name).await?.allow_forking)
+    }
+
+    /// [Svc::get_org_settings]
+    async fn get_org_settings(&self, ctx: &Ctx) -> Result<OrganizationSettings> {
+        // Some of these settings are not supported yet by the GitHub API
+        // client, so we call the API directly
+        let path = format!("/orgs/{}", ctx.org);
+        let org = self.api_request(ctx, Method::GET, &path, None).await?;
+        let mut settings = OrganizationSettings::default();
+        for setting in OrganizationSetting::ALL {
+            settings.set(setting, org[setting.api_name()].as_bool());
+        }
+        settings.team_creation_permission =
+            org["members_can_create_teams"].as_bool().map(TeamCreationPermission::from);
+        Ok(settings)
+    }
+
+    /// [Svc::get_org_community_health_file]
+    async fn get_org_community_health_file(&self, ctx: &Ctx, path: &str) -> Result<Option<String>> {
+        let Some(file) = self.get_community_health_file(ctx, path).await? else {
+            return Ok(None);
+        };
+        Ok(Some(decode_content(&file)?))
+    }
+
+    /// [Svc::get_repository_actions_allow_list]
+    async fn get_repository_actions_allow_list(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<ActionsAllowList> {
+        // The actions allowed are only relevant when the repository is set up
+        // to allow the selected ones, so we check the permissions first
+        let path = format!("/repos/{}/{repo_name}/actions/permissions", ctx.org);
+        let permissions = self.api_request(ctx, Method::GET, &path, None).await?;
+        if permissions["allowed_actions"].as_str() != Some("selected") {
+            return Ok(ActionsAllowList::default());
+        }
+        let path = format!(
+            "/repos/{}/{repo_name}/actions/permissions/selected-actions",
+            ctx.org
+        );
+        let selected_actions = self.api_request(ctx, Method::GET, &path, None).await?;
+        Ok(parse_actions_allow_list(&selected_actions))
+    }
+
+    /// [Svc::get_repository_code_scanning_setup]
+    async fn get_repository_code_scanning_setup(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<CodeScanningSetup>> {
+        // The code scanning default setup is not supported yet by the GitHub
+        // API client, so we call the API directly. GitHub replies with a 403
+        // or a 404 when the default setup is not available in the repository
+        // (i.e. Advanced Security not enabled or no supported languages).
+        let path = format!("/repos/{}/{repo_name}/code-scanning/default-setup", ctx.org);
+        match self.api_request(ctx, Method::GET, &path, None).await {
+            Ok(setup) => Ok(Some(parse_code_scanning_setup(&setup))),
+            Err(err) if err.to_string().contains("403") || err.to_string().contains("404") => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// [Svc::get_repository_private_vulnerability_reporting]
+    async fn get_repository_private_vulnerability_reporting(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<bool> {
+        // Private vulnerability reporting is not supported yet by the GitHub
+        // API client, so we call the API directly
+        let path = format!("/repos/{}/{repo_name}/private-vulnerability-reporting", ctx.org);
+        let setting = self.api_request(ctx, Method::GET, &path, None).await?;
+        Ok(setting["enabled"].as_bool().unwrap_or(false))
+    }
+
+    /// [Svc::get_repository_codeowners]
+    async fn get_repository_codeowners(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Option<String>> {
+        for path in CODEOWNERS_PATHS {
+            if let Some(file) = self.get_repository_file(ctx, repo_name, path).await? {
+                return Ok(Some(decode_content(&file)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// [Svc::get_org_secret_visibility]
+    async fn get_org_secret_visibility(&self, ctx: &Ctx, secret_name: &str) -> Result<Option<String>> {
+        let path = format!("/orgs/{}/actions/secrets/{secret_name}", ctx.org);
+        match self.api_request(ctx, Method::GET, &path, None).await {
+            Ok(secret) => Ok(secret["visibility"].as_str().map(ToString::to_string)),
+            Err(err) if err.to_string().contains("404") => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// [Svc::get_org_verified_domains]
+    async fn get_org_verified_domains(&self, ctx: &Ctx) -> Result<OrgVerifiedDomains> {
+        // Verified domains are only available in the GraphQL API
+        let org = self.query_org_verified_domains(ctx).await?;
+        let domains = org["domains"]["nodes"]
+            .as_array()
+            .map(|nodes| {
+                nodes.iter().filter_map(|node| node["domain"].as_str().map(ToString::to_string)).collect()
+            })
+            .unwrap_or_default();
+        Ok(OrgVerifiedDomains {
+            domains,
+            gate_enabled: org["notificationDeliveryRestrictionEnabledSetting"].as_str() == Some("ENABLED"),
+        })
+    }
+
+    /// [Svc::get_org_membership_role]
+    async fn get_org_membership_role(&self, ctx: &Ctx, user_name: &UserName) -> Result<Option<String>> {
+        let path = format!("/orgs/{}/memberships/{user_name}", ctx.org);
+        match self.api_request(ctx, Method::GET, &path, None).await {
+            Ok(membership) => Ok(membership["role"].as_str().map(ToString::to_string)),
+            Err(err) if err.to_string().contains("404") => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// [Svc::get_repository_discussions]
+    async fn get_repository_discussions(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<DiscussionSettings> {
+        // Discussions categories are only available in the GraphQL API
+        let body = json!({
+            "query": REPOSITORY_DISCUSSIONS_QUERY,
+            "variables": { "owner": ctx.org, "name": repo_name },
+        });
+        let response = self.api_request(ctx, Method::POST, "/graphql", Some(body)).await?;
+        if let Some(errors) = response.get("errors") {
+            return Err(format_err!("error getting discussions settings: {errors}"));
+        }
+        let repo = &response["data"]["repository"];
+        let categories = repo["discussionCategories"]["nodes"]
+            .as_array()
+            .map(|nodes| {
+                nodes.iter().filter_map(|node| node["name"].as_str().map(ToString::to_string)).collect()
+            })
+            .unwrap_or_default();
+        Ok(DiscussionSettings {
+            enabled: repo["hasDiscussionsEnabled"].as_bool().unwrap_or_default(),
+            categories: Some(categories),
+        })
+    }
+
+    /// [Svc::get_repository_merge_commit_settings]
+    async fn get_repository_merge_commit_settings(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<MergeCommitSettings> {
+        // These settings are not supported yet by the GitHub API client, so
+        // we call the API directly
+        let path = format!("/repos/{}/{repo_name}", ctx.org);
+        let repo = self.api_request(ctx, Method::GET, &path, None).await?;
+        Ok(serde_json::from_value(repo)?)
+    }
+
     /// [Svc::get_user_login]
     async fn get_user_login(&self, ctx: &Ctx, user_name: &UserName) -> Result<UserName> {
         let client = self.setup_client(ctx.inst_id)?;
         Ok(client.users().get_by_username_public_user(user_name).await?.login)
     }
 
+    /// [Svc::list_apps_installations]
+    async fn list_apps_installations(&self, ctx: &Ctx) -> Result<Vec<AppInstallation>> {
+        const PER_PAGE: i64 = 100;
+        let client = self.setup_client(ctx.inst_id)?;
+        let mut installations = vec![];
+        for page in 1.. {
+            let response = client.orgs().list_app_installations(&ctx.org, PER_PAGE, page).await?;
+            let done = (response.installations.len() as i64) < PER_PAGE;
+            installations.extend(response.installations);
+            if done {
+                break;
+            }
+        }
+
+        let mut apps_installations = vec![];
+        for installation in installations {
+            // Repositories the app has access to are only listed when the
+            // installation is limited to some repositories. Our own
+            // installation's repositories are listed using the app token.
+            let repositories = if installation.repository_selection.to_string() == "all" {
+                None
+            } else {
+                let apps = client.apps();
+                let mut repos_names = vec![];
+                for page in 1.. {
+                    let repos: Vec<RepositoryName> = if ctx.inst_id == Some(installation.id) {
+                        apps.list_repos_accessible_to_installation(PER_PAGE, page)
+                            .await
+                            .map(|r| r.repositories.into_iter().map(|r| r.name).collect())
+                    } else {
+                        apps.list_installation_repos_for_authenticated_user(installation.id, PER_PAGE, page)
+                            .await
+                            .map(|r| r.repositories.into_iter().map(|r| r.name).collect())
+                    }
+                    .context(format!(
+                        "error listing app {} installation repositories",
+                        installation.app_slug
+                    ))?;
+                    let done = (repos.len() as i64) < PER_PAGE;
+                    repos_names.extend(repos);
+                    if done {
+                        break;
+                    }
+                }
+                Some(repos_names)
+            };
+            apps_installations.push(AppInstallation {
+                id: installation.id,
+                app_slug: installation.app_slug,
+                repositories,
+            });
+        }
+        Ok(apps_installations)
+    }
+
+    /// [Svc::list_child_teams]
+    async fn list_child_teams(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<TeamName>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let teams = client.teams().list_all_child_in_org(&ctx.org, team_name).await?;
+        Ok(teams.into_iter().map(|t| t.slug).collect())
+    }
+
+    /// [Svc::list_gitignore_templates]
+    async fn list_gitignore_templates(&self, ctx: &Ctx) -> Result<Vec<String>> {
+        #[cached(
+            time = 3600,
+            sync_writes = true,
+            result = true,
+            key = "String",
+            convert = r#"{ String::new() }"#
+        )]
+        async fn inner(client: &Client) -> Result<Vec<String>> {
+            let templates = client.gitignore().get_all_templates().await?;
+            Ok(templates)
+        }
+        let client = self.setup_client(ctx.inst_id)?;
+        inner(&client).await
+    }
+
+    /// [Svc::list_license_templates]
+    async fn list_license_templates(&self, ctx: &Ctx) -> Result<Vec<String>> {
+        #[cached(
+            time = 3600,
+            sync_writes = true,
+            result = true,
+            key = "String",
+            convert = r#"{ String::new() }"#
+        )]
+        async fn inner(svc: &SvcApi, ctx: &Ctx) -> Result<Vec<String>> {
+            let body = json!({ "query": LICENSES_QUERY });
+            let response = svc.api_request(ctx, Method::POST, "/graphql", Some(body)).await?;
+            if let Some(errors) = response.get("errors") {
+                return Err(format_err!("error listing licenses: {errors}"));
+            }
+            let licenses = response["data"]["licenses"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|license| license["key"].as_str().map(ToString::to_string))
+                .collect();
+            Ok(licenses)
+        }
+        inner(self, ctx).await
+    }
+
     /// [Svc::list_org_admins]
     async fn list_org_admins(&self, ctx: &Ctx) -> Result<Vec<SimpleUser>> {
         #[cached(
@@ -417,10 +1405,27 @@ impl Svc for SvcApi {
                 .await?;
             Ok(members)
         }
+
+        if let Some(admins) = self.take_prefetched(&ctx.org, |info| info.admins.take()) {
+            return Ok(admins);
+        }
         let client = self.setup_client(ctx.inst_id)?;
         inner(&client, &ctx.org).await
     }
 
+    /// [Svc::list_org_blocked_users]
+    async fn list_org_blocked_users(&self, ctx: &Ctx) -> Result<Vec<UserName>> {
+        let path = format!("/orgs/{}/blocks?per_page=100", ctx.org);
+        let users = self.api_request(ctx, Method::GET, &path, None).await?;
+        let users_names = users
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|user| user["login"].as_str().map(ToString::to_string))
+            .collect();
+        Ok(users_names)
+    }
+
     /// [Svc::list_org_members]
     async fn list_org_members(&self, ctx: &Ctx) -> Result<Vec<SimpleUser>> {
         #[cached(
@@ -437,12 +1442,140 @@ impl Svc for SvcApi {
                 .await?;
             Ok(members)
         }
+
+        if let Some(members) = self.take_prefetched(&ctx.org, |info| info.members.take()) {
+            return Ok(members);
+        }
         let client = self.setup_client(ctx.inst_id)?;
         inner(&client, &ctx.org).await
     }
 
+    /// [Svc::list_org_required_workflows]
+    async fn list_org_required_workflows(&self, ctx: &Ctx) -> Result<Vec<OrgRequiredWorkflow>> {
+        // The rulesets list does not include the rules, so we need to get
+        // each of the rulesets managed by CLOWarden individually
+        let path = format!("/orgs/{}/rulesets?per_page=100", ctx.org);
+        let rulesets = self.api_request(ctx, Method::GET, &path, None).await?;
+        let mut workflows = vec![];
+        for ruleset in rulesets.as_array().into_iter().flatten() {
+            let Some(name) = ruleset["name"].as_str() else {
+                continue;
+            };
+            let Some(workflow_name) = name.strip_prefix(REQUIRED_WORKFLOW_RULESET_PREFIX) else {
+                continue;
+            };
+            let Some(id) = ruleset["id"].as_i64() else {
+                continue;
+            };
+            let path = format!("/orgs/{}/rulesets/{id}", ctx.org);
+            let ruleset = self.api_request(ctx, Method::GET, &path, None).await?;
+            if let Some(workflow) = parse_required_workflow_ruleset(workflow_name, &ruleset) {
+                workflows.push(OrgRequiredWorkflow { id, workflow });
+            }
+        }
+        Ok(workflows)
+    }
+
+    /// [Svc::list_org_runner_group_repositories]
+    async fn list_org_runner_group_repositories(
+        &self,
+        ctx: &Ctx,
+        runner_group_id: i64,
+    ) -> Result<Vec<RepositoryName>> {
+        const PER_PAGE: usize = 100;
+        let mut repos_names = vec![];
+        for page in 1.. {
+            let path = format!(
+                "/orgs/{}/actions/runner-groups/{runner_group_id}/repositories?per_page={PER_PAGE}&page={page}",
+                ctx.org
+            );
+            let response = self.api_request(ctx, Method::GET, &path, None).await?;
+            let repos = response["repositories"].as_array().cloned().unwrap_or_default();
+            repos_names.extend(repos.iter().filter_map(|repo| Some(repo["name"].as_str()?.to_string())));
+            if repos.len() < PER_PAGE {
+                break;
+            }
+        }
+        Ok(repos_names)
+    }
+
+    /// [Svc::list_org_runner_groups]
+    async fn list_org_runner_groups(&self, ctx: &Ctx) -> Result<Vec<OrgRunnerGroup>> {
+        const PER_PAGE: usize = 100;
+        let mut runner_groups = vec![];
+        for page in 1.. {
+            let path = format!(
+                "/orgs/{}/actions/runner-groups?per_page={PER_PAGE}&page={page}",
+                ctx.org
+            );
+            let response = self.api_request(ctx, Method::GET, &path, None).await?;
+            let groups = response["runner_groups"].as_array().cloned().unwrap_or_default();
+            runner_groups.extend(groups.iter().filter_map(parse_runner_group));
+            if groups.len() < PER_PAGE {
+                break;
+            }
+        }
+        Ok(runner_groups)
+    }
+
+    /// [Svc::list_org_secret_repositories]
+    async fn list_org_secret_repositories(
+        &self,
+        ctx: &Ctx,
+        secret_name: &str,
+    ) -> Result<Vec<RepositoryName>> {
+        const PER_PAGE: usize = 100;
+        let mut repos_names = vec![];
+        for page in 1.. {
+            let path = format!(
+                "/orgs/{}/actions/secrets/{secret_name}/repositories?per_page={PER_PAGE}&page={page}",
+                ctx.org
+            );
+            let response = self.api_request(ctx, Method::GET, &path, None).await?;
+            let repos = response["repositories"].as_array().cloned().unwrap_or_default();
+            repos_names.extend(repos.iter().filter_map(|repo| Some(repo["name"].as_str()?.to_string())));
+            if repos.len() < PER_PAGE {
+                break;
+            }
+        }
+        Ok(repos_names)
+    }
+
+    /// [Svc::list_org_security_manager_teams]
+    async fn list_org_security_manager_teams(&self, ctx: &Ctx) -> Result<Vec<TeamName>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let teams = client.orgs().list_security_manager_teams(&ctx.org).await?;
+        Ok(teams.into_iter().map(|t| t.slug).collect())
+    }
+
+    /// [Svc::list_org_webhooks]
+    async fn list_org_webhooks(&self, ctx: &Ctx) -> Result<Vec<OrgWebhook>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let webhooks = client
+            .orgs()
+            .list_all_webhooks(&ctx.org)
+            .await?
+            .into_iter()
+            .filter_map(|hook| {
+                let config = hook.config?;
+                Some(OrgWebhook {
+                    id: hook.id,
+                    url: config.url,
+                    events: hook.events,
+                    content_type: config.content_type,
+                    active: hook.active,
+                })
+            })
+            .collect();
+        Ok(webhooks)
+    }
+
     /// [Svc::list_repositories]
     async fn list_repositories(&self, ctx: &Ctx) -> Result<Vec<MinimalRepository>> {
+        if let Some(repos) = self.take_prefetched(&ctx.org, |info| info.repositories.take()) {
+            return Ok(repos);
+        }
+
         let client = self.setup_client(ctx.inst_id)?;
         let repos = client
             .repos()
@@ -489,6 +1622,50 @@ impl Svc for SvcApi {
         inner(&client, &ctx.org, repo_name).await
     }
 
+    /// [Svc::list_repository_labels]
+    async fn list_repository_labels(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<String>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let labels = client.issues().list_all_labels_for_repo(&ctx.org, repo_name).await?;
+        Ok(labels.into_iter().map(|l| l.name).collect())
+    }
+
+    /// [Svc::list_repository_merged_branches]
+    async fn list_repository_merged_branches(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+    ) -> Result<Vec<MergedBranch>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let default_branch = client.repos().get(&ctx.org, repo_name).await?.default_branch;
+        let branches = client.repos().list_all_branches(&ctx.org, repo_name, false).await?;
+
+        // A branch is merged when its tip is reachable from the default
+        // branch, in which case the merge base is the branch's tip
+        let mut merged_branches = vec![];
+        for branch in branches {
+            if branch.name == default_branch {
+                continue;
+            }
+            let path = format!(
+                "/repos/{}/{repo_name}/compare/{default_branch}...{}",
+                ctx.org, branch.name
+            );
+            let comparison = self.api_request(ctx, Method::GET, &path, None).await?;
+            if !matches!(comparison["status"].as_str(), Some("behind" | "identical")) {
+                continue;
+            }
+            let Some(committed_at) = comparison["merge_base_commit"]["commit"]["committer"]["date"].as_str()
+            else {
+                continue;
+            };
+            merged_branches.push(MergedBranch {
+                name: branch.name,
+                last_commit_at: OffsetDateTime::parse(committed_at, &Rfc3339)?,
+            });
+        }
+        Ok(merged_branches)
+    }
+
     /// [Svc::list_repository_teams]
     async fn list_repository_teams(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<Team>> {
         let client = self.setup_client(ctx.inst_id)?;
@@ -534,6 +1711,32 @@ impl Svc for SvcApi {
         Ok(teams)
     }
 
+    /// [Svc::list_repository_watchers]
+    async fn list_repository_watchers(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<Vec<UserName>> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let watchers = client.activity().list_all_watchers_for_repo(&ctx.org, repo_name).await?;
+        Ok(watchers.into_iter().map(|user| user.login).collect())
+    }
+
+    /// [Svc::prefetch_org_info]
+    async fn prefetch_org_info(&self, ctx: &Ctx) -> Result<()> {
+        let (admins, members, repositories) = try_join!(
+            self.list_org_admins(ctx),
+            self.list_org_members(ctx),
+            self.list_repositories(ctx),
+        )?;
+        let info = PrefetchedOrgInfo {
+            admins: Some(admins),
+            members: Some(members),
+            repositories: Some(repositories),
+        };
+        self.prefetched
+            .lock()
+            .expect("prefetched lock not poisoned")
+            .cache_set(ctx.org.clone(), info);
+        Ok(())
+    }
+
     /// [Svc::remove_repository_collaborator]
     async fn remove_repository_collaborator(
         &self,
@@ -546,19 +1749,146 @@ impl Svc for SvcApi {
         Ok(())
     }
 
-    /// [Svc::remove_repository_invitation]
-    async fn remove_repository_invitation(
+    /// [Svc::remove_repository_watcher]
+    async fn remove_repository_watcher(
         &self,
         ctx: &Ctx,
         repo_name: &RepositoryName,
-        invitation_id: i64,
+        user_token: &str,
     ) -> Result<()> {
-        let client = self.setup_client(ctx.inst_id)?;
-        client.repos().delete_invitation(&ctx.org, repo_name, invitation_id).await?;
+        let client = self.setup_user_client(user_token);
+        client.activity().delete_repo_subscription(&ctx.org, repo_name).await?;
         Ok(())
     }
 
-    /// [Svc::remove_repository_team]
+    /// [Svc::reparent_child_teams]
+    async fn reparent_child_teams(&self, ctx: &Ctx, team_name: &TeamName) -> Result<Vec<TeamName>> {
+        // The GitHub API client does not support removing the parent of a
+        // team, so we call the API directly
+        let path = format!("/orgs/{}/teams/{team_name}", ctx.org);
+        let team = self.api_request(ctx, Method::GET, &path, None).await?;
+        let parent_team_id = team["parent"]["id"].as_i64();
+        let child_teams = self.list_child_teams(ctx, team_name).await?;
+        for child_team_name in &child_teams {
+            let path = format!("/orgs/{}/teams/{child_team_name}", ctx.org);
+            let body = json!({ "parent_team_id": parent_team_id });
+            self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        }
+        Ok(child_teams)
+    }
+
+    /// [Svc::repository_exists]
+    async fn repository_exists(&self, ctx: &Ctx, repo_name: &RepositoryName) -> Result<bool> {
+        let client = self.setup_client(ctx.inst_id)?;
+        match client.repos().get(&ctx.org, repo_name).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.to_string().contains("404") => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// [Svc::repository_file_exists]
+    async fn repository_file_exists(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        path: &str,
+        ref_: Option<String>,
+    ) -> Result<bool> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let ref_ = ref_.unwrap_or_default();
+        match client.repos().get_content_file(&ctx.org, repo_name, path, &ref_).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.to_string().contains("404") => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// [Svc::retire_team]
+    async fn retire_team(&self, ctx: &Ctx, team_name: &TeamName, new_team_name: &TeamName) -> Result<()> {
+        // Secret teams cannot be nested, so the team is also removed from its
+        // parent (if any). The GitHub API client does not support this, so we
+        // call the API directly
+        let path = format!("/orgs/{}/teams/{team_name}", ctx.org);
+        let body = json!({
+            "name": new_team_name,
+            "description": RETIRED_TEAM_DESCRIPTION,
+            "privacy": "secret",
+            "parent_team_id": null,
+        });
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::remove_org_required_workflow]
+    async fn remove_org_required_workflow(&self, ctx: &Ctx, workflow_id: i64) -> Result<()> {
+        let path = format!("/orgs/{}/rulesets/{workflow_id}", ctx.org);
+        self.api_request(ctx, Method::DELETE, &path, None).await?;
+        Ok(())
+    }
+
+    /// [Svc::remove_org_security_manager_team]
+    async fn remove_org_security_manager_team(&self, ctx: &Ctx, team_name: &TeamName) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        client.orgs().remove_security_manager_team(&ctx.org, team_name).await?;
+        Ok(())
+    }
+
+    /// [Svc::remove_org_webhook]
+    async fn remove_org_webhook(&self, ctx: &Ctx, webhook_id: i64) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        client.orgs().delete_webhook(&ctx.org, webhook_id).await?;
+        Ok(())
+    }
+
+    /// [Svc::remove_repository_app]
+    async fn remove_repository_app(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        app_slug: &AppSlug,
+    ) -> Result<()> {
+        let Some(installation) =
+            self.list_apps_installations(ctx).await?.into_iter().find(|i| &i.app_slug == app_slug)
+        else {
+            return Err(format_err!("app {app_slug} installation not found"));
+        };
+        if installation.repositories.is_none() {
+            return Err(format_err!(
+                "app {app_slug} has access to all repositories, cannot remove access to a single one"
+            ));
+        }
+        let client = self.setup_client(ctx.inst_id)?;
+        let repo = client.repos().get(&ctx.org, repo_name).await?;
+        client.apps().remove_repo_from_installation(installation.id, repo.id).await?;
+        Ok(())
+    }
+
+    /// [Svc::remove_repository_invitation]
+    async fn remove_repository_invitation(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        invitation_id: i64,
+    ) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        client.repos().delete_invitation(&ctx.org, repo_name, invitation_id).await?;
+        Ok(())
+    }
+
+    /// [Svc::remove_repository_label]
+    async fn remove_repository_label(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        label_name: &str,
+    ) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        client.issues().delete_label(&ctx.org, repo_name, label_name).await?;
+        Ok(())
+    }
+
+    /// [Svc::remove_repository_team]
     async fn remove_repository_team(
         &self,
         ctx: &Ctx,
@@ -596,6 +1926,75 @@ impl Svc for SvcApi {
         Ok(())
     }
 
+    /// [Svc::team_exists]
+    async fn team_exists(&self, ctx: &Ctx, team_name: &TeamName) -> Result<bool> {
+        let client = self.setup_client(ctx.inst_id)?;
+        match client.teams().get_by_name(&ctx.org, team_name).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.to_string().contains("404") => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// [Svc::unblock_org_user]
+    async fn unblock_org_user(&self, ctx: &Ctx, user_name: &UserName) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        client.orgs().unblock_user(&ctx.org, user_name).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_required_workflow]
+    async fn update_org_required_workflow(
+        &self,
+        ctx: &Ctx,
+        workflow_id: i64,
+        workflow: &RequiredWorkflow,
+    ) -> Result<()> {
+        let path = format!("/orgs/{}/rulesets/{workflow_id}", ctx.org);
+        let body = self.required_workflow_ruleset_body(ctx, workflow).await?;
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_runner_group_repositories]
+    async fn update_org_runner_group_repositories(
+        &self,
+        ctx: &Ctx,
+        runner_group_id: i64,
+        repos_names: &[RepositoryName],
+    ) -> Result<()> {
+        let repos_ids = self.get_repositories_ids(ctx, repos_names).await?;
+        let path = format!(
+            "/orgs/{}/actions/runner-groups/{runner_group_id}/repositories",
+            ctx.org
+        );
+        let body = json!({ "selected_repository_ids": repos_ids });
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_secret_repositories]
+    async fn update_org_secret_repositories(
+        &self,
+        ctx: &Ctx,
+        secret_name: &str,
+        repos_names: &[RepositoryName],
+    ) -> Result<()> {
+        let repos_ids = self.get_repositories_ids(ctx, repos_names).await?;
+        let path = format!("/orgs/{}/actions/secrets/{secret_name}/repositories", ctx.org);
+        let body = json!({ "selected_repository_ids": repos_ids });
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_webhook]
+    async fn update_org_webhook(&self, ctx: &Ctx, webhook_id: i64, webhook: &Webhook) -> Result<()> {
+        let path = format!("/orgs/{}/hooks/{webhook_id}", ctx.org);
+        let body = webhook_request_body(webhook);
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
     /// [Svc::update_repository_collaborator_role]
     async fn update_repository_collaborator_role(
         &self,
@@ -629,22 +2028,292 @@ impl Svc for SvcApi {
         Ok(())
     }
 
-    /// [Svc::update_repository_team_role]
-    async fn update_repository_team_role(
+    /// [Svc::update_repository_allow_forking]
+    async fn update_repository_allow_forking(
         &self,
         ctx: &Ctx,
         repo_name: &RepositoryName,
-        team_name: &TeamName,
-        role: &Role,
+        allow_forking: bool,
     ) -> Result<()> {
-        let client = self.setup_client(ctx.inst_id)?;
-        let body = TeamsAddUpdateRepoPermissionsInOrgRequest {
-            permission: Some(role.into()),
+        let path = format!("/repos/{}/{repo_name}", ctx.org);
+        let body = json!({ "allow_forking": allow_forking });
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_setting]
+    async fn update_org_setting(&self, ctx: &Ctx, setting: OrganizationSetting, value: bool) -> Result<()> {
+        let path = format!("/orgs/{}", ctx.org);
+        let body = json!({ setting.api_name(): value });
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_community_health_file]
+    async fn update_org_community_health_file(&self, ctx: &Ctx, path: &str, content: &str) -> Result<()> {
+        // The sha of the current file is required to update it
+        let sha = self.get_community_health_file(ctx, path).await?.map(|file| file.sha);
+        let api_path = format!("/repos/{}/{COMMUNITY_HEALTH_FILES_REPO}/contents/{path}", ctx.org);
+        let mut body = json!({
+            "message": format!("Update {path}"),
+            "content": b64.encode(content),
+        });
+        if let Some(sha) = sha {
+            body["sha"] = json!(sha);
+        }
+        self.api_request(ctx, Method::PUT, &api_path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_verified_domains_gate]
+    async fn update_org_verified_domains_gate(&self, ctx: &Ctx, enabled: bool) -> Result<()> {
+        // The mutation requires the organization's node id
+        let org = self.query_org_verified_domains(ctx).await?;
+        let Some(org_id) = org["id"].as_str() else {
+            return Err(format_err!("organization {} not found", ctx.org));
         };
-        client
-            .teams()
-            .add_or_update_repo_permissions_in_org(&ctx.org, team_name, &ctx.org, repo_name, &body)
+        let body = json!({
+            "query": UPDATE_ORG_VERIFIED_DOMAINS_GATE_MUTATION,
+            "variables": {
+                "ownerId": org_id,
+                "settingValue": if enabled { "ENABLED" } else { "DISABLED" },
+            },
+        });
+        let response = self.api_request(ctx, Method::POST, "/graphql", Some(body)).await?;
+        if let Some(errors) = response.get("errors") {
+            return Err(format_err!("error updating verified domains gate: {errors}"));
+        }
+        Ok(())
+    }
+
+    /// [Svc::update_org_team_creation_permission]
+    async fn update_org_team_creation_permission(
+        &self,
+        ctx: &Ctx,
+        permission: TeamCreationPermission,
+    ) -> Result<()> {
+        let path = format!("/orgs/{}", ctx.org);
+        let body = json!({ "members_can_create_teams": permission.members_can_create_teams() });
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_org_membership_to_member]
+    async fn update_org_membership_to_member(&self, ctx: &Ctx, user_name: &UserName) -> Result<()> {
+        let path = format!("/orgs/{}/memberships/{user_name}", ctx.org);
+        let body = json!({ "role": "member" });
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_repository_branch_protection]
+    async fn update_repository_branch_protection(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        branch: &str,
+        protection: &BranchProtection,
+    ) -> Result<()> {
+        let path = format!("/repos/{}/{repo_name}/branches/{branch}/protection", ctx.org);
+        let body = branch_protection_request_body(protection);
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_repository_actions_allow_list]
+    async fn update_repository_actions_allow_list(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        allow_list: &ActionsAllowList,
+    ) -> Result<()> {
+        // When the allow-list is disabled, all actions are allowed again
+        let path = format!("/repos/{}/{repo_name}/actions/permissions", ctx.org);
+        let allowed_actions = if allow_list.enabled { "selected" } else { "all" };
+        let body = json!({ "enabled": true, "allowed_actions": allowed_actions });
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        if allow_list.enabled {
+            let path = format!(
+                "/repos/{}/{repo_name}/actions/permissions/selected-actions",
+                ctx.org
+            );
+            let body = actions_allow_list_request_body(allow_list);
+            self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        }
+        Ok(())
+    }
+
+    /// [Svc::update_repository_code_scanning_setup]
+    async fn update_repository_code_scanning_setup(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        setup: &CodeScanningSetup,
+    ) -> Result<()> {
+        let path = format!("/repos/{}/{repo_name}/code-scanning/default-setup", ctx.org);
+        let body = code_scanning_setup_request_body(setup);
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_repository_codeowners]
+    async fn update_repository_codeowners(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        content: &str,
+        update_mode: CodeownersUpdateMode,
+    ) -> Result<()> {
+        // The file is updated in the location where GitHub finds it (if any),
+        // and the sha of the current file is required to update it
+        let mut path = CODEOWNERS_PATHS[0];
+        let mut sha = None;
+        for candidate in CODEOWNERS_PATHS {
+            if let Some(file) = self.get_repository_file(ctx, repo_name, candidate).await? {
+                path = candidate;
+                sha = Some(file.sha);
+                break;
+            }
+        }
+        let contents_path = format!("/repos/{}/{repo_name}/contents/{path}", ctx.org);
+        let mut body = json!({
+            "message": "Update CODEOWNERS file",
+            "content": b64.encode(content),
+        });
+        if let Some(sha) = sha {
+            body["sha"] = json!(sha);
+        }
+
+        if update_mode == CodeownersUpdateMode::Commit {
+            self.api_request(ctx, Method::PUT, &contents_path, Some(body)).await?;
+            return Ok(());
+        }
+
+        // Changes proposed in a pull request are committed to a branch created
+        // from the default branch (it's reset if it exists already)
+        let repo = self
+            .api_request(ctx, Method::GET, &format!("/repos/{}/{repo_name}", ctx.org), None)
             .await?;
+        let Some(default_branch) = repo["default_branch"].as_str() else {
+            return Err(format_err!("repository {repo_name} default branch not found"));
+        };
+        let ref_path = format!("/repos/{}/{repo_name}/git/ref/heads/{default_branch}", ctx.org);
+        let default_ref = self.api_request(ctx, Method::GET, &ref_path, None).await?;
+        let Some(head_sha) = default_ref["object"]["sha"].as_str() else {
+            return Err(format_err!(
+                "repository {repo_name} default branch head not found"
+            ));
+        };
+        let refs_path = format!("/repos/{}/{repo_name}/git/refs", ctx.org);
+        let ref_body = json!({ "ref": format!("refs/heads/{CODEOWNERS_BRANCH}"), "sha": head_sha });
+        if let Err(err) = self.api_request(ctx, Method::POST, &refs_path, Some(ref_body)).await {
+            if !err.to_string().contains("422") {
+                return Err(err);
+            }
+            let branch_path = format!(
+                "/repos/{}/{repo_name}/git/refs/heads/{CODEOWNERS_BRANCH}",
+                ctx.org
+            );
+            let ref_body = json!({ "sha": head_sha, "force": true });
+            self.api_request(ctx, Method::PATCH, &branch_path, Some(ref_body)).await?;
+        }
+        body["branch"] = json!(CODEOWNERS_BRANCH);
+        self.api_request(ctx, Method::PUT, &contents_path, Some(body)).await?;
+
+        // The pull request may be open already from a previous update
+        let pulls_path = format!("/repos/{}/{repo_name}/pulls", ctx.org);
+        let pull_body = json!({
+            "title": "Update CODEOWNERS file",
+            "body": "This pull request updates the CODEOWNERS file from the teams with write access to the repository defined in CLOWarden.",
+            "head": CODEOWNERS_BRANCH,
+            "base": default_branch,
+        });
+        match self.api_request(ctx, Method::POST, &pulls_path, Some(pull_body)).await {
+            Err(err) if !err.to_string().contains("422") => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// [Svc::update_repository_private_vulnerability_reporting]
+    async fn update_repository_private_vulnerability_reporting(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        enabled: bool,
+    ) -> Result<()> {
+        let path = format!("/repos/{}/{repo_name}/private-vulnerability-reporting", ctx.org);
+        let method = if enabled { Method::PUT } else { Method::DELETE };
+        self.api_request(ctx, method, &path, None).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_repository_discussions]
+    async fn update_repository_discussions(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        enabled: bool,
+    ) -> Result<()> {
+        let path = format!("/repos/{}/{repo_name}", ctx.org);
+        let body = json!({ "has_discussions": enabled });
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_repository_merge_commit_settings]
+    async fn update_repository_merge_commit_settings(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        settings: &MergeCommitSettings,
+    ) -> Result<()> {
+        let path = format!("/repos/{}/{repo_name}", ctx.org);
+        let body = serde_json::to_value(settings)?;
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_repository_topics]
+    async fn update_repository_topics(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        topics: &[String],
+    ) -> Result<()> {
+        let path = format!("/repos/{}/{repo_name}/topics", ctx.org);
+        let body = json!({ "names": topics });
+        self.api_request(ctx, Method::PUT, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// [Svc::update_repository_template_status]
+    async fn update_repository_template_status(
+        &self,
+        ctx: &Ctx,
+        repo_name: &RepositoryName,
+        is_template: bool,
+    ) -> Result<()> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let body = ReposUpdateRequest {
+            allow_auto_merge: None,
+            allow_merge_commit: None,
+            allow_rebase_merge: None,
+            allow_squash_merge: None,
+            archived: None,
+            default_branch: String::new(),
+            delete_branch_on_merge: None,
+            description: String::new(),
+            has_issues: None,
+            has_projects: None,
+            has_wiki: None,
+            homepage: String::new(),
+            is_template: Some(is_template),
+            name: repo_name.clone(),
+            private: None,
+            security_and_analysis: None,
+            visibility: None,
+        };
+        client.repos().update(&ctx.org, repo_name, &body).await?;
         Ok(())
     }
 
@@ -683,6 +2352,252 @@ impl Svc for SvcApi {
         client.repos().update(&ctx.org, repo_name, &body).await?;
         Ok(())
     }
+
+    /// [Svc::update_team_notifications]
+    async fn update_team_notifications(
+        &self,
+        ctx: &Ctx,
+        team_name: &TeamName,
+        notifications: TeamNotifications,
+    ) -> Result<()> {
+        let path = format!("/orgs/{}/teams/{team_name}", ctx.org);
+        let notification_setting = match notifications {
+            TeamNotifications::Enabled => "notifications_enabled",
+            TeamNotifications::Disabled => "notifications_disabled",
+        };
+        let body = json!({ "notification_setting": notification_setting });
+        self.api_request(ctx, Method::PATCH, &path, Some(body)).await?;
+        Ok(())
+    }
+}
+
+/// Information about an app installed in the organization.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AppInstallation {
+    pub id: i64,
+    pub app_slug: AppSlug,
+    /// Repositories the app has access to (None when it has access to all).
+    pub repositories: Option<Vec<RepositoryName>>,
+}
+
+/// Information about the organization's verified domains.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrgVerifiedDomains {
+    pub domains: Vec<String>,
+    /// Whether notifications are restricted to the verified domains.
+    pub gate_enabled: bool,
+}
+
+/// Information about a self-hosted runner group in the organization.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrgRunnerGroup {
+    pub id: i64,
+    pub name: String,
+    /// Repositories that can use the group: all, selected or private.
+    pub visibility: String,
+}
+
+/// Information about a webhook registered in the organization.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrgWebhook {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<String>,
+    pub content_type: String,
+    pub active: bool,
+}
+
+/// Information about a branch already merged into the repository's default
+/// branch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedBranch {
+    pub name: String,
+    pub last_commit_at: OffsetDateTime,
+}
+
+/// Information about a required workflow enforced in the organization.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrgRequiredWorkflow {
+    /// Id of the ruleset used to enforce the workflow.
+    pub id: i64,
+    pub workflow: RequiredWorkflow,
+}
+
+/// Prepare the body of a request to update a branch protection. All fields
+/// are required by GitHub, so the ones not set are explicitly disabled.
+fn branch_protection_request_body(protection: &BranchProtection) -> Value {
+    let required_status_checks = protection
+        .required_status_checks
+        .as_ref()
+        .map(|contexts| json!({ "strict": false, "contexts": contexts }));
+    let required_pull_request_reviews = if protection.required_approving_review_count.is_some()
+        || protection.require_code_owner_reviews
+        || protection.dismiss_stale_reviews
+    {
+        let mut reviews = json!({
+            "dismiss_stale_reviews": protection.dismiss_stale_reviews,
+            "require_code_owner_reviews": protection.require_code_owner_reviews,
+        });
+        if let Some(count) = protection.required_approving_review_count {
+            reviews["required_approving_review_count"] = json!(count);
+        }
+        Some(reviews)
+    } else {
+        None
+    };
+    json!({
+        "required_status_checks": required_status_checks,
+        "enforce_admins": protection.enforce_admins,
+        "required_pull_request_reviews": required_pull_request_reviews,
+        "restrictions": null,
+    })
+}
+
+/// Prepare the body of a request to update the actions allowed in a
+/// repository.
+fn actions_allow_list_request_body(allow_list: &ActionsAllowList) -> Value {
+    json!({
+        "github_owned_allowed": allow_list.github_owned_allowed,
+        "verified_allowed": allow_list.verified_allowed,
+        "patterns_allowed": allow_list.patterns_allowed,
+    })
+}
+
+/// Prepare the body of a request to update a code scanning default setup.
+fn code_scanning_setup_request_body(setup: &CodeScanningSetup) -> Value {
+    let state = if setup.enabled {
+        "configured"
+    } else {
+        "not-configured"
+    };
+    let mut body = json!({ "state": state });
+    if let (true, Some(query_suite)) = (setup.enabled, setup.query_suite) {
+        body["query_suite"] = json!(query_suite);
+    }
+    body
+}
+
+/// Decode the content of the file provided (the GitHub API returns it base64
+/// encoded, split in multiple lines).
+fn decode_content(file: &ContentFile) -> Result<String> {
+    let mut content = file.content.as_bytes().to_owned();
+    content.retain(|b| !b" \n\t\r\x0b\x0c".contains(b));
+    Ok(String::from_utf8(b64.decode(content)?)?)
+}
+
+/// Parse the selected actions returned by the GitHub API into an enabled
+/// actions allow-list.
+fn parse_actions_allow_list(selected_actions: &Value) -> ActionsAllowList {
+    ActionsAllowList {
+        enabled: true,
+        github_owned_allowed: selected_actions["github_owned_allowed"].as_bool().unwrap_or_default(),
+        verified_allowed: selected_actions["verified_allowed"].as_bool().unwrap_or_default(),
+        patterns_allowed: selected_actions["patterns_allowed"]
+            .as_array()
+            .map(|patterns| patterns.iter().filter_map(|p| p.as_str().map(ToString::to_string)).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Parse the self-hosted runner group returned by the GitHub API. Registration
+/// tokens are never requested, so only the group's details are available.
+fn parse_runner_group(runner_group: &Value) -> Option<OrgRunnerGroup> {
+    Some(OrgRunnerGroup {
+        id: runner_group["id"].as_i64()?,
+        name: runner_group["name"].as_str()?.to_string(),
+        visibility: runner_group["visibility"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+/// Parse the code scanning default setup returned by the GitHub API.
+fn parse_code_scanning_setup(setup: &Value) -> CodeScanningSetup {
+    let enabled = setup["state"].as_str() == Some("configured");
+    let query_suite = match setup["query_suite"].as_str() {
+        Some("default") if enabled => Some(CodeScanningQuerySuite::Default),
+        Some("extended") if enabled => Some(CodeScanningQuerySuite::Extended),
+        _ => None,
+    };
+    CodeScanningSetup { enabled, query_suite }
+}
+
+/// Parse the required workflow enforced by the ruleset provided. The workflow
+/// name is taken from the ruleset's name. None is returned when the ruleset
+/// does not contain a workflows rule.
+fn parse_required_workflow_ruleset(workflow_name: &str, ruleset: &Value) -> Option<RequiredWorkflow> {
+    let (repository, _) = workflow_name.split_once('/')?;
+    let rule = ruleset["rules"]
+        .as_array()?
+        .iter()
+        .find(|rule| rule["type"].as_str() == Some("workflows"))?;
+    let workflow = &rule["parameters"]["workflows"][0];
+    let repositories: Vec<String> = ruleset["conditions"]["repository_name"]["include"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.as_str().map(ToString::to_string))
+        .collect();
+    Some(RequiredWorkflow {
+        repository: repository.to_string(),
+        path: workflow["path"].as_str()?.to_string(),
+        ref_: workflow["ref"].as_str().map(ToString::to_string),
+        repositories: if repositories == ["~ALL"] {
+            None
+        } else {
+            Some(repositories)
+        },
+    })
+}
+
+/// Prepare the body of a request to create or update the webhook provided.
+/// The GitHub API client does not support setting the webhook's config
+/// easily, so we call the API directly using this body.
+fn webhook_request_body(webhook: &Webhook) -> Value {
+    let mut config = json!({
+        "url": webhook.url,
+        "content_type": webhook.content_type(),
+    });
+    if let Some(secret) = &webhook.secret {
+        config["secret"] = json!(secret);
+    }
+    json!({
+        "active": webhook.is_active(),
+        "events": webhook.events,
+        "config": config,
+    })
+}
+
+/// Wait until a newly created resource is visible in GitHub, as it may take a
+/// bit before it can be used in subsequent requests. The function provided is
+/// called periodically to check it until the maximum wait time is reached.
+/// Returns whether the resource became visible or not.
+async fn wait_until_visible<F, Fut>(max_wait: Duration, is_visible: F) -> Result<bool>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let started_at = Instant::now();
+    loop {
+        if is_visible().await? {
+            return Ok(true);
+        }
+        if started_at.elapsed() >= max_wait {
+            return Ok(false);
+        }
+        sleep(VISIBILITY_CHECK_INTERVAL).await;
+    }
+}
+
+/// Organization information prefetched when warming up. Each piece of
+/// information is only used once, afterwards it's fetched again as usual.
+struct PrefetchedOrgInfo {
+    admins: Option<Vec<SimpleUser>>,
+    members: Option<Vec<SimpleUser>>,
+    repositories: Option<Vec<MinimalRepository>>,
+}
+
+/// Create a new cache to keep the organizations information prefetched.
+fn new_prefetched_cache() -> Mutex<TimedCache<String, PrefetchedOrgInfo>> {
+    Mutex::new(TimedCache::with_lifespan(PREFETCHED_ORG_INFO_LIFESPAN))
 }
 
 /// Information about the target of a GitHub API request.
@@ -699,3 +2614,363 @@ impl From<&Organization> for Ctx {
         }
     }
 }
+
+/// Helper function to create a new ReposCreateInOrgRequest instance from the
+/// repository provided.
+fn new_repos_create_in_org_request(repo: &Repository) -> ReposCreateInOrgRequest {
+    let visibility = match repo.visibility {
+        Some(Visibility::Internal) => Some(ReposCreateInOrgRequestVisibility::Internal),
+        Some(Visibility::Private) => Some(ReposCreateInOrgRequestVisibility::Private),
+        Some(Visibility::Public) => Some(ReposCreateInOrgRequestVisibility::Public),
+        None => None,
+    };
+    ReposCreateInOrgRequest {
+        allow_auto_merge: None,
+        allow_merge_commit: None,
+        allow_rebase_merge: None,
+        allow_squash_merge: None,
+        // Branch protection can only be applied to an existing branch
+        auto_init: repo.branch_protection.is_some().then_some(true),
+        delete_branch_on_merge: None,
+        description: String::new(),
+        gitignore_template: repo.gitignore_template.clone().unwrap_or_default(),
+        has_issues: None,
+        has_projects: None,
+        has_wiki: None,
+        homepage: String::new(),
+        is_template: repo.is_template,
+        license_template: repo.license_template.clone().unwrap_or_default(),
+        name: repo.name.clone(),
+        private: None,
+        team_id: 0,
+        visibility,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use reqwest_middleware::ClientBuilder;
+
+    use super::*;
+    use crate::testutil::{local_server, RedirectTo};
+
+    #[test]
+    fn new_repos_create_in_org_request_includes_templates() {
+        let repo = Repository {
+            name: "repo1".to_string(),
+            gitignore_template: Some("Rust".to_string()),
+            license_template: Some("apache-2.0".to_string()),
+            ..Default::default()
+        };
+        let body = new_repos_create_in_org_request(&repo);
+        assert_eq!(body.name, "repo1");
+        assert_eq!(body.gitignore_template, "Rust");
+        assert_eq!(body.license_template, "apache-2.0");
+    }
+
+    #[test]
+    fn new_repos_create_in_org_request_without_templates() {
+        let repo = Repository {
+            name: "repo1".to_string(),
+            ..Default::default()
+        };
+        let body = new_repos_create_in_org_request(&repo);
+        assert!(body.gitignore_template.is_empty());
+        assert!(body.license_template.is_empty());
+        assert!(body.auto_init.is_none());
+    }
+
+    #[test]
+    fn new_repos_create_in_org_request_with_branch_protection() {
+        let repo = Repository {
+            name: "repo1".to_string(),
+            branch_protection: Some(BranchProtection::default()),
+            ..Default::default()
+        };
+        let body = new_repos_create_in_org_request(&repo);
+        assert_eq!(body.auto_init, Some(true));
+    }
+
+    #[test]
+    fn branch_protection_request_body_reviews_and_checks() {
+        let protection = BranchProtection {
+            required_approving_review_count: Some(2),
+            require_code_owner_reviews: true,
+            required_status_checks: Some(vec!["ci".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            branch_protection_request_body(&protection),
+            json!({
+                "required_status_checks": { "strict": false, "contexts": ["ci"] },
+                "enforce_admins": false,
+                "required_pull_request_reviews": {
+                    "dismiss_stale_reviews": false,
+                    "require_code_owner_reviews": true,
+                    "required_approving_review_count": 2,
+                },
+                "restrictions": null,
+            })
+        );
+    }
+
+    #[test]
+    fn branch_protection_request_body_enforce_admins_only() {
+        let protection = BranchProtection {
+            enforce_admins: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            branch_protection_request_body(&protection),
+            json!({
+                "required_status_checks": null,
+                "enforce_admins": true,
+                "required_pull_request_reviews": null,
+                "restrictions": null,
+            })
+        );
+    }
+
+    #[test]
+    fn actions_allow_list_request_body_patterns() {
+        let allow_list = ActionsAllowList {
+            enabled: true,
+            github_owned_allowed: true,
+            verified_allowed: false,
+            patterns_allowed: vec!["org/*".to_string()],
+        };
+        assert_eq!(
+            actions_allow_list_request_body(&allow_list),
+            json!({ "github_owned_allowed": true, "verified_allowed": false, "patterns_allowed": ["org/*"] })
+        );
+    }
+
+    #[test]
+    fn parse_actions_allow_list_selected_actions() {
+        let selected_actions = json!({
+            "github_owned_allowed": true,
+            "verified_allowed": true,
+            "patterns_allowed": ["org/action@v1", "org/repo/*"],
+        });
+        assert_eq!(
+            parse_actions_allow_list(&selected_actions),
+            ActionsAllowList {
+                enabled: true,
+                github_owned_allowed: true,
+                verified_allowed: true,
+                patterns_allowed: vec!["org/action@v1".to_string(), "org/repo/*".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_runner_group_selected() {
+        let runner_group = json!({
+            "id": 2,
+            "name": "group1",
+            "visibility": "selected",
+            "default": false,
+            "runners_url": "https://api.github.com/orgs/org/actions/runner-groups/2/runners",
+        });
+        assert_eq!(
+            parse_runner_group(&runner_group),
+            Some(OrgRunnerGroup {
+                id: 2,
+                name: "group1".to_string(),
+                visibility: "selected".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn code_scanning_setup_request_body_enabled() {
+        let setup = CodeScanningSetup {
+            enabled: true,
+            query_suite: Some(CodeScanningQuerySuite::Extended),
+        };
+        assert_eq!(
+            code_scanning_setup_request_body(&setup),
+            json!({ "state": "configured", "query_suite": "extended" })
+        );
+    }
+
+    #[test]
+    fn code_scanning_setup_request_body_disabled() {
+        let setup = CodeScanningSetup {
+            enabled: false,
+            query_suite: Some(CodeScanningQuerySuite::Extended),
+        };
+        assert_eq!(
+            code_scanning_setup_request_body(&setup),
+            json!({ "state": "not-configured" })
+        );
+    }
+
+    #[test]
+    fn parse_code_scanning_setup_configured() {
+        let setup = json!({ "state": "configured", "query_suite": "default", "languages": ["rust"] });
+        assert_eq!(
+            parse_code_scanning_setup(&setup),
+            CodeScanningSetup {
+                enabled: true,
+                query_suite: Some(CodeScanningQuerySuite::Default),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_code_scanning_setup_not_configured() {
+        let setup = json!({ "state": "not-configured", "query_suite": "default" });
+        assert_eq!(parse_code_scanning_setup(&setup), CodeScanningSetup::default());
+    }
+
+    #[test]
+    fn parse_required_workflow_ruleset_all_repositories() {
+        let ruleset = json!({
+            "conditions": {
+                "repository_name": { "include": ["~ALL"], "exclude": [] },
+            },
+            "rules": [{
+                "type": "workflows",
+                "parameters": {
+                    "workflows": [{ "path": ".github/workflows/ci.yml", "repository_id": 1 }],
+                },
+            }],
+        });
+        let workflow = parse_required_workflow_ruleset("repo1/.github/workflows/ci.yml", &ruleset).unwrap();
+        assert_eq!(
+            workflow,
+            RequiredWorkflow {
+                repository: "repo1".to_string(),
+                path: ".github/workflows/ci.yml".to_string(),
+                ref_: None,
+                repositories: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_required_workflow_ruleset_some_repositories() {
+        let ruleset = json!({
+            "conditions": {
+                "repository_name": { "include": ["repo2", "repo3"], "exclude": [] },
+            },
+            "rules": [{
+                "type": "workflows",
+                "parameters": {
+                    "workflows": [{ "path": "ci.yml", "repository_id": 1, "ref": "v1" }],
+                },
+            }],
+        });
+        let workflow = parse_required_workflow_ruleset("repo1/ci.yml", &ruleset).unwrap();
+        assert_eq!(workflow.ref_, Some("v1".to_string()));
+        assert_eq!(
+            workflow.repositories,
+            Some(vec!["repo2".to_string(), "repo3".to_string()])
+        );
+    }
+
+    #[test]
+    fn parse_required_workflow_ruleset_without_workflows_rule() {
+        let ruleset = json!({ "rules": [{ "type": "deletion" }] });
+        assert!(parse_required_workflow_ruleset("repo1/ci.yml", &ruleset).is_none());
+    }
+
+    #[tokio::test]
+    async fn wait_until_visible_resource_visible_after_delay() {
+        let ctx = Ctx {
+            inst_id: None,
+            org: "org".to_string(),
+        };
+        let created_at = Instant::now();
+        let mut svc = MockSvc::new();
+        svc.expect_team_exists()
+            .withf(|_, team_name| team_name == "team1")
+            .returning(move |_, _| Ok(created_at.elapsed() >= Duration::from_millis(600)));
+
+        let team_name = "team1".to_string();
+        let visible = wait_until_visible(Duration::from_secs(5), || svc.team_exists(&ctx, &team_name))
+            .await
+            .unwrap();
+        assert!(visible);
+        assert!(created_at.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn wait_until_visible_max_wait_reached() {
+        let ctx = Ctx {
+            inst_id: None,
+            org: "org".to_string(),
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_repository_exists().returning(|_, _| Ok(false));
+
+        let repo_name = "repo1".to_string();
+        let visible = wait_until_visible(Duration::from_millis(500), || {
+            svc.repository_exists(&ctx, &repo_name)
+        })
+        .await
+        .unwrap();
+        assert!(!visible);
+    }
+
+    /// Setup a svc whose requests are all sent to a local server that always
+    /// responds with the JSON body provided. The number of requests received
+    /// by the server is returned as well.
+    fn svc_with_server(body: &'static str) -> (SvcApi, Arc<AtomicUsize>) {
+        let (addr, requests) = local_server(|_| "200 OK", body);
+        let svc = SvcApi {
+            app_credentials: None,
+            token: Some("token".to_string()),
+            http_client: ClientBuilder::new(reqwest::Client::new()).with(RedirectTo(addr)).build(),
+            creation_max_wait: Duration::from_secs(0),
+            prefetched: new_prefetched_cache(),
+        };
+        (svc, requests)
+    }
+
+    #[tokio::test]
+    async fn list_org_members_second_call_hits_cache() {
+        let (svc, requests) = svc_with_server("[]");
+        let ctx = Ctx {
+            inst_id: None,
+            org: "org-members-cache".to_string(),
+        };
+        assert!(svc.list_org_members(&ctx).await.unwrap().is_empty());
+        assert!(svc.list_org_members(&ctx).await.unwrap().is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn prefetched_org_info_is_used_once() {
+        let (svc, requests) = svc_with_server("[]");
+        let ctx = Ctx {
+            inst_id: None,
+            org: "org-prefetch".to_string(),
+        };
+        svc.prefetch_org_info(&ctx).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // Repositories are not cached, so only the first call after the
+        // prefetch avoids hitting the server
+        assert!(svc.list_repositories(&ctx).await.unwrap().is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        assert!(svc.list_repositories(&ctx).await.unwrap().is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn list_license_templates_includes_all_licenses() {
+        let (svc, _) = svc_with_server(r#"{"data":{"licenses":[{"key":"0bsd"},{"key":"apache-2.0"}]}}"#);
+        let ctx = Ctx {
+            inst_id: None,
+            org: "org".to_string(),
+        };
+        assert_eq!(
+            svc.list_license_templates(&ctx).await.unwrap(),
+            vec!["0bsd".to_string(), "apache-2.0".to_string()]
+        );
+    }
+}
@@ -3,8 +3,10 @@
 //! configuration or the service, and validating and comparing them.
 
 use std::{
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     fmt::{self, Write},
+    future::Future,
+    str::FromStr,
 };
 
 use anyhow::{format_err, Context, Result};
@@ -14,19 +16,23 @@ use futures::{
 };
 use lazy_static::lazy_static;
 use octorust::types::{
-    OrgMembershipState, RepositoryInvitationPermissions, RepositoryPermissions, TeamMembershipRole,
-    TeamPermissions, TeamsAddUpdateRepoPermissionsInOrgRequestPermission,
+    MinimalRepository, OrgMembershipState, RepositoryInvitationPermissions, RepositoryPermissions,
+    TeamMembershipRole, TeamPermissions, TeamsAddUpdateRepoPermissionsInOrgRequestPermission,
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use time::{macros::format_description, Date, Duration};
 
 use crate::{
-    cfg::Legacy,
+    cfg::{
+        Organization, OrganizationRunnerGroup, OrganizationSetting, RequiredWorkflow, TeamCreationPermission,
+        Webhook,
+    },
     directory::{Directory, DirectoryChange, Team, TeamName, UserName},
     github::{DynGH, Source},
     multierror::MultiError,
-    services::{Change, ChangeDetails},
+    services::{AccessChange, Change, ChangeDetails, ChangeExplanation, ChangeRisk, UserImpact},
 };
 
 use super::{
@@ -34,19 +40,49 @@ use super::{
     service::{Ctx, DynSvc},
 };
 
+/// Period of time before a collaborator's access expires during which it will
+/// be reported as expiring.
+const COLLABORATOR_EXPIRATION_NOTICE: Duration = Duration::days(7);
+
 lazy_static! {
     /// Regular expression to match temporary private forks created for GitHub
     /// security advisories.
     static ref GHSA_TEMP_FORK: Regex =
         Regex::new("^(.+)-ghsa(-[23456789cfghjmpqrvwx]{4}){3}$").expect("expr in GHSA_TEMP_FORK to be valid");
+
+    /// Regular expression to match valid repository topics (as normalized by
+    /// GitHub: lowercase letters, numbers and hyphens, up to 50 characters).
+    static ref VALID_TOPIC: Regex =
+        Regex::new("^[a-z0-9][a-z0-9-]{0,49}$").expect("expr in VALID_TOPIC to be valid");
+
+    /// Regular expression to match valid GitHub user names (alphanumeric
+    /// characters or single hyphens, up to 39 characters).
+    pub(crate) static ref VALID_USER_NAME: Regex =
+        Regex::new("^[a-zA-Z0-9](-?[a-zA-Z0-9]){0,38}$").expect("expr in VALID_USER_NAME to be valid");
+
+    /// Regular expression to match valid actions allow-list patterns (like
+    /// owner/*, owner/repo@ref or owner/repo/path@ref).
+    static ref VALID_ACTIONS_PATTERN: Regex =
+        Regex::new(r"^[a-zA-Z0-9_.*-]+/[a-zA-Z0-9_./*-]+(@[a-zA-Z0-9_./*-]+)?$")
+            .expect("expr in VALID_ACTIONS_PATTERN to be valid");
 }
 
+/// Maximum number of topics GitHub allows per repository.
+const MAX_TOPICS: usize = 20;
+
+/// Description set on the teams retired by CLOWarden, used to identify them
+/// in future reconciliations.
+pub(crate) const RETIRED_TEAM_DESCRIPTION: &str = "Team retired by CLOWarden";
+
 /// Type alias to represent a repository name.
 pub type RepositoryName = String;
 
 /// Type alias to represent a repository invitation_id.
 pub type RepositoryInvitationId = i64;
 
+/// Type alias to represent a GitHub app slug.
+pub type AppSlug = String;
+
 /// GitHub's service state.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct State {
@@ -59,50 +95,70 @@ impl State {
     pub async fn new_from_config(
         gh: DynGH,
         svc: DynSvc,
-        legacy: &Legacy,
+        org: &Organization,
         ctx: &Ctx,
         src: &Source,
     ) -> Result<State> {
+        State::new_from_config_with(gh, org, src, &ActualStateInfo::Service { svc, ctx }).await
+    }
+
+    /// Create a new State instance from the configuration reference provided,
+    /// using the organization snapshot provided instead of the service to get
+    /// the information needed from the actual state (offline).
+    pub async fn new_from_config_offline(
+        gh: DynGH,
+        org: &Organization,
+        src: &Source,
+        snapshot: &OrgSnapshot,
+    ) -> Result<State> {
+        State::new_from_config_with(gh, org, src, &ActualStateInfo::Snapshot(snapshot)).await
+    }
+
+    /// Create a new State instance from the configuration reference provided,
+    /// getting the information needed from the actual state from the source
+    /// provided.
+    async fn new_from_config_with(
+        gh: DynGH,
+        org: &Organization,
+        src: &Source,
+        actual: &ActualStateInfo<'_>,
+    ) -> Result<State> {
+        let legacy = &org.legacy;
         if legacy.enabled {
             // We need to get some information from the service's actual state
             // to deal with some service's particularities.
-            let org_admins: Vec<UserName> =
-                svc.list_org_admins(ctx).await?.into_iter().map(|a| a.login).collect();
-            let repositories_in_service = svc.list_repositories(ctx).await?;
+            let org_admins = actual.org_admins().await?;
+            let archived_repositories = actual.archived_repositories().await?;
 
             // Helper function to check if a repository has been archived. We
             // cannot add or remove collaborators or teams to an archived repo,
             // so we will just ignore them and no changes will be applied to
             // them while they stay archived.
-            let is_repository_archived = |repo_name: &RepositoryName| {
-                for repo in &repositories_in_service {
-                    if &repo.name == repo_name {
-                        return repo.archived;
-                    }
-                }
-                false
-            };
+            let is_repository_archived =
+                |repo_name: &RepositoryName| archived_repositories.contains(repo_name);
 
             // Prepare directory
             let mut directory = Directory::new_from_config(gh.clone(), legacy, src).await?;
+            directory.validate_teams_size(org)?;
 
             // Team's members that are org admins are considered maintainers by
             // GitHub, so we do the same with the members defined in the config
-            for team in &mut directory.teams {
-                let mut org_admins_members = vec![];
-                for user_name in &team.members.clone() {
-                    if org_admins.contains(user_name) {
-                        org_admins_members.push(user_name.clone());
-                        team.maintainers.push(user_name.clone());
-                    }
-                }
-                team.members.retain(|user_name| !org_admins_members.contains(user_name));
-            }
+            // (unless it has been disabled for the organization)
+            promote_org_admins(org, &mut directory, &org_admins);
+
+            // Service accounts are added to their teams as members once the
+            // directory has been validated and org admins promoted, as they
+            // are not part of the people directory and must never be admins
+            add_service_accounts(org, &mut directory)?;
 
             // Prepare repositories
-            let repositories = legacy::sheriff::Cfg::get(gh, src, &legacy.sheriff_permissions_path)
+            let today = time::OffsetDateTime::now_utc().date();
+            let roles_ordering = org.roles_ordering.clone().unwrap_or_default();
+            let default_team_role = org.default_team_role.clone().unwrap_or_default();
+            let sheriff_cfg = legacy::sheriff::Cfg::get(gh, src, legacy, &default_team_role)
                 .await
-                .context("invalid github service configuration")?
+                .context("invalid github service configuration")?;
+            let repositories: Vec<Repository> = sheriff_cfg
                 .repositories
                 .into_iter()
                 .filter(|r| !is_repository_archived(&r.name))
@@ -112,6 +168,34 @@ impl State {
                         r.visibility = Some(Visibility::default());
                     }
 
+                    // Use organization's default labels setting when none is provided
+                    if r.strip_default_labels.is_none() && org.strip_default_labels {
+                        r.strip_default_labels = Some(true);
+                    }
+
+                    // Use organization's default branch protection when none is provided
+                    if r.branch_protection.is_none() {
+                        r.branch_protection.clone_from(&org.default_branch_protection);
+                    }
+
+                    // Normalize topics the same way GitHub does
+                    if let Some(topics) = r.topics.as_mut() {
+                        normalize_topics(topics);
+                    }
+
+                    // Watchers are only managed when the organization has
+                    // service accounts (they are not available in the CLI)
+                    if org.service_accounts.is_none() {
+                        r.watchers = None;
+                    }
+
+                    // Remove collaborators whose access has expired (before
+                    // flattening teams, so that their team role applies)
+                    r.remove_expired_collaborators(today);
+
+                    // Add flattened teams' members as collaborators
+                    r.flatten_teams(&directory, &roles_ordering);
+
                     // Remove organization admins from collaborators list
                     if let Some(collaborators) = r.collaborators {
                         r.collaborators = Some(
@@ -125,12 +209,14 @@ impl State {
                     r
                 })
                 .collect();
+            validate_watchers(org, &repositories)?;
 
             let state = State {
                 directory,
                 repositories,
             };
-            state.validate(svc, ctx).await?;
+            let forbidden_repository_names = org.forbidden_repository_names.as_deref().unwrap_or_default();
+            state.validate(actual, &roles_ordering, forbidden_repository_names).await?;
 
             return Ok(state);
         }
@@ -139,12 +225,81 @@ impl State {
         ))
     }
 
+    /// Create a new State instance from the legacy Sheriff configuration,
+    /// interpreting it as Sheriff would. The CLOWarden specific processing
+    /// (org admins promotion, flattened teams, collaborators expiration,
+    /// default visibility, etc) is not applied, so that the resulting state
+    /// can be used as a reference to detect where both tools diverge.
+    pub async fn new_from_sheriff_config(
+        gh: DynGH,
+        svc: DynSvc,
+        org: &Organization,
+        ctx: &Ctx,
+        src: &Source,
+    ) -> Result<State> {
+        let legacy = &org.legacy;
+        if !legacy.enabled {
+            return Err(format_err!(
+                "only configuration in legacy format supported at the moment"
+            ));
+        }
+
+        // Archived repositories are ignored by CLOWarden, as no changes can
+        // be applied to them, so we skip them here as well
+        let archived_repositories: HashSet<RepositoryName> = svc
+            .list_repositories(ctx)
+            .await?
+            .into_iter()
+            .filter(|r| r.archived)
+            .map(|r| r.name)
+            .collect();
+
+        // Prepare directory and repositories
+        let directory = Directory::new_from_config(gh.clone(), legacy, src).await?;
+        let default_team_role = org.default_team_role.clone().unwrap_or_default();
+        let sheriff_cfg = legacy::sheriff::Cfg::get(gh, src, legacy, &default_team_role)
+            .await
+            .context("invalid github service configuration")?;
+        let repositories = sheriff_cfg
+            .repositories
+            .into_iter()
+            .filter(|r| !archived_repositories.contains(&r.name))
+            .map(|r| Repository {
+                name: r.name,
+                collaborators: r.collaborators,
+                teams: r.teams,
+                visibility: r.visibility,
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(State {
+            directory,
+            repositories,
+        })
+    }
+
     /// Create a new State instance from the service's actual state.
     pub async fn new_from_service(svc: DynSvc, ctx: &Ctx) -> Result<State> {
+        State::new_from_service_incremental(svc, ctx, None).await
+    }
+
+    /// Create a new State instance from the service's actual state, reusing
+    /// the repositories in the snapshot provided that haven't been updated
+    /// since it was taken.
+    ///
+    /// NOTE: some changes (i.e. collaborators added from GitHub) do not update
+    /// the repository, so they won't be noticed until a full state is built.
+    pub async fn new_from_service_incremental(
+        svc: DynSvc,
+        ctx: &Ctx,
+        snapshot: Option<&RepositoriesSnapshot>,
+    ) -> Result<State> {
         let mut state = State::default();
 
-        // Teams
+        // Teams (the ones retired are not managed anymore)
         for team in stream::iter(svc.list_teams(ctx).await?)
+            .filter(|team| future::ready(team.description != RETIRED_TEAM_DESCRIPTION))
             .map(|team| async {
                 // Get maintainers and members (including pending invitations)
                 let mut maintainers: Vec<UserName> =
@@ -193,8 +348,13 @@ impl State {
         let org_admins: Vec<UserName> =
             svc.list_org_admins(ctx).await?.into_iter().map(|a| a.login).collect();
         for repo in stream::iter(svc.list_repositories(ctx).await?)
-            .filter(|repo| future::ready(!repo.archived && !GHSA_TEMP_FORK.is_match(&repo.name)))
+            .filter(|repo| future::ready(!repo.archived && !is_ghsa_temp_fork(&repo.name)))
             .map(|repo| async {
+                // Reuse repository from the snapshot if it hasn't been updated
+                if let Some(snapshot_repo) = snapshot.and_then(|s| s.get_unmodified_repository(&repo)) {
+                    return Ok(snapshot_repo.clone());
+                }
+
                 // Get collaborators (including pending invitations and excluding org admins)
                 let mut collaborators: BTreeMap<UserName, Role> = svc
                     .list_repository_collaborators(ctx, &repo.name)
@@ -229,12 +389,21 @@ impl State {
                     .collect();
                 let teams = if teams.is_empty() { None } else { Some(teams) };
 
+                // Get topics
+                let mut topics = repo.topics;
+                topics.sort();
+                let topics = if topics.is_empty() { None } else { Some(topics) };
+
                 // Setup repository from info collected
                 Ok(Repository {
                     name: repo.name,
                     collaborators,
                     teams,
                     visibility: Some(repo.visibility.into()),
+                    // Only set for template repositories, as it's disabled by default
+                    is_template: repo.is_template.then_some(true),
+                    topics,
+                    ..Default::default()
                 })
             })
             .buffer_unordered(1)
@@ -255,12 +424,19 @@ impl State {
     /// one provided.
     #[must_use]
     pub fn diff(&self, new: &State) -> Changes {
-        Changes {
+        self.diff_explained(new).into()
+    }
+
+    /// Returns the changes detected between this state instance and the new
+    /// one provided, along with the values compared to compute each of them.
+    #[must_use]
+    pub fn diff_explained(&self, new: &State) -> ExplainedChanges {
+        ExplainedChanges {
             directory: self
                 .directory
-                .diff(&new.directory)
+                .diff_explained(&new.directory)
                 .into_iter()
-                .filter(|change| {
+                .filter(|(change, _)| {
                     // We are not interested in users' changes
                     !matches!(
                         change,
@@ -274,26 +450,412 @@ impl State {
         }
     }
 
-    /// Validate state.
-    async fn validate(&self, svc: DynSvc, ctx: &Ctx) -> Result<()> {
+    /// Returns the net effect on the users' access of moving from this state to
+    /// the new one provided. Only the users whose effective role in any of the
+    /// repositories changes are included, sorted by user name.
+    #[must_use]
+    pub fn users_impact(&self, new: &State, roles_ordering: &RolesOrdering) -> Vec<UserImpact> {
+        let old_roles = self.effective_roles(roles_ordering);
+        let new_roles = new.effective_roles(roles_ordering);
+
+        let user_names: BTreeSet<&UserName> = old_roles.keys().chain(new_roles.keys()).collect();
+        let mut users_impact = vec![];
+        for user_name in user_names {
+            let old_user_roles = old_roles.get(user_name);
+            let new_user_roles = new_roles.get(user_name);
+            let repos_names: BTreeSet<&RepositoryName> =
+                old_user_roles.into_iter().chain(new_user_roles).flat_map(BTreeMap::keys).collect();
+            let mut access_changes = vec![];
+            for repo_name in repos_names {
+                let old_role = old_user_roles.and_then(|roles| roles.get(repo_name));
+                let new_role = new_user_roles.and_then(|roles| roles.get(repo_name));
+                if old_role != new_role {
+                    access_changes.push(AccessChange {
+                        resource: repo_name.clone(),
+                        old_role: old_role.map(ToString::to_string),
+                        new_role: new_role.map(ToString::to_string),
+                    });
+                }
+            }
+            if !access_changes.is_empty() {
+                users_impact.push(UserImpact {
+                    user_name: user_name.clone(),
+                    access_changes,
+                });
+            }
+        }
+        users_impact
+    }
+
+    /// Returns a warning for each change from this (base) state to the new one
+    /// provided that the author of the pull request proposing it may not be
+    /// entitled to make: granting themselves more access (to a team or to a
+    /// repository) or modifying teams they don't maintain in this state.
+    #[must_use]
+    pub fn pr_author_warnings(
+        &self,
+        new: &State,
+        pr_author: &str,
+        roles_ordering: &RolesOrdering,
+    ) -> Vec<String> {
+        let is_author = |user_name: &UserName| user_name.eq_ignore_ascii_case(pr_author);
+        let mut warnings = vec![];
+
+        // Teams changes
+        let mut teams_out_of_scope = BTreeSet::new();
+        for change in self.directory.diff(&new.directory) {
+            match &change {
+                DirectoryChange::TeamAdded(team) => {
+                    if team.maintainers.iter().chain(team.members.iter()).any(is_author) {
+                        warnings.push(format!(
+                            "team[{}]: pull request author {pr_author} adds themselves to the team",
+                            team.name
+                        ));
+                    }
+                    continue;
+                }
+                DirectoryChange::TeamMaintainerAdded(team_name, user_name)
+                | DirectoryChange::TeamMemberAdded(team_name, user_name)
+                    if is_author(user_name) =>
+                {
+                    warnings.push(format!(
+                        "team[{team_name}]: pull request author {pr_author} adds themselves to the team"
+                    ));
+                }
+                _ => {}
+            }
+            if let Some(team) = change.team_name().and_then(|team_name| self.directory.get_team(team_name)) {
+                if !team.maintainers.iter().any(is_author) {
+                    teams_out_of_scope.insert(team.name.clone());
+                }
+            }
+        }
+        for team_name in teams_out_of_scope {
+            warnings.push(format!(
+                "team[{team_name}]: pull request author {pr_author} modifies a team they don't maintain"
+            ));
+        }
+
+        // Repositories access
+        let old_roles = self.effective_roles(roles_ordering);
+        let new_roles = new.effective_roles(roles_ordering);
+        for (user_name, new_user_roles) in new_roles.iter().filter(|(user_name, _)| is_author(user_name)) {
+            for (repo_name, new_role) in new_user_roles {
+                let old_role = old_roles.get(user_name).and_then(|roles| roles.get(repo_name));
+                if old_role.is_none_or(|old_role| roles_ordering.is_higher(new_role, old_role)) {
+                    warnings.push(format!(
+                        "repo[{repo_name}]: pull request author {pr_author} grants themselves {new_role} access"
+                    ));
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns some suggestions to improve the configuration this state was
+    /// built from, using the actual state provided as reference. Suggestions
+    /// are grouped by category (categories without suggestions are omitted).
+    #[must_use]
+    pub fn lint(
+        &self,
+        actual_state: &State,
+        roles_ordering: &RolesOrdering,
+    ) -> BTreeMap<&'static str, Vec<String>> {
+        let mut suggestions: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+        let mut suggest = |category, suggestion| suggestions.entry(category).or_default().push(suggestion);
+
+        // Collaborators that already get the same (or a higher) role from
+        // one of the teams with access to the repository
+        for repo in &self.repositories {
+            for (user_name, user_role) in repo.collaborators.iter().flatten() {
+                for (team_name, team_role) in repo.teams.iter().flatten() {
+                    let Some(team) = self.directory.get_team(team_name) else {
+                        continue;
+                    };
+                    let is_team_member =
+                        team.maintainers.contains(user_name) || team.members.contains(user_name);
+                    if is_team_member && !roles_ordering.is_higher(user_role, team_role) {
+                        suggest(
+                            "Collaborators covered by teams",
+                            format!(
+                                "repo[{}]: collaborator {user_name} can be removed, they already get \
+                                {team_role} access from team {team_name}",
+                                repo.name
+                            ),
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Teams without access to any repository
+        let teams_in_use: HashSet<&TeamName> = self
+            .repositories
+            .iter()
+            .flat_map(|repo| repo.teams.iter().chain(repo.flattened_teams.iter()))
+            .flat_map(BTreeMap::keys)
+            .collect();
+        for team in &self.directory.teams {
+            if !teams_in_use.contains(&team.name) {
+                suggest(
+                    "Teams without repositories",
+                    format!("team[{}]: not used in any repository", team.name),
+                );
+            }
+        }
+
+        // Users in the directory that do not belong to any team
+        let users_in_teams: HashSet<&UserName> = self
+            .directory
+            .teams
+            .iter()
+            .flat_map(|team| team.maintainers.iter().chain(team.members.iter()))
+            .collect();
+        for user in &self.directory.users {
+            if let Some(user_name) = &user.user_name {
+                if !users_in_teams.contains(user_name) {
+                    suggest(
+                        "Users not in any team",
+                        format!(
+                            "user[{}]: {user_name} does not belong to any team",
+                            user.full_name
+                        ),
+                    );
+                }
+            }
+        }
+
+        // Repositories in the service not defined in the configuration
+        let repos_in_cfg: HashSet<&RepositoryName> = self.repositories.iter().map(|r| &r.name).collect();
+        for repo in &actual_state.repositories {
+            if !repos_in_cfg.contains(&repo.name) {
+                suggest(
+                    "Unmanaged repositories",
+                    format!("repo[{}]: not defined in the configuration", repo.name),
+                );
+            }
+        }
+
+        suggestions
+    }
+
+    /// Get from the service the opt-in settings of the repositories and teams
+    /// that define them in the desired state provided. These settings require
+    /// extra requests per repository or team, so they are only fetched when
+    /// needed. This is the sequence of fetches that must be run on the actual
+    /// state before diffing it against the desired state.
+    pub async fn fetch_opt_in_settings(
+        &mut self,
+        svc: DynSvc,
+        ctx: &Ctx,
+        org: &Organization,
+        desired_state: &State,
+    ) -> Result<()> {
+        let repos = &desired_state.repositories;
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.repositories,
+            repos,
+            |r| &r.name,
+            |r, _| r.discussions.is_some(),
+            |svc, repo_name| async move {
+                svc.get_repository_discussions(ctx, &repo_name).await.context(format!(
+                    "error getting repository {repo_name} discussions settings"
+                ))
+            },
+            |repo, discussions| repo.discussions = Some(discussions),
+        )
+        .await?;
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.repositories,
+            repos,
+            |r| &r.name,
+            |r, _| !r.merge_commit_settings().is_empty(),
+            |svc, repo_name| async move {
+                svc.get_repository_merge_commit_settings(ctx, &repo_name).await.context(format!(
+                    "error getting repository {repo_name} merge commit settings"
+                ))
+            },
+            |repo, settings| {
+                repo.squash_merge_commit_title = settings.squash_merge_commit_title;
+                repo.squash_merge_commit_message = settings.squash_merge_commit_message;
+                repo.allow_merge_commit = settings.allow_merge_commit;
+                repo.merge_commit_title = settings.merge_commit_title;
+                repo.merge_commit_message = settings.merge_commit_message;
+                repo.delete_branch_on_merge = settings.delete_branch_on_merge;
+                repo.allow_update_branch = settings.allow_update_branch;
+                repo.web_commit_signoff_required = settings.web_commit_signoff_required;
+            },
+        )
+        .await?;
+        // Repositories where the default setup is not supported are left
+        // without it, so that they are skipped
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.repositories,
+            repos,
+            |r| &r.name,
+            |r, _| r.code_scanning.is_some(),
+            |svc, repo_name| async move {
+                svc.get_repository_code_scanning_setup(ctx, &repo_name).await.context(format!(
+                    "error getting repository {repo_name} code scanning setup"
+                ))
+            },
+            |repo, code_scanning| repo.code_scanning = code_scanning,
+        )
+        .await?;
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.repositories,
+            repos,
+            |r| &r.name,
+            |r, _| r.actions_allow_list.is_some(),
+            |svc, repo_name| async move {
+                svc.get_repository_actions_allow_list(ctx, &repo_name)
+                    .await
+                    .context(format!("error getting repository {repo_name} actions allow-list"))
+            },
+            |repo, allow_list| repo.actions_allow_list = Some(allow_list),
+        )
+        .await?;
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.directory.teams,
+            &desired_state.directory.teams,
+            |t| &t.name,
+            |t, _| t.notifications.is_some(),
+            |svc, team_name| async move {
+                svc.get_team_notifications(ctx, &team_name)
+                    .await
+                    .context(format!("error getting team {team_name} notifications"))
+            },
+            |team, notifications| team.notifications = Some(notifications),
+        )
+        .await?;
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.repositories,
+            repos,
+            |r| &r.name,
+            |r, repo| {
+                r.private_vulnerability_reporting.is_some() && repo.visibility == Some(Visibility::Public)
+            },
+            |svc, repo_name| async move {
+                svc.get_repository_private_vulnerability_reporting(ctx, &repo_name).await.context(format!(
+                    "error getting repository {repo_name} private vulnerability reporting"
+                ))
+            },
+            |repo, enabled| repo.private_vulnerability_reporting = Some(enabled),
+        )
+        .await?;
+        // The forking policy is only meaningful for non public repositories
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.repositories,
+            repos,
+            |r| &r.name,
+            |r, repo| r.allow_forking.is_some() && repo.visibility != Some(Visibility::Public),
+            |svc, repo_name| async move {
+                svc.get_repository_allow_forking(ctx, &repo_name)
+                    .await
+                    .context(format!("error getting repository {repo_name} forking policy"))
+            },
+            |repo, allow_forking| repo.allow_forking = Some(allow_forking),
+        )
+        .await?;
+        // Only the organization's service accounts watching the repositories
+        // are taken into account
+        let service_accounts: &Vec<&UserName> = &org
+            .service_accounts
+            .iter()
+            .flatten()
+            .map(|service_account| &service_account.login)
+            .collect();
+        fetch_opt_in_setting(
+            &svc,
+            &mut self.repositories,
+            repos,
+            |r| &r.name,
+            |r, _| r.watchers.is_some(),
+            |svc, repo_name| async move {
+                let watchers = svc
+                    .list_repository_watchers(ctx, &repo_name)
+                    .await
+                    .context(format!("error listing repository {repo_name} watchers"))?;
+                Ok(watchers.into_iter().filter(|user_name| service_accounts.contains(&user_name)).collect())
+            },
+            |repo, watchers| repo.watchers = Some(watchers),
+        )
+        .await?;
+        self.fetch_installed_apps(svc, ctx, desired_state).await
+    }
+
+    /// Get from the service the apps installed in the repositories that
+    /// define allowed or denied apps in the desired state provided. Listing
+    /// the apps installations requires some extra permissions, so they are
+    /// only fetched when needed.
+    pub async fn fetch_installed_apps(
+        &mut self,
+        svc: DynSvc,
+        ctx: &Ctx,
+        desired_state: &State,
+    ) -> Result<()> {
+        let defined = |repo_name: &RepositoryName| {
+            desired_state
+                .repositories
+                .iter()
+                .any(|r| r.name == *repo_name && (r.allowed_apps.is_some() || r.denied_apps.is_some()))
+        };
+        if !self.repositories.iter().any(|r| defined(&r.name)) {
+            return Ok(());
+        }
+        let apps_installations =
+            svc.list_apps_installations(ctx).await.context("error listing apps installations")?;
+        for repo in &mut self.repositories {
+            if !defined(&repo.name) {
+                continue;
+            }
+            let mut installed_apps: Vec<AppSlug> = apps_installations
+                .iter()
+                .filter(|i| i.repositories.as_ref().is_none_or(|repos| repos.contains(&repo.name)))
+                .map(|i| i.app_slug.clone())
+                .collect();
+            installed_apps.sort();
+            repo.installed_apps = Some(installed_apps);
+        }
+        Ok(())
+    }
+
+    /// Validate state. The roles ordering provided is used to check if roles
+    /// are being downgraded. New repositories (not present yet in the service)
+    /// cannot have a name matching any of the forbidden names patterns.
+    async fn validate(
+        &self,
+        actual: &ActualStateInfo<'_>,
+        roles_ordering: &RolesOrdering,
+        forbidden_repository_names: &[String],
+    ) -> Result<()> {
         let mut merr = MultiError::new(Some("invalid github service configuration".to_string()));
 
         // Helper closure to get the highest role from a team membership for a
-        // given user in the repository provided
-        let get_highest_team_role = |repo: &Repository, user_name: &UserName| {
-            let mut highest_team_role = None;
-            if let Some(teams) = &repo.teams {
-                for (team_name, role) in teams {
-                    if let Some(team) = self.directory.get_team(team_name) {
-                        if team.maintainers.contains(user_name) || team.members.contains(user_name) {
-                            if highest_team_role.is_none() {
-                                highest_team_role = Some((team_name.clone(), role.clone()));
-                            } else {
-                                let highest_role = highest_team_role.as_ref().unwrap().1.clone();
-                                if role > &highest_role {
-                                    highest_team_role = Some((team_name.clone(), role.clone()));
-                                }
-                            }
+        // given user in the repository provided that is higher than the role
+        // provided (if any)
+        let get_higher_team_role = |repo: &Repository, user_name: &UserName, user_role: &Role| {
+            let mut highest_team_role: Option<(TeamName, Role)> = None;
+            for (team_name, role) in repo.teams.iter().chain(repo.flattened_teams.iter()).flatten() {
+                if !roles_ordering.is_higher(role, user_role) {
+                    continue;
+                }
+                if let Some(team) = self.directory.get_team(team_name) {
+                    if team.maintainers.contains(user_name) || team.members.contains(user_name) {
+                        let is_highest = highest_team_role
+                            .as_ref()
+                            .is_none_or(|(_, highest_role)| roles_ordering.is_higher(role, highest_role));
+                        if is_highest {
+                            highest_team_role = Some((team_name.clone(), role.clone()));
                         }
                     }
                 }
@@ -302,8 +864,7 @@ impl State {
         };
 
         // Check teams' maintainers are members of the organization
-        let org_members: Vec<UserName> =
-            svc.list_org_members(ctx).await?.into_iter().map(|m| m.login).collect();
+        let org_members = actual.org_members().await?;
         for team in &self.directory.teams {
             for user_name in &team.maintainers {
                 if !org_members.contains(user_name) {
@@ -315,6 +876,46 @@ impl State {
             }
         }
 
+        // Prepare forbidden repository names patterns (only new repositories
+        // are checked, so we also need the ones already in the service)
+        let mut forbidden_names_patterns = vec![];
+        for pattern in forbidden_repository_names {
+            match Regex::new(pattern) {
+                Ok(re) => forbidden_names_patterns.push(re),
+                Err(err) => {
+                    merr.push(format_err!(
+                        "invalid forbidden repository name pattern {pattern}: {err}"
+                    ));
+                }
+            }
+        }
+        let uses_templates = self
+            .repositories
+            .iter()
+            .any(|r| r.gitignore_template.is_some() || r.license_template.is_some());
+        let repositories_in_service: Vec<RepositoryName> =
+            if forbidden_names_patterns.is_empty() && !uses_templates {
+                vec![]
+            } else {
+                actual.repositories().await?
+            };
+
+        // Prepare gitignore and license templates available in GitHub (they
+        // are only used when creating repositories, so only the new ones are
+        // checked)
+        let new_repositories: Vec<&Repository> =
+            self.repositories.iter().filter(|r| !repositories_in_service.contains(&r.name)).collect();
+        let gitignore_templates = if new_repositories.iter().any(|r| r.gitignore_template.is_some()) {
+            actual.gitignore_templates().await?
+        } else {
+            vec![]
+        };
+        let license_templates = if new_repositories.iter().any(|r| r.license_template.is_some()) {
+            actual.license_templates().await?
+        } else {
+            vec![]
+        };
+
         for (i, repo) in self.repositories.iter().enumerate() {
             // Define id to be used in subsequent error messages. When
             // available, it'll be the repo name. Otherwise we'll use its
@@ -325,117 +926,504 @@ impl State {
                 repo.name.clone()
             };
 
-            // Check teams used in repositories exist in directory
-            let teams_in_directory: Vec<&TeamName> = self.directory.teams.iter().map(|t| &t.name).collect();
-            if let Some(teams) = &repo.teams {
-                for team_name in teams.keys() {
-                    if !teams_in_directory.contains(&team_name) {
+            // Check new repositories names do not match any forbidden pattern
+            let is_new = !repositories_in_service.contains(&repo.name);
+            if is_new {
+                for re in &forbidden_names_patterns {
+                    if re.is_match(&repo.name) {
                         merr.push(format_err!(
-                            "repo[{id}]: team {team_name} does not exist in directory"
+                            "repo[{id}]: name matches forbidden pattern {}",
+                            re.as_str()
                         ));
                     }
                 }
             }
 
-            // Check explicitly defined collaborators haven't been assigned a
-            // role with less privileges than the ones they'd have from any of
-            // the teams they are members of
-            if let Some(collaborators) = &repo.collaborators {
-                for (user_name, user_role) in collaborators {
-                    let highest_team_role = get_highest_team_role(repo, user_name);
-                    if let Some((team_name, highest_team_role)) = highest_team_role {
-                        if &highest_team_role > user_role {
-                            merr.push(format_err!(
-                                "repo[{id}]: collaborator {user_name} already has {highest_team_role} \
-                                access from team {team_name}"
-                            ));
-                        }
+            // Check apps are not allowed and denied at the same time
+            if let (Some(allowed_apps), Some(denied_apps)) = (&repo.allowed_apps, &repo.denied_apps) {
+                for app_slug in allowed_apps {
+                    if denied_apps.contains(app_slug) {
+                        merr.push(format_err!(
+                            "repo[{id}]: app {app_slug} cannot be both allowed and denied"
+                        ));
                     }
                 }
             }
-        }
 
-        if merr.contains_errors() {
-            return Err(merr.into());
-        }
-        Ok(())
-    }
+            // Check topics are valid (once normalized) and within GitHub limits
+            if let Some(topics) = &repo.topics {
+                for topic in topics {
+                    if !VALID_TOPIC.is_match(topic) {
+                        merr.push(format_err!(
+                            "repo[{id}]: invalid topic {topic} (only lowercase letters, numbers and hyphens are allowed, up to 50 characters)"
+                        ));
+                    }
+                }
+                if topics.len() > MAX_TOPICS {
+                    merr.push(format_err!(
+                        "repo[{id}]: too many topics (maximum allowed is {MAX_TOPICS})"
+                    ));
+                }
+            }
 
-    /// Returns the changes detected between two lists of repositories.
-    #[allow(clippy::too_many_lines)]
-    fn repositories_diff(old: &[Repository], new: &[Repository]) -> Vec<RepositoryChange> {
-        let mut changes = vec![];
+            // Check actions allow-list patterns are valid
+            if let Some(allow_list) = &repo.actions_allow_list {
+                for pattern in &allow_list.patterns_allowed {
+                    if !VALID_ACTIONS_PATTERN.is_match(pattern) {
+                        merr.push(format_err!(
+                            "repo[{id}]: invalid actions allow-list pattern {pattern} (expected owner/repo@ref, owner/repo/path@ref or owner/*)"
+                        ));
+                    }
+                }
+            }
 
-        // Repositories
-        let repos_old: BTreeMap<&RepositoryName, &Repository> = old.iter().map(|r| (&r.name, r)).collect();
-        let repos_new: BTreeMap<&RepositoryName, &Repository> = new.iter().map(|r| (&r.name, r)).collect();
+            // Check CODEOWNERS file managed has some owners
+            if repo.codeowners.is_some() && codeowners_owners(repo, "").is_empty() {
+                merr.push(format_err!(
+                    "repo[{id}]: codeowners: at least one team with write access to the repository is required"
+                ));
+            }
 
-        // Helper closures to get the team's/collaborator's role
-        let team_role = |collection: &BTreeMap<&RepositoryName, &Repository>,
-                         repo_name: &RepositoryName,
-                         team_name: &TeamName| {
-            if let Some(teams) = collection[repo_name].teams.as_ref() {
-                return teams.get(&team_name.to_string()).cloned().unwrap_or_default();
+            // Check forking policy is only set for private or internal repositories
+            let is_public = repo.visibility.clone().unwrap_or_default() == Visibility::Public;
+            if repo.allow_forking.is_some() && is_public {
+                merr.push(format_err!(
+                    "repo[{id}]: allow_forking can only be set for private or internal repositories"
+                ));
             }
-            Role::default()
-        };
-        let user_role = |collection: &BTreeMap<&RepositoryName, &Repository>,
-                         repo_name: &RepositoryName,
-                         user_name: &UserName| {
-            if let Some(collaborators) = collection[repo_name].collaborators.as_ref() {
-                return collaborators.get(&user_name.to_string()).cloned().unwrap_or_default();
+
+            // Check private vulnerability reporting is only set for public repositories
+            if repo.private_vulnerability_reporting.is_some() && !is_public {
+                merr.push(format_err!(
+                    "repo[{id}]: private_vulnerability_reporting can only be set for public repositories"
+                ));
             }
-            Role::default()
-        };
 
-        // Repositories added
-        let repos_names_old: HashSet<&RepositoryName> = repos_old.keys().copied().collect();
-        let repos_names_new: HashSet<&RepositoryName> = repos_new.keys().copied().collect();
-        for repo_name in repos_names_new.difference(&repos_names_old) {
-            changes.push(RepositoryChange::RepositoryAdded(repos_new[*repo_name].clone()));
-        }
+            // Check collaborators expiration dates are valid
+            if let Some(collaborators_expiration) = &repo.collaborators_expiration {
+                for (user_name, expires_at) in collaborators_expiration {
+                    if let Err(err) = parse_expiration_date(expires_at) {
+                        merr.push(format_err!("repo[{id}]: collaborator[{user_name}]: {err}"));
+                    }
+                    if !repo.collaborators.as_ref().is_some_and(|c| c.contains_key(user_name)) {
+                        merr.push(format_err!(
+                            "repo[{id}]: collaborator[{user_name}]: expiration set for a user not listed as collaborator"
+                        ));
+                    }
+                }
+            }
 
-        // Repositories teams and collaborators added/removed
-        for repo_name in repos_new.keys() {
-            if !repos_names_old.contains(repo_name) {
-                // New repo, no need to track additional changes on it
-                continue;
+            // Check discussions categories are valid
+            if let Some(discussions) = &repo.discussions {
+                if let Some(categories) = &discussions.categories {
+                    if !discussions.enabled && !categories.is_empty() {
+                        merr.push(format_err!(
+                            "repo[{id}]: discussions categories can only be set when discussions are enabled"
+                        ));
+                    }
+                    let mut categories_seen = HashSet::new();
+                    for category in categories {
+                        if category.trim().is_empty() {
+                            merr.push(format_err!(
+                                "repo[{id}]: discussions category name cannot be empty"
+                            ));
+                        } else if !categories_seen.insert(category.to_lowercase()) {
+                            merr.push(format_err!(
+                                "repo[{id}]: duplicate discussions category {category}"
+                            ));
+                        }
+                    }
+                }
             }
 
-            // Teams
-            let mut teams_old = HashSet::new();
-            if let Some(teams) = &repos_old[repo_name].teams {
-                teams_old = teams.iter().map(|(name, _)| name).collect();
+            // Check branch protection required approving reviews are within
+            // the range supported by GitHub
+            let branch_protection = repo.branch_protection.as_ref();
+            if branch_protection.and_then(|p| p.required_approving_review_count).is_some_and(|c| c > 6) {
+                merr.push(format_err!(
+                    "repo[{id}]: branch protection required approving review count must be between 0 and 6"
+                ));
             }
-            let mut teams_new = HashSet::new();
-            if let Some(teams) = &repos_new[repo_name].teams {
-                teams_new = teams.iter().map(|(name, _)| name).collect();
+
+            // Check squash merge commit settings are a combination supported
+            // by GitHub (title and message must be set together)
+            match (repo.squash_merge_commit_title, repo.squash_merge_commit_message) {
+                (None, None)
+                | (Some(SquashMergeCommitTitle::PrTitle), Some(SquashMergeCommitMessage::PrBody))
+                | (Some(SquashMergeCommitTitle::PrTitle), Some(SquashMergeCommitMessage::Blank))
+                | (
+                    Some(SquashMergeCommitTitle::CommitOrPrTitle),
+                    Some(SquashMergeCommitMessage::CommitMessages),
+                ) => {}
+                (Some(_), None) | (None, Some(_)) => merr.push(format_err!(
+                    "repo[{id}]: squash_merge_commit_title and squash_merge_commit_message must be set together"
+                )),
+                (Some(title), Some(message)) => merr.push(format_err!(
+                    "repo[{id}]: squash merge commit message {message} cannot be used with title {title}"
+                )),
             }
-            for team_name in teams_old.difference(&teams_new) {
-                changes.push(RepositoryChange::TeamRemoved(
-                    (*repo_name).to_string(),
-                    (*team_name).to_string(),
-                ));
+
+            // Check merge commit settings are a combination supported by
+            // GitHub and that they are only set when merge commits are allowed
+            match (repo.merge_commit_title, repo.merge_commit_message) {
+                (None, None)
+                | (Some(MergeCommitTitle::PrTitle), Some(MergeCommitMessage::PrBody))
+                | (Some(MergeCommitTitle::PrTitle), Some(MergeCommitMessage::Blank))
+                | (Some(MergeCommitTitle::MergeMessage), Some(MergeCommitMessage::PrTitle)) => {}
+                (Some(_), None) | (None, Some(_)) => merr.push(format_err!(
+                    "repo[{id}]: merge_commit_title and merge_commit_message must be set together"
+                )),
+                (Some(title), Some(message)) => merr.push(format_err!(
+                    "repo[{id}]: merge commit message {message} cannot be used with title {title}"
+                )),
             }
-            for team_name in teams_new.difference(&teams_old) {
-                changes.push(RepositoryChange::TeamAdded(
-                    (*repo_name).to_string(),
-                    (*team_name).to_string(),
-                    team_role(&repos_new, repo_name, team_name),
+            let merge_commit_settings_set =
+                repo.merge_commit_title.is_some() || repo.merge_commit_message.is_some();
+            if merge_commit_settings_set && repo.allow_merge_commit == Some(false) {
+                merr.push(format_err!(
+                    "repo[{id}]: merge_commit_title and merge_commit_message can only be set when merge commits are allowed"
                 ));
             }
-            for team_name in &teams_new {
-                if !teams_old.contains(team_name) {
-                    // New team, no need to track additional changes on it
-                    continue;
-                }
+
+            // Check new repositories gitignore and license templates are
+            // available in GitHub
+            if let Some(gitignore_template) = repo.gitignore_template.as_ref().filter(|_| is_new) {
+                if !gitignore_templates.contains(gitignore_template) {
+                    merr.push(format_err!(
+                        "repo[{id}]: gitignore template {gitignore_template} is not available"
+                    ));
+                }
+            }
+            if let Some(license_template) = repo.license_template.as_ref().filter(|_| is_new) {
+                if !license_templates.contains(license_template) {
+                    merr.push(format_err!(
+                        "repo[{id}]: license template {license_template} is not available"
+                    ));
+                }
+            }
+
+            // Check teams used in repositories exist in directory
+            let teams_in_directory: Vec<&TeamName> = self.directory.teams.iter().map(|t| &t.name).collect();
+            for team_name in repo.teams.iter().chain(repo.flattened_teams.iter()).flat_map(BTreeMap::keys) {
+                if !teams_in_directory.contains(&team_name) {
+                    merr.push(format_err!(
+                        "repo[{id}]: team {team_name} does not exist in directory"
+                    ));
+                }
+            }
+
+            // Check explicitly defined collaborators haven't been assigned a
+            // role with less privileges than the ones they'd have from any of
+            // the teams they are members of (this includes the overrides of
+            // the members of flattened teams)
+            if let Some(collaborators) = &repo.collaborators {
+                for (user_name, user_role) in collaborators {
+                    if let Some((team_name, team_role)) = get_higher_team_role(repo, user_name, user_role) {
+                        merr.push(format_err!(
+                            "repo[{id}]: collaborator {user_name} already has {team_role} \
+                            access from team {team_name}"
+                        ));
+                    }
+                }
+            }
+        }
+
+        if merr.contains_errors() {
+            return Err(merr.into());
+        }
+        Ok(())
+    }
+
+    /// Returns some warnings about the state. Warnings do not make the state
+    /// invalid, but point to some issues that may need some attention.
+    pub async fn warnings(&self, svc: DynSvc, ctx: &Ctx) -> Result<Vec<String>> {
+        let secret_teams: HashSet<TeamName> = list_secret_teams(svc, ctx).await?.into_iter().collect();
+        Ok(State::secret_teams_warnings(&self.repositories, &secret_teams))
+    }
+
+    /// Returns some warnings about the state, using the organization snapshot
+    /// provided instead of the service (offline).
+    #[must_use]
+    pub fn warnings_offline(&self, snapshot: &OrgSnapshot) -> Vec<String> {
+        let secret_teams: HashSet<TeamName> = snapshot.secret_teams.iter().cloned().collect();
+        State::secret_teams_warnings(&self.repositories, &secret_teams)
+    }
+
+    /// Returns a warning for each public repository where the maintainers
+    /// access relies solely on secret teams. Secret teams are only visible to
+    /// their members, so it won't be clear to everyone who maintains them.
+    fn secret_teams_warnings(repositories: &[Repository], secret_teams: &HashSet<TeamName>) -> Vec<String> {
+        let mut warnings = vec![];
+        for repo in repositories {
+            if repo.visibility.clone().unwrap_or_default() != Visibility::Public {
+                continue;
+            }
+
+            // Members of flattened teams are added as collaborators, so only
+            // the teams explicitly assigned to the repository are considered
+            let maintainers_teams: Vec<&str> = repo
+                .teams
+                .iter()
+                .flatten()
+                .filter(|(_, role)| **role >= Role::Maintain)
+                .map(|(team_name, _)| team_name.as_str())
+                .collect();
+            let maintainers_collaborators_found =
+                repo.collaborators.iter().flatten().any(|(_, role)| *role >= Role::Maintain);
+            if maintainers_teams.is_empty() || maintainers_collaborators_found {
+                continue;
+            }
+
+            if maintainers_teams.iter().all(|team_name| secret_teams.contains(*team_name)) {
+                warnings.push(format!(
+                    "repo[{}]: maintainers access relies solely on secret teams ({})",
+                    repo.name,
+                    maintainers_teams.join(", ")
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Returns a warning for each owner referenced in the CODEOWNERS file of
+    /// the repositories that is not in the directory or that does not have
+    /// write access to the repository (GitHub ignores these owners). This
+    /// requires fetching the CODEOWNERS file of each repository.
+    pub async fn codeowners_warnings(&self, svc: DynSvc, ctx: &Ctx) -> Result<Vec<String>> {
+        let mut warnings = vec![];
+        for repo in &self.repositories {
+            let codeowners = svc
+                .get_repository_codeowners(ctx, &repo.name)
+                .await
+                .context(format!("error getting repository {} CODEOWNERS file", &repo.name))?;
+            if let Some(codeowners) = codeowners {
+                warnings.extend(self.repository_codeowners_warnings(repo, &ctx.org, &codeowners));
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Returns a warning for each dangling owner found in the CODEOWNERS file
+    /// content of the repository provided. Owners identified by their email
+    /// address cannot be checked, so they are ignored.
+    fn repository_codeowners_warnings(&self, repo: &Repository, org: &str, codeowners: &str) -> Vec<String> {
+        let mut warnings = vec![];
+        for (i, line) in codeowners.lines().enumerate() {
+            // The first entry in each line is the files pattern
+            let line = line.split('#').next().unwrap_or_default();
+            for owner in line.split_whitespace().skip(1) {
+                let Some(owner_name) = owner.strip_prefix('@') else {
+                    continue;
+                };
+                let problem = if let Some((owner_org, team_name)) = owner_name.split_once('/') {
+                    let team_name = team_name.to_lowercase();
+                    let team_access =
+                        repo.teams.iter().flatten().any(|(t, role)| *t == team_name && *role >= Role::Write);
+                    if !owner_org.eq_ignore_ascii_case(org) {
+                        Some("team belongs to a different organization")
+                    } else if self.directory.get_team(&team_name).is_none() {
+                        Some("team not found in the directory")
+                    } else if !team_access {
+                        Some("team does not have write access to the repository")
+                    } else {
+                        None
+                    }
+                } else if !self.user_has_write_access(repo, owner_name) {
+                    Some("user does not have write access to the repository")
+                } else {
+                    None
+                };
+                if let Some(problem) = problem {
+                    warnings.push(format!(
+                        "repo[{}]: CODEOWNERS line {}: owner {owner}: {problem}",
+                        repo.name,
+                        i + 1
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Check if the user provided has write access to the repository, either
+    /// as a collaborator or as a member of one of the repository's teams.
+    fn user_has_write_access(&self, repo: &Repository, user_name: &str) -> bool {
+        let is_user = |name: &UserName| name.eq_ignore_ascii_case(user_name);
+        let collaborator_access = repo
+            .collaborators
+            .iter()
+            .flatten()
+            .any(|(name, role)| is_user(name) && *role >= Role::Write);
+        let team_access = repo.teams.iter().flatten().any(|(team_name, role)| {
+            *role >= Role::Write
+                && self
+                    .directory
+                    .get_team(team_name)
+                    .is_some_and(|team| team.maintainers.iter().chain(team.members.iter()).any(is_user))
+        });
+        collaborator_access || team_access
+    }
+
+    /// Returns a warning for each branch already merged into the default branch
+    /// of its repository that hasn't been updated in the number of days
+    /// provided. Branches are only reported, never deleted. This requires an
+    /// extra request per branch, so it should only be used when requested.
+    pub async fn stale_branches_warnings(
+        &self,
+        svc: DynSvc,
+        ctx: &Ctx,
+        max_age_days: u32,
+    ) -> Result<Vec<String>> {
+        let cutoff = time::OffsetDateTime::now_utc() - Duration::days(i64::from(max_age_days));
+        let mut warnings = vec![];
+        for repo in &self.repositories {
+            let merged_branches = svc
+                .list_repository_merged_branches(ctx, &repo.name)
+                .await
+                .context(format!("error listing repository {} merged branches", &repo.name))?;
+            for branch in merged_branches {
+                if branch.last_commit_at < cutoff {
+                    warnings.push(format!(
+                        "repo[{}]: branch {} was merged and has not been updated since {}",
+                        repo.name,
+                        branch.name,
+                        branch.last_commit_at.date()
+                    ));
+                }
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Returns the effective role of each user in each of the repositories,
+    /// considering both the collaborators and the teams with access to them.
+    /// When a user gets access from multiple sources, the highest role
+    /// (according to the roles ordering provided) is used.
+    pub(crate) fn effective_roles(
+        &self,
+        roles_ordering: &RolesOrdering,
+    ) -> BTreeMap<UserName, BTreeMap<RepositoryName, Role>> {
+        let mut effective_roles: BTreeMap<UserName, BTreeMap<RepositoryName, Role>> = BTreeMap::new();
+        for repo in &self.repositories {
+            let mut grant = |user_name: &UserName, role: &Role| {
+                let repos_roles = effective_roles.entry(user_name.clone()).or_default();
+                let user_role = repos_roles.entry(repo.name.clone()).or_insert_with(|| role.clone());
+                if roles_ordering.is_higher(role, user_role) {
+                    *user_role = role.clone();
+                }
+            };
+            for (user_name, role) in repo.collaborators.iter().flatten() {
+                grant(user_name, role);
+            }
+            for (team_name, role) in repo.teams.iter().chain(repo.flattened_teams.iter()).flatten() {
+                if let Some(team) = self.directory.get_team(team_name) {
+                    for user_name in team.maintainers.iter().chain(team.members.iter()) {
+                        grant(user_name, role);
+                    }
+                }
+            }
+        }
+        effective_roles
+    }
+
+    /// Returns the changes detected between two lists of repositories, along
+    /// with the values compared to compute them.
+    #[allow(clippy::too_many_lines)]
+    fn repositories_diff(
+        old: &[Repository],
+        new: &[Repository],
+    ) -> Vec<(RepositoryChange, ChangeExplanation)> {
+        let mut changes = vec![];
+        let today = time::OffsetDateTime::now_utc().date();
+
+        // Repositories
+        let repos_old: BTreeMap<&RepositoryName, &Repository> = old.iter().map(|r| (&r.name, r)).collect();
+        let repos_new: BTreeMap<&RepositoryName, &Repository> = new.iter().map(|r| (&r.name, r)).collect();
+
+        // Helper closures to get the team's/collaborator's role
+        let team_role = |collection: &BTreeMap<&RepositoryName, &Repository>,
+                         repo_name: &RepositoryName,
+                         team_name: &TeamName| {
+            if let Some(teams) = collection[repo_name].teams.as_ref() {
+                return teams.get(&team_name.to_string()).cloned().unwrap_or_default();
+            }
+            Role::default()
+        };
+        let user_role = |collection: &BTreeMap<&RepositoryName, &Repository>,
+                         repo_name: &RepositoryName,
+                         user_name: &UserName| {
+            if let Some(collaborators) = collection[repo_name].collaborators.as_ref() {
+                return collaborators.get(&user_name.to_string()).cloned().unwrap_or_default();
+            }
+            Role::default()
+        };
+
+        // Repositories added
+        let repos_names_old: HashSet<&RepositoryName> = repos_old.keys().copied().collect();
+        let repos_names_new: HashSet<&RepositoryName> = repos_new.keys().copied().collect();
+        for repo_name in repos_names_new.difference(&repos_names_old) {
+            changes.push((
+                RepositoryChange::RepositoryAdded(repos_new[*repo_name].clone()),
+                ChangeExplanation::added("repository", repo_name),
+            ));
+
+            // Watchers can only be added once the repository exists
+            for user_name in repos_new[*repo_name].watchers.iter().flatten() {
+                changes.push((
+                    RepositoryChange::WatcherAdded((*repo_name).to_string(), user_name.clone()),
+                    ChangeExplanation::added("watcher", user_name),
+                ));
+            }
+        }
+
+        // Repositories teams and collaborators added/removed
+        for repo_name in repos_new.keys() {
+            if !repos_names_old.contains(repo_name) {
+                // New repo, no need to track additional changes on it
+                continue;
+            }
+
+            // Teams
+            let mut teams_old = HashSet::new();
+            if let Some(teams) = &repos_old[repo_name].teams {
+                teams_old = teams.iter().map(|(name, _)| name).collect();
+            }
+            let mut teams_new = HashSet::new();
+            if let Some(teams) = &repos_new[repo_name].teams {
+                teams_new = teams.iter().map(|(name, _)| name).collect();
+            }
+            for team_name in teams_old.difference(&teams_new) {
+                changes.push((
+                    RepositoryChange::TeamRemoved((*repo_name).to_string(), (*team_name).to_string()),
+                    ChangeExplanation::removed("team role", team_role(&repos_old, repo_name, team_name)),
+                ));
+            }
+            for team_name in teams_new.difference(&teams_old) {
                 let role_new = team_role(&repos_new, repo_name, team_name);
-                let role_old = team_role(&repos_old, repo_name, team_name);
-                if role_new != role_old {
-                    changes.push(RepositoryChange::TeamRoleUpdated(
+                changes.push((
+                    RepositoryChange::TeamAdded(
                         (*repo_name).to_string(),
                         (*team_name).to_string(),
-                        role_new,
+                        role_new.clone(),
+                    ),
+                    ChangeExplanation::added("team role", role_new),
+                ));
+            }
+            for team_name in &teams_new {
+                if !teams_old.contains(team_name) {
+                    // New team, no need to track additional changes on it
+                    continue;
+                }
+                let role_new = team_role(&repos_new, repo_name, team_name);
+                let role_old = team_role(&repos_old, repo_name, team_name);
+                if role_new != role_old {
+                    changes.push((
+                        RepositoryChange::TeamRoleUpdated(
+                            (*repo_name).to_string(),
+                            (*team_name).to_string(),
+                            role_new.clone(),
+                        ),
+                        ChangeExplanation::updated("team role", role_old, role_new),
                     ));
                 }
             }
@@ -450,16 +1438,23 @@ impl State {
                 collaborators_new = collaborators.iter().map(|(name, _)| name).collect();
             }
             for user_name in collaborators_old.difference(&collaborators_new) {
-                changes.push(RepositoryChange::CollaboratorRemoved(
-                    (*repo_name).to_string(),
-                    (*user_name).to_string(),
+                changes.push((
+                    RepositoryChange::CollaboratorRemoved((*repo_name).to_string(), (*user_name).to_string()),
+                    ChangeExplanation::removed(
+                        "collaborator role",
+                        user_role(&repos_old, repo_name, user_name),
+                    ),
                 ));
             }
             for user_name in collaborators_new.difference(&collaborators_old) {
-                changes.push(RepositoryChange::CollaboratorAdded(
-                    (*repo_name).to_string(),
-                    (*user_name).to_string(),
-                    user_role(&repos_new, repo_name, user_name),
+                let role_new = user_role(&repos_new, repo_name, user_name);
+                changes.push((
+                    RepositoryChange::CollaboratorAdded(
+                        (*repo_name).to_string(),
+                        (*user_name).to_string(),
+                        role_new.clone(),
+                    ),
+                    ChangeExplanation::added("collaborator role", role_new),
                 ));
             }
             for user_name in &collaborators_new {
@@ -470,12 +1465,46 @@ impl State {
                 let role_new = user_role(&repos_new, repo_name, user_name);
                 let role_old = user_role(&repos_old, repo_name, user_name);
                 if role_new != role_old {
-                    changes.push(RepositoryChange::CollaboratorRoleUpdated(
-                        (*repo_name).to_string(),
-                        (*user_name).to_string(),
-                        role_new,
+                    changes.push((
+                        RepositoryChange::CollaboratorRoleUpdated(
+                            (*repo_name).to_string(),
+                            (*user_name).to_string(),
+                            role_new.clone(),
+                        ),
+                        ChangeExplanation::updated("collaborator role", role_old, role_new),
                     ));
                 }
+
+                // Report collaborators whose access is about to expire
+                if let Some(expires_at) = repos_new[repo_name].collaborator_expiration(user_name) {
+                    if expires_at - today <= COLLABORATOR_EXPIRATION_NOTICE {
+                        changes.push((
+                            RepositoryChange::CollaboratorAccessExpiring(
+                                (*repo_name).to_string(),
+                                (*user_name).to_string(),
+                                expires_at,
+                            ),
+                            ChangeExplanation::added("collaborator access expiration", expires_at),
+                        ));
+                    }
+                }
+            }
+
+            // Apps with access to the repository (only checked when the
+            // allowed or denied apps have been defined)
+            let repo_new = repos_new[repo_name];
+            if let Some(installed_apps) = &repos_old[repo_name].installed_apps {
+                for app_slug in installed_apps {
+                    let allowed = repo_new.allowed_apps.as_ref().is_none_or(|apps| apps.contains(app_slug));
+                    let denied = repo_new.denied_apps.as_ref().is_some_and(|apps| apps.contains(app_slug));
+                    if !allowed || denied {
+                        let access_new = if denied { "denied" } else { "not allowed" };
+                        changes.push((
+                            RepositoryChange::UnexpectedAppAccess((*repo_name).to_string(), app_slug.clone()),
+                            ChangeExplanation::updated("app access", "installed", access_new),
+                        ));
+                    }
+                }
             }
 
             // Visibility
@@ -483,632 +1512,5143 @@ impl State {
             let visibility_old = &repos_old[repo_name].visibility;
             if visibility_new != visibility_old {
                 let visibility_new = visibility_new.clone().unwrap_or_default();
-                changes.push(RepositoryChange::VisibilityUpdated(
-                    (*repo_name).to_string(),
-                    visibility_new,
+                changes.push((
+                    RepositoryChange::VisibilityUpdated((*repo_name).to_string(), visibility_new.clone()),
+                    ChangeExplanation {
+                        field: "visibility",
+                        old: visibility_old.as_ref().map(ToString::to_string),
+                        new: Some(visibility_new.to_string()),
+                    },
                 ));
             }
-        }
 
-        changes
-    }
-}
+            // Topics (only checked when they have been defined)
+            if let Some(topics_new) = &repo_new.topics {
+                let topics_old = repos_old[repo_name].topics.as_deref().unwrap_or_default();
+                let topics_old_set: BTreeSet<&String> = topics_old.iter().collect();
+                let topics_new_set: BTreeSet<&String> = topics_new.iter().collect();
+                if topics_old_set != topics_new_set {
+                    changes.push((
+                        RepositoryChange::TopicsUpdated((*repo_name).to_string(), topics_new.clone()),
+                        ChangeExplanation::updated("topics", topics_old.join(", "), topics_new.join(", ")),
+                    ));
+                }
+            }
 
-/// Repository information.
-#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
-pub struct Repository {
-    pub name: String,
+            // Forking policy (only checked when it has been defined)
+            if let Some(allow_forking_new) = repo_new.allow_forking {
+                let allow_forking_old = repos_old[repo_name].allow_forking;
+                if allow_forking_old != Some(allow_forking_new) {
+                    changes.push((
+                        RepositoryChange::ForkingPolicyUpdated((*repo_name).to_string(), allow_forking_new),
+                        ChangeExplanation {
+                            field: "allow forking",
+                            old: allow_forking_old.as_ref().map(ToString::to_string),
+                            new: Some(allow_forking_new.to_string()),
+                        },
+                    ));
+                }
+            }
 
-    #[serde(alias = "external_collaborators", skip_serializing_if = "Option::is_none")]
-    pub collaborators: Option<BTreeMap<UserName, Role>>,
+            // Template status (only checked when it has been defined)
+            if let Some(is_template_new) = repo_new.is_template {
+                let is_template_old = repos_old[repo_name].is_template.unwrap_or(false);
+                if is_template_old != is_template_new {
+                    changes.push((
+                        RepositoryChange::TemplateStatusUpdated((*repo_name).to_string(), is_template_new),
+                        ChangeExplanation::updated(
+                            "template",
+                            enabled_status(is_template_old),
+                            enabled_status(is_template_new),
+                        ),
+                    ));
+                }
+            }
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub teams: Option<BTreeMap<TeamName, Role>>,
+            // Discussions settings (only checked when they have been defined)
+            if let Some(discussions_new) = &repo_new.discussions {
+                let discussions_old = repos_old[repo_name].discussions.as_ref();
+                if !discussions_old.is_some_and(|d| discussions_new.is_satisfied_by(d)) {
+                    changes.push((
+                        RepositoryChange::DiscussionsSettingsUpdated(
+                            (*repo_name).to_string(),
+                            discussions_new.clone(),
+                        ),
+                        ChangeExplanation {
+                            field: "discussions",
+                            old: discussions_old.map(ToString::to_string),
+                            new: Some(discussions_new.to_string()),
+                        },
+                    ));
+                }
+            }
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub visibility: Option<Visibility>,
+            // Merge commit settings (only checked when they have been defined)
+            let merge_commit_settings_new = repo_new.merge_commit_settings();
+            let merge_commit_settings_old = repos_old[repo_name].merge_commit_settings();
+            if !merge_commit_settings_new.is_empty()
+                && !merge_commit_settings_new.is_satisfied_by(&merge_commit_settings_old)
+            {
+                let explanation = ChangeExplanation::updated(
+                    "merge commit settings",
+                    &merge_commit_settings_old,
+                    &merge_commit_settings_new,
+                );
+                changes.push((
+                    RepositoryChange::MergeCommitSettingsUpdated(
+                        (*repo_name).to_string(),
+                        merge_commit_settings_new,
+                    ),
+                    explanation,
+                ));
+            }
+
+            // Code scanning default setup (only checked when it has been
+            // defined and it is supported in the repository)
+            if let (Some(code_scanning_new), Some(code_scanning_old)) =
+                (&repo_new.code_scanning, &repos_old[repo_name].code_scanning)
+            {
+                if !code_scanning_new.is_satisfied_by(code_scanning_old) {
+                    changes.push((
+                        RepositoryChange::CodeScanningUpdated(
+                            (*repo_name).to_string(),
+                            code_scanning_new.clone(),
+                        ),
+                        ChangeExplanation::updated("code scanning", code_scanning_old, code_scanning_new),
+                    ));
+                }
+            }
+
+            // Actions allow-list (only checked when it has been defined)
+            if let (Some(allow_list_new), Some(allow_list_old)) = (
+                &repo_new.actions_allow_list,
+                &repos_old[repo_name].actions_allow_list,
+            ) {
+                if !allow_list_new.is_satisfied_by(allow_list_old) {
+                    changes.push((
+                        RepositoryChange::ActionsAllowListUpdated(
+                            (*repo_name).to_string(),
+                            allow_list_new.clone(),
+                        ),
+                        ChangeExplanation::updated("actions allow-list", allow_list_old, allow_list_new),
+                    ));
+                }
+            }
+
+            // Watchers (only checked when they have been defined)
+            if let Some(watchers_new) = &repo_new.watchers {
+                let watchers_old = repos_old[repo_name].watchers.as_deref().unwrap_or_default();
+                for user_name in watchers_new {
+                    if !watchers_old.contains(user_name) {
+                        changes.push((
+                            RepositoryChange::WatcherAdded((*repo_name).to_string(), user_name.clone()),
+                            ChangeExplanation::added("watcher", user_name),
+                        ));
+                    }
+                }
+                for user_name in watchers_old {
+                    if !watchers_new.contains(user_name) {
+                        changes.push((
+                            RepositoryChange::WatcherRemoved((*repo_name).to_string(), user_name.clone()),
+                            ChangeExplanation::removed("watcher", user_name),
+                        ));
+                    }
+                }
+            }
+
+            // Private vulnerability reporting (only checked when it has been defined)
+            if let Some(pvr_new) = repo_new.private_vulnerability_reporting {
+                let pvr_old = repos_old[repo_name].private_vulnerability_reporting;
+                if pvr_old != Some(pvr_new) {
+                    changes.push((
+                        RepositoryChange::PrivateVulnerabilityReportingUpdated(
+                            (*repo_name).to_string(),
+                            pvr_new,
+                        ),
+                        ChangeExplanation {
+                            field: "private vulnerability reporting",
+                            old: pvr_old.map(enabled_status).map(ToString::to_string),
+                            new: Some(enabled_status(pvr_new).to_string()),
+                        },
+                    ));
+                }
+            }
+        }
+
+        changes
+    }
 }
 
-/// Role a user or team may have been assigned.
-#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum Role {
-    #[default]
-    Read,
-    Triage,
-    Write,
-    Maintain,
-    Admin,
+/// Fetch from the service the setting of the entities (i.e. repositories or
+/// teams) provided that define it in the desired state. The selector receives
+/// the desired and the actual entity, and decides if the setting is needed.
+async fn fetch_opt_in_setting<E, T, Fut>(
+    svc: &DynSvc,
+    entities: &mut [E],
+    desired_entities: &[E],
+    name: fn(&E) -> &String,
+    selector: impl Fn(&E, &E) -> bool,
+    fetch: impl Fn(DynSvc, String) -> Fut,
+    set: impl Fn(&mut E, T),
+) -> Result<()>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    for entity in entities.iter_mut() {
+        let defined = desired_entities.iter().any(|e| name(e) == name(entity) && selector(e, entity));
+        if !defined {
+            continue;
+        }
+        let value = fetch(svc.clone(), name(entity).clone()).await?;
+        set(entity, value);
+    }
+    Ok(())
 }
 
-impl fmt::Display for Role {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Role::Read => write!(f, "read"),
-            Role::Triage => write!(f, "triage"),
-            Role::Write => write!(f, "write"),
-            Role::Maintain => write!(f, "maintain"),
-            Role::Admin => write!(f, "admin"),
+/// Promote the teams' members that are organization admins to maintainers,
+/// unless it has been disabled for the organization provided.
+fn promote_org_admins(org: &Organization, directory: &mut Directory, org_admins: &[UserName]) {
+    if org.disable_admins_promotion {
+        return;
+    }
+    for team in &mut directory.teams {
+        let mut org_admins_members = vec![];
+        for user_name in &team.members.clone() {
+            if org_admins.contains(user_name) {
+                org_admins_members.push(user_name.clone());
+                team.maintainers.push(user_name.clone());
+            }
         }
+        team.members.retain(|user_name| !org_admins_members.contains(user_name));
     }
 }
 
-impl From<Option<RepositoryPermissions>> for Role {
-    fn from(permissions: Option<RepositoryPermissions>) -> Self {
-        match permissions {
-            Some(p) if p.admin => Role::Admin,
-            Some(p) if p.maintain => Role::Maintain,
-            Some(p) if p.push => Role::Write,
-            Some(p) if p.triage => Role::Triage,
-            Some(p) if p.pull => Role::Read,
-            Some(_) | None => Role::default(),
+/// Check the repositories watchers are service accounts of the organization
+/// with a token configured (subscriptions can only be managed by the users
+/// themselves, so their token is required to update them).
+fn validate_watchers(org: &Organization, repositories: &[Repository]) -> Result<()> {
+    let mut merr = MultiError::new(Some("invalid repositories watchers".to_string()));
+    for repo in repositories {
+        for user_name in repo.watchers.iter().flatten() {
+            let service_account = org.service_accounts.iter().flatten().find(|sa| &sa.login == user_name);
+            match service_account {
+                None => merr.push(format_err!(
+                    "repo[{}]: watcher {user_name} is not a service account",
+                    repo.name
+                )),
+                Some(service_account) if service_account.token.is_none() => merr.push(format_err!(
+                    "repo[{}]: watcher {user_name} does not have a token configured",
+                    repo.name
+                )),
+                Some(_) => {}
+            }
         }
     }
+    if merr.contains_errors() {
+        return Err(merr.into());
+    }
+    Ok(())
 }
 
-impl From<RepositoryInvitationPermissions> for Role {
-    fn from(permissions: RepositoryInvitationPermissions) -> Self {
-        match permissions {
-            RepositoryInvitationPermissions::Read => Role::Read,
-            RepositoryInvitationPermissions::Triage => Role::Triage,
-            RepositoryInvitationPermissions::Write => Role::Write,
-            RepositoryInvitationPermissions::Maintain => Role::Maintain,
-            RepositoryInvitationPermissions::Admin => Role::Admin,
-            _ => Role::default(),
+/// Add the organization's service accounts as members of the teams they must
+/// belong to. Service accounts cannot be maintainers of any of the teams, as
+/// they must never be promoted, and the teams must exist in the directory.
+fn add_service_accounts(org: &Organization, directory: &mut Directory) -> Result<()> {
+    let Some(service_accounts) = &org.service_accounts else {
+        return Ok(());
+    };
+
+    let mut merr = MultiError::new(Some("invalid service accounts configuration".to_string()));
+    let mut logins_seen = HashSet::new();
+    for (i, service_account) in service_accounts.iter().enumerate() {
+        // Define id to be used in subsequent error messages. When available,
+        // it'll be the login. Otherwise we'll use its index on the list.
+        let id = if service_account.login.is_empty() {
+            format!("{i}")
+        } else {
+            service_account.login.clone()
+        };
+
+        // Login must be provided and unique
+        if service_account.login.is_empty() {
+            merr.push(format_err!("service_account[{id}]: login must be provided"));
+            continue;
+        }
+        if !logins_seen.insert(&service_account.login) {
+            merr.push(format_err!("service_account[{id}]: duplicate service account"));
+            continue;
         }
-    }
-}
 
-impl From<&Role> for RepositoryInvitationPermissions {
-    fn from(role: &Role) -> Self {
-        match role {
-            Role::Read => RepositoryInvitationPermissions::Read,
-            Role::Triage => RepositoryInvitationPermissions::Triage,
-            Role::Write => RepositoryInvitationPermissions::Write,
-            Role::Maintain => RepositoryInvitationPermissions::Maintain,
-            Role::Admin => RepositoryInvitationPermissions::Admin,
+        for team_name in &service_account.teams {
+            let Some(team) = directory.teams.iter_mut().find(|t| &t.name == team_name) else {
+                merr.push(format_err!(
+                    "service_account[{id}]: team {team_name} does not exist"
+                ));
+                continue;
+            };
+            if team.maintainers.contains(&service_account.login) {
+                merr.push(format_err!(
+                    "service_account[{id}]: cannot be a maintainer of team {team_name}"
+                ));
+                continue;
+            }
+            if !team.members.contains(&service_account.login) {
+                team.members.push(service_account.login.clone());
+            }
         }
     }
-}
 
-impl From<&Role> for TeamsAddUpdateRepoPermissionsInOrgRequestPermission {
-    fn from(role: &Role) -> Self {
-        match role {
-            Role::Read => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Pull,
-            Role::Triage => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Triage,
-            Role::Write => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Push,
-            Role::Maintain => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Maintain,
-            Role::Admin => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Admin,
-        }
+    if merr.contains_errors() {
+        return Err(merr.into());
     }
+    Ok(())
 }
 
-impl From<Option<TeamPermissions>> for Role {
-    fn from(permissions: Option<TeamPermissions>) -> Self {
-        match permissions {
-            Some(p) if p.admin => Role::Admin,
-            Some(p) if p.maintain => Role::Maintain,
-            Some(p) if p.push => Role::Write,
-            Some(p) if p.triage => Role::Triage,
-            Some(p) if p.pull => Role::Read,
-            Some(_) | None => Role::default(),
-        }
+/// Normalize the topics provided the same way GitHub does (lowercase, with
+/// spaces replaced by hyphens), sorting them and removing duplicates.
+fn normalize_topics(topics: &mut Vec<String>) {
+    for topic in topics.iter_mut() {
+        *topic = topic.trim().to_lowercase().replace(' ', "-");
     }
+    topics.sort();
+    topics.dedup();
 }
 
-/// Repository visibility.
-#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum Visibility {
-    Internal,
-    Private,
-    #[default]
-    Public,
+/// Check if the repository provided is a temporary private fork created for a
+/// GitHub security advisory. These repositories are not managed by CLOWarden.
+#[must_use]
+pub fn is_ghsa_temp_fork(repo_name: &str) -> bool {
+    GHSA_TEMP_FORK.is_match(repo_name)
 }
 
-impl fmt::Display for Visibility {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Visibility::Internal => write!(f, "internal"),
-            Visibility::Private => write!(f, "private"),
-            Visibility::Public => write!(f, "public"),
-        }
-    }
+/// List the secret teams in the organization.
+async fn list_secret_teams(svc: DynSvc, ctx: &Ctx) -> Result<Vec<TeamName>> {
+    let secret_teams = svc
+        .list_teams(ctx)
+        .await?
+        .into_iter()
+        .filter(|t| t.privacy.to_string() == "secret")
+        .map(|t| t.slug)
+        .collect();
+    Ok(secret_teams)
 }
 
-impl From<String> for Visibility {
-    fn from(value: String) -> Self {
-        match value.as_str() {
-            "internal" => Visibility::Internal,
-            "private" => Visibility::Private,
-            "public" => Visibility::Public,
-            _ => Visibility::default(),
-        }
-    }
+/// Snapshot of the organization's actual state, including the information
+/// needed to build and validate a state from the configuration without
+/// using the service (offline validation).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrgSnapshot {
+    pub state: State,
+    pub org_admins: Vec<UserName>,
+    pub org_members: Vec<UserName>,
+    pub archived_repositories: Vec<RepositoryName>,
+    pub secret_teams: Vec<TeamName>,
+    pub gitignore_templates: Vec<String>,
+    pub license_templates: Vec<String>,
+    /// Unix timestamp of the moment the snapshot was taken.
+    pub taken_at: i64,
 }
 
-/// Represents the changes between two states.
-#[derive(Debug, Clone, Default, PartialEq)]
-pub struct Changes {
-    pub directory: Vec<DirectoryChange>,
-    pub repositories: Vec<RepositoryChange>,
+impl OrgSnapshot {
+    /// Take a new snapshot of the organization's actual state.
+    pub async fn new(svc: DynSvc, ctx: &Ctx) -> Result<Self> {
+        let state = State::new_from_service(svc.clone(), ctx).await?;
+        let archived_repositories = svc
+            .list_repositories(ctx)
+            .await?
+            .into_iter()
+            .filter(|r| r.archived)
+            .map(|r| r.name)
+            .collect();
+        Ok(Self {
+            state,
+            org_admins: svc.list_org_admins(ctx).await?.into_iter().map(|a| a.login).collect(),
+            org_members: svc.list_org_members(ctx).await?.into_iter().map(|m| m.login).collect(),
+            archived_repositories,
+            secret_teams: list_secret_teams(svc.clone(), ctx).await?,
+            gitignore_templates: svc.list_gitignore_templates(ctx).await?,
+            license_templates: svc.list_license_templates(ctx).await?,
+            taken_at: time::OffsetDateTime::now_utc().unix_timestamp(),
+        })
+    }
 }
 
-/// Represents a repository change.
-#[derive(Debug, Clone, PartialEq)]
-pub enum RepositoryChange {
-    RepositoryAdded(Repository),
-    TeamAdded(RepositoryName, TeamName, Role),
-    TeamRemoved(RepositoryName, TeamName),
-    TeamRoleUpdated(RepositoryName, TeamName, Role),
-    CollaboratorAdded(RepositoryName, UserName, Role),
-    CollaboratorRemoved(RepositoryName, UserName),
-    CollaboratorRoleUpdated(RepositoryName, UserName, Role),
-    VisibilityUpdated(RepositoryName, Visibility),
+/// Source of the information from the actual state needed to build and
+/// validate a state from the configuration.
+enum ActualStateInfo<'a> {
+    /// Information is obtained from the service.
+    Service { svc: DynSvc, ctx: &'a Ctx },
+    /// Information is obtained from an organization snapshot (offline).
+    Snapshot(&'a OrgSnapshot),
 }
 
-impl Change for RepositoryChange {
-    /// [Change::details]
-    fn details(&self) -> ChangeDetails {
+impl ActualStateInfo<'_> {
+    /// Get the organization admins.
+    async fn org_admins(&self) -> Result<Vec<UserName>> {
         match self {
-            RepositoryChange::RepositoryAdded(repo) => ChangeDetails {
-                kind: "repository-added".to_string(),
-                extra: json!({ "repo": repo }),
-            },
-            RepositoryChange::TeamAdded(repo_name, team_name, role) => ChangeDetails {
-                kind: "repository-team-added".to_string(),
-                extra: json!({ "repo_name": repo_name, "team_name": team_name, "role": role }),
-            },
-            RepositoryChange::TeamRemoved(repo_name, team_name) => ChangeDetails {
-                kind: "repository-team-removed".to_string(),
-                extra: json!({ "repo_name": repo_name, "team_name": team_name }),
-            },
-            RepositoryChange::TeamRoleUpdated(repo_name, team_name, role) => ChangeDetails {
-                kind: "repository-team-role-updated".to_string(),
-                extra: json!({ "repo_name": repo_name, "team_name": team_name, "role": role }),
-            },
-            RepositoryChange::CollaboratorAdded(repo_name, user_name, role) => ChangeDetails {
-                kind: "repository-collaborator-added".to_string(),
-                extra: json!({ "repo_name": repo_name, "user_name": user_name, "role": role }),
-            },
-            RepositoryChange::CollaboratorRemoved(repo_name, user_name) => ChangeDetails {
-                kind: "repository-collaborator-removed".to_string(),
-                extra: json!({ "repo_name": repo_name, "user_name": user_name }),
-            },
-            RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, role) => ChangeDetails {
-                kind: "repository-collaborator-role-updated".to_string(),
-                extra: json!({ "repo_name": repo_name, "user_name": user_name, "role": role }),
-            },
-            RepositoryChange::VisibilityUpdated(repo_name, visibility) => ChangeDetails {
-                kind: "repository-visibility-updated".to_string(),
-                extra: json!({ "repo_name": repo_name, "visibility": visibility }),
-            },
+            Self::Service { svc, ctx } => {
+                Ok(svc.list_org_admins(ctx).await?.into_iter().map(|a| a.login).collect())
+            }
+            Self::Snapshot(snapshot) => Ok(snapshot.org_admins.clone()),
         }
     }
 
-    /// [Change::keywords]
-    fn keywords(&self) -> Vec<&str> {
+    /// Get the organization members.
+    async fn org_members(&self) -> Result<Vec<UserName>> {
         match self {
-            RepositoryChange::RepositoryAdded(repo) => {
-                let mut keywords = vec!["repository", "added", &repo.name];
-                if let Some(teams) = &repo.teams {
-                    for team_name in teams.keys() {
-                        keywords.push(team_name);
-                    }
-                }
-                if let Some(collaborators) = &repo.collaborators {
-                    for user_name in collaborators.keys() {
-                        keywords.push(user_name);
-                    }
-                }
-                keywords
-            }
-            RepositoryChange::TeamAdded(repo_name, team_name, _) => {
-                vec!["repository", "team", "added", repo_name, team_name]
-            }
-            RepositoryChange::TeamRemoved(repo_name, team_name) => {
-                vec!["repository", "team", "removed", repo_name, team_name]
-            }
-            RepositoryChange::TeamRoleUpdated(repo_name, team_name, _) => {
-                vec!["repository", "team", "updated", repo_name, team_name]
-            }
-            RepositoryChange::CollaboratorAdded(repo_name, user_name, _) => {
-                vec!["repository", "collaborator", "added", repo_name, user_name]
-            }
-            RepositoryChange::CollaboratorRemoved(repo_name, user_name) => {
-                vec!["repository", "collaborator", "removed", repo_name, user_name]
-            }
-            RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, _) => {
-                vec![
-                    "repository",
-                    "collaborator",
-                    "role",
-                    "updated",
-                    repo_name,
-                    user_name,
-                ]
+            Self::Service { svc, ctx } => {
+                Ok(svc.list_org_members(ctx).await?.into_iter().map(|m| m.login).collect())
             }
-            RepositoryChange::VisibilityUpdated(repo_name, _) => {
-                vec!["repository", "visibility", "updated", repo_name]
+            Self::Snapshot(snapshot) => Ok(snapshot.org_members.clone()),
+        }
+    }
+
+    /// Get the names of all the repositories in the organization (including
+    /// the archived ones).
+    async fn repositories(&self) -> Result<Vec<RepositoryName>> {
+        match self {
+            Self::Service { svc, ctx } => {
+                Ok(svc.list_repositories(ctx).await?.into_iter().map(|r| r.name).collect())
             }
+            Self::Snapshot(snapshot) => Ok(snapshot
+                .state
+                .repositories
+                .iter()
+                .map(|r| r.name.clone())
+                .chain(snapshot.archived_repositories.iter().cloned())
+                .collect()),
         }
     }
 
-    /// [Change::template_format]
-    fn template_format(&self) -> Result<String> {
-        let mut s = String::new();
+    /// Get the names of the archived repositories in the organization.
+    async fn archived_repositories(&self) -> Result<Vec<RepositoryName>> {
+        match self {
+            Self::Service { svc, ctx } => Ok(svc
+                .list_repositories(ctx)
+                .await?
+                .into_iter()
+                .filter(|r| r.archived)
+                .map(|r| r.name)
+                .collect()),
+            Self::Snapshot(snapshot) => Ok(snapshot.archived_repositories.clone()),
+        }
+    }
 
+    /// Get the gitignore templates available.
+    async fn gitignore_templates(&self) -> Result<Vec<String>> {
         match self {
-            RepositoryChange::RepositoryAdded(repo) => {
-                write!(
-                    s,
-                    "- repository **{}** has been *added* (visibility: **{}**)",
-                    repo.name,
-                    repo.visibility.clone().unwrap_or_default()
-                )?;
-                if let Some(teams) = &repo.teams {
-                    if !teams.is_empty() {
-                        write!(s, "\n\t- Teams")?;
-                        for (team_name, role) in teams {
-                            write!(s, "\n\t\t- **{team_name}**: *{role}*")?;
-                        }
-                    }
-                }
-                if let Some(collaborators) = &repo.collaborators {
-                    if !collaborators.is_empty() {
-                        write!(s, "\n\t- Collaborators")?;
-                        for (user_name, role) in collaborators {
-                            write!(s, "\n\t\t- **{user_name}**: *{role}*")?;
-                        }
-                    }
-                }
-            }
-            RepositoryChange::TeamAdded(repo_name, team_name, role) => {
-                write!(
-                    s,
-                    "- team **{team_name}** has been *added* to repository **{repo_name}** (role: **{role}**)"
-                )?;
-            }
-            RepositoryChange::TeamRemoved(repo_name, team_name) => {
-                write!(
-                    s,
-                    "- team **{team_name}** has been *removed* from repository **{repo_name}**"
-                )?;
-            }
-            RepositoryChange::TeamRoleUpdated(repo_name, team_name, role) => {
-                write!(
-                    s,
-                    "- team **{team_name}** role in repository **{repo_name}** has been *updated* to **{role}**"
-                )?;
-            }
-            RepositoryChange::CollaboratorAdded(repo_name, user_name, role) => {
-                write!(
-                    s,
-                    "- user **{user_name}** is now a collaborator (role: **{role}**) of repository **{repo_name}**"
-                )?;
-            }
-            RepositoryChange::CollaboratorRemoved(repo_name, user_name) => {
-                write!(
-                    s,
-                    "- user **{user_name}** is no longer a collaborator of repository **{repo_name}**"
-                )?;
-            }
-            RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, role) => {
-                write!(
-                    s,
-                    "- user **{user_name}** role in repository **{repo_name}** has been updated to **{role}**"
-                )?;
-            }
-            RepositoryChange::VisibilityUpdated(repo_name, visibility) => {
-                write!(
-                    s,
-                    "- repository **{repo_name}** visibility has been updated to **{visibility}**"
-                )?;
-            }
+            Self::Service { svc, ctx } => svc.list_gitignore_templates(ctx).await,
+            Self::Snapshot(snapshot) => Ok(snapshot.gitignore_templates.clone()),
         }
+    }
 
-        Ok(s)
+    /// Get the license templates available.
+    async fn license_templates(&self) -> Result<Vec<String>> {
+        match self {
+            Self::Service { svc, ctx } => svc.list_license_templates(ctx).await,
+            Self::Snapshot(snapshot) => Ok(snapshot.license_templates.clone()),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::directory::User;
+/// Snapshot of the repositories in the actual state, used to avoid getting
+/// again from the service the ones that haven't been updated since it was
+/// taken (incremental reconcile).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepositoriesSnapshot {
+    pub repositories: Vec<Repository>,
+    /// Repositories collaborators invitations sent (tracked to detect the
+    /// ones declined).
+    #[serde(default)]
+    pub invitations: Vec<CollaboratorInvitation>,
+    /// Removals deferred until the organization's grace period elapses.
+    #[serde(default)]
+    pub pending_removals: Vec<PendingRemoval>,
+    /// Unix timestamp of the moment the snapshot was taken.
+    pub taken_at: i64,
+}
+
+impl RepositoriesSnapshot {
+    /// Get the repository provided from the snapshot, as long as it hasn't
+    /// been updated since the snapshot was taken.
+    fn get_unmodified_repository(&self, repo: &MinimalRepository) -> Option<&Repository> {
+        let last_update = repo.updated_at.max(repo.pushed_at)?.timestamp();
+        if last_update >= self.taken_at {
+            return None;
+        }
+        self.repositories.iter().find(|r| r.name == repo.name)
+    }
+}
+
+/// Repository collaborator invitation sent by CLOWarden. GitHub deletes the
+/// invitations declined, so they are tracked across reconciliations to be
+/// able to detect them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollaboratorInvitation {
+    pub repository: RepositoryName,
+    pub user_name: UserName,
+    /// Whether the invitation is still waiting for the collaborator's answer.
+    pub pending: bool,
+    /// Number of times the collaborator has declined the invitation.
+    pub declines: u32,
+}
+
+/// Removal (of a team member or a repository collaborator) deferred until
+/// the organization's removals grace period elapses.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PendingRemoval {
+    /// Kind of the change, as in its details.
+    pub kind: String,
+    /// Extra information of the change, as in its details.
+    pub extra: serde_json::Value,
+    /// Unix timestamp of the moment the removal was first detected.
+    pub detected_at: i64,
+}
+
+impl PendingRemoval {
+    /// Check if the pending removal corresponds to the change details provided.
+    #[must_use]
+    pub fn matches(&self, details: &ChangeDetails) -> bool {
+        self.kind == details.kind && self.extra == details.extra
+    }
+}
+
+/// Repository information.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Repository {
+    pub name: String,
+
+    #[serde(alias = "external_collaborators", skip_serializing_if = "Option::is_none")]
+    pub collaborators: Option<BTreeMap<UserName, Role>>,
+
+    /// Date (YYYY-MM-DD) when the access of some collaborators expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collaborators_expiration: Option<BTreeMap<UserName, String>>,
+
+    /// Collaborators removed because their access has expired (only
+    /// available in the desired state).
+    #[serde(skip)]
+    pub expired_collaborators: Option<BTreeSet<UserName>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teams: Option<BTreeMap<TeamName, Role>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flattened_teams: Option<BTreeMap<TeamName, Role>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitignore_template: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_template: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_apps: Option<Vec<AppSlug>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub denied_apps: Option<Vec<AppSlug>>,
+
+    /// Apps with access to the repository (only available in the actual
+    /// state, as reported by the service).
+    #[serde(skip)]
+    pub installed_apps: Option<Vec<AppSlug>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_forking: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_template: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip_default_labels: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discussions: Option<DiscussionSettings>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squash_merge_commit_title: Option<SquashMergeCommitTitle>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squash_merge_commit_message: Option<SquashMergeCommitMessage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_merge_commit: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_commit_title: Option<MergeCommitTitle>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_commit_message: Option<MergeCommitMessage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete_branch_on_merge: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_update_branch: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_commit_signoff_required: Option<bool>,
+
+    /// Protection applied to the default branch when the repository is
+    /// created (ignored for existing repositories).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_protection: Option<BranchProtection>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_scanning: Option<CodeScanningSetup>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions_allow_list: Option<ActionsAllowList>,
+
+    /// CODEOWNERS file management. When set, the repository's CODEOWNERS
+    /// file is generated from the teams with write access to it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codeowners: Option<CodeownersManagement>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_vulnerability_reporting: Option<bool>,
+
+    /// Service accounts watching the repository. In the actual state, only
+    /// the organization's service accounts are included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watchers: Option<Vec<UserName>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topics: Option<Vec<String>>,
+}
+
+impl Repository {
+    /// Returns the merge commit settings of the repository.
+    #[must_use]
+    pub fn merge_commit_settings(&self) -> MergeCommitSettings {
+        MergeCommitSettings {
+            squash_merge_commit_title: self.squash_merge_commit_title,
+            squash_merge_commit_message: self.squash_merge_commit_message,
+            allow_merge_commit: self.allow_merge_commit,
+            merge_commit_title: self.merge_commit_title,
+            merge_commit_message: self.merge_commit_message,
+            delete_branch_on_merge: self.delete_branch_on_merge,
+            allow_update_branch: self.allow_update_branch,
+            web_commit_signoff_required: self.web_commit_signoff_required,
+        }
+    }
+
+    /// Returns the date when the access of the collaborator provided expires,
+    /// if any. Invalid dates are ignored (they are reported when validating
+    /// the state).
+    fn collaborator_expiration(&self, user_name: &UserName) -> Option<Date> {
+        let expires_at = self.collaborators_expiration.as_ref()?.get(user_name)?;
+        parse_expiration_date(expires_at).ok()
+    }
+
+    /// Remove the collaborators whose access has expired, keeping track of
+    /// them in the expired collaborators list.
+    fn remove_expired_collaborators(&mut self, today: Date) {
+        let Some(collaborators_expiration) = &mut self.collaborators_expiration else {
+            return;
+        };
+        let expired: BTreeSet<UserName> = collaborators_expiration
+            .iter()
+            .filter(|(_, expires_at)| parse_expiration_date(expires_at).is_ok_and(|date| date <= today))
+            .map(|(user_name, _)| user_name.clone())
+            .collect();
+        for user_name in &expired {
+            collaborators_expiration.remove(user_name);
+            if let Some(collaborators) = &mut self.collaborators {
+                collaborators.remove(user_name);
+            }
+        }
+        if !expired.is_empty() {
+            self.expired_collaborators = Some(expired);
+        }
+    }
+
+    /// Add the maintainers and members of the flattened teams to the
+    /// repository's collaborators, using the role defined for the team. Users
+    /// explicitly listed as collaborators keep the role defined for them, so
+    /// that it can be used to override the one they'd get from the team. When
+    /// a user belongs to multiple flattened teams, the highest role (according
+    /// to the roles ordering provided) is used.
+    fn flatten_teams(&mut self, directory: &Directory, roles_ordering: &RolesOrdering) {
+        let Some(flattened_teams) = &self.flattened_teams else {
+            return;
+        };
+
+        let mut collaborators = self.collaborators.take().unwrap_or_default();
+        let overrides: HashSet<UserName> = collaborators.keys().cloned().collect();
+        for (team_name, role) in flattened_teams {
+            let Some(team) = directory.get_team(team_name) else {
+                continue;
+            };
+            for user_name in team.maintainers.iter().chain(team.members.iter()) {
+                if overrides.contains(user_name) {
+                    continue;
+                }
+                let user_role = collaborators.entry(user_name.clone()).or_insert_with(|| role.clone());
+                if roles_ordering.is_higher(role, user_role) {
+                    *user_role = role.clone();
+                }
+            }
+        }
+        if !collaborators.is_empty() {
+            self.collaborators = Some(collaborators);
+        }
+    }
+}
+
+/// Branch protection settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BranchProtection {
+    /// Number of approving reviews required to merge pull requests (0-6).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_approving_review_count: Option<u8>,
+
+    #[serde(default)]
+    pub require_code_owner_reviews: bool,
+
+    #[serde(default)]
+    pub dismiss_stale_reviews: bool,
+
+    /// Status checks that must pass before merging.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_status_checks: Option<Vec<String>>,
+
+    #[serde(default)]
+    pub enforce_admins: bool,
+}
+
+/// Repository code scanning default setup (CodeQL without a workflow).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CodeScanningSetup {
+    pub enabled: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_suite: Option<CodeScanningQuerySuite>,
+}
+
+impl CodeScanningSetup {
+    /// Check if the actual setup provided satisfies this one. The query suite
+    /// is only compared when it has been defined and the setup is enabled.
+    fn is_satisfied_by(&self, actual: &CodeScanningSetup) -> bool {
+        if self.enabled != actual.enabled {
+            return false;
+        }
+        !self.enabled || self.query_suite.is_none() || self.query_suite == actual.query_suite
+    }
+}
+
+impl fmt::Display for CodeScanningSetup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.enabled {
+            return write!(f, "disabled");
+        }
+        match self.query_suite {
+            Some(query_suite) => write!(f, "enabled (query suite: {query_suite})"),
+            None => write!(f, "enabled"),
+        }
+    }
+}
+
+/// Repository GitHub Actions allow-list. When enabled, only the actions
+/// allowed by it (in addition to the local ones) can be used in the
+/// repository's workflows.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionsAllowList {
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub github_owned_allowed: bool,
+
+    #[serde(default)]
+    pub verified_allowed: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns_allowed: Vec<String>,
+}
+
+impl ActionsAllowList {
+    /// Check if the actual allow-list provided satisfies this one. The
+    /// actions allowed are only compared when the allow-list is enabled, and
+    /// the order of the patterns is not relevant.
+    fn is_satisfied_by(&self, actual: &ActionsAllowList) -> bool {
+        if self.enabled != actual.enabled {
+            return false;
+        }
+        if !self.enabled {
+            return true;
+        }
+        let patterns_new: BTreeSet<&String> = self.patterns_allowed.iter().collect();
+        let patterns_old: BTreeSet<&String> = actual.patterns_allowed.iter().collect();
+        self.github_owned_allowed == actual.github_owned_allowed
+            && self.verified_allowed == actual.verified_allowed
+            && patterns_new == patterns_old
+    }
+}
+
+impl fmt::Display for ActionsAllowList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.enabled {
+            return write!(f, "disabled");
+        }
+        let mut allowed = vec![];
+        if self.github_owned_allowed {
+            allowed.push("GitHub owned".to_string());
+        }
+        if self.verified_allowed {
+            allowed.push("verified creators".to_string());
+        }
+        allowed.extend(self.patterns_allowed.iter().cloned());
+        if allowed.is_empty() {
+            return write!(f, "enabled (local actions only)");
+        }
+        write!(f, "enabled ({})", allowed.join(", "))
+    }
+}
+
+/// Repository CODEOWNERS file management settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CodeownersManagement {
+    #[serde(default)]
+    pub update_mode: CodeownersUpdateMode,
+}
+
+/// How the CODEOWNERS file is updated in the repository's default branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeownersUpdateMode {
+    /// Commit the changes directly to the default branch.
+    #[default]
+    Commit,
+    /// Propose the changes in a pull request.
+    PullRequest,
+}
+
+impl fmt::Display for CodeownersUpdateMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeownersUpdateMode::Commit => write!(f, "commit"),
+            CodeownersUpdateMode::PullRequest => write!(f, "pull request"),
+        }
+    }
+}
+
+/// Returns the owners of the repository's CODEOWNERS file generated by
+/// CLOWarden: the teams with write access (or higher) to the repository,
+/// sorted by name. Flattened teams are not included, as they don't get
+/// access to the repository as a team.
+#[must_use]
+pub fn codeowners_owners(repo: &Repository, org: &str) -> Vec<String> {
+    let mut owners: Vec<String> = repo
+        .teams
+        .iter()
+        .flatten()
+        .filter(|(_, role)| **role >= Role::Write)
+        .map(|(team_name, _)| format!("@{org}/{team_name}"))
+        .collect();
+    owners.sort();
+    owners
+}
+
+/// Returns the content of the CODEOWNERS file generated by CLOWarden for the
+/// owners provided (all files in the repository are owned by them).
+#[must_use]
+pub fn codeowners_content(owners: &[String]) -> String {
+    format!(
+        "# This file is managed by CLOWarden from the teams with write access to\n\
+         # the repository, please do not edit it manually.\n\
+         * {}\n",
+        owners.join(" ")
+    )
+}
+
+/// Locations where GitHub looks for the Dependabot configuration file.
+pub const DEPENDABOT_CONFIG_PATHS: [&str; 2] = [".github/dependabot.yml", ".github/dependabot.yaml"];
+
+/// Default Dependabot configuration file added to the repositories missing
+/// it when the organization enforces its Dependabot configuration policy.
+pub const DEFAULT_DEPENDABOT_CONFIG: &str = "\
+# This file was added by CLOWarden, as all the repositories in the
+# organization must have a Dependabot configuration file.
+version: 2
+updates:
+  - package-ecosystem: \"github-actions\"
+    directory: \"/\"
+    schedule:
+      interval: \"weekly\"
+";
+
+/// Query suite used by the code scanning default setup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeScanningQuerySuite {
+    Default,
+    Extended,
+}
+
+impl fmt::Display for CodeScanningQuerySuite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeScanningQuerySuite::Default => write!(f, "default"),
+            CodeScanningQuerySuite::Extended => write!(f, "extended"),
+        }
+    }
+}
+
+/// Repository discussions settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiscussionSettings {
+    pub enabled: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categories: Option<Vec<String>>,
+}
+
+impl DiscussionSettings {
+    /// Check if the actual settings provided satisfy these ones. Categories
+    /// are only compared when they have been defined (order is not relevant).
+    fn is_satisfied_by(&self, actual: &DiscussionSettings) -> bool {
+        if self.enabled != actual.enabled {
+            return false;
+        }
+        let Some(categories) = &self.categories else {
+            return true;
+        };
+        let categories: HashSet<&String> = categories.iter().collect();
+        let actual_categories: HashSet<&String> = actual.categories.iter().flatten().collect();
+        categories == actual_categories
+    }
+}
+
+impl fmt::Display for DiscussionSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.enabled {
+            return write!(f, "disabled");
+        }
+        match &self.categories {
+            Some(categories) if !categories.is_empty() => {
+                write!(f, "enabled (categories: {})", categories.join(", "))
+            }
+            _ => write!(f, "enabled"),
+        }
+    }
+}
+
+/// Repository merge commit settings, along with some other pull requests and
+/// commits related settings managed the same way. Only the settings defined
+/// are taken into account. Field names match the ones used by the GitHub API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergeCommitSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squash_merge_commit_title: Option<SquashMergeCommitTitle>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squash_merge_commit_message: Option<SquashMergeCommitMessage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_merge_commit: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_commit_title: Option<MergeCommitTitle>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_commit_message: Option<MergeCommitMessage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete_branch_on_merge: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_update_branch: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_commit_signoff_required: Option<bool>,
+}
+
+impl MergeCommitSettings {
+    /// Check if no settings have been defined.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.squash_merge_commit_title.is_none()
+            && self.squash_merge_commit_message.is_none()
+            && self.allow_merge_commit.is_none()
+            && self.merge_commit_title.is_none()
+            && self.merge_commit_message.is_none()
+            && self.delete_branch_on_merge.is_none()
+            && self.allow_update_branch.is_none()
+            && self.web_commit_signoff_required.is_none()
+    }
+
+    /// Check if the actual settings provided satisfy these ones (settings
+    /// not defined are ignored).
+    fn is_satisfied_by(&self, actual: &MergeCommitSettings) -> bool {
+        let satisfied = |expected: Option<_>, actual: Option<_>| expected.is_none() || expected == actual;
+        satisfied(self.squash_merge_commit_title, actual.squash_merge_commit_title)
+            && satisfied(
+                self.squash_merge_commit_message,
+                actual.squash_merge_commit_message,
+            )
+            && satisfied(self.allow_merge_commit, actual.allow_merge_commit)
+            && satisfied(self.merge_commit_title, actual.merge_commit_title)
+            && satisfied(self.merge_commit_message, actual.merge_commit_message)
+            && satisfied(self.delete_branch_on_merge, actual.delete_branch_on_merge)
+            && satisfied(self.allow_update_branch, actual.allow_update_branch)
+            && satisfied(
+                self.web_commit_signoff_required,
+                actual.web_commit_signoff_required,
+            )
+    }
+}
+
+impl fmt::Display for MergeCommitSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut settings = vec![];
+        if let Some(title) = self.squash_merge_commit_title {
+            settings.push(format!("squash merge commits title: {}", title.description()));
+        }
+        if let Some(message) = self.squash_merge_commit_message {
+            settings.push(format!("squash merge commits message: {}", message.description()));
+        }
+        let status = |enabled: bool| if enabled { "enabled" } else { "disabled" };
+        if let Some(allow_merge_commit) = self.allow_merge_commit {
+            settings.push(format!("merge commits: {}", status(allow_merge_commit)));
+        }
+        if let Some(title) = self.merge_commit_title {
+            settings.push(format!("merge commits title: {}", title.description()));
+        }
+        if let Some(message) = self.merge_commit_message {
+            settings.push(format!("merge commits message: {}", message.description()));
+        }
+        if let Some(delete_branch_on_merge) = self.delete_branch_on_merge {
+            settings.push(format!(
+                "delete head branches on merge: {}",
+                status(delete_branch_on_merge)
+            ));
+        }
+        if let Some(allow_update_branch) = self.allow_update_branch {
+            settings.push(format!(
+                "suggest updating pull request branches: {}",
+                status(allow_update_branch)
+            ));
+        }
+        if let Some(web_commit_signoff_required) = self.web_commit_signoff_required {
+            settings.push(format!(
+                "require sign off on web-based commits: {}",
+                status(web_commit_signoff_required)
+            ));
+        }
+        write!(f, "{}", settings.join(", "))
+    }
+}
+
+/// Title used for squash merge commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SquashMergeCommitTitle {
+    PrTitle,
+    CommitOrPrTitle,
+}
+
+impl SquashMergeCommitTitle {
+    /// Return a human readable description of the title used.
+    fn description(self) -> &'static str {
+        match self {
+            SquashMergeCommitTitle::PrTitle => "pull request title",
+            SquashMergeCommitTitle::CommitOrPrTitle => "commit or pull request title",
+        }
+    }
+}
+
+impl fmt::Display for SquashMergeCommitTitle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SquashMergeCommitTitle::PrTitle => write!(f, "PR_TITLE"),
+            SquashMergeCommitTitle::CommitOrPrTitle => write!(f, "COMMIT_OR_PR_TITLE"),
+        }
+    }
+}
+
+/// Message used for squash merge commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SquashMergeCommitMessage {
+    PrBody,
+    CommitMessages,
+    Blank,
+}
+
+impl SquashMergeCommitMessage {
+    /// Return a human readable description of the message used.
+    fn description(self) -> &'static str {
+        match self {
+            SquashMergeCommitMessage::PrBody => "pull request body",
+            SquashMergeCommitMessage::CommitMessages => "commit messages",
+            SquashMergeCommitMessage::Blank => "blank",
+        }
+    }
+}
+
+impl fmt::Display for SquashMergeCommitMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SquashMergeCommitMessage::PrBody => write!(f, "PR_BODY"),
+            SquashMergeCommitMessage::CommitMessages => write!(f, "COMMIT_MESSAGES"),
+            SquashMergeCommitMessage::Blank => write!(f, "BLANK"),
+        }
+    }
+}
+
+/// Title used for merge commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MergeCommitTitle {
+    PrTitle,
+    MergeMessage,
+}
+
+impl MergeCommitTitle {
+    /// Return a human readable description of the title used.
+    fn description(self) -> &'static str {
+        match self {
+            MergeCommitTitle::PrTitle => "pull request title",
+            MergeCommitTitle::MergeMessage => "default merge message",
+        }
+    }
+}
+
+impl fmt::Display for MergeCommitTitle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeCommitTitle::PrTitle => write!(f, "PR_TITLE"),
+            MergeCommitTitle::MergeMessage => write!(f, "MERGE_MESSAGE"),
+        }
+    }
+}
+
+/// Message used for merge commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MergeCommitMessage {
+    PrTitle,
+    PrBody,
+    Blank,
+}
+
+impl MergeCommitMessage {
+    /// Return a human readable description of the message used.
+    fn description(self) -> &'static str {
+        match self {
+            MergeCommitMessage::PrTitle => "pull request title",
+            MergeCommitMessage::PrBody => "pull request body",
+            MergeCommitMessage::Blank => "blank",
+        }
+    }
+}
+
+impl fmt::Display for MergeCommitMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeCommitMessage::PrTitle => write!(f, "PR_TITLE"),
+            MergeCommitMessage::PrBody => write!(f, "PR_BODY"),
+            MergeCommitMessage::Blank => write!(f, "BLANK"),
+        }
+    }
+}
+
+/// Role a user or team may have been assigned.
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Read,
+    Triage,
+    Write,
+    Maintain,
+    Admin,
+}
+
+/// Ordering used when comparing roles. It is defined as a list of chains of
+/// roles, sorted from lower to higher privileges. A role is higher than other
+/// when it appears after it in any of the chains. Roles that do not appear
+/// together in any chain are not comparable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RolesOrdering(Vec<Vec<Role>>);
+
+impl Default for RolesOrdering {
+    fn default() -> Self {
+        RolesOrdering(vec![vec![
+            Role::Read,
+            Role::Triage,
+            Role::Write,
+            Role::Maintain,
+            Role::Admin,
+        ]])
+    }
+}
+
+impl RolesOrdering {
+    /// Create a new RolesOrdering instance from the chains of roles provided.
+    #[must_use]
+    pub fn new(chains: Vec<Vec<Role>>) -> Self {
+        RolesOrdering(chains)
+    }
+
+    /// Check if the role provided is higher than the other one.
+    #[must_use]
+    pub fn is_higher(&self, role: &Role, other: &Role) -> bool {
+        self.0.iter().any(|chain| {
+            let position = |role: &Role| chain.iter().position(|r| r == role);
+            matches!((position(role), position(other)), (Some(i), Some(j)) if i > j)
+        })
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Read => write!(f, "read"),
+            Role::Triage => write!(f, "triage"),
+            Role::Write => write!(f, "write"),
+            Role::Maintain => write!(f, "maintain"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Role::Read),
+            "triage" => Ok(Role::Triage),
+            "write" => Ok(Role::Write),
+            "maintain" => Ok(Role::Maintain),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("invalid role: {s}")),
+        }
+    }
+}
+
+impl From<Option<RepositoryPermissions>> for Role {
+    fn from(permissions: Option<RepositoryPermissions>) -> Self {
+        match permissions {
+            Some(p) if p.admin => Role::Admin,
+            Some(p) if p.maintain => Role::Maintain,
+            Some(p) if p.push => Role::Write,
+            Some(p) if p.triage => Role::Triage,
+            Some(p) if p.pull => Role::Read,
+            Some(_) | None => Role::default(),
+        }
+    }
+}
+
+impl From<RepositoryInvitationPermissions> for Role {
+    fn from(permissions: RepositoryInvitationPermissions) -> Self {
+        match permissions {
+            RepositoryInvitationPermissions::Read => Role::Read,
+            RepositoryInvitationPermissions::Triage => Role::Triage,
+            RepositoryInvitationPermissions::Write => Role::Write,
+            RepositoryInvitationPermissions::Maintain => Role::Maintain,
+            RepositoryInvitationPermissions::Admin => Role::Admin,
+            _ => Role::default(),
+        }
+    }
+}
+
+impl From<&Role> for RepositoryInvitationPermissions {
+    fn from(role: &Role) -> Self {
+        match role {
+            Role::Read => RepositoryInvitationPermissions::Read,
+            Role::Triage => RepositoryInvitationPermissions::Triage,
+            Role::Write => RepositoryInvitationPermissions::Write,
+            Role::Maintain => RepositoryInvitationPermissions::Maintain,
+            Role::Admin => RepositoryInvitationPermissions::Admin,
+        }
+    }
+}
+
+impl From<&Role> for TeamsAddUpdateRepoPermissionsInOrgRequestPermission {
+    fn from(role: &Role) -> Self {
+        match role {
+            Role::Read => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Pull,
+            Role::Triage => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Triage,
+            Role::Write => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Push,
+            Role::Maintain => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Maintain,
+            Role::Admin => TeamsAddUpdateRepoPermissionsInOrgRequestPermission::Admin,
+        }
+    }
+}
+
+impl From<Option<TeamPermissions>> for Role {
+    fn from(permissions: Option<TeamPermissions>) -> Self {
+        match permissions {
+            Some(p) if p.admin => Role::Admin,
+            Some(p) if p.maintain => Role::Maintain,
+            Some(p) if p.push => Role::Write,
+            Some(p) if p.triage => Role::Triage,
+            Some(p) if p.pull => Role::Read,
+            Some(_) | None => Role::default(),
+        }
+    }
+}
+
+/// Repository visibility.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Internal,
+    Private,
+    #[default]
+    Public,
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Visibility::Internal => write!(f, "internal"),
+            Visibility::Private => write!(f, "private"),
+            Visibility::Public => write!(f, "public"),
+        }
+    }
+}
+
+impl From<String> for Visibility {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "internal" => Visibility::Internal,
+            "private" => Visibility::Private,
+            "public" => Visibility::Public,
+            _ => Visibility::default(),
+        }
+    }
+}
+
+/// Represents the changes between two states.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    pub directory: Vec<DirectoryChange>,
+    pub repositories: Vec<RepositoryChange>,
+}
+
+impl Changes {
+    /// Returns the changes in this instance that are not present in the
+    /// other one provided.
+    #[must_use]
+    pub fn difference(&self, other: &Changes) -> Changes {
+        Changes {
+            directory: self.directory.iter().filter(|c| !other.directory.contains(c)).cloned().collect(),
+            repositories: self
+                .repositories
+                .iter()
+                .filter(|c| !other.repositories.contains(c))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Check if there are no changes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.directory.is_empty() && self.repositories.is_empty()
+    }
+}
+
+/// Represents the changes between two states, along with the values compared
+/// to compute each of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExplainedChanges {
+    pub directory: Vec<(DirectoryChange, ChangeExplanation)>,
+    pub repositories: Vec<(RepositoryChange, ChangeExplanation)>,
+}
+
+impl From<ExplainedChanges> for Changes {
+    fn from(changes: ExplainedChanges) -> Self {
+        Changes {
+            directory: changes.directory.into_iter().map(|(change, _)| change).collect(),
+            repositories: changes.repositories.into_iter().map(|(change, _)| change).collect(),
+        }
+    }
+}
+
+/// Represents a repository change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepositoryChange {
+    RepositoryAdded(Repository),
+    TeamAdded(RepositoryName, TeamName, Role),
+    TeamRemoved(RepositoryName, TeamName),
+    TeamRoleUpdated(RepositoryName, TeamName, Role),
+    CollaboratorAdded(RepositoryName, UserName, Role),
+    CollaboratorRemoved(RepositoryName, UserName),
+    CollaboratorRoleUpdated(RepositoryName, UserName, Role),
+    VisibilityUpdated(RepositoryName, Visibility),
+    UnexpectedAppAccess(RepositoryName, AppSlug),
+    ForkingPolicyUpdated(RepositoryName, bool),
+    TemplateStatusUpdated(RepositoryName, bool),
+    CollaboratorAccessExpiring(RepositoryName, UserName, Date),
+    DiscussionsSettingsUpdated(RepositoryName, DiscussionSettings),
+    MergeCommitSettingsUpdated(RepositoryName, MergeCommitSettings),
+    CodeScanningUpdated(RepositoryName, CodeScanningSetup),
+    ActionsAllowListUpdated(RepositoryName, ActionsAllowList),
+    /// CODEOWNERS file updated (repository name, owners, update mode).
+    CodeownersUpdated(RepositoryName, Vec<String>, CodeownersUpdateMode),
+    DependabotConfigAdded(RepositoryName),
+    PrivateVulnerabilityReportingUpdated(RepositoryName, bool),
+    WatcherAdded(RepositoryName, UserName),
+    WatcherRemoved(RepositoryName, UserName),
+    TopicsUpdated(RepositoryName, Vec<String>),
+}
+
+impl RepositoryChange {
+    /// Returns the name of the repository affected by the change.
+    #[must_use]
+    pub fn repository_name(&self) -> &RepositoryName {
+        match self {
+            RepositoryChange::RepositoryAdded(repo) => &repo.name,
+            RepositoryChange::TeamAdded(repo_name, _, _)
+            | RepositoryChange::TeamRemoved(repo_name, _)
+            | RepositoryChange::TeamRoleUpdated(repo_name, _, _)
+            | RepositoryChange::CollaboratorAdded(repo_name, _, _)
+            | RepositoryChange::CollaboratorRemoved(repo_name, _)
+            | RepositoryChange::CollaboratorRoleUpdated(repo_name, _, _)
+            | RepositoryChange::VisibilityUpdated(repo_name, _)
+            | RepositoryChange::UnexpectedAppAccess(repo_name, _)
+            | RepositoryChange::ForkingPolicyUpdated(repo_name, _)
+            | RepositoryChange::TemplateStatusUpdated(repo_name, _)
+            | RepositoryChange::CollaboratorAccessExpiring(repo_name, _, _)
+            | RepositoryChange::DiscussionsSettingsUpdated(repo_name, _)
+            | RepositoryChange::MergeCommitSettingsUpdated(repo_name, _)
+            | RepositoryChange::CodeScanningUpdated(repo_name, _)
+            | RepositoryChange::ActionsAllowListUpdated(repo_name, _)
+            | RepositoryChange::CodeownersUpdated(repo_name, _, _)
+            | RepositoryChange::DependabotConfigAdded(repo_name)
+            | RepositoryChange::PrivateVulnerabilityReportingUpdated(repo_name, _)
+            | RepositoryChange::WatcherAdded(repo_name, _)
+            | RepositoryChange::WatcherRemoved(repo_name, _)
+            | RepositoryChange::TopicsUpdated(repo_name, _) => repo_name,
+        }
+    }
+}
+
+impl Change for RepositoryChange {
+    /// [Change::details]
+    fn details(&self) -> ChangeDetails {
+        match self {
+            RepositoryChange::RepositoryAdded(repo) => ChangeDetails {
+                kind: "repository-added".to_string(),
+                extra: json!({ "repo": repo }),
+            },
+            RepositoryChange::TeamAdded(repo_name, team_name, role) => ChangeDetails {
+                kind: "repository-team-added".to_string(),
+                extra: json!({ "repo_name": repo_name, "team_name": team_name, "role": role }),
+            },
+            RepositoryChange::TeamRemoved(repo_name, team_name) => ChangeDetails {
+                kind: "repository-team-removed".to_string(),
+                extra: json!({ "repo_name": repo_name, "team_name": team_name }),
+            },
+            RepositoryChange::TeamRoleUpdated(repo_name, team_name, role) => ChangeDetails {
+                kind: "repository-team-role-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "team_name": team_name, "role": role }),
+            },
+            RepositoryChange::CollaboratorAdded(repo_name, user_name, role) => ChangeDetails {
+                kind: "repository-collaborator-added".to_string(),
+                extra: json!({ "repo_name": repo_name, "user_name": user_name, "role": role }),
+            },
+            RepositoryChange::CollaboratorRemoved(repo_name, user_name) => ChangeDetails {
+                kind: "repository-collaborator-removed".to_string(),
+                extra: json!({ "repo_name": repo_name, "user_name": user_name }),
+            },
+            RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, role) => ChangeDetails {
+                kind: "repository-collaborator-role-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "user_name": user_name, "role": role }),
+            },
+            RepositoryChange::VisibilityUpdated(repo_name, visibility) => ChangeDetails {
+                kind: "repository-visibility-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "visibility": visibility }),
+            },
+            RepositoryChange::UnexpectedAppAccess(repo_name, app_slug) => ChangeDetails {
+                kind: "repository-app-unexpected".to_string(),
+                extra: json!({ "repo_name": repo_name, "app_slug": app_slug }),
+            },
+            RepositoryChange::ForkingPolicyUpdated(repo_name, allow_forking) => ChangeDetails {
+                kind: "repository-forking-policy-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "allow_forking": allow_forking }),
+            },
+            RepositoryChange::TemplateStatusUpdated(repo_name, is_template) => ChangeDetails {
+                kind: "repository-template-status-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "is_template": is_template }),
+            },
+            RepositoryChange::CollaboratorAccessExpiring(repo_name, user_name, expires_at) => ChangeDetails {
+                kind: "repository-collaborator-access-expiring".to_string(),
+                extra: json!({
+                    "repo_name": repo_name,
+                    "user_name": user_name,
+                    "expires_at": expires_at.to_string(),
+                }),
+            },
+            RepositoryChange::DiscussionsSettingsUpdated(repo_name, discussions) => ChangeDetails {
+                kind: "repository-discussions-settings-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "discussions": discussions }),
+            },
+            RepositoryChange::MergeCommitSettingsUpdated(repo_name, settings) => ChangeDetails {
+                kind: "repository-merge-commit-settings-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "settings": settings }),
+            },
+            RepositoryChange::CodeScanningUpdated(repo_name, code_scanning) => ChangeDetails {
+                kind: "repository-code-scanning-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "code_scanning": code_scanning }),
+            },
+            RepositoryChange::ActionsAllowListUpdated(repo_name, allow_list) => ChangeDetails {
+                kind: "repository-actions-allow-list-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "allow_list": allow_list }),
+            },
+            RepositoryChange::CodeownersUpdated(repo_name, owners, update_mode) => ChangeDetails {
+                kind: "repository-codeowners-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "owners": owners, "update_mode": update_mode }),
+            },
+            RepositoryChange::DependabotConfigAdded(repo_name) => ChangeDetails {
+                kind: "repository-dependabot-config-added".to_string(),
+                extra: json!({ "repo_name": repo_name }),
+            },
+            RepositoryChange::PrivateVulnerabilityReportingUpdated(repo_name, enabled) => ChangeDetails {
+                kind: "repository-private-vulnerability-reporting-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "enabled": enabled }),
+            },
+            RepositoryChange::WatcherAdded(repo_name, user_name) => ChangeDetails {
+                kind: "repository-watcher-added".to_string(),
+                extra: json!({ "repo_name": repo_name, "user_name": user_name }),
+            },
+            RepositoryChange::WatcherRemoved(repo_name, user_name) => ChangeDetails {
+                kind: "repository-watcher-removed".to_string(),
+                extra: json!({ "repo_name": repo_name, "user_name": user_name }),
+            },
+            RepositoryChange::TopicsUpdated(repo_name, topics) => ChangeDetails {
+                kind: "repository-topics-updated".to_string(),
+                extra: json!({ "repo_name": repo_name, "topics": topics }),
+            },
+        }
+    }
+
+    /// [Change::keywords]
+    fn keywords(&self) -> Vec<&str> {
+        match self {
+            RepositoryChange::RepositoryAdded(repo) => {
+                let mut keywords = vec!["repository", "added", &repo.name];
+                if let Some(teams) = &repo.teams {
+                    for team_name in teams.keys() {
+                        keywords.push(team_name);
+                    }
+                }
+                if let Some(collaborators) = &repo.collaborators {
+                    for user_name in collaborators.keys() {
+                        keywords.push(user_name);
+                    }
+                }
+                keywords
+            }
+            RepositoryChange::TeamAdded(repo_name, team_name, _) => {
+                vec!["repository", "team", "added", repo_name, team_name]
+            }
+            RepositoryChange::TeamRemoved(repo_name, team_name) => {
+                vec!["repository", "team", "removed", repo_name, team_name]
+            }
+            RepositoryChange::TeamRoleUpdated(repo_name, team_name, _) => {
+                vec!["repository", "team", "updated", repo_name, team_name]
+            }
+            RepositoryChange::CollaboratorAdded(repo_name, user_name, _) => {
+                vec!["repository", "collaborator", "added", repo_name, user_name]
+            }
+            RepositoryChange::CollaboratorRemoved(repo_name, user_name) => {
+                vec!["repository", "collaborator", "removed", repo_name, user_name]
+            }
+            RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, _) => {
+                vec![
+                    "repository",
+                    "collaborator",
+                    "role",
+                    "updated",
+                    repo_name,
+                    user_name,
+                ]
+            }
+            RepositoryChange::VisibilityUpdated(repo_name, _) => {
+                vec!["repository", "visibility", "updated", repo_name]
+            }
+            RepositoryChange::UnexpectedAppAccess(repo_name, app_slug) => {
+                vec!["repository", "app", "unexpected", repo_name, app_slug]
+            }
+            RepositoryChange::ForkingPolicyUpdated(repo_name, _) => {
+                vec!["repository", "forking", "updated", repo_name]
+            }
+            RepositoryChange::TemplateStatusUpdated(repo_name, _) => {
+                vec!["repository", "template", "updated", repo_name]
+            }
+            RepositoryChange::CollaboratorAccessExpiring(repo_name, user_name, _) => {
+                vec!["repository", "collaborator", "expiring", repo_name, user_name]
+            }
+            RepositoryChange::DiscussionsSettingsUpdated(repo_name, _) => {
+                vec!["repository", "discussions", "updated", repo_name]
+            }
+            RepositoryChange::MergeCommitSettingsUpdated(repo_name, _) => {
+                vec!["repository", "merge", "updated", repo_name]
+            }
+            RepositoryChange::CodeScanningUpdated(repo_name, _) => {
+                vec!["repository", "code", "scanning", "updated", repo_name]
+            }
+            RepositoryChange::ActionsAllowListUpdated(repo_name, _) => {
+                vec!["repository", "actions", "allow-list", "updated", repo_name]
+            }
+            RepositoryChange::CodeownersUpdated(repo_name, _, _) => {
+                vec!["repository", "codeowners", "updated", repo_name]
+            }
+            RepositoryChange::DependabotConfigAdded(repo_name) => {
+                vec!["repository", "dependabot", "added", repo_name]
+            }
+            RepositoryChange::PrivateVulnerabilityReportingUpdated(repo_name, _) => {
+                vec!["repository", "vulnerability", "reporting", "updated", repo_name]
+            }
+            RepositoryChange::WatcherAdded(repo_name, user_name) => {
+                vec!["repository", "watcher", "added", repo_name, user_name]
+            }
+            RepositoryChange::WatcherRemoved(repo_name, user_name) => {
+                vec!["repository", "watcher", "removed", repo_name, user_name]
+            }
+            RepositoryChange::TopicsUpdated(repo_name, _) => {
+                vec!["repository", "topics", "updated", repo_name]
+            }
+        }
+    }
+
+    /// [Change::risk]
+    fn risk(&self) -> ChangeRisk {
+        match self {
+            RepositoryChange::RepositoryAdded(repo) => {
+                let admin_granted = repo
+                    .teams
+                    .iter()
+                    .chain(repo.collaborators.iter())
+                    .flat_map(BTreeMap::values)
+                    .any(|role| *role == Role::Admin);
+                if admin_granted {
+                    ChangeRisk::High
+                } else {
+                    ChangeRisk::Low
+                }
+            }
+            RepositoryChange::TeamAdded(_, _, Role::Admin)
+            | RepositoryChange::TeamRoleUpdated(_, _, Role::Admin)
+            | RepositoryChange::CollaboratorAdded(_, _, Role::Admin)
+            | RepositoryChange::CollaboratorRoleUpdated(_, _, Role::Admin)
+            | RepositoryChange::VisibilityUpdated(_, Visibility::Public)
+            | RepositoryChange::UnexpectedAppAccess(_, _)
+            | RepositoryChange::CodeScanningUpdated(_, CodeScanningSetup { enabled: false, .. })
+            | RepositoryChange::ActionsAllowListUpdated(_, ActionsAllowList { enabled: false, .. })
+            | RepositoryChange::PrivateVulnerabilityReportingUpdated(_, false) => ChangeRisk::High,
+            _ => ChangeRisk::Low,
+        }
+    }
+
+    /// [Change::template_format]
+    fn template_format(&self) -> Result<String> {
+        let mut s = String::new();
+
+        match self {
+            RepositoryChange::RepositoryAdded(repo) => {
+                write!(
+                    s,
+                    "- repository **{}** has been *added* (visibility: **{}**)",
+                    repo.name,
+                    repo.visibility.clone().unwrap_or_default()
+                )?;
+                if let Some(allow_forking) = repo.allow_forking {
+                    write!(s, "\n\t- Forking: **{}**", forking_policy(allow_forking))?;
+                }
+                if let Some(is_template) = repo.is_template {
+                    write!(s, "\n\t- Template: **{}**", enabled_status(is_template))?;
+                }
+                if let Some(discussions) = &repo.discussions {
+                    write!(s, "\n\t- Discussions: **{discussions}**")?;
+                }
+                let merge_commit_settings = repo.merge_commit_settings();
+                if !merge_commit_settings.is_empty() {
+                    write!(s, "\n\t- Merge commits: **{merge_commit_settings}**")?;
+                }
+                if let Some(enabled) = repo.private_vulnerability_reporting {
+                    write!(
+                        s,
+                        "\n\t- Private vulnerability reporting: **{}**",
+                        enabled_status(enabled)
+                    )?;
+                }
+                if let Some(topics) = repo.topics.as_ref().filter(|topics| !topics.is_empty()) {
+                    write!(s, "\n\t- Topics: **{}**", topics.join("**, **"))?;
+                }
+
+                if let Some(teams) = &repo.teams {
+                    if !teams.is_empty() {
+                        write!(s, "\n\t- Teams")?;
+                        for (team_name, role) in teams {
+                            write!(s, "\n\t\t- **{team_name}**: *{role}*")?;
+                        }
+                    }
+                }
+                if let Some(collaborators) = &repo.collaborators {
+                    if !collaborators.is_empty() {
+                        write!(s, "\n\t- Collaborators")?;
+                        for (user_name, role) in collaborators {
+                            write!(s, "\n\t\t- **{user_name}**: *{role}*")?;
+                        }
+                    }
+                }
+            }
+            RepositoryChange::TeamAdded(repo_name, team_name, role) => {
+                write!(
+                    s,
+                    "- team **{team_name}** has been *added* to repository **{repo_name}** (role: **{role}**)"
+                )?;
+            }
+            RepositoryChange::TeamRemoved(repo_name, team_name) => {
+                write!(
+                    s,
+                    "- team **{team_name}** has been *removed* from repository **{repo_name}**"
+                )?;
+            }
+            RepositoryChange::TeamRoleUpdated(repo_name, team_name, role) => {
+                write!(
+                    s,
+                    "- team **{team_name}** role in repository **{repo_name}** has been *updated* to **{role}**"
+                )?;
+            }
+            RepositoryChange::CollaboratorAdded(repo_name, user_name, role) => {
+                write!(
+                    s,
+                    "- user **{user_name}** is now a collaborator (role: **{role}**) of repository **{repo_name}**"
+                )?;
+            }
+            RepositoryChange::CollaboratorRemoved(repo_name, user_name) => {
+                write!(
+                    s,
+                    "- user **{user_name}** is no longer a collaborator of repository **{repo_name}**"
+                )?;
+            }
+            RepositoryChange::CollaboratorRoleUpdated(repo_name, user_name, role) => {
+                write!(
+                    s,
+                    "- user **{user_name}** role in repository **{repo_name}** has been updated to **{role}**"
+                )?;
+            }
+            RepositoryChange::VisibilityUpdated(repo_name, visibility) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** visibility has been updated to **{visibility}**"
+                )?;
+            }
+            RepositoryChange::UnexpectedAppAccess(repo_name, app_slug) => {
+                write!(
+                    s,
+                    "- app **{app_slug}** has *unexpected* access to repository **{repo_name}**"
+                )?;
+            }
+            RepositoryChange::ForkingPolicyUpdated(repo_name, allow_forking) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** forking policy has been updated to **{}**",
+                    forking_policy(*allow_forking)
+                )?;
+            }
+            RepositoryChange::TemplateStatusUpdated(repo_name, is_template) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** template status has been **{}**",
+                    enabled_status(*is_template)
+                )?;
+            }
+            RepositoryChange::CollaboratorAccessExpiring(repo_name, user_name, expires_at) => {
+                write!(
+                    s,
+                    "- user **{user_name}** access to repository **{repo_name}** will *expire* on **{expires_at}**"
+                )?;
+            }
+            RepositoryChange::DiscussionsSettingsUpdated(repo_name, discussions) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** discussions settings have been updated to **{discussions}**"
+                )?;
+            }
+            RepositoryChange::MergeCommitSettingsUpdated(repo_name, settings) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** merge commit settings have been updated to **{settings}**"
+                )?;
+            }
+            RepositoryChange::CodeScanningUpdated(repo_name, code_scanning) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** code scanning default setup has been updated to **{code_scanning}**"
+                )?;
+            }
+            RepositoryChange::ActionsAllowListUpdated(repo_name, allow_list) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** actions allow-list has been updated to **{allow_list}**"
+                )?;
+            }
+            RepositoryChange::CodeownersUpdated(repo_name, owners, update_mode) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** CODEOWNERS file has been updated (owners: **{}**, via {update_mode})",
+                    owners.join("**, **")
+                )?;
+            }
+            RepositoryChange::DependabotConfigAdded(repo_name) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** default Dependabot configuration file has been added"
+                )?;
+            }
+            RepositoryChange::PrivateVulnerabilityReportingUpdated(repo_name, enabled) => {
+                write!(
+                    s,
+                    "- repository **{repo_name}** private vulnerability reporting has been **{}**",
+                    enabled_status(*enabled)
+                )?;
+            }
+            RepositoryChange::WatcherAdded(repo_name, user_name) => {
+                write!(
+                    s,
+                    "- service account **{user_name}** is now *watching* repository **{repo_name}**"
+                )?;
+            }
+            RepositoryChange::WatcherRemoved(repo_name, user_name) => {
+                write!(
+                    s,
+                    "- service account **{user_name}** is *no longer watching* repository **{repo_name}**"
+                )?;
+            }
+            RepositoryChange::TopicsUpdated(repo_name, topics) => {
+                if topics.is_empty() {
+                    write!(s, "- repository **{repo_name}** topics have been *removed*")?;
+                } else {
+                    write!(
+                        s,
+                        "- repository **{repo_name}** topics have been updated to **{}**",
+                        topics.join("**, **")
+                    )?;
+                }
+            }
+        }
+
+        Ok(s)
+    }
+}
+
+/// Represents a change in the teams hierarchy. These changes are not detected
+/// when comparing states, they are a side effect of removing a parent team.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeamHierarchyChange {
+    /// Child teams of the team removed moved to its parent (or to the top
+    /// level of the hierarchy if it had no parent).
+    ChildTeamsReparented(TeamName, Vec<TeamName>),
+    /// Child teams of the team removed left for GitHub to handle.
+    ChildTeamsOrphaned(TeamName, Vec<TeamName>),
+}
+
+impl Change for TeamHierarchyChange {
+    /// [Change::details]
+    fn details(&self) -> ChangeDetails {
+        match self {
+            TeamHierarchyChange::ChildTeamsReparented(team_name, child_teams) => ChangeDetails {
+                kind: "team-child-teams-reparented".to_string(),
+                extra: json!({ "team_name": team_name, "child_teams": child_teams }),
+            },
+            TeamHierarchyChange::ChildTeamsOrphaned(team_name, child_teams) => ChangeDetails {
+                kind: "team-child-teams-orphaned".to_string(),
+                extra: json!({ "team_name": team_name, "child_teams": child_teams }),
+            },
+        }
+    }
+
+    /// [Change::keywords]
+    fn keywords(&self) -> Vec<&str> {
+        let (mut keywords, team_name, child_teams) = match self {
+            TeamHierarchyChange::ChildTeamsReparented(team_name, child_teams) => {
+                (vec!["team", "child", "reparented"], team_name, child_teams)
+            }
+            TeamHierarchyChange::ChildTeamsOrphaned(team_name, child_teams) => {
+                (vec!["team", "child", "orphaned"], team_name, child_teams)
+            }
+        };
+        keywords.push(team_name);
+        keywords.extend(child_teams.iter().map(String::as_str));
+        keywords
+    }
+
+    /// [Change::risk]
+    fn risk(&self) -> ChangeRisk {
+        match self {
+            TeamHierarchyChange::ChildTeamsReparented(_, _) => ChangeRisk::Low,
+            TeamHierarchyChange::ChildTeamsOrphaned(_, _) => ChangeRisk::High,
+        }
+    }
+
+    /// [Change::template_format]
+    fn template_format(&self) -> Result<String> {
+        let mut s = String::new();
+
+        match self {
+            TeamHierarchyChange::ChildTeamsReparented(team_name, child_teams) => {
+                write!(
+                    s,
+                    "- child teams of team **{team_name}** have been *moved* to its parent: **{}**",
+                    child_teams.join("**, **")
+                )?;
+            }
+            TeamHierarchyChange::ChildTeamsOrphaned(team_name, child_teams) => {
+                write!(
+                    s,
+                    "- child teams of team **{team_name}** have been *orphaned*: **{}**",
+                    child_teams.join("**, **")
+                )?;
+            }
+        }
+
+        Ok(s)
+    }
+}
+
+/// Represents the retirement of a team. When enabled for the organization,
+/// teams are retired instead of being removed, so these changes replace the
+/// corresponding team removals.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeamRetirementChange {
+    /// Team renamed using the retired teams prefix and made secret.
+    TeamRetired(TeamName, TeamName),
+}
+
+impl Change for TeamRetirementChange {
+    /// [Change::details]
+    fn details(&self) -> ChangeDetails {
+        match self {
+            TeamRetirementChange::TeamRetired(team_name, new_team_name) => ChangeDetails {
+                kind: "team-retired".to_string(),
+                extra: json!({ "team_name": team_name, "new_team_name": new_team_name }),
+            },
+        }
+    }
+
+    /// [Change::keywords]
+    fn keywords(&self) -> Vec<&str> {
+        match self {
+            TeamRetirementChange::TeamRetired(team_name, new_team_name) => {
+                vec!["team", "retired", team_name, new_team_name]
+            }
+        }
+    }
+
+    /// [Change::risk]
+    fn risk(&self) -> ChangeRisk {
+        match self {
+            TeamRetirementChange::TeamRetired(_, _) => ChangeRisk::Low,
+        }
+    }
+
+    /// [Change::template_format]
+    fn template_format(&self) -> Result<String> {
+        let mut s = String::new();
+
+        match self {
+            TeamRetirementChange::TeamRetired(team_name, new_team_name) => {
+                write!(
+                    s,
+                    "- team **{team_name}** has been *retired* (renamed to **{new_team_name}** and made secret)"
+                )?;
+            }
+        }
+
+        Ok(s)
+    }
+}
+
+/// Represents a repository change that could not be applied because the
+/// repository was archived while the reconciliation was running. Archived
+/// repositories are read-only, so these changes are skipped instead of being
+/// reported as failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepositoryArchivedChange {
+    /// Change skipped as the repository is archived.
+    ChangeSkipped(RepositoryChange),
+}
+
+impl Change for RepositoryArchivedChange {
+    /// [Change::details]
+    fn details(&self) -> ChangeDetails {
+        match self {
+            RepositoryArchivedChange::ChangeSkipped(change) => ChangeDetails {
+                kind: "repository-archived-change-skipped".to_string(),
+                extra: json!({
+                    "repo_name": change.repository_name(),
+                    "change_kind": change.details().kind,
+                }),
+            },
+        }
+    }
+
+    /// [Change::keywords]
+    fn keywords(&self) -> Vec<&str> {
+        match self {
+            RepositoryArchivedChange::ChangeSkipped(change) => {
+                vec!["repository", "archived", "skipped", change.repository_name()]
+            }
+        }
+    }
+
+    /// [Change::risk]
+    fn risk(&self) -> ChangeRisk {
+        match self {
+            RepositoryArchivedChange::ChangeSkipped(_) => ChangeRisk::Low,
+        }
+    }
+
+    /// [Change::template_format]
+    fn template_format(&self) -> Result<String> {
+        let mut s = String::new();
+
+        match self {
+            RepositoryArchivedChange::ChangeSkipped(change) => {
+                write!(
+                    s,
+                    "- change **{}** in repository **{}** has been *skipped* (the repository has been archived)",
+                    change.details().kind,
+                    change.repository_name()
+                )?;
+            }
+        }
+
+        Ok(s)
+    }
+}
+
+/// Represents an organization change. These changes are not detected when
+/// comparing states, as organization settings are defined in the server's
+/// configuration instead of in the configuration files.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrganizationChange {
+    WebhookAdded(Webhook),
+    WebhookUpdated(Webhook),
+    WebhookRemoved(String),
+    CommunityHealthFileAdded(String, String),
+    CommunityHealthFileUpdated(String, String),
+    SettingUpdated(OrganizationSetting, bool),
+    RequiredWorkflowAdded(RequiredWorkflow),
+    /// Required workflow updated (ruleset id, new workflow definition).
+    RequiredWorkflowUpdated(i64, RequiredWorkflow),
+    /// Required workflow removed (ruleset id, workflow name).
+    RequiredWorkflowRemoved(i64, String),
+    /// Repositories selected to access a secret updated (secret name, new
+    /// list of repositories).
+    SecretRepositoriesUpdated(String, Vec<RepositoryName>),
+    /// Self-hosted runner group added to the organization.
+    RunnerGroupAdded(OrganizationRunnerGroup),
+    /// Repositories that can use a self-hosted runner group updated (runner
+    /// group id, new runner group definition).
+    RunnerGroupRepositoriesUpdated(i64, OrganizationRunnerGroup),
+    /// Service account invited to join the organization as a member.
+    ServiceAccountInvited(UserName),
+    /// Service account demoted from admin to member of the organization.
+    ServiceAccountDemoted(UserName),
+    /// Verified domains gate enabled or disabled.
+    VerifiedDomainsGateUpdated(bool),
+    /// Permission required to create teams in the organization updated.
+    TeamCreationPermissionUpdated(TeamCreationPermission),
+    /// User blocked from the organization.
+    UserBlocked(UserName),
+    /// User unblocked from the organization.
+    UserUnblocked(UserName),
+    /// Security manager role granted to a team.
+    SecurityManagerTeamAdded(TeamName),
+    /// Security manager role revoked from a team.
+    SecurityManagerTeamRemoved(TeamName),
+}
+
+impl Change for OrganizationChange {
+    /// [Change::details]
+    fn details(&self) -> ChangeDetails {
+        // Webhooks secrets must never be included in the change details
+        match self {
+            OrganizationChange::WebhookAdded(webhook) => ChangeDetails {
+                kind: "organization-webhook-added".to_string(),
+                extra: webhook_details(webhook),
+            },
+            OrganizationChange::WebhookUpdated(webhook) => ChangeDetails {
+                kind: "organization-webhook-updated".to_string(),
+                extra: webhook_details(webhook),
+            },
+            OrganizationChange::WebhookRemoved(url) => ChangeDetails {
+                kind: "organization-webhook-removed".to_string(),
+                extra: json!({ "url": url }),
+            },
+            OrganizationChange::CommunityHealthFileAdded(path, _) => ChangeDetails {
+                kind: "organization-community-health-file-added".to_string(),
+                extra: json!({ "path": path }),
+            },
+            OrganizationChange::CommunityHealthFileUpdated(path, _) => ChangeDetails {
+                kind: "organization-community-health-file-updated".to_string(),
+                extra: json!({ "path": path }),
+            },
+            OrganizationChange::SettingUpdated(setting, value) => ChangeDetails {
+                kind: "organization-setting-updated".to_string(),
+                extra: json!({ "setting": setting, "value": value }),
+            },
+            OrganizationChange::RequiredWorkflowAdded(workflow) => ChangeDetails {
+                kind: "organization-required-workflow-added".to_string(),
+                extra: json!({ "workflow": workflow }),
+            },
+            OrganizationChange::RequiredWorkflowUpdated(_, workflow) => ChangeDetails {
+                kind: "organization-required-workflow-updated".to_string(),
+                extra: json!({ "workflow": workflow }),
+            },
+            OrganizationChange::RequiredWorkflowRemoved(_, name) => ChangeDetails {
+                kind: "organization-required-workflow-removed".to_string(),
+                extra: json!({ "name": name }),
+            },
+            OrganizationChange::SecretRepositoriesUpdated(name, repos_names) => ChangeDetails {
+                kind: "organization-secret-repositories-updated".to_string(),
+                extra: json!({ "name": name, "repositories": repos_names }),
+            },
+            OrganizationChange::RunnerGroupAdded(runner_group) => ChangeDetails {
+                kind: "organization-runner-group-added".to_string(),
+                extra: json!({ "name": runner_group.name, "repositories": runner_group.repositories }),
+            },
+            OrganizationChange::RunnerGroupRepositoriesUpdated(_, runner_group) => ChangeDetails {
+                kind: "organization-runner-group-repositories-updated".to_string(),
+                extra: json!({ "name": runner_group.name, "repositories": runner_group.repositories }),
+            },
+            OrganizationChange::ServiceAccountInvited(user_name) => ChangeDetails {
+                kind: "organization-service-account-invited".to_string(),
+                extra: json!({ "user_name": user_name }),
+            },
+            OrganizationChange::ServiceAccountDemoted(user_name) => ChangeDetails {
+                kind: "organization-service-account-demoted".to_string(),
+                extra: json!({ "user_name": user_name }),
+            },
+            OrganizationChange::VerifiedDomainsGateUpdated(enabled) => ChangeDetails {
+                kind: "organization-verified-domains-gate-updated".to_string(),
+                extra: json!({ "enabled": enabled }),
+            },
+            OrganizationChange::TeamCreationPermissionUpdated(permission) => ChangeDetails {
+                kind: "organization-team-creation-permission-updated".to_string(),
+                extra: json!({ "permission": permission }),
+            },
+            OrganizationChange::UserBlocked(user_name) => ChangeDetails {
+                kind: "organization-user-blocked".to_string(),
+                extra: json!({ "user_name": user_name }),
+            },
+            OrganizationChange::UserUnblocked(user_name) => ChangeDetails {
+                kind: "organization-user-unblocked".to_string(),
+                extra: json!({ "user_name": user_name }),
+            },
+            OrganizationChange::SecurityManagerTeamAdded(team_name) => ChangeDetails {
+                kind: "organization-security-manager-team-added".to_string(),
+                extra: json!({ "team_name": team_name }),
+            },
+            OrganizationChange::SecurityManagerTeamRemoved(team_name) => ChangeDetails {
+                kind: "organization-security-manager-team-removed".to_string(),
+                extra: json!({ "team_name": team_name }),
+            },
+        }
+    }
+
+    /// [Change::keywords]
+    fn keywords(&self) -> Vec<&str> {
+        match self {
+            OrganizationChange::WebhookAdded(webhook) => vec!["webhook", "added", &webhook.url],
+            OrganizationChange::WebhookUpdated(webhook) => vec!["webhook", "updated", &webhook.url],
+            OrganizationChange::WebhookRemoved(url) => vec!["webhook", "removed", url],
+            OrganizationChange::CommunityHealthFileAdded(path, _) => {
+                vec!["community", "health", "file", "added", path]
+            }
+            OrganizationChange::CommunityHealthFileUpdated(path, _) => {
+                vec!["community", "health", "file", "updated", path]
+            }
+            OrganizationChange::SettingUpdated(setting, _) => {
+                vec!["setting", "updated", setting.api_name()]
+            }
+            OrganizationChange::RequiredWorkflowAdded(workflow) => {
+                vec![
+                    "required",
+                    "workflow",
+                    "added",
+                    &workflow.repository,
+                    &workflow.path,
+                ]
+            }
+            OrganizationChange::RequiredWorkflowUpdated(_, workflow) => {
+                vec![
+                    "required",
+                    "workflow",
+                    "updated",
+                    &workflow.repository,
+                    &workflow.path,
+                ]
+            }
+            OrganizationChange::RequiredWorkflowRemoved(_, name) => {
+                vec!["required", "workflow", "removed", name]
+            }
+            OrganizationChange::SecretRepositoriesUpdated(name, _) => {
+                vec!["secret", "repositories", "updated", name]
+            }
+            OrganizationChange::RunnerGroupAdded(runner_group) => {
+                vec!["runner", "group", "added", &runner_group.name]
+            }
+            OrganizationChange::RunnerGroupRepositoriesUpdated(_, runner_group) => {
+                vec!["runner", "group", "repositories", "updated", &runner_group.name]
+            }
+            OrganizationChange::ServiceAccountInvited(user_name) => {
+                vec!["service", "account", "invited", user_name]
+            }
+            OrganizationChange::ServiceAccountDemoted(user_name) => {
+                vec!["service", "account", "demoted", user_name]
+            }
+            OrganizationChange::VerifiedDomainsGateUpdated(_) => {
+                vec!["verified", "domains", "gate", "updated"]
+            }
+            OrganizationChange::TeamCreationPermissionUpdated(_) => {
+                vec!["team", "creation", "permission", "updated"]
+            }
+            OrganizationChange::UserBlocked(user_name) => vec!["user", "blocked", user_name],
+            OrganizationChange::UserUnblocked(user_name) => vec!["user", "unblocked", user_name],
+            OrganizationChange::SecurityManagerTeamAdded(team_name) => {
+                vec!["security", "manager", "team", "added", team_name]
+            }
+            OrganizationChange::SecurityManagerTeamRemoved(team_name) => {
+                vec!["security", "manager", "team", "removed", team_name]
+            }
+        }
+    }
+
+    /// [Change::risk]
+    fn risk(&self) -> ChangeRisk {
+        match self {
+            OrganizationChange::WebhookAdded(_)
+            | OrganizationChange::WebhookUpdated(_)
+            | OrganizationChange::CommunityHealthFileAdded(_, _)
+            | OrganizationChange::CommunityHealthFileUpdated(_, _)
+            | OrganizationChange::ServiceAccountDemoted(_)
+            | OrganizationChange::TeamCreationPermissionUpdated(TeamCreationPermission::Owners)
+            | OrganizationChange::UserBlocked(_)
+            | OrganizationChange::SecurityManagerTeamRemoved(_) => ChangeRisk::Low,
+            OrganizationChange::WebhookRemoved(_)
+            | OrganizationChange::SettingUpdated(_, _)
+            | OrganizationChange::RequiredWorkflowAdded(_)
+            | OrganizationChange::RequiredWorkflowUpdated(_, _)
+            | OrganizationChange::RequiredWorkflowRemoved(_, _)
+            | OrganizationChange::SecretRepositoriesUpdated(_, _)
+            | OrganizationChange::RunnerGroupAdded(_)
+            | OrganizationChange::RunnerGroupRepositoriesUpdated(_, _)
+            | OrganizationChange::ServiceAccountInvited(_)
+            | OrganizationChange::VerifiedDomainsGateUpdated(_)
+            | OrganizationChange::TeamCreationPermissionUpdated(TeamCreationPermission::AllMembers)
+            | OrganizationChange::UserUnblocked(_)
+            | OrganizationChange::SecurityManagerTeamAdded(_) => ChangeRisk::High,
+        }
+    }
+
+    /// [Change::template_format]
+    fn template_format(&self) -> Result<String> {
+        let mut s = String::new();
+
+        match self {
+            OrganizationChange::WebhookAdded(webhook) => {
+                write!(
+                    s,
+                    "- webhook **{}** has been *added* to the organization (events: **{}**)",
+                    webhook.url,
+                    webhook.events.join("**, **")
+                )?;
+            }
+            OrganizationChange::WebhookUpdated(webhook) => {
+                write!(
+                    s,
+                    "- webhook **{}** has been *updated* (events: **{}**, active: **{}**)",
+                    webhook.url,
+                    webhook.events.join("**, **"),
+                    webhook.is_active()
+                )?;
+            }
+            OrganizationChange::WebhookRemoved(url) => {
+                write!(s, "- webhook **{url}** has been *removed* from the organization")?;
+            }
+            OrganizationChange::CommunityHealthFileAdded(path, _) => {
+                write!(
+                    s,
+                    "- community health file **{path}** has been *added* to the organization"
+                )?;
+            }
+            OrganizationChange::CommunityHealthFileUpdated(path, _) => {
+                write!(s, "- community health file **{path}** has been *updated*")?;
+            }
+            OrganizationChange::SettingUpdated(setting, value) => {
+                let status = if *value { "enabled" } else { "disabled" };
+                write!(
+                    s,
+                    "- organization setting **{}** has been *{status}*",
+                    setting.description()
+                )?;
+            }
+            OrganizationChange::RequiredWorkflowAdded(workflow) => {
+                write!(
+                    s,
+                    "- workflow **{}** is now *required* in {}",
+                    workflow.name(),
+                    required_workflow_scope(workflow)
+                )?;
+            }
+            OrganizationChange::RequiredWorkflowUpdated(_, workflow) => {
+                write!(
+                    s,
+                    "- required workflow **{}** has been *updated* (required in {})",
+                    workflow.name(),
+                    required_workflow_scope(workflow)
+                )?;
+            }
+            OrganizationChange::RequiredWorkflowRemoved(_, name) => {
+                write!(
+                    s,
+                    "- workflow **{name}** is *no longer required* in the organization"
+                )?;
+            }
+            OrganizationChange::SecretRepositoriesUpdated(name, repos_names) => {
+                if repos_names.is_empty() {
+                    write!(
+                        s,
+                        "- organization secret **{name}** is *no longer available* to any repository"
+                    )?;
+                } else {
+                    write!(
+                        s,
+                        "- organization secret **{name}** is now *available* to repositories **{}**",
+                        repos_names.join("**, **")
+                    )?;
+                }
+            }
+            OrganizationChange::RunnerGroupAdded(runner_group) => {
+                write!(
+                    s,
+                    "- runner group **{}** has been *added* to the organization",
+                    runner_group.name
+                )?;
+                if !runner_group.repositories.is_empty() {
+                    write!(
+                        s,
+                        " (available to repositories **{}**)",
+                        runner_group.repositories.join("**, **")
+                    )?;
+                }
+            }
+            OrganizationChange::RunnerGroupRepositoriesUpdated(_, runner_group) => {
+                let name = &runner_group.name;
+                if runner_group.repositories.is_empty() {
+                    write!(
+                        s,
+                        "- runner group **{name}** is *no longer available* to any repository"
+                    )?;
+                } else {
+                    write!(
+                        s,
+                        "- runner group **{name}** is now *available* to repositories **{}**",
+                        runner_group.repositories.join("**, **")
+                    )?;
+                }
+            }
+            OrganizationChange::ServiceAccountInvited(user_name) => {
+                write!(
+                    s,
+                    "- service account **{user_name}** has been *invited* to the organization as a member"
+                )?;
+            }
+            OrganizationChange::ServiceAccountDemoted(user_name) => {
+                write!(
+                    s,
+                    "- service account **{user_name}** has been *demoted* from admin to member of the organization"
+                )?;
+            }
+            OrganizationChange::VerifiedDomainsGateUpdated(enabled) => {
+                if *enabled {
+                    write!(
+                        s,
+                        "- verified domains gate has been *enabled* (organization notifications will only be sent to members emails in verified domains)"
+                    )?;
+                } else {
+                    write!(
+                        s,
+                        "- verified domains gate has been *disabled* (organization notifications will be sent to members emails in any domain)"
+                    )?;
+                }
+            }
+            OrganizationChange::TeamCreationPermissionUpdated(permission) => {
+                write!(
+                    s,
+                    "- organization team creation permission has been *updated* (teams can now be created by **{permission}**)"
+                )?;
+            }
+            OrganizationChange::UserBlocked(user_name) => {
+                write!(
+                    s,
+                    "- user **{user_name}** has been *blocked* from the organization"
+                )?;
+            }
+            OrganizationChange::UserUnblocked(user_name) => {
+                write!(
+                    s,
+                    "- user **{user_name}** has been *unblocked* from the organization"
+                )?;
+            }
+            OrganizationChange::SecurityManagerTeamAdded(team_name) => {
+                write!(
+                    s,
+                    "- team **{team_name}** has been granted the *security manager* role"
+                )?;
+            }
+            OrganizationChange::SecurityManagerTeamRemoved(team_name) => {
+                write!(
+                    s,
+                    "- team **{team_name}** security manager role has been *revoked*"
+                )?;
+            }
+        }
+
+        Ok(s)
+    }
+}
+
+/// Returns the details of the webhook provided (secret excluded).
+fn webhook_details(webhook: &Webhook) -> serde_json::Value {
+    json!({
+        "url": webhook.url,
+        "events": webhook.events,
+        "content_type": webhook.content_type(),
+        "active": webhook.is_active(),
+    })
+}
+
+/// Returns a description of the repositories where the required workflow
+/// provided applies.
+fn required_workflow_scope(workflow: &RequiredWorkflow) -> String {
+    let scope = match &workflow.repositories {
+        Some(repositories) => format!("repositories **{}**", repositories.join("**, **")),
+        None => "**all** repositories".to_string(),
+    };
+    match &workflow.ref_ {
+        Some(ref_) => format!("{scope}, using ref **{ref_}**"),
+        None => scope,
+    }
+}
+
+/// Parse the collaborator access expiration date provided (YYYY-MM-DD).
+fn parse_expiration_date(expires_at: &str) -> Result<Date> {
+    Date::parse(expires_at, format_description!("[year]-[month]-[day]"))
+        .map_err(|err| format_err!("invalid expiration date {expires_at}: {err}"))
+}
+
+/// Returns a description of the forking policy provided.
+fn forking_policy(allow_forking: bool) -> &'static str {
+    if allow_forking {
+        "allowed"
+    } else {
+        "not allowed"
+    }
+}
+
+/// Returns a human readable description of the status of a setting that can
+/// be enabled or disabled.
+fn enabled_status(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::cfg::ServiceAccount;
+    use crate::directory::User;
+    use crate::github::MockGH;
+    use crate::services::github::service::{AppInstallation, MergedBranch, MockSvc};
+
+    fn ctx() -> Ctx {
+        Ctx {
+            inst_id: None,
+            org: "org".to_string(),
+        }
+    }
+
+    fn service(svc: MockSvc, ctx: &Ctx) -> ActualStateInfo<'_> {
+        ActualStateInfo::Service {
+            svc: Arc::new(svc),
+            ctx,
+        }
+    }
+
+    fn write_maintain_incomparable() -> RolesOrdering {
+        RolesOrdering::new(vec![
+            vec![Role::Read, Role::Triage, Role::Write, Role::Admin],
+            vec![Role::Read, Role::Triage, Role::Maintain, Role::Admin],
+        ])
+    }
+
+    fn collaborator_with_lower_role_than_team() -> State {
+        State {
+            directory: Directory {
+                teams: vec![Team {
+                    name: "team1".to_string(),
+                    members: vec!["user1".to_string()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Maintain)])),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn promote_org_admins_enabled() {
+        let mut directory = Directory {
+            teams: vec![Team {
+                name: "team1".to_string(),
+                maintainers: vec!["user1".to_string()],
+                members: vec!["admin1".to_string(), "user2".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        promote_org_admins(&Organization::default(), &mut directory, &["admin1".to_string()]);
+        assert_eq!(
+            directory.teams[0].maintainers,
+            vec!["user1".to_string(), "admin1".to_string()]
+        );
+        assert_eq!(directory.teams[0].members, vec!["user2".to_string()]);
+    }
+
+    #[test]
+    fn add_service_accounts_to_teams() {
+        let mut directory = Directory {
+            teams: vec![Team {
+                name: "team1".to_string(),
+                maintainers: vec!["user1".to_string()],
+                members: vec!["user2".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let org = Organization {
+            service_accounts: Some(vec![ServiceAccount {
+                login: "bot1".to_string(),
+                teams: vec!["team1".to_string()],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        add_service_accounts(&org, &mut directory).unwrap();
+        assert_eq!(directory.teams[0].maintainers, vec!["user1".to_string()]);
+        assert_eq!(
+            directory.teams[0].members,
+            vec!["user2".to_string(), "bot1".to_string()]
+        );
+    }
+
+    #[test]
+    fn add_service_accounts_invalid() {
+        let mut directory = Directory {
+            teams: vec![Team {
+                name: "team1".to_string(),
+                maintainers: vec!["bot1".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let org = Organization {
+            service_accounts: Some(vec![
+                ServiceAccount {
+                    login: "bot1".to_string(),
+                    teams: vec!["team1".to_string(), "team2".to_string()],
+                    ..Default::default()
+                },
+                ServiceAccount {
+                    login: "bot1".to_string(),
+                    teams: vec![],
+                    ..Default::default()
+                },
+                ServiceAccount::default(),
+            ]),
+            ..Default::default()
+        };
+        let err = add_service_accounts(&org, &mut directory).unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "service_account[bot1]: cannot be a maintainer of team team1",
+                "service_account[bot1]: team team2 does not exist",
+                "service_account[bot1]: duplicate service account",
+                "service_account[2]: login must be provided",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_watchers_invalid() {
+        let org = Organization {
+            service_accounts: Some(vec![
+                ServiceAccount {
+                    login: "bot1".to_string(),
+                    token: Some("token1".to_string()),
+                    ..Default::default()
+                },
+                ServiceAccount {
+                    login: "bot2".to_string(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+        let repositories = vec![Repository {
+            name: "repo1".to_string(),
+            watchers: Some(vec!["bot1".to_string(), "bot2".to_string(), "user1".to_string()]),
+            ..Default::default()
+        }];
+        let err = validate_watchers(&org, &repositories).unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "repo[repo1]: watcher bot2 does not have a token configured",
+                "repo[repo1]: watcher user1 is not a service account",
+            ]
+        );
+    }
+
+    #[test]
+    fn promote_org_admins_disabled() {
+        let mut directory = Directory {
+            teams: vec![Team {
+                name: "team1".to_string(),
+                maintainers: vec!["user1".to_string()],
+                members: vec!["admin1".to_string(), "user2".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let org = Organization {
+            disable_admins_promotion: true,
+            ..Default::default()
+        };
+        promote_org_admins(&org, &mut directory, &["admin1".to_string()]);
+        assert_eq!(directory.teams[0].maintainers, vec!["user1".to_string()]);
+        assert_eq!(
+            directory.teams[0].members,
+            vec!["admin1".to_string(), "user2".to_string()]
+        );
+    }
+
+    #[test]
+    fn changes_difference() {
+        let changes1 = Changes {
+            directory: vec![
+                DirectoryChange::TeamRemoved("team1".to_string()),
+                DirectoryChange::TeamMaintainerAdded("team2".to_string(), "user1".to_string()),
+            ],
+            repositories: vec![
+                RepositoryChange::TeamAdded("repo1".to_string(), "team2".to_string(), Role::Write),
+                RepositoryChange::VisibilityUpdated("repo1".to_string(), Visibility::Public),
+            ],
+        };
+        let changes2 = Changes {
+            directory: vec![DirectoryChange::TeamRemoved("team1".to_string())],
+            repositories: vec![
+                RepositoryChange::TeamAdded("repo1".to_string(), "team2".to_string(), Role::Maintain),
+                RepositoryChange::VisibilityUpdated("repo1".to_string(), Visibility::Public),
+            ],
+        };
+        assert_eq!(
+            changes1.difference(&changes2),
+            Changes {
+                directory: vec![DirectoryChange::TeamMaintainerAdded(
+                    "team2".to_string(),
+                    "user1".to_string()
+                )],
+                repositories: vec![RepositoryChange::TeamAdded(
+                    "repo1".to_string(),
+                    "team2".to_string(),
+                    Role::Write
+                )],
+            }
+        );
+        assert!(changes1.difference(&changes1).is_empty());
+    }
+
+    #[test]
+    fn diff_user_added_discarded() {
+        let user1 = User {
+            full_name: "user1".to_string(),
+            ..Default::default()
+        };
+        let state1 = State::default();
+        let state2 = State {
+            directory: Directory {
+                users: vec![user1],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_added() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            ..Default::default()
+        };
+        let state1 = State::default();
+        let state2 = State {
+            repositories: vec![repo1.clone()],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::RepositoryAdded(repo1)],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_team_added() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            ..Default::default()
+        };
+        let repo1_adding_team = Repository {
+            teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_adding_team],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::TeamAdded(
+                    "repo1".to_string(),
+                    "team1".to_string(),
+                    Role::Write
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_team_removed() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+            ..Default::default()
+        };
+        let repo1_removing_team = Repository {
+            teams: None,
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_removing_team],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::TeamRemoved(
+                    "repo1".to_string(),
+                    "team1".to_string(),
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_team_role_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+            ..Default::default()
+        };
+        let repo1_updating_team_role = Repository {
+            teams: Some(BTreeMap::from([("team1".to_string(), Role::Read)])),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_team_role],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::TeamRoleUpdated(
+                    "repo1".to_string(),
+                    "team1".to_string(),
+                    Role::Read
+                )],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            state1.diff_explained(&state2).repositories[0].1.to_string(),
+            "team role old=write new=read"
+        );
+    }
+
+    #[test]
+    fn diff_explained_repository_added_and_collaborator_removed() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Maintain)])),
+            ..Default::default()
+        };
+        let repo1_removing_collaborator = Repository {
+            collaborators: None,
+            ..repo1.clone()
+        };
+        let repo2 = Repository {
+            name: "repo2".to_string(),
+            ..Default::default()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_removing_collaborator, repo2],
+            ..Default::default()
+        };
+        let explanations: Vec<String> = state1
+            .diff_explained(&state2)
+            .repositories
+            .into_iter()
+            .map(|(_, explanation)| explanation.to_string())
+            .collect();
+        assert_eq!(
+            explanations,
+            vec![
+                "repository old=- new=repo2",
+                "collaborator role old=maintain new=-"
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_repository_collaborator_added() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            ..Default::default()
+        };
+        let repo1_adding_collaborator = Repository {
+            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_adding_collaborator],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::CollaboratorAdded(
+                    "repo1".to_string(),
+                    "user1".to_string(),
+                    Role::Write
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_collaborator_removed() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
+            ..Default::default()
+        };
+        let repo1_removing_collaborator = Repository {
+            collaborators: None,
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_removing_collaborator],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::CollaboratorRemoved(
+                    "repo1".to_string(),
+                    "user1".to_string(),
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_collaborator_role_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
+            ..Default::default()
+        };
+        let repo1_updating_collaborator_role = Repository {
+            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Read)])),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_collaborator_role],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::CollaboratorRoleUpdated(
+                    "repo1".to_string(),
+                    "user1".to_string(),
+                    Role::Read
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_visibility_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            visibility: Some(Visibility::Private),
+            ..Default::default()
+        };
+        let repo1_updating_visibility = Repository {
+            visibility: Some(Visibility::Public),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_visibility],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::VisibilityUpdated(
+                    "repo1".to_string(),
+                    Visibility::Public
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_forking_policy_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            visibility: Some(Visibility::Private),
+            allow_forking: Some(true),
+            ..Default::default()
+        };
+        let repo1_updating_forking_policy = Repository {
+            allow_forking: Some(false),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_forking_policy],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::ForkingPolicyUpdated("repo1".to_string(), false)],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_forking_policy_not_defined() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            visibility: Some(Visibility::Private),
+            allow_forking: Some(true),
+            ..Default::default()
+        };
+        let repo1_without_forking_policy = Repository {
+            allow_forking: None,
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_without_forking_policy],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_collaborator_access_expiring() {
+        let expires_at = time::OffsetDateTime::now_utc().date() + Duration::days(3);
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            collaborators: Some(BTreeMap::from([
+                ("user1".to_string(), Role::Write),
+                ("user2".to_string(), Role::Write),
+            ])),
+            ..Default::default()
+        };
+        let repo1_with_expirations = Repository {
+            collaborators_expiration: Some(BTreeMap::from([
+                ("user1".to_string(), expires_at.to_string()),
+                ("user2".to_string(), "2999-01-01".to_string()),
+            ])),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_with_expirations],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::CollaboratorAccessExpiring(
+                    "repo1".to_string(),
+                    "user1".to_string(),
+                    expires_at
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn repository_remove_expired_collaborators() {
+        let mut repo = Repository {
+            name: "repo1".to_string(),
+            collaborators: Some(BTreeMap::from([
+                ("user1".to_string(), Role::Write),
+                ("user2".to_string(), Role::Write),
+                ("user3".to_string(), Role::Write),
+            ])),
+            collaborators_expiration: Some(BTreeMap::from([
+                ("user1".to_string(), "2024-01-01".to_string()),
+                ("user2".to_string(), "2024-01-02".to_string()),
+            ])),
+            ..Default::default()
+        };
+        repo.remove_expired_collaborators(parse_expiration_date("2024-01-01").unwrap());
+        assert_eq!(
+            repo.collaborators,
+            Some(BTreeMap::from([
+                ("user2".to_string(), Role::Write),
+                ("user3".to_string(), Role::Write),
+            ]))
+        );
+        assert_eq!(
+            repo.collaborators_expiration,
+            Some(BTreeMap::from([("user2".to_string(), "2024-01-02".to_string())]))
+        );
+        assert_eq!(
+            repo.expired_collaborators,
+            Some(BTreeSet::from(["user1".to_string()]))
+        );
+    }
+
+    #[test]
+    fn diff_repository_discussions_settings_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            discussions: Some(DiscussionSettings {
+                enabled: true,
+                categories: Some(vec!["General".to_string(), "Q&A".to_string()]),
+            }),
+            ..Default::default()
+        };
+        let discussions = DiscussionSettings {
+            enabled: true,
+            categories: Some(vec!["Announcements".to_string(), "General".to_string()]),
+        };
+        let repo1_updating_discussions = Repository {
+            discussions: Some(discussions.clone()),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_discussions],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::DiscussionsSettingsUpdated(
+                    "repo1".to_string(),
+                    discussions
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_discussions_settings_unchanged() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            discussions: Some(DiscussionSettings {
+                enabled: true,
+                categories: Some(vec!["General".to_string(), "Q&A".to_string()]),
+            }),
+            ..Default::default()
+        };
+        let repo1_categories_not_defined = Repository {
+            discussions: Some(DiscussionSettings {
+                enabled: true,
+                categories: None,
+            }),
+            ..repo1.clone()
+        };
+        let repo1_categories_reordered = Repository {
+            discussions: Some(DiscussionSettings {
+                enabled: true,
+                categories: Some(vec!["Q&A".to_string(), "General".to_string()]),
+            }),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        for repo in [repo1_categories_not_defined, repo1_categories_reordered] {
+            let state2 = State {
+                repositories: vec![repo],
+                ..Default::default()
+            };
+            assert_eq!(state1.diff(&state2), Changes::default());
+        }
+    }
+
+    #[test]
+    fn secret_teams_warnings_secret_team_on_public_repo() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([
+                ("team1".to_string(), Role::Maintain),
+                ("team2".to_string(), Role::Write),
+            ])),
+            visibility: Some(Visibility::Public),
+            ..Default::default()
+        };
+        let secret_teams = HashSet::from(["team1".to_string()]);
+        assert_eq!(
+            State::secret_teams_warnings(&[repo1], &secret_teams),
+            vec!["repo[repo1]: maintainers access relies solely on secret teams (team1)".to_string()]
+        );
+    }
+
+    #[test]
+    fn secret_teams_warnings_secret_team_on_private_repo() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([("team1".to_string(), Role::Maintain)])),
+            visibility: Some(Visibility::Private),
+            ..Default::default()
+        };
+        let secret_teams = HashSet::from(["team1".to_string()]);
+        assert!(State::secret_teams_warnings(&[repo1], &secret_teams).is_empty());
+    }
+
+    #[test]
+    fn secret_teams_warnings_secret_and_visible_teams_on_public_repo() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([
+                ("team1".to_string(), Role::Maintain),
+                ("team2".to_string(), Role::Admin),
+            ])),
+            visibility: Some(Visibility::Public),
+            ..Default::default()
+        };
+        let secret_teams = HashSet::from(["team1".to_string()]);
+        assert!(State::secret_teams_warnings(&[repo1], &secret_teams).is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetch_opt_in_settings_fetches_actions_allow_list_only_where_defined() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_repository_actions_allow_list()
+            .withf(|_, repo_name| repo_name == "repo1")
+            .times(1)
+            .returning(|_, _| {
+                Ok(ActionsAllowList {
+                    enabled: true,
+                    github_owned_allowed: true,
+                    ..Default::default()
+                })
+            });
+        let desired_state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    actions_allow_list: Some(ActionsAllowList::default()),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut actual_state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        actual_state
+            .fetch_opt_in_settings(Arc::new(svc), &ctx(), &Organization::default(), &desired_state)
+            .await
+            .unwrap();
+        assert_eq!(
+            actual_state.repositories[0].actions_allow_list,
+            Some(ActionsAllowList {
+                enabled: true,
+                github_owned_allowed: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(actual_state.repositories[1].actions_allow_list, None);
+    }
+
+    #[tokio::test]
+    async fn fetch_opt_in_settings_fetches_watchers() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_repository_watchers()
+            .withf(|_, repo_name| repo_name == "repo1")
+            .times(1)
+            .returning(|_, _| Ok(vec!["bot1".to_string(), "user1".to_string()]));
+        let org = Organization {
+            service_accounts: Some(vec![ServiceAccount {
+                login: "bot1".to_string(),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let desired_state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                watchers: Some(vec!["bot1".to_string()]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut actual_state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        actual_state
+            .fetch_opt_in_settings(Arc::new(svc), &ctx(), &org, &desired_state)
+            .await
+            .unwrap();
+        assert_eq!(
+            actual_state.repositories[0].watchers,
+            Some(vec!["bot1".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_opt_in_settings_fetches_forking_policy_only_for_non_public_repositories() {
+        let mut svc = MockSvc::new();
+        svc.expect_get_repository_allow_forking()
+            .withf(|_, repo_name| repo_name == "repo1")
+            .times(1)
+            .returning(|_, _| Ok(true));
+        let desired_state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    visibility: Some(Visibility::Private),
+                    allow_forking: Some(false),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    visibility: Some(Visibility::Private),
+                    allow_forking: Some(false),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo3".to_string(),
+                    visibility: Some(Visibility::Private),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut actual_state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    visibility: Some(Visibility::Private),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    visibility: Some(Visibility::Public),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo3".to_string(),
+                    visibility: Some(Visibility::Private),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        actual_state
+            .fetch_opt_in_settings(Arc::new(svc), &ctx(), &Organization::default(), &desired_state)
+            .await
+            .unwrap();
+        assert_eq!(actual_state.repositories[0].allow_forking, Some(true));
+        assert_eq!(actual_state.repositories[1].allow_forking, None);
+        assert_eq!(actual_state.repositories[2].allow_forking, None);
+    }
+
+    #[tokio::test]
+    async fn fetch_installed_apps_not_listed_when_no_apps_defined() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_apps_installations().times(0);
+        let desired_state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut actual_state = desired_state.clone();
+
+        actual_state.fetch_installed_apps(Arc::new(svc), &ctx(), &desired_state).await.unwrap();
+        assert_eq!(actual_state.repositories[0].installed_apps, None);
+    }
+
+    #[tokio::test]
+    async fn fetch_installed_apps_only_repositories_defining_them() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_apps_installations().times(1).returning(|_| {
+            Ok(vec![
+                AppInstallation {
+                    id: 1,
+                    app_slug: "app2".to_string(),
+                    repositories: None,
+                },
+                AppInstallation {
+                    id: 2,
+                    app_slug: "app1".to_string(),
+                    repositories: Some(vec!["repo1".to_string()]),
+                },
+                AppInstallation {
+                    id: 3,
+                    app_slug: "app3".to_string(),
+                    repositories: Some(vec!["repo2".to_string()]),
+                },
+            ])
+        });
+        let desired_state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    denied_apps: Some(vec!["app3".to_string()]),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut actual_state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        actual_state.fetch_installed_apps(Arc::new(svc), &ctx(), &desired_state).await.unwrap();
+        assert_eq!(
+            actual_state.repositories[0].installed_apps,
+            Some(vec!["app1".to_string(), "app2".to_string()])
+        );
+        assert_eq!(actual_state.repositories[1].installed_apps, None);
+    }
+
+    #[tokio::test]
+    async fn stale_branches_warnings_old_merged_branches() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_repository_merged_branches()
+            .withf(|_, repo_name| repo_name == "repo1")
+            .returning(|_, _| {
+                let now = time::OffsetDateTime::now_utc();
+                Ok(vec![
+                    MergedBranch {
+                        name: "old-branch".to_string(),
+                        last_commit_at: time::macros::datetime!(2020-01-15 10:00 UTC),
+                    },
+                    MergedBranch {
+                        name: "recent-branch".to_string(),
+                        last_commit_at: now - Duration::days(5),
+                    },
+                ])
+            });
+        let state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let warnings = state.stale_branches_warnings(Arc::new(svc), &ctx(), 30).await.unwrap();
+        assert_eq!(
+            warnings,
+            vec!["repo[repo1]: branch old-branch was merged and has not been updated since 2020-01-15"]
+        );
+    }
+
+    #[test]
+    fn repository_codeowners_warnings_dangling_owners() {
+        let state = State {
+            directory: Directory {
+                teams: vec![
+                    Team {
+                        name: "team1".to_string(),
+                        members: vec!["user1".to_string()],
+                        ..Default::default()
+                    },
+                    Team {
+                        name: "team2".to_string(),
+                        members: vec!["user2".to_string()],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([
+                ("team1".to_string(), Role::Write),
+                ("team2".to_string(), Role::Read),
+            ])),
+            collaborators: Some(BTreeMap::from([("user3".to_string(), Role::Maintain)])),
+            ..Default::default()
+        };
+        let codeowners = r"
+# Default owners
+*       @org/team1 @User1 @user3 owner@example.com
+/docs/  @org/team2 @user2  # docs
+/src/   @org/team3 @other-org/team1
+";
+        assert_eq!(
+            state.repository_codeowners_warnings(&repo1, "org", codeowners),
+            vec![
+                "repo[repo1]: CODEOWNERS line 4: owner @org/team2: team does not have write access to the repository",
+                "repo[repo1]: CODEOWNERS line 4: owner @user2: user does not have write access to the repository",
+                "repo[repo1]: CODEOWNERS line 5: owner @org/team3: team not found in the directory",
+                "repo[repo1]: CODEOWNERS line 5: owner @other-org/team1: team belongs to a different organization",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_dependabot_config_is_valid() {
+        let config: serde_yaml::Value = serde_yaml::from_str(DEFAULT_DEPENDABOT_CONFIG).unwrap();
+        assert_eq!(config["version"], serde_yaml::Value::from(2));
+        assert!(config["updates"].as_sequence().is_some_and(|updates| !updates.is_empty()));
+    }
+
+    #[test]
+    fn codeowners_owners_teams_with_write_access() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([
+                ("team3".to_string(), Role::Admin),
+                ("team1".to_string(), Role::Write),
+                ("team2".to_string(), Role::Triage),
+                ("team4".to_string(), Role::Maintain),
+            ])),
+            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Admin)])),
+            ..Default::default()
+        };
+        let owners = codeowners_owners(&repo1, "org");
+        assert_eq!(owners, vec!["@org/team1", "@org/team3", "@org/team4"]);
+        assert_eq!(
+            codeowners_content(&owners),
+            "# This file is managed by CLOWarden from the teams with write access to\n\
+             # the repository, please do not edit it manually.\n\
+             * @org/team1 @org/team3 @org/team4\n"
+        );
+    }
+
+    #[test]
+    fn diff_multiple_changes() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            teams: Some(BTreeMap::from([
+                ("team1".to_string(), Role::Write),
+                ("team2".to_string(), Role::Write),
+            ])),
+            visibility: Some(Visibility::Private),
+            ..Default::default()
+        };
+        let repo1_updated = Repository {
+            teams: Some(BTreeMap::from([
+                ("team1".to_string(), Role::Write),
+                ("team3".to_string(), Role::Write),
+            ])),
+            visibility: Some(Visibility::Public),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updated],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![
+                    RepositoryChange::TeamRemoved("repo1".to_string(), "team2".to_string()),
+                    RepositoryChange::TeamAdded("repo1".to_string(), "team3".to_string(), Role::Write),
+                    RepositoryChange::VisibilityUpdated("repo1".to_string(), Visibility::Public)
+                ],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn flatten_teams_with_override() {
+        let directory = Directory {
+            teams: vec![Team {
+                name: "team1".to_string(),
+                maintainers: vec!["user1".to_string()],
+                members: vec!["user2".to_string(), "user3".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut repo1 = Repository {
+            name: "repo1".to_string(),
+            collaborators: Some(BTreeMap::from([("user2".to_string(), Role::Admin)])),
+            flattened_teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+            ..Default::default()
+        };
+        repo1.flatten_teams(&directory, &RolesOrdering::default());
+        assert_eq!(
+            repo1.collaborators,
+            Some(BTreeMap::from([
+                ("user1".to_string(), Role::Write),
+                ("user2".to_string(), Role::Admin),
+                ("user3".to_string(), Role::Write),
+            ]))
+        );
+    }
+
+    #[test]
+    fn flatten_teams_highest_role_wins() {
+        let directory = Directory {
+            teams: vec![
+                Team {
+                    name: "team1".to_string(),
+                    members: vec!["user1".to_string()],
+                    ..Default::default()
+                },
+                Team {
+                    name: "team2".to_string(),
+                    members: vec!["user1".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut repo1 = Repository {
+            name: "repo1".to_string(),
+            flattened_teams: Some(BTreeMap::from([
+                ("team1".to_string(), Role::Triage),
+                ("team2".to_string(), Role::Maintain),
+            ])),
+            ..Default::default()
+        };
+        repo1.flatten_teams(&directory, &RolesOrdering::default());
+        assert_eq!(
+            repo1.collaborators,
+            Some(BTreeMap::from([("user1".to_string(), Role::Maintain)]))
+        );
+    }
+
+    #[test]
+    fn flatten_teams_incomparable_roles() {
+        let directory = Directory {
+            teams: vec![
+                Team {
+                    name: "team1".to_string(),
+                    members: vec!["user1".to_string()],
+                    ..Default::default()
+                },
+                Team {
+                    name: "team2".to_string(),
+                    members: vec!["user1".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut repo1 = Repository {
+            name: "repo1".to_string(),
+            flattened_teams: Some(BTreeMap::from([
+                ("team1".to_string(), Role::Write),
+                ("team2".to_string(), Role::Maintain),
+            ])),
+            ..Default::default()
+        };
+        repo1.flatten_teams(&directory, &write_maintain_incomparable());
+        assert_eq!(
+            repo1.collaborators,
+            Some(BTreeMap::from([("user1".to_string(), Role::Write)]))
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_collaborator_role_downgraded() {
+        let state = collaborator_with_lower_role_than_team();
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["repo[repo1]: collaborator user1 already has maintain access from team team1".to_string()]
+        );
+    }
+
+    #[test]
+    fn diff_repository_merge_commit_settings_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            squash_merge_commit_title: Some(SquashMergeCommitTitle::CommitOrPrTitle),
+            squash_merge_commit_message: Some(SquashMergeCommitMessage::CommitMessages),
+            ..Default::default()
+        };
+        let repo1_updating_merge_commit_settings = Repository {
+            squash_merge_commit_title: Some(SquashMergeCommitTitle::PrTitle),
+            squash_merge_commit_message: Some(SquashMergeCommitMessage::PrBody),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_merge_commit_settings],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::MergeCommitSettingsUpdated(
+                    "repo1".to_string(),
+                    MergeCommitSettings {
+                        squash_merge_commit_title: Some(SquashMergeCommitTitle::PrTitle),
+                        squash_merge_commit_message: Some(SquashMergeCommitMessage::PrBody),
+                        ..Default::default()
+                    }
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_merge_commit_title_and_message_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            allow_merge_commit: Some(true),
+            merge_commit_title: Some(MergeCommitTitle::MergeMessage),
+            merge_commit_message: Some(MergeCommitMessage::PrTitle),
+            squash_merge_commit_title: Some(SquashMergeCommitTitle::PrTitle),
+            squash_merge_commit_message: Some(SquashMergeCommitMessage::PrBody),
+            ..Default::default()
+        };
+        let repo1_updating_merge_commit_settings = Repository {
+            squash_merge_commit_title: None,
+            squash_merge_commit_message: None,
+            merge_commit_title: Some(MergeCommitTitle::PrTitle),
+            merge_commit_message: Some(MergeCommitMessage::PrBody),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_merge_commit_settings],
+            ..Default::default()
+        };
+        let changes = state1.diff(&state2);
+        assert_eq!(
+            changes,
+            Changes {
+                repositories: vec![RepositoryChange::MergeCommitSettingsUpdated(
+                    "repo1".to_string(),
+                    MergeCommitSettings {
+                        allow_merge_commit: Some(true),
+                        merge_commit_title: Some(MergeCommitTitle::PrTitle),
+                        merge_commit_message: Some(MergeCommitMessage::PrBody),
+                        ..Default::default()
+                    }
+                )],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            changes.repositories[0].template_format().unwrap(),
+            "- repository **repo1** merge commit settings have been updated to **merge commits: enabled, \
+             merge commits title: pull request title, merge commits message: pull request body**"
+        );
+    }
+
+    #[test]
+    fn diff_repository_merge_commit_title_and_message_unchanged() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            merge_commit_title: Some(MergeCommitTitle::PrTitle),
+            merge_commit_message: Some(MergeCommitMessage::Blank),
+            ..Default::default()
+        };
+        let state1 = State {
+            repositories: vec![Repository {
+                allow_merge_commit: Some(true),
+                ..repo1.clone()
+            }],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn repository_merge_commit_title_and_message_must_be_valid() {
+        let repo: Repository =
+            serde_yaml::from_str("name: repo1\nmerge_commit_title: PR_TITLE\nmerge_commit_message: BLANK\n")
+                .unwrap();
+        assert_eq!(repo.merge_commit_title, Some(MergeCommitTitle::PrTitle));
+        assert_eq!(repo.merge_commit_message, Some(MergeCommitMessage::Blank));
+        let err =
+            serde_yaml::from_str::<Repository>("name: repo1\nmerge_commit_title: PR_BODY\n").unwrap_err();
+        assert!(err.to_string().contains("unknown variant"));
+    }
+
+    #[test]
+    fn diff_repository_actions_allow_list_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            actions_allow_list: Some(ActionsAllowList {
+                enabled: false,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let allow_list = ActionsAllowList {
+            enabled: true,
+            github_owned_allowed: true,
+            verified_allowed: false,
+            patterns_allowed: vec!["org/action@v1".to_string()],
+        };
+        let repo1_updating_allow_list = Repository {
+            actions_allow_list: Some(allow_list.clone()),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_allow_list],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::ActionsAllowListUpdated(
+                    "repo1".to_string(),
+                    allow_list
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_actions_allow_list_patterns_order_ignored() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            actions_allow_list: Some(ActionsAllowList {
+                enabled: true,
+                patterns_allowed: vec!["org/action1@v1".to_string(), "org/action2@v2".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let repo1_reordering_patterns = Repository {
+            actions_allow_list: Some(ActionsAllowList {
+                enabled: true,
+                patterns_allowed: vec!["org/action2@v2".to_string(), "org/action1@v1".to_string()],
+                ..Default::default()
+            }),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_reordering_patterns],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_update_branch_and_signoff_settings_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            allow_update_branch: Some(false),
+            web_commit_signoff_required: Some(false),
+            ..Default::default()
+        };
+        let repo1_updating_settings = Repository {
+            allow_update_branch: Some(true),
+            web_commit_signoff_required: Some(true),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_settings],
+            ..Default::default()
+        };
+        let changes = state1.diff(&state2);
+        assert_eq!(
+            changes,
+            Changes {
+                repositories: vec![RepositoryChange::MergeCommitSettingsUpdated(
+                    "repo1".to_string(),
+                    MergeCommitSettings {
+                        allow_update_branch: Some(true),
+                        web_commit_signoff_required: Some(true),
+                        ..Default::default()
+                    }
+                )],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            changes.repositories[0].template_format().unwrap(),
+            "- repository **repo1** merge commit settings have been updated to **suggest updating pull \
+             request branches: enabled, require sign off on web-based commits: enabled**"
+        );
+    }
+
+    #[test]
+    fn repository_update_branch_and_signoff_settings_must_be_booleans() {
+        let err =
+            serde_yaml::from_str::<Repository>("name: repo1\nallow_update_branch: sometimes\n").unwrap_err();
+        assert!(err.to_string().contains("invalid type"));
+        let err =
+            serde_yaml::from_str::<Repository>("name: repo1\nweb_commit_signoff_required: 1\n").unwrap_err();
+        assert!(err.to_string().contains("invalid type"));
+    }
+
+    #[test]
+    fn diff_repository_merge_commit_settings_not_defined() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            squash_merge_commit_title: Some(SquashMergeCommitTitle::PrTitle),
+            squash_merge_commit_message: Some(SquashMergeCommitMessage::PrBody),
+            ..Default::default()
+        };
+        let repo1_without_merge_commit_settings = Repository {
+            squash_merge_commit_title: None,
+            squash_merge_commit_message: None,
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_without_merge_commit_settings],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_code_scanning_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            code_scanning: Some(CodeScanningSetup {
+                enabled: true,
+                query_suite: Some(CodeScanningQuerySuite::Default),
+            }),
+            ..Default::default()
+        };
+        let repo1_updating_code_scanning = Repository {
+            code_scanning: Some(CodeScanningSetup {
+                enabled: true,
+                query_suite: Some(CodeScanningQuerySuite::Extended),
+            }),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_code_scanning],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::CodeScanningUpdated(
+                    "repo1".to_string(),
+                    CodeScanningSetup {
+                        enabled: true,
+                        query_suite: Some(CodeScanningQuerySuite::Extended),
+                    }
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_private_vulnerability_reporting_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            visibility: Some(Visibility::Public),
+            private_vulnerability_reporting: Some(true),
+            ..Default::default()
+        };
+        let repo1_disabling_pvr = Repository {
+            private_vulnerability_reporting: Some(false),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_disabling_pvr],
+            ..Default::default()
+        };
+        let changes = state1.diff(&state2);
+        assert_eq!(
+            changes,
+            Changes {
+                repositories: vec![RepositoryChange::PrivateVulnerabilityReportingUpdated(
+                    "repo1".to_string(),
+                    false
+                )],
+                ..Default::default()
+            }
+        );
+        assert_eq!(changes.repositories[0].risk(), ChangeRisk::High);
+        assert_eq!(
+            changes.repositories[0].template_format().unwrap(),
+            "- repository **repo1** private vulnerability reporting has been **disabled**"
+        );
+    }
+
+    #[test]
+    fn diff_repository_private_vulnerability_reporting_not_defined() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            visibility: Some(Visibility::Public),
+            private_vulnerability_reporting: Some(true),
+            ..Default::default()
+        };
+        let repo1_without_pvr = Repository {
+            private_vulnerability_reporting: None,
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_without_pvr],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_template_status_enabled() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            ..Default::default()
+        };
+        let repo1_template = Repository {
+            is_template: Some(true),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_template],
+            ..Default::default()
+        };
+        let changes = state1.diff(&state2);
+        assert_eq!(
+            changes,
+            Changes {
+                repositories: vec![RepositoryChange::TemplateStatusUpdated("repo1".to_string(), true)],
+                ..Default::default()
+            }
+        );
+        assert_eq!(changes.repositories[0].risk(), ChangeRisk::Low);
+        assert_eq!(
+            changes.repositories[0].template_format().unwrap(),
+            "- repository **repo1** template status has been **enabled**"
+        );
+    }
+
+    #[test]
+    fn diff_repository_template_status_disabled() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            is_template: Some(true),
+            ..Default::default()
+        };
+        let repo1_not_template = Repository {
+            is_template: Some(false),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_not_template],
+            ..Default::default()
+        };
+        let changes = state1.diff(&state2);
+        assert_eq!(
+            changes,
+            Changes {
+                repositories: vec![RepositoryChange::TemplateStatusUpdated(
+                    "repo1".to_string(),
+                    false
+                )],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            changes.repositories[0].template_format().unwrap(),
+            "- repository **repo1** template status has been **disabled**"
+        );
+    }
 
     #[test]
-    fn diff_user_added_discarded() {
-        let user1 = User {
-            full_name: "user1".to_string(),
+    fn diff_repository_template_status_not_defined_or_unchanged() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            is_template: Some(true),
+            ..Default::default()
+        };
+        let repo1_without_template_status = Repository {
+            is_template: None,
+            ..repo1.clone()
+        };
+        let repo2 = Repository {
+            name: "repo2".to_string(),
+            ..Default::default()
+        };
+        let repo2_not_template = Repository {
+            is_template: Some(false),
+            ..repo2.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1, repo2],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_without_template_status, repo2_not_template],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_watchers() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            watchers: Some(vec!["bot1".to_string()]),
+            ..Default::default()
+        };
+        let repo1_updated = Repository {
+            watchers: Some(vec!["bot2".to_string()]),
+            ..repo1.clone()
+        };
+        let repo2 = Repository {
+            name: "repo2".to_string(),
+            watchers: Some(vec!["bot1".to_string()]),
+            ..Default::default()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updated, repo2.clone()],
+            ..Default::default()
+        };
+        let changes = state1.diff(&state2);
+        assert_eq!(
+            changes,
+            Changes {
+                repositories: vec![
+                    RepositoryChange::RepositoryAdded(repo2),
+                    RepositoryChange::WatcherAdded("repo2".to_string(), "bot1".to_string()),
+                    RepositoryChange::WatcherAdded("repo1".to_string(), "bot2".to_string()),
+                    RepositoryChange::WatcherRemoved("repo1".to_string(), "bot1".to_string()),
+                ],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            changes.repositories[3].template_format().unwrap(),
+            "- service account **bot1** is *no longer watching* repository **repo1**"
+        );
+    }
+
+    #[test]
+    fn diff_repository_watchers_not_defined() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            watchers: Some(vec!["bot1".to_string()]),
+            ..Default::default()
+        };
+        let repo1_without_watchers = Repository {
+            watchers: None,
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_without_watchers],
+            ..Default::default()
+        };
+        assert!(state1.diff(&state2).repositories.is_empty());
+    }
+
+    #[test]
+    fn diff_repository_code_scanning_query_suite_not_defined() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            code_scanning: Some(CodeScanningSetup {
+                enabled: true,
+                query_suite: Some(CodeScanningQuerySuite::Extended),
+            }),
+            ..Default::default()
+        };
+        let repo1_without_query_suite = Repository {
+            code_scanning: Some(CodeScanningSetup {
+                enabled: true,
+                query_suite: None,
+            }),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_without_query_suite],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_topics_updated() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            topics: Some(vec!["cncf".to_string(), "kubernetes".to_string()]),
+            ..Default::default()
+        };
+        let repo1_updating_topics = Repository {
+            topics: Some(vec!["cncf".to_string(), "security".to_string()]),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_updating_topics],
+            ..Default::default()
+        };
+        assert_eq!(
+            state1.diff(&state2),
+            Changes {
+                repositories: vec![RepositoryChange::TopicsUpdated(
+                    "repo1".to_string(),
+                    vec!["cncf".to_string(), "security".to_string()]
+                )],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn diff_repository_topics_normalized_unchanged() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            topics: Some(vec!["cloud-native".to_string(), "kubernetes".to_string()]),
+            ..Default::default()
+        };
+        let mut topics = vec![
+            "Kubernetes".to_string(),
+            "Cloud Native".to_string(),
+            "kubernetes".to_string(),
+        ];
+        normalize_topics(&mut topics);
+        let repo1_with_mixed_case_topics = Repository {
+            topics: Some(topics),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_with_mixed_case_topics],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_topics_not_defined() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            topics: Some(vec!["cncf".to_string()]),
+            ..Default::default()
+        };
+        let repo1_without_topics = Repository {
+            topics: None,
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
+            ..Default::default()
+        };
+        let state2 = State {
+            repositories: vec![repo1_without_topics],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[test]
+    fn diff_repository_code_scanning_unsupported() {
+        let repo1 = Repository {
+            name: "repo1".to_string(),
+            ..Default::default()
+        };
+        let repo1_enabling_code_scanning = Repository {
+            code_scanning: Some(CodeScanningSetup {
+                enabled: true,
+                query_suite: None,
+            }),
+            ..repo1.clone()
+        };
+        let state1 = State {
+            repositories: vec![repo1],
             ..Default::default()
         };
-        let state1 = State::default();
         let state2 = State {
+            repositories: vec![repo1_enabling_code_scanning],
+            ..Default::default()
+        };
+        assert_eq!(state1.diff(&state2), Changes::default());
+    }
+
+    #[tokio::test]
+    async fn validate_squash_merge_commit_settings() {
+        let state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    squash_merge_commit_title: Some(SquashMergeCommitTitle::PrTitle),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    squash_merge_commit_title: Some(SquashMergeCommitTitle::PrTitle),
+                    squash_merge_commit_message: Some(SquashMergeCommitMessage::CommitMessages),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo3".to_string(),
+                    squash_merge_commit_title: Some(SquashMergeCommitTitle::PrTitle),
+                    squash_merge_commit_message: Some(SquashMergeCommitMessage::Blank),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "repo[repo1]: squash_merge_commit_title and squash_merge_commit_message must be set together"
+                    .to_string(),
+                "repo[repo2]: squash merge commit message COMMIT_MESSAGES cannot be used with title PR_TITLE"
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_merge_commit_settings() {
+        let state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    merge_commit_title: Some(MergeCommitTitle::PrTitle),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    merge_commit_title: Some(MergeCommitTitle::MergeMessage),
+                    merge_commit_message: Some(MergeCommitMessage::PrBody),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo3".to_string(),
+                    allow_merge_commit: Some(false),
+                    merge_commit_title: Some(MergeCommitTitle::PrTitle),
+                    merge_commit_message: Some(MergeCommitMessage::PrBody),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo4".to_string(),
+                    allow_merge_commit: Some(true),
+                    merge_commit_title: Some(MergeCommitTitle::PrTitle),
+                    merge_commit_message: Some(MergeCommitMessage::Blank),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "repo[repo1]: merge_commit_title and merge_commit_message must be set together".to_string(),
+                "repo[repo2]: merge commit message PR_BODY cannot be used with title MERGE_MESSAGE"
+                    .to_string(),
+                "repo[repo3]: merge_commit_title and merge_commit_message can only be set when merge commits are allowed"
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_repository_name_forbidden() {
+        let state = State {
+            repositories: vec![
+                Repository {
+                    name: "test-repo1".to_string(),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+        svc.expect_list_repositories().returning(|_| Ok(vec![]));
+
+        let forbidden_repository_names = vec!["^test-".to_string(), "^tmp-".to_string()];
+        let err = state
+            .validate(
+                &service(svc, &ctx()),
+                &RolesOrdering::default(),
+                &forbidden_repository_names,
+            )
+            .await
+            .unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["repo[test-repo1]: name matches forbidden pattern ^test-".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_templates_only_checked_for_new_repositories() {
+        let state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    gitignore_template: Some("Unknown".to_string()),
+                    license_template: Some("unknown".to_string()),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    gitignore_template: Some("Rust".to_string()),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo3".to_string(),
+                    gitignore_template: Some("Unknown".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+        svc.expect_list_repositories().times(1).returning(|_| {
+            Ok(vec![serde_json::from_value(
+                serde_json::json!({ "name": "repo1" }),
+            )
+            .unwrap()])
+        });
+        svc.expect_list_gitignore_templates().times(1).returning(|_| Ok(vec!["Rust".to_string()]));
+        svc.expect_list_license_templates().never();
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["repo[repo3]: gitignore template Unknown is not available".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_repository_name_allowed() {
+        let state = State {
+            repositories: vec![Repository {
+                name: "repo1-test".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+        svc.expect_list_repositories().returning(|_| Ok(vec![]));
+
+        let forbidden_repository_names = vec!["^test-".to_string(), "^tmp-".to_string()];
+        state
+            .validate(
+                &service(svc, &ctx()),
+                &RolesOrdering::default(),
+                &forbidden_repository_names,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn validate_invalid_topics() {
+        let mut topics = vec![
+            "Cloud Native".to_string(),
+            "k8s_operator".to_string(),
+            "-leading-hyphen".to_string(),
+        ];
+        normalize_topics(&mut topics);
+        let state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                topics: Some(topics),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "repo[repo1]: invalid topic -leading-hyphen (only lowercase letters, numbers and hyphens are allowed, up to 50 characters)",
+                "repo[repo1]: invalid topic k8s_operator (only lowercase letters, numbers and hyphens are allowed, up to 50 characters)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_invalid_actions_allow_list_patterns() {
+        let state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                actions_allow_list: Some(ActionsAllowList {
+                    enabled: true,
+                    patterns_allowed: vec![
+                        "org/*".to_string(),
+                        "org/repo/path@v1".to_string(),
+                        "org action@v1".to_string(),
+                        "action".to_string(),
+                    ],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "repo[repo1]: invalid actions allow-list pattern org action@v1 (expected owner/repo@ref, owner/repo/path@ref or owner/*)",
+                "repo[repo1]: invalid actions allow-list pattern action (expected owner/repo@ref, owner/repo/path@ref or owner/*)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_codeowners_without_owners() {
+        let state = State {
             directory: Directory {
-                users: vec![user1],
+                teams: vec![Team {
+                    name: "team1".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Read)])),
+                codeowners: Some(CodeownersManagement::default()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "repo[repo1]: codeowners: at least one team with write access to the repository is required"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_private_vulnerability_reporting_not_public() {
+        let state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    visibility: Some(Visibility::Public),
+                    private_vulnerability_reporting: Some(true),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    visibility: Some(Visibility::Private),
+                    private_vulnerability_reporting: Some(true),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        let err = state.validate(&service(svc, &ctx()), &RolesOrdering::default(), &[]).await.unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
+        assert_eq!(
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec!["repo[repo2]: private_vulnerability_reporting can only be set for public repositories"]
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_collaborator_role_incomparable_with_team_role() {
+        let state = collaborator_with_lower_role_than_team();
+        let mut svc = MockSvc::new();
+        svc.expect_list_org_members().returning(|_| Ok(vec![]));
+
+        state.validate(&service(svc, &ctx()), &write_maintain_incomparable(), &[]).await.unwrap();
+    }
+
+    fn offline_gh(permissions_file: &'static str) -> MockGH {
+        let mut gh = MockGH::new();
+        gh.expect_get_file_content().returning(move |_, path| match path {
+            "config.yaml" => Ok(permissions_file.to_string()),
+            _ => Err(format_err!("404 Not Found")),
+        });
+        gh
+    }
+
+    fn offline_org() -> Organization {
+        Organization {
+            name: "org".to_string(),
+            legacy: crate::cfg::Legacy {
+                enabled: true,
+                sheriff_permissions_path: "config.yaml".to_string(),
                 ..Default::default()
             },
             ..Default::default()
-        };
-        assert_eq!(state1.diff(&state2), Changes::default());
+        }
     }
 
-    #[test]
-    fn diff_repository_added() {
-        let repo1 = Repository {
-            name: "repo1".to_string(),
-            ..Default::default()
-        };
-        let state1 = State::default();
-        let state2 = State {
-            repositories: vec![repo1.clone()],
+    fn offline_src() -> Source {
+        Source {
+            inst_id: None,
+            owner: "org".to_string(),
+            repo: ".clowarden".to_string(),
+            ref_: "main".to_string(),
+            location: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn new_from_config_offline_valid() {
+        let gh = offline_gh(
+            r"
+teams:
+  - name: team1
+    maintainers:
+      - user1
+repositories:
+  - name: repo1
+    teams:
+      team1: write
+    gitignore_template: Rust
+  - name: repo2
+    teams:
+      team1: read
+",
+        );
+        let snapshot = OrgSnapshot {
+            org_members: vec!["user1".to_string()],
+            archived_repositories: vec!["repo2".to_string()],
+            gitignore_templates: vec!["Rust".to_string()],
             ..Default::default()
         };
+
+        let state = State::new_from_config_offline(Arc::new(gh), &offline_org(), &offline_src(), &snapshot)
+            .await
+            .unwrap();
+        assert_eq!(state.directory.teams.len(), 1);
         assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![RepositoryChange::RepositoryAdded(repo1)],
-                ..Default::default()
-            }
+            state.repositories.iter().map(|r| r.name.as_str()).collect::<Vec<&str>>(),
+            vec!["repo1"]
         );
     }
 
-    #[test]
-    fn diff_repository_team_added() {
-        let repo1 = Repository {
-            name: "repo1".to_string(),
-            ..Default::default()
-        };
-        let repo1_adding_team = Repository {
-            teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
-            ..repo1.clone()
-        };
-        let state1 = State {
-            repositories: vec![repo1],
-            ..Default::default()
-        };
-        let state2 = State {
-            repositories: vec![repo1_adding_team],
-            ..Default::default()
-        };
+    #[tokio::test]
+    async fn new_from_config_offline_invalid() {
+        let gh = offline_gh(
+            r"
+teams:
+  - name: team1
+    maintainers:
+      - user1
+repositories:
+  - name: repo1
+    teams:
+      team1: write
+    gitignore_template: Rust
+",
+        );
+        let snapshot = OrgSnapshot::default();
+
+        let err = State::new_from_config_offline(Arc::new(gh), &offline_org(), &offline_src(), &snapshot)
+            .await
+            .unwrap_err();
+        let merr = err.downcast_ref::<MultiError>().unwrap();
         assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![RepositoryChange::TeamAdded(
-                    "repo1".to_string(),
-                    "team1".to_string(),
-                    Role::Write
-                )],
-                ..Default::default()
-            }
+            merr.errors().iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "team[team1]: user1 must be an organization member to be a maintainer".to_string(),
+                "repo[repo1]: gitignore template Rust is not available".to_string(),
+            ]
         );
     }
 
     #[test]
-    fn diff_repository_team_removed() {
-        let repo1 = Repository {
-            name: "repo1".to_string(),
-            teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
-            ..Default::default()
-        };
-        let repo1_removing_team = Repository {
-            teams: None,
-            ..repo1.clone()
-        };
-        let state1 = State {
-            repositories: vec![repo1],
+    fn warnings_offline_secret_teams() {
+        let state = State {
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Maintain)])),
+                ..Default::default()
+            }],
             ..Default::default()
         };
-        let state2 = State {
-            repositories: vec![repo1_removing_team],
+        let snapshot = OrgSnapshot {
+            secret_teams: vec!["team1".to_string()],
             ..Default::default()
         };
+
         assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![RepositoryChange::TeamRemoved(
-                    "repo1".to_string(),
-                    "team1".to_string(),
-                )],
-                ..Default::default()
-            }
+            state.warnings_offline(&snapshot),
+            vec!["repo[repo1]: maintainers access relies solely on secret teams (team1)".to_string()]
         );
     }
 
     #[test]
-    fn diff_repository_team_role_updated() {
-        let repo1 = Repository {
-            name: "repo1".to_string(),
-            teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
-            ..Default::default()
-        };
-        let repo1_updating_team_role = Repository {
-            teams: Some(BTreeMap::from([("team1".to_string(), Role::Read)])),
-            ..repo1.clone()
-        };
-        let state1 = State {
-            repositories: vec![repo1],
-            ..Default::default()
+    fn roles_ordering_is_higher() {
+        let default = RolesOrdering::default();
+        assert!(default.is_higher(&Role::Maintain, &Role::Write));
+        assert!(!default.is_higher(&Role::Write, &Role::Maintain));
+        assert!(!default.is_higher(&Role::Write, &Role::Write));
+
+        let custom = write_maintain_incomparable();
+        assert!(!custom.is_higher(&Role::Maintain, &Role::Write));
+        assert!(!custom.is_higher(&Role::Write, &Role::Maintain));
+        assert!(custom.is_higher(&Role::Admin, &Role::Maintain));
+        assert!(custom.is_higher(&Role::Write, &Role::Read));
+    }
+
+    #[test]
+    fn lint_suggestions() {
+        let desired_state = State {
+            directory: Directory {
+                teams: vec![
+                    Team {
+                        name: "team1".to_string(),
+                        members: vec!["user1".to_string()],
+                        ..Default::default()
+                    },
+                    Team {
+                        name: "team2".to_string(),
+                        ..Default::default()
+                    },
+                ],
+                users: vec![
+                    User {
+                        full_name: "User 1".to_string(),
+                        user_name: Some("user1".to_string()),
+                        ..Default::default()
+                    },
+                    User {
+                        full_name: "User 2".to_string(),
+                        user_name: Some("user2".to_string()),
+                        ..Default::default()
+                    },
+                ],
+            },
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                collaborators: Some(BTreeMap::from([
+                    ("user1".to_string(), Role::Write),
+                    ("user2".to_string(), Role::Write),
+                ])),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Maintain)])),
+                ..Default::default()
+            }],
         };
-        let state2 = State {
-            repositories: vec![repo1_updating_team_role],
+        let actual_state = State {
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    ..Default::default()
+                },
+            ],
             ..Default::default()
         };
         assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![RepositoryChange::TeamRoleUpdated(
-                    "repo1".to_string(),
-                    "team1".to_string(),
-                    Role::Read
-                )],
-                ..Default::default()
-            }
+            desired_state.lint(&actual_state, &RolesOrdering::default()),
+            BTreeMap::from([
+                (
+                    "Collaborators covered by teams",
+                    vec![
+                        "repo[repo1]: collaborator user1 can be removed, they already get maintain access \
+                        from team team1"
+                            .to_string()
+                    ]
+                ),
+                (
+                    "Teams without repositories",
+                    vec!["team[team2]: not used in any repository".to_string()]
+                ),
+                (
+                    "Unmanaged repositories",
+                    vec!["repo[repo2]: not defined in the configuration".to_string()]
+                ),
+                (
+                    "Users not in any team",
+                    vec!["user[User 2]: user2 does not belong to any team".to_string()]
+                ),
+            ])
         );
     }
 
     #[test]
-    fn diff_repository_collaborator_added() {
+    fn diff_repository_unexpected_app_access() {
         let repo1 = Repository {
             name: "repo1".to_string(),
+            installed_apps: Some(vec!["app1".to_string(), "app2".to_string(), "app3".to_string()]),
             ..Default::default()
         };
-        let repo1_adding_collaborator = Repository {
-            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
-            ..repo1.clone()
+        let repo1_restricting_apps = Repository {
+            name: "repo1".to_string(),
+            allowed_apps: Some(vec!["app1".to_string(), "app2".to_string()]),
+            denied_apps: Some(vec!["app2".to_string()]),
+            ..Default::default()
         };
         let state1 = State {
             repositories: vec![repo1],
             ..Default::default()
         };
         let state2 = State {
-            repositories: vec![repo1_adding_collaborator],
+            repositories: vec![repo1_restricting_apps],
             ..Default::default()
         };
         assert_eq!(
             state1.diff(&state2),
             Changes {
-                repositories: vec![RepositoryChange::CollaboratorAdded(
-                    "repo1".to_string(),
-                    "user1".to_string(),
-                    Role::Write
-                )],
+                repositories: vec![
+                    RepositoryChange::UnexpectedAppAccess("repo1".to_string(), "app2".to_string()),
+                    RepositoryChange::UnexpectedAppAccess("repo1".to_string(), "app3".to_string()),
+                ],
                 ..Default::default()
             }
         );
     }
 
     #[test]
-    fn diff_repository_collaborator_removed() {
+    fn diff_repository_apps_not_restricted() {
         let repo1 = Repository {
             name: "repo1".to_string(),
-            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
+            installed_apps: Some(vec!["app1".to_string()]),
             ..Default::default()
         };
-        let repo1_removing_collaborator = Repository {
-            collaborators: None,
-            ..repo1.clone()
-        };
         let state1 = State {
             repositories: vec![repo1],
             ..Default::default()
         };
         let state2 = State {
-            repositories: vec![repo1_removing_collaborator],
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                ..Default::default()
+            }],
             ..Default::default()
         };
-        assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![RepositoryChange::CollaboratorRemoved(
-                    "repo1".to_string(),
-                    "user1".to_string(),
-                )],
-                ..Default::default()
-            }
-        );
+        assert_eq!(state1.diff(&state2), Changes::default());
     }
 
     #[test]
-    fn diff_repository_collaborator_role_updated() {
-        let repo1 = Repository {
-            name: "repo1".to_string(),
-            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Write)])),
+    fn users_impact_team_and_collaborator_changes() {
+        let team1 = |members: Vec<&str>| Team {
+            name: "team1".to_string(),
+            members: members.into_iter().map(ToString::to_string).collect(),
             ..Default::default()
         };
-        let repo1_updating_collaborator_role = Repository {
-            collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Read)])),
-            ..repo1.clone()
-        };
         let state1 = State {
-            repositories: vec![repo1],
-            ..Default::default()
+            directory: Directory {
+                teams: vec![team1(vec!["user1", "user2"])],
+                ..Default::default()
+            },
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                collaborators: Some(BTreeMap::from([("user3".to_string(), Role::Read)])),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+                ..Default::default()
+            }],
         };
         let state2 = State {
-            repositories: vec![repo1_updating_collaborator_role],
-            ..Default::default()
+            directory: Directory {
+                teams: vec![team1(vec!["user1", "user4"])],
+                ..Default::default()
+            },
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                collaborators: Some(BTreeMap::from([
+                    ("user1".to_string(), Role::Admin),
+                    ("user3".to_string(), Role::Maintain),
+                ])),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+                ..Default::default()
+            }],
+        };
+        let access_change = |old_role: Option<&str>, new_role: Option<&str>| AccessChange {
+            resource: "repo1".to_string(),
+            old_role: old_role.map(ToString::to_string),
+            new_role: new_role.map(ToString::to_string),
         };
         assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![RepositoryChange::CollaboratorRoleUpdated(
-                    "repo1".to_string(),
-                    "user1".to_string(),
-                    Role::Read
-                )],
-                ..Default::default()
-            }
+            state1.users_impact(&state2, &RolesOrdering::default()),
+            vec![
+                UserImpact {
+                    user_name: "user1".to_string(),
+                    access_changes: vec![access_change(Some("write"), Some("admin"))],
+                },
+                UserImpact {
+                    user_name: "user2".to_string(),
+                    access_changes: vec![access_change(Some("write"), None)],
+                },
+                UserImpact {
+                    user_name: "user3".to_string(),
+                    access_changes: vec![access_change(Some("read"), Some("maintain"))],
+                },
+                UserImpact {
+                    user_name: "user4".to_string(),
+                    access_changes: vec![access_change(None, Some("write"))],
+                },
+            ]
         );
     }
 
     #[test]
-    fn diff_repository_visibility_updated() {
-        let repo1 = Repository {
-            name: "repo1".to_string(),
-            visibility: Some(Visibility::Private),
-            ..Default::default()
-        };
-        let repo1_updating_visibility = Repository {
-            visibility: Some(Visibility::Public),
-            ..repo1.clone()
-        };
+    fn users_impact_no_effective_changes() {
         let state1 = State {
-            repositories: vec![repo1],
-            ..Default::default()
-        };
-        let state2 = State {
-            repositories: vec![repo1_updating_visibility],
-            ..Default::default()
-        };
-        assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![RepositoryChange::VisibilityUpdated(
-                    "repo1".to_string(),
-                    Visibility::Public
-                )],
+            directory: Directory {
+                teams: vec![Team {
+                    name: "team1".to_string(),
+                    members: vec!["user1".to_string()],
+                    ..Default::default()
+                }],
                 ..Default::default()
-            }
-        );
+            },
+            repositories: vec![Repository {
+                name: "repo1".to_string(),
+                teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+                ..Default::default()
+            }],
+        };
+
+        // Collaborator explicitly added with the same role the team provides
+        let mut state2 = state1.clone();
+        state2.repositories[0].collaborators = Some(BTreeMap::from([("user1".to_string(), Role::Write)]));
+        assert!(state1.users_impact(&state2, &RolesOrdering::default()).is_empty());
     }
 
     #[test]
-    fn diff_multiple_changes() {
-        let repo1 = Repository {
-            name: "repo1".to_string(),
-            teams: Some(BTreeMap::from([
-                ("team1".to_string(), Role::Write),
-                ("team2".to_string(), Role::Write),
-            ])),
-            visibility: Some(Visibility::Private),
-            ..Default::default()
-        };
-        let repo1_updated = Repository {
-            teams: Some(BTreeMap::from([
-                ("team1".to_string(), Role::Write),
-                ("team3".to_string(), Role::Write),
-            ])),
-            visibility: Some(Visibility::Public),
-            ..repo1.clone()
-        };
+    fn pr_author_warnings_self_escalation() {
         let state1 = State {
-            repositories: vec![repo1],
-            ..Default::default()
-        };
-        let state2 = State {
-            repositories: vec![repo1_updated],
-            ..Default::default()
-        };
-        assert_eq!(
-            state1.diff(&state2),
-            Changes {
-                repositories: vec![
-                    RepositoryChange::TeamRemoved("repo1".to_string(), "team2".to_string()),
-                    RepositoryChange::TeamAdded("repo1".to_string(), "team3".to_string(), Role::Write),
-                    RepositoryChange::VisibilityUpdated("repo1".to_string(), Visibility::Public)
+            directory: Directory {
+                teams: vec![
+                    Team {
+                        name: "team1".to_string(),
+                        maintainers: vec!["user2".to_string()],
+                        members: vec!["user3".to_string()],
+                        ..Default::default()
+                    },
+                    Team {
+                        name: "team2".to_string(),
+                        maintainers: vec!["user1".to_string()],
+                        ..Default::default()
+                    },
                 ],
                 ..Default::default()
-            }
+            },
+            repositories: vec![
+                Repository {
+                    name: "repo1".to_string(),
+                    teams: Some(BTreeMap::from([("team1".to_string(), Role::Write)])),
+                    ..Default::default()
+                },
+                Repository {
+                    name: "repo2".to_string(),
+                    collaborators: Some(BTreeMap::from([("user1".to_string(), Role::Read)])),
+                    ..Default::default()
+                },
+            ],
+        };
+
+        // Author adds themselves to a team they don't maintain, raises their
+        // own role in a repository and adds a member to the team they
+        // maintain (which is fine)
+        let mut state2 = state1.clone();
+        state2.directory.teams[0].members.push("user1".to_string());
+        state2.directory.teams[1].members.push("user4".to_string());
+        state2.repositories[1].collaborators = Some(BTreeMap::from([("user1".to_string(), Role::Write)]));
+        assert_eq!(
+            state1.pr_author_warnings(&state2, "user1", &RolesOrdering::default()),
+            vec![
+                "team[team1]: pull request author user1 adds themselves to the team",
+                "team[team1]: pull request author user1 modifies a team they don't maintain",
+                "repo[repo1]: pull request author user1 grants themselves write access",
+                "repo[repo2]: pull request author user1 grants themselves write access",
+            ]
+        );
+
+        // Maintainers can modify their own teams
+        let mut state3 = state1.clone();
+        state3.directory.teams[0].members.push("user4".to_string());
+        assert!(state1.pr_author_warnings(&state3, "user2", &RolesOrdering::default()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn new_from_service_skips_retired_teams() {
+        let mut svc = MockSvc::new();
+        svc.expect_list_teams().times(1).returning(|_| {
+            Ok(vec![
+                serde_json::from_value(serde_json::json!({ "slug": "team1", "name": "team1" })).unwrap(),
+                serde_json::from_value(serde_json::json!({
+                    "slug": "archived-team2",
+                    "name": "archived-team2",
+                    "description": RETIRED_TEAM_DESCRIPTION,
+                }))
+                .unwrap(),
+            ])
+        });
+        svc.expect_list_team_maintainers()
+            .withf(|_, team_name| team_name == "team1")
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+        svc.expect_list_team_members()
+            .withf(|_, team_name| team_name == "team1")
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+        svc.expect_list_team_invitations()
+            .withf(|_, team_name| team_name == "team1")
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+        svc.expect_list_org_admins().returning(|_| Ok(vec![]));
+        svc.expect_list_repositories().returning(|_| Ok(vec![]));
+
+        let state = State::new_from_service(Arc::new(svc), &ctx()).await.unwrap();
+        assert_eq!(
+            state.directory.teams.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(),
+            vec!["team1"]
         );
     }
 }
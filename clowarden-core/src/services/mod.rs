@@ -1,13 +1,22 @@
 //! This module defines some types and traits that service handlers
 //! implementations will rely upon.
 
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    fmt::{self, Debug},
+    sync::Arc,
+};
 
 use anyhow::Result;
 use as_any::AsAny;
 use async_trait::async_trait;
+#[cfg(any(test, feature = "mocks"))]
+use mockall::automock;
 
-use crate::{cfg::Organization, github::Source};
+use crate::{
+    cfg::{Organization, ValidationExemption},
+    directory::UserName,
+    github::Source,
+};
 
 pub mod github;
 
@@ -16,24 +25,190 @@ pub type ServiceName = &'static str;
 
 /// Trait that defines some operations a service handler must support.
 #[async_trait]
+#[allow(clippy::ref_option_ref)]
+#[cfg_attr(any(test, feature = "mocks"), automock)]
 pub trait ServiceHandler {
     /// Return a summary of the changes detected in the service's state as
-    /// defined in the configuration from the base to the head reference.
-    async fn get_changes_summary(&self, org: &Organization, head_src: &Source) -> Result<ChangesSummary>;
+    /// defined in the configuration from the base to the head reference. When
+    /// the author of the pull request proposing the changes is provided, the
+    /// changes they may not be entitled to make are reported as well. The
+    /// failures of the validation checks exempted are reported as warnings.
+    async fn get_changes_summary(
+        &self,
+        org: &Organization,
+        head_src: &Source,
+        pr_author: Option<&UserName>,
+        exemptions: &[ValidationExemption],
+    ) -> Result<ChangesSummary>;
 
     /// Apply the changes needed so that the actual state (as defined in the
     /// service) matches the desired state (as defined in the configuration).
-    async fn reconcile(&self, org: &Organization) -> Result<ChangesApplied>;
+    /// When a snapshot of the actual state taken in a previous reconciliation
+    /// is provided, the resources that haven't been updated since then may be
+    /// read from it instead of from the service (incremental reconcile). In
+    /// full reconciliations, the snapshot is only used to carry over the
+    /// information that cannot be obtained from the service.
+    async fn reconcile(
+        &self,
+        org: &Organization,
+        snapshot: Option<&StateSnapshot>,
+        incremental: bool,
+    ) -> Result<ReconcileOutput>;
+
+    /// Apply again the changes that failed in a previous reconciliation, in
+    /// the same order they were applied originally. Changes are detected
+    /// again before applying them, so the ones already satisfied are skipped.
+    async fn retry_failed(&self, org: &Organization, failed: &[ChangeDetails]) -> Result<ChangesApplied>;
+
+    /// Prefetch some of the information of the organization provided from the
+    /// service, so that the first reconciliation after startup can read it
+    /// from the cache.
+    async fn warm_up(&self, _org: &Organization) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Type alias to represent a service handler trait object.
 pub type DynServiceHandler = Arc<dyn ServiceHandler + Send + Sync>;
 
+/// Trait that defines the operations needed to keep track of the progress of
+/// a reconciliation, so that it can be resumed from where it stopped if it is
+/// interrupted (i.e. the process is killed).
+#[async_trait]
+#[cfg_attr(test, automock)]
+pub trait ReconcileCheckpoint {
+    /// Get the changes applied on the service provided by the last
+    /// reconciliation of the organization given, in case it was interrupted,
+    /// in the same order they were applied.
+    async fn get_changes_applied(&self, org: &str, service_name: ServiceName) -> Result<Vec<ChangeDetails>>;
+
+    /// Register a change applied successfully by the reconciliation in
+    /// progress of the organization provided.
+    async fn register_change_applied(
+        &self,
+        org: &str,
+        service_name: ServiceName,
+        change: &ChangeDetails,
+    ) -> Result<()>;
+
+    /// Discard the changes registered for the organization provided, once its
+    /// reconciliation has completed.
+    async fn reset(&self, org: &str, service_name: ServiceName) -> Result<()>;
+}
+
+/// Type alias to represent a reconcile checkpoint trait object.
+pub type DynReconcileCheckpoint = Arc<dyn ReconcileCheckpoint + Send + Sync>;
+
 /// Represents a summary of changes detected in the service's state as defined
 /// in the configuration from the base to the head reference.
 pub struct ChangesSummary {
     pub changes: Vec<DynChange>,
     pub base_ref_config_status: BaseRefConfigStatus,
+    /// Issues found in the configuration that don't make it invalid, but that
+    /// may need some attention.
+    pub warnings: Vec<String>,
+    /// Net effect of the changes on the access of each of the users affected
+    /// (only available when supported by the service handler).
+    pub users_impact: Vec<UserImpact>,
+}
+
+/// Represents the net effect of some changes on the access of a user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserImpact {
+    pub user_name: UserName,
+    pub access_changes: Vec<AccessChange>,
+}
+
+impl fmt::Display for UserImpact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access_changes: Vec<String> = self.access_changes.iter().map(ToString::to_string).collect();
+        write!(f, "**{}**: {}", self.user_name, access_changes.join(", "))
+    }
+}
+
+/// Represents a change in the access a user has to a resource. A missing old
+/// role means the access was gained, and a missing new one that it was lost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessChange {
+    pub resource: String,
+    pub old_role: Option<String>,
+    pub new_role: Option<String>,
+}
+
+impl fmt::Display for AccessChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old_role, &self.new_role) {
+            (None, Some(new_role)) => write!(f, "gains **{new_role}** access to **{}**", self.resource),
+            (Some(old_role), None) => write!(f, "loses **{old_role}** access to **{}**", self.resource),
+            (Some(old_role), Some(new_role)) => write!(
+                f,
+                "access to **{}** changes from **{old_role}** to **{new_role}**",
+                self.resource
+            ),
+            (None, None) => write!(f, "no access to **{}**", self.resource),
+        }
+    }
+}
+
+/// Represents the values compared when a change was computed, which explain
+/// why it was emitted (i.e. to debug diffs that keep reappearing). A missing
+/// old value means the entity was added, and a missing new one that it was
+/// removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeExplanation {
+    pub field: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl ChangeExplanation {
+    /// Create a new explanation for an entity that was added.
+    pub fn added(field: &'static str, new: impl fmt::Display) -> Self {
+        Self {
+            field,
+            old: None,
+            new: Some(new.to_string()),
+        }
+    }
+
+    /// Create a new explanation for an entity that was removed.
+    pub fn removed(field: &'static str, old: impl fmt::Display) -> Self {
+        Self {
+            field,
+            old: Some(old.to_string()),
+            new: None,
+        }
+    }
+
+    /// Create a new explanation for a value that was updated.
+    pub fn updated(field: &'static str, old: impl fmt::Display, new: impl fmt::Display) -> Self {
+        Self {
+            field,
+            old: Some(old.to_string()),
+            new: Some(new.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ChangeExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old = self.old.as_deref().unwrap_or("-");
+        let new = self.new.as_deref().unwrap_or("-");
+        write!(f, "{} old={old} new={new}", self.field)
+    }
+}
+
+/// Type alias to represent a snapshot of the service's actual state. Its
+/// content is specific to each service handler.
+pub type StateSnapshot = serde_json::Value;
+
+/// Represents the output of a reconciliation.
+#[derive(Debug, Default)]
+pub struct ReconcileOutput {
+    pub changes_applied: ChangesApplied,
+    /// Snapshot of the actual state that can be used in the next incremental
+    /// reconciliation (only available when supported by the service handler).
+    pub snapshot: Option<StateSnapshot>,
 }
 
 /// Type alias to represent some changes applied on a service.
@@ -48,6 +223,17 @@ pub struct ChangeApplied {
     pub applied_at: time::OffsetDateTime,
 }
 
+/// Type alias to represent some changes checked on a service.
+pub type ChangesChecked = Vec<ChangeChecked>;
+
+/// Represents the result of checking if a change could be applied on a
+/// service, without applying it (dry apply).
+#[derive(Debug)]
+pub struct ChangeChecked {
+    pub change: DynChange,
+    pub error: Option<String>,
+}
+
 /// Trait that defines some operations a Change implementation must support.
 pub trait Change: AsAny + Debug {
     /// Return some details about the change.
@@ -56,6 +242,10 @@ pub trait Change: AsAny + Debug {
     /// Keywords used to facilitate locating specific changes on searches.
     fn keywords(&self) -> Vec<&str>;
 
+    /// Risk level of the change. High risk changes may require some extra
+    /// attention from reviewers.
+    fn risk(&self) -> ChangeRisk;
+
     /// Format change to be used on a template.
     fn template_format(&self) -> Result<String>;
 }
@@ -63,6 +253,54 @@ pub trait Change: AsAny + Debug {
 /// Type alias to represent a change trait object.
 pub type DynChange = Box<dyn Change + Send + Sync>;
 
+/// Change annotated with the location (path:line) in the configuration file
+/// of the entry that caused it.
+#[derive(Debug)]
+pub struct LocatedChange {
+    pub change: DynChange,
+    pub location: String,
+}
+
+impl LocatedChange {
+    /// Annotate the change provided with its location, if known.
+    #[must_use]
+    pub fn wrap(change: DynChange, location: Option<String>) -> DynChange {
+        match location {
+            Some(location) => Box::new(LocatedChange { change, location }),
+            None => change,
+        }
+    }
+}
+
+impl Change for LocatedChange {
+    /// [Change::details]
+    fn details(&self) -> ChangeDetails {
+        self.change.details()
+    }
+
+    /// [Change::keywords]
+    fn keywords(&self) -> Vec<&str> {
+        self.change.keywords()
+    }
+
+    /// [Change::risk]
+    fn risk(&self) -> ChangeRisk {
+        self.change.risk()
+    }
+
+    /// [Change::template_format]
+    fn template_format(&self) -> Result<String> {
+        // The location is added at the end of the first line, as some changes
+        // include additional details in the lines that follow
+        let s = self.change.template_format()?;
+        let located = match s.split_once('\n') {
+            Some((first_line, rest)) => format!("{first_line} (`{}`)\n{rest}", self.location),
+            None => format!("{s} (`{}`)", self.location),
+        };
+        Ok(located)
+    }
+}
+
 /// Status of the configuration in the base reference.
 #[derive(Debug, Clone, PartialEq)]
 pub enum BaseRefConfigStatus {
@@ -79,9 +317,48 @@ impl BaseRefConfigStatus {
     }
 }
 
+/// Risk level of a change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeRisk {
+    Low,
+    High,
+}
+
 /// ChangeDetails represents some details about a change.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ChangeDetails {
     pub kind: String,
     pub extra: serde_json::Value,
 }
+
+#[cfg(test)]
+mod tests {
+    use as_any::Downcast;
+
+    use super::*;
+    use crate::services::github::state::RepositoryChange;
+
+    #[test]
+    fn located_change_template_format() {
+        let change = Box::new(RepositoryChange::TeamRemoved(
+            "repo1".to_string(),
+            "team1".to_string(),
+        ));
+        let change = LocatedChange::wrap(change, Some("config.yaml:12".to_string()));
+        assert_eq!(
+            change.template_format().unwrap(),
+            "- team **team1** has been *removed* from repository **repo1** (`config.yaml:12`)"
+        );
+        assert_eq!(change.details().kind, "repository-team-removed");
+    }
+
+    #[test]
+    fn located_change_unknown_location() {
+        let change = Box::new(RepositoryChange::TeamRemoved(
+            "repo1".to_string(),
+            "team1".to_string(),
+        ));
+        let change = LocatedChange::wrap(change, None);
+        assert!((*change).downcast_ref::<RepositoryChange>().is_some());
+    }
+}
@@ -0,0 +1,68 @@
+//! This module contains some utilities shared by the tests.
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use async_trait::async_trait;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use task_local_extensions::Extensions;
+
+/// Middleware that sends all requests to the address provided, used to serve
+/// the GitHub API from a local server.
+pub(crate) struct RedirectTo(pub(crate) SocketAddr);
+
+#[async_trait]
+impl Middleware for RedirectTo {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let url = req.url_mut();
+        _ = url.set_scheme("http");
+        _ = url.set_host(Some(&self.0.ip().to_string()));
+        _ = url.set_port(Some(self.0.port()));
+        next.run(req, extensions).await
+    }
+}
+
+/// Start a local HTTP server that responds to each request with the status
+/// returned by the function provided for the request path, along with the
+/// JSON body provided. The server address is returned, as well as the number
+/// of requests it has received.
+pub(crate) fn local_server(
+    status: fn(&str) -> &'static str,
+    body: &'static str,
+) -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let requests_received = requests.clone();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            requests_received.fetch_add(1, Ordering::SeqCst);
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).unwrap_or_default();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+            _ = stream.write_all(
+                format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    status(path),
+                    body.len()
+                )
+                .as_bytes(),
+            );
+        }
+    });
+    (addr, requests)
+}
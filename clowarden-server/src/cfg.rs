@@ -10,7 +10,7 @@ use figment::{
 };
 use serde::{Deserialize, Serialize};
 
-use clowarden_core::cfg::{GitHubApp, Organization, Services};
+use clowarden_core::cfg::{GitHubApp, GitHubClient, Organization, Services};
 
 /// Server configuration.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -57,6 +57,8 @@ pub(crate) struct HttpServer {
     pub static_path: PathBuf,
     pub basic_auth: Option<BasicAuth>,
     pub github_app: GitHubApp,
+    #[serde(default)]
+    pub github_client: GitHubClient,
 }
 
 /// Basic authentication configuration.
@@ -11,7 +11,9 @@ use serde::{Deserialize, Serialize};
 use tokio_postgres::types::Json;
 use uuid::Uuid;
 
-use clowarden_core::services::{ChangesApplied, ServiceName};
+use clowarden_core::services::{
+    ChangeDetails, ChangesApplied, ReconcileCheckpoint, ServiceName, StateSnapshot,
+};
 
 use crate::jobs::ReconcileInput;
 
@@ -19,6 +21,26 @@ use crate::jobs::ReconcileInput;
 #[async_trait]
 #[cfg_attr(test, automock)]
 pub(crate) trait DB {
+    /// Get the changes that failed to be applied on the service provided in
+    /// the latest reconciliation of the organization given, in the same order
+    /// they were applied.
+    async fn get_failed_changes(&self, org: &str, service_name: ServiceName) -> Result<Vec<ChangeDetails>>;
+
+    /// Get the latest reconciliation of the organization provided that
+    /// completed without errors (no matter if any changes were applied).
+    async fn get_last_successful_reconciliation(&self, org: &str) -> Result<Option<JsonString>>;
+
+    /// Get all the changes applied to the repository provided in the given
+    /// organization, ordered chronologically. Details about the
+    /// reconciliation of each change (like who created or merged the PR that
+    /// triggered it) are included as well.
+    async fn get_repository_changes(&self, org: &str, repo_name: &str) -> Result<JsonString>;
+
+    /// Get the latest snapshot of the service's actual state registered for
+    /// the organization provided.
+    async fn get_state_snapshot(&self, org: &str, service_name: ServiceName)
+        -> Result<Option<StateSnapshot>>;
+
     /// Register the reconciliation provided.
     async fn register_reconciliation(
         &self,
@@ -29,6 +51,15 @@ pub(crate) trait DB {
 
     /// Search changes that match the criteria provided.
     async fn search_changes(&self, input: &SearchChangesInput) -> Result<(Count, JsonString)>;
+
+    /// Update the snapshot of the service's actual state for the organization
+    /// provided. Any previous snapshot will be discarded.
+    async fn update_state_snapshot(
+        &self,
+        org: &str,
+        service_name: ServiceName,
+        snapshot: &StateSnapshot,
+    ) -> Result<()>;
 }
 
 /// Type alias to represent a DB trait object.
@@ -54,6 +85,80 @@ impl PgDB {
 
 #[async_trait]
 impl DB for PgDB {
+    /// [DB::get_failed_changes]
+    async fn get_failed_changes(&self, org: &str, service_name: ServiceName) -> Result<Vec<ChangeDetails>> {
+        let db = self.pool.get().await?;
+        let failed_changes = db
+            .query(
+                "
+                select kind, extra
+                from change
+                where reconciliation_id = (
+                    select reconciliation_id
+                    from reconciliation
+                    where organization = $1::text
+                    order by completed_at desc
+                    limit 1
+                )
+                and service = $2::text
+                and error is not null
+                order by applied_at asc
+                ",
+                &[&org, &service_name],
+            )
+            .await?
+            .iter()
+            .map(|row| ChangeDetails {
+                kind: row.get("kind"),
+                extra: row.get::<_, Option<serde_json::Value>>("extra").unwrap_or_default(),
+            })
+            .collect();
+        Ok(failed_changes)
+    }
+
+    /// [DB::get_last_successful_reconciliation]
+    async fn get_last_successful_reconciliation(&self, org: &str) -> Result<Option<JsonString>> {
+        let db = self.pool.get().await?;
+        let row = db
+            .query_one(
+                "select get_last_successful_reconciliation($1::text)::text",
+                &[&org],
+            )
+            .await?;
+        let reconciliation: Option<String> = row.get(0);
+        Ok(reconciliation)
+    }
+
+    /// [DB::get_state_snapshot]
+    async fn get_state_snapshot(
+        &self,
+        org: &str,
+        service_name: ServiceName,
+    ) -> Result<Option<StateSnapshot>> {
+        let db = self.pool.get().await?;
+        let snapshot = db
+            .query_opt(
+                "select snapshot from state_snapshot where organization = $1::text and service = $2::text",
+                &[&org, &service_name],
+            )
+            .await?
+            .map(|row| row.get("snapshot"));
+        Ok(snapshot)
+    }
+
+    /// [DB::get_repository_changes]
+    async fn get_repository_changes(&self, org: &str, repo_name: &str) -> Result<JsonString> {
+        let db = self.pool.get().await?;
+        let row = db
+            .query_one(
+                "select get_repository_changes($1::text, $2::text)::text",
+                &[&org, &repo_name],
+            )
+            .await?;
+        let changes: String = row.get(0);
+        Ok(changes)
+    }
+
     /// [DB::register_reconciliation]
     async fn register_reconciliation(
         &self,
@@ -88,14 +193,16 @@ impl DB for PgDB {
                     pr_number,
                     pr_created_by,
                     pr_merged_by,
-                    pr_merged_at
+                    pr_merged_at,
+                    observe_only
                 ) values (
                     $1::text,
                     $2::text,
                     $3::bigint,
                     $4::text,
                     $5::text,
-                    $6::timestamptz
+                    $6::timestamptz,
+                    $7::boolean
                 )
                 returning reconciliation_id
                 ",
@@ -106,6 +213,7 @@ impl DB for PgDB {
                     &input.pr_created_by,
                     &input.pr_merged_by,
                     &input.pr_merged_at,
+                    &input.org.observe_only,
                 ],
             )
             .await?
@@ -141,7 +249,8 @@ impl DB for PgDB {
                         applied_at,
                         error,
                         reconciliation_id,
-                        tsdoc
+                        tsdoc,
+                        actor
                     ) values (
                         $1::text,
                         $2::text,
@@ -149,7 +258,8 @@ impl DB for PgDB {
                         $4::timestamptz,
                         $5::text,
                         $6::uuid,
-                        to_tsvector($7::text)
+                        to_tsvector($7::text),
+                        $8::text
                     )
                     ",
                     &[
@@ -160,6 +270,7 @@ impl DB for PgDB {
                         &change_applied.error,
                         &reconciliation_id,
                         &change_keywords.join(" "),
+                        &input.actor(),
                     ],
                 )
                 .await?;
@@ -183,6 +294,84 @@ impl DB for PgDB {
         let changes: String = row.get("changes");
         Ok((count, changes))
     }
+
+    /// [DB::update_state_snapshot]
+    async fn update_state_snapshot(
+        &self,
+        org: &str,
+        service_name: ServiceName,
+        snapshot: &StateSnapshot,
+    ) -> Result<()> {
+        let db = self.pool.get().await?;
+        db.execute(
+            "
+            insert into state_snapshot (organization, service, snapshot)
+            values ($1::text, $2::text, $3::jsonb)
+            on conflict (organization, service) do update
+            set snapshot = excluded.snapshot, created_at = current_timestamp
+            ",
+            &[&org, &service_name, snapshot],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ReconcileCheckpoint for PgDB {
+    /// [ReconcileCheckpoint::get_changes_applied]
+    async fn get_changes_applied(&self, org: &str, service_name: ServiceName) -> Result<Vec<ChangeDetails>> {
+        let db = self.pool.get().await?;
+        let changes = db
+            .query(
+                "
+                select kind, extra
+                from reconcile_checkpoint
+                where organization = $1::text
+                and service = $2::text
+                order by applied_at asc
+                ",
+                &[&org, &service_name],
+            )
+            .await?
+            .iter()
+            .map(|row| ChangeDetails {
+                kind: row.get("kind"),
+                extra: row.get::<_, Option<serde_json::Value>>("extra").unwrap_or_default(),
+            })
+            .collect();
+        Ok(changes)
+    }
+
+    /// [ReconcileCheckpoint::register_change_applied]
+    async fn register_change_applied(
+        &self,
+        org: &str,
+        service_name: ServiceName,
+        change: &ChangeDetails,
+    ) -> Result<()> {
+        let db = self.pool.get().await?;
+        db.execute(
+            "
+            insert into reconcile_checkpoint (organization, service, kind, extra)
+            values ($1::text, $2::text, $3::text, $4::jsonb)
+            ",
+            &[&org, &service_name, &change.kind, &change.extra],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// [ReconcileCheckpoint::reset]
+    async fn reset(&self, org: &str, service_name: ServiceName) -> Result<()> {
+        let db = self.pool.get().await?;
+        db.execute(
+            "delete from reconcile_checkpoint where organization = $1::text and service = $2::text",
+            &[&org, &service_name],
+        )
+        .await?;
+        Ok(())
+    }
 }
 
 /// Query input used when searching for changes.
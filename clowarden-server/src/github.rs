@@ -11,14 +11,18 @@ use octorust::{
     auth::{Credentials, InstallationTokenGenerator, JWTCredentials},
     types::{
         ChecksCreateRequest, ChecksCreateRequestConclusion, ChecksCreateRequestOutput, JobStatus,
-        OrganizationSimple, PullRequestData, PullsUpdateReviewRequest, Repository, SimpleUser,
+        OrganizationSimple, PullRequestData, PullsUpdateReviewRequest, RateLimit, Repository, SimpleUser,
     },
     Client,
 };
+use reqwest_middleware::ClientWithMiddleware;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use clowarden_core::cfg::{GitHubApp, Organization};
+use clowarden_core::{
+    cfg::{GitHubApp, GitHubClient, Organization},
+    github::new_http_client,
+};
 
 /// Name used for the check run in GitHub.
 const CHECK_RUN_NAME: &str = "CLOWarden";
@@ -30,6 +34,9 @@ pub(crate) trait GH {
     /// Create a check run.
     async fn create_check_run(&self, ctx: &Ctx, body: &ChecksCreateRequest) -> Result<()>;
 
+    /// Get the GitHub API rate limit status of the installation.
+    async fn get_rate_limit(&self, ctx: &Ctx) -> Result<RateLimit>;
+
     /// List pull request files.
     async fn list_pr_files(&self, ctx: &Ctx, pr_number: i64) -> Result<Vec<FileName>>;
 
@@ -49,17 +56,21 @@ type FileName = String;
 /// GH implementation backed by the GitHub API.
 pub(crate) struct GHApi {
     app_credentials: JWTCredentials,
+    http_client: ClientWithMiddleware,
 }
 
 impl GHApi {
     /// Create a new GHApi instance.
-    pub(crate) fn new(gh_app: &GitHubApp) -> Result<Self> {
+    pub(crate) fn new(gh_app: &GitHubApp, gh_client: &GitHubClient) -> Result<Self> {
         // Setup GitHub app credentials
         let private_key = pem::parse(&gh_app.private_key)?.contents().to_owned();
         let app_credentials =
             JWTCredentials::new(gh_app.app_id, private_key).context("error setting up credentials")?;
 
-        Ok(Self { app_credentials })
+        Ok(Self {
+            app_credentials,
+            http_client: new_http_client(gh_client)?,
+        })
     }
 
     /// Setup GitHub API client for the installation id provided.
@@ -68,7 +79,7 @@ impl GHApi {
         let tg = InstallationTokenGenerator::new(inst_id, self.app_credentials.clone());
         let credentials = Credentials::InstallationToken(tg);
 
-        Ok(Client::new(user_agent, credentials)?)
+        Ok(Client::custom(user_agent, credentials, self.http_client.clone()))
     }
 }
 
@@ -81,6 +92,13 @@ impl GH for GHApi {
         Ok(())
     }
 
+    /// [GH::get_rate_limit]
+    async fn get_rate_limit(&self, ctx: &Ctx) -> Result<RateLimit> {
+        let client = self.setup_client(ctx.inst_id)?;
+        let rate_limit = client.rate_limit().get().await?.rate;
+        Ok(rate_limit)
+    }
+
     /// [GH::list_pr_files]
     async fn list_pr_files(&self, ctx: &Ctx, pr_number: i64) -> Result<Vec<FileName>> {
         let client = self.setup_client(ctx.inst_id)?;
@@ -115,6 +133,7 @@ type EventPayload = [u8];
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) enum Event {
     PullRequest(PullRequestEvent),
+    Repository(RepositoryEvent),
 }
 
 impl TryFrom<(&EventHeader, &EventPayload)> for Event {
@@ -128,6 +147,11 @@ impl TryFrom<(&EventHeader, &EventPayload)> for Event {
                         .map_err(|err| EventError::InvalidBody(err.to_string()))?;
                     Ok(Event::PullRequest(event))
                 }
+                b"repository" => {
+                    let event: RepositoryEvent = serde_json::from_slice(event_body)
+                        .map_err(|err| EventError::InvalidBody(err.to_string()))?;
+                    Ok(Event::Repository(event))
+                }
                 _ => Err(EventError::UnsupportedEvent),
             },
             None => Err(EventError::MissingHeader),
@@ -162,8 +186,30 @@ pub(crate) struct PullRequestEvent {
 #[serde(rename_all = "snake_case")]
 pub(crate) enum PullRequestEventAction {
     Closed,
+    Labeled,
     Opened,
     Synchronize,
+    Unlabeled,
+    #[serde(other)]
+    Other,
+}
+
+/// Repository event payload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RepositoryEvent {
+    pub action: RepositoryEventAction,
+    pub organization: Option<OrganizationSimple>,
+    pub repository: Repository,
+    pub sender: SimpleUser,
+}
+
+/// Repository event action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RepositoryEventAction {
+    Archived,
+    Renamed,
+    Transferred,
     #[serde(other)]
     Other,
 }
@@ -6,7 +6,7 @@ use std::{fmt::Display, path::Path};
 use anyhow::{format_err, Error, Result};
 use axum::{
     body::{Body, Bytes},
-    extract::{FromRef, RawQuery, State},
+    extract::{self, FromRef, RawQuery, State},
     http::{
         header::{CACHE_CONTROL, CONTENT_TYPE},
         HeaderMap, HeaderValue, Response, StatusCode,
@@ -29,13 +29,15 @@ use tower_http::{
 };
 use tracing::{error, instrument, trace};
 
-use clowarden_core::cfg::Organization;
+use clowarden_core::{cfg::Organization, services::github::state::is_ghsa_temp_fork};
 
 use crate::{
     cfg::Config,
     db::{DynDB, SearchChangesInput},
-    github::{self, Ctx, DynGH, Event, EventError, PullRequestEvent, PullRequestEventAction},
-    jobs::{Job, ReconcileInput, ValidateInput},
+    github::{
+        self, Ctx, DynGH, Event, EventError, PullRequestEvent, PullRequestEventAction, RepositoryEventAction,
+    },
+    jobs::{Job, ReconcileInput, RetryFailedInput, ValidateInput},
 };
 
 /// Audit index HTML document cache duration.
@@ -90,6 +92,14 @@ pub(crate) fn setup_router(
     // Setup audit router
     let mut audit_router = Router::new()
         .route("/api/organizations", get(list_organizations))
+        .route(
+            "/api/organizations/:org/repositories/:repo/changes",
+            get(get_repository_changes),
+        )
+        .route(
+            "/api/organizations/:org/reconciliations/last-successful",
+            get(get_last_successful_reconciliation),
+        )
         .route("/api/changes/search", get(search_changes))
         .nest_service(
             "/static",
@@ -113,12 +123,23 @@ pub(crate) fn setup_router(
     }
 
     // Setup main router
-    let router = Router::new()
+    let mut router = Router::new()
         .route("/webhook/github", post(event))
         .route("/health-check", get(health_check))
         .route("/audit", get(|| async { Redirect::permanent("/audit/") }))
         .route("/", get_service(ServeFile::new(&root_index_path)))
-        .nest("/audit/", audit_router)
+        .nest("/audit/", audit_router);
+
+    // Setup jobs router (only available when basic auth is enabled, as it
+    // allows triggering jobs on demand)
+    if let Some(basic_auth) = cfg.server.basic_auth.as_ref().filter(|basic_auth| basic_auth.enabled) {
+        let jobs_router = Router::new().route("/:org/retry-failed", post(retry_failed)).layer(
+            ValidateRequestHeaderLayer::basic(&basic_auth.username, &basic_auth.password),
+        );
+        router = router.nest("/api/jobs", jobs_router);
+    }
+
+    let router = router
         .nest_service(
             "/static",
             get_service(SetResponseHeader::overriding(
@@ -202,8 +223,10 @@ async fn event(
             // Check if we are interested on the event's action
             if ![
                 PullRequestEventAction::Closed,
+                PullRequestEventAction::Labeled,
                 PullRequestEventAction::Opened,
                 PullRequestEventAction::Synchronize,
+                PullRequestEventAction::Unlabeled,
             ]
             .contains(&event.action)
             {
@@ -227,8 +250,12 @@ async fn event(
 
             // Take action on event
             match event.action {
-                PullRequestEventAction::Opened | PullRequestEventAction::Synchronize => {
-                    // Create validation in-progress check run
+                PullRequestEventAction::Labeled
+                | PullRequestEventAction::Opened
+                | PullRequestEventAction::Synchronize
+                | PullRequestEventAction::Unlabeled => {
+                    // Create validation in-progress check run (labels may
+                    // exempt the changes from some validation checks)
                     let ctx = Ctx::from(&org);
                     let check_body = github::new_checks_create_request(
                         event.pull_request.head.sha.clone(),
@@ -252,11 +279,81 @@ async fn event(
                 _ => {}
             }
         }
+        Event::Repository(event) => {
+            // Check event comes from a registered organization
+            let Some(gh_org) = &event.organization else {
+                return Ok(());
+            };
+            let Some(org) = orgs.iter().find(|o| o.name == gh_org.login).cloned() else {
+                return Ok(());
+            };
+
+            // Check if we are interested on the event's action
+            if ![
+                RepositoryEventAction::Archived,
+                RepositoryEventAction::Renamed,
+                RepositoryEventAction::Transferred,
+            ]
+            .contains(&event.action)
+            {
+                return Ok(());
+            }
+
+            // Ignore repositories not managed by CLOWarden
+            if is_ghsa_temp_fork(&event.repository.name) {
+                return Ok(());
+            }
+
+            // Enqueue reconcile job, so that the changes made to the
+            // repository outside CLOWarden are taken into account promptly
+            _ = jobs_tx.send(Job::Reconcile(ReconcileInput {
+                org,
+                ..Default::default()
+            }));
+        }
     }
 
     Ok(())
 }
 
+/// Handler that returns the latest reconciliation of an organization that
+/// completed without errors (useful to monitor that reconciliations run).
+async fn get_last_successful_reconciliation(
+    State(db): State<DynDB>,
+    extract::Path(org_name): extract::Path<String>,
+) -> impl IntoResponse {
+    // Get last successful reconciliation from database
+    let Some(reconciliation) =
+        db.get_last_successful_reconciliation(&org_name).await.map_err(internal_error)?
+    else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    // Return reconciliation as json
+    Response::builder()
+        .header(CACHE_CONTROL, format!("max-age={DEFAULT_API_MAX_AGE}"))
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(Body::from(reconciliation))
+        .map_err(internal_error)
+}
+
+/// Handler that returns all the changes applied to a repository, ordered
+/// chronologically (repository audit trail).
+async fn get_repository_changes(
+    State(db): State<DynDB>,
+    extract::Path((org_name, repo_name)): extract::Path<(String, String)>,
+) -> impl IntoResponse {
+    // Get repository changes from database
+    let changes = db.get_repository_changes(&org_name, &repo_name).await.map_err(internal_error)?;
+
+    // Return changes as json
+    Response::builder()
+        .header(CACHE_CONTROL, format!("max-age={DEFAULT_API_MAX_AGE}"))
+        .header(CONTENT_TYPE, APPLICATION_JSON.as_ref())
+        .body(Body::from(changes))
+        .map_err(internal_error)
+}
+
 /// Handler that lists the registered organizations.
 #[allow(clippy::unused_async)]
 async fn list_organizations(State(orgs): State<Vec<Organization>>) -> impl IntoResponse {
@@ -272,6 +369,21 @@ async fn list_organizations(State(orgs): State<Vec<Organization>>) -> impl IntoR
         .map_err(internal_error)
 }
 
+/// Handler that enqueues a job to retry the changes that failed in the latest
+/// reconciliation of the organization provided.
+#[allow(clippy::unused_async)]
+async fn retry_failed(
+    State(jobs_tx): State<mpsc::UnboundedSender<Job>>,
+    State(orgs): State<Vec<Organization>>,
+    extract::Path(org_name): extract::Path<String>,
+) -> impl IntoResponse {
+    let Some(org) = orgs.into_iter().find(|o| o.name == org_name) else {
+        return StatusCode::NOT_FOUND;
+    };
+    _ = jobs_tx.send(Job::RetryFailed(RetryFailedInput { org }));
+    StatusCode::ACCEPTED
+}
+
 /// Handler that allows searching for changes.
 async fn search_changes(State(db): State<DynDB>, RawQuery(query): RawQuery) -> impl IntoResponse {
     // Search changes in database
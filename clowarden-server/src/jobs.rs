@@ -1,40 +1,61 @@
 //! This module defines the types and functionality needed to schedule and
 //! process jobs.
 
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
 
 use ::time::OffsetDateTime;
 use anyhow::{Error, Result};
 use askama::Template;
-use futures::future::{self, JoinAll};
+use futures::{
+    future::{self, JoinAll},
+    stream, Future, StreamExt,
+};
 use octorust::types::{ChecksCreateRequestConclusion, JobStatus, PullRequestData};
 use serde::{Deserialize, Serialize};
 use tokio::{
-    sync::mpsc,
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore},
     task::JoinHandle,
     time::{self, sleep, MissedTickBehavior},
 };
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, instrument};
+use tracing::{debug, error, instrument, warn};
 
-use self::core::github::Source;
+use self::core::github::{is_transient_error, Source};
 use clowarden_core::{
     self as core,
-    cfg::Organization,
-    directory::Directory,
+    cfg::{GitHubClient, Organization, ValidationExemption},
+    directory::{Directory, UserName},
     multierror::MultiError,
-    services::{BaseRefConfigStatus, ChangesApplied, ChangesSummary, DynServiceHandler, ServiceName},
+    services::{ChangesApplied, ChangesSummary, DynServiceHandler, ServiceName},
 };
 
 use crate::{
     db::DynDB,
     github::{self, Ctx, DynGH},
+    notifier::{DynNotifier, ReconciliationEvent},
     tmpl,
 };
 
 /// How often periodic reconcile jobs should be scheduled (in seconds).
 const RECONCILE_FREQUENCY: u64 = 60 * 60; // Every hour
 
+/// How often periodic reconcile jobs should be full reconciliations for
+/// organizations with incremental reconcile enabled (number of periodic
+/// reconcile jobs).
+const FULL_RECONCILE_FREQUENCY: u64 = 24; // Once a day
+
+/// How often deferred reconcile jobs should be checked, to schedule them as
+/// soon as the organization's reconcile window opens (in seconds).
+const DEFERRED_RECONCILE_CHECK_FREQUENCY: u64 = 60 * 5; // Every 5 minutes
+
+/// Actor recorded for the changes applied by reconciliations that were not
+/// triggered from a pull request.
+pub(crate) const SCHEDULER_ACTOR: &str = "system/scheduler";
+
 /// Represents a job to be executed.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -48,6 +69,12 @@ pub(crate) enum Job {
     /// comments.
     Reconcile(ReconcileInput),
 
+    /// A retry failed job applies again the changes that failed in the latest
+    /// reconciliation of an organization (i.e. due to a transient error in
+    /// the external service). Changes that are already satisfied are skipped.
+    /// It's triggered manually.
+    RetryFailed(RetryFailedInput),
+
     /// A validate job verifies that the proposed changes to the configuration
     /// files in a pull request are valid, providing feedback to address issues
     /// whenever possible, as well as a summary of changes to facilitate
@@ -60,6 +87,7 @@ impl Job {
     pub(crate) fn org_name(&self) -> &str {
         match self {
             Job::Reconcile(input) => &input.org.name,
+            Job::RetryFailed(input) => &input.org.name,
             Job::Validate(input) => &input.org.name,
         }
     }
@@ -73,6 +101,7 @@ pub(crate) struct ReconcileInput {
     pub pr_created_by: Option<String>,
     pub pr_merged_by: Option<String>,
     pub pr_merged_at: Option<OffsetDateTime>,
+    pub incremental: bool,
 }
 
 impl ReconcileInput {
@@ -84,6 +113,7 @@ impl ReconcileInput {
             pr_created_by: pr.user.map(|u| u.login),
             pr_merged_by: pr.merged_by.map(|u| u.login),
             pr_merged_at: None,
+            incremental: false,
         };
         if let Some(pr_merged_at) = pr.merged_at {
             if let Ok(pr_merged_at) = OffsetDateTime::from_unix_timestamp(pr_merged_at.timestamp()) {
@@ -94,6 +124,20 @@ impl ReconcileInput {
         }
         input
     }
+
+    /// Return the actor that triggered the reconciliation. This is the user
+    /// who merged the pull request (or the one who created it when the merger
+    /// is not available). Reconciliations not triggered from a pull request
+    /// are attributed to the scheduler.
+    pub(crate) fn actor(&self) -> &str {
+        self.pr_merged_by.as_deref().or(self.pr_created_by.as_deref()).unwrap_or(SCHEDULER_ACTOR)
+    }
+}
+
+/// Information required to process a retry failed job.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RetryFailedInput {
+    pub org: Organization,
 }
 
 /// Information required to process a validate job.
@@ -105,6 +149,8 @@ pub(crate) struct ValidateInput {
     pub pr_head_repo: Option<String>,
     pub pr_head_ref: String,
     pub pr_head_sha: String,
+    pub pr_created_by: Option<String>,
+    pub pr_labels: Vec<String>,
 }
 
 impl ValidateInput {
@@ -117,6 +163,8 @@ impl ValidateInput {
             pr_head_repo: pr.head.repo.map(|r| r.name),
             pr_head_ref: pr.head.ref_,
             pr_head_sha: pr.head.sha,
+            pr_created_by: pr.user.map(|u| u.login),
+            pr_labels: pr.labels.into_iter().map(|l| l.name).collect(),
         }
     }
 }
@@ -125,24 +173,40 @@ impl ValidateInput {
 /// a worker for each organization, plus an additional task to route jobs to
 /// the corresponding organization worker. All tasks will stop when the
 /// cancellation token is cancelled.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn handler(
     db: &DynDB,
     gh: &DynGH,
     ghc: &core::github::DynGH,
     services: &HashMap<ServiceName, DynServiceHandler>,
+    notifier: &DynNotifier,
     mut jobs_rx: mpsc::UnboundedReceiver<Job>,
     cancel_token: CancellationToken,
     orgs: &Vec<Organization>,
+    gh_client: &GitHubClient,
 ) -> JoinAll<JoinHandle<()>> {
     let mut handles = Vec::with_capacity(orgs.len() + 1);
     let mut orgs_jobs_tx_channels = HashMap::new();
+    let limiter = JobsLimiter::new(gh_client.max_concurrent_jobs);
 
     // Create a worker for each organization
     for org in orgs {
         let (org_jobs_tx, org_jobs_rx) = mpsc::unbounded_channel();
         orgs_jobs_tx_channels.insert(org.name.clone(), org_jobs_tx);
-        let org_worker = OrgWorker::new(db.clone(), gh.clone(), ghc.clone(), services.clone());
-        handles.push(org_worker.run(org_jobs_rx, cancel_token.clone()));
+        let org_worker = OrgWorker::new(
+            db.clone(),
+            gh.clone(),
+            ghc.clone(),
+            services.clone(),
+            notifier.clone(),
+            limiter.clone(),
+            gh_client.rate_limit_warning_threshold,
+            gh_client.validate_services_concurrency,
+            gh_client.validate_job_max_retries,
+            Duration::from_secs(gh_client.validate_job_retry_backoff),
+            gh_client.warm_cache,
+        );
+        handles.push(org_worker.run(org.clone(), org_jobs_rx, cancel_token.clone()));
     }
 
     // Create a worker to route jobs to the corresponding org worker
@@ -168,6 +232,28 @@ pub(crate) fn handler(
     future::join_all(handles)
 }
 
+/// Limits the number of jobs processed concurrently across all organizations
+/// workers, as they all share the same GitHub API rate limit.
+#[derive(Clone)]
+struct JobsLimiter(Option<Arc<Semaphore>>);
+
+impl JobsLimiter {
+    /// Create a new JobsLimiter instance. No limit is applied when the maximum
+    /// number of concurrent jobs is not provided (or is zero).
+    fn new(max_concurrent_jobs: Option<usize>) -> Self {
+        Self(max_concurrent_jobs.filter(|max| *max > 0).map(|max| Arc::new(Semaphore::new(max))))
+    }
+
+    /// Wait until a job can be processed. The permit returned must be kept
+    /// while the job is being processed.
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.0 {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+}
+
 /// An organization worker is in charge of processing jobs for a given
 /// organization.
 struct OrgWorker {
@@ -175,39 +261,74 @@ struct OrgWorker {
     gh: DynGH,
     ghc: core::github::DynGH,
     services: HashMap<ServiceName, DynServiceHandler>,
+    notifier: DynNotifier,
+    limiter: JobsLimiter,
+    rate_limit_warning_threshold: i64,
+    validate_services_concurrency: usize,
+    validate_job_max_retries: usize,
+    validate_job_retry_backoff: Duration,
+    warm_cache: bool,
 }
 
 impl OrgWorker {
     /// Create a new organization worker instance.
+    #[allow(clippy::too_many_arguments)]
     fn new(
         db: DynDB,
         gh: DynGH,
         ghc: core::github::DynGH,
         services: HashMap<ServiceName, DynServiceHandler>,
+        notifier: DynNotifier,
+        limiter: JobsLimiter,
+        rate_limit_warning_threshold: i64,
+        validate_services_concurrency: usize,
+        validate_job_max_retries: usize,
+        validate_job_retry_backoff: Duration,
+        warm_cache: bool,
     ) -> Self {
         Self {
             db,
             gh,
             ghc,
             services,
+            notifier,
+            limiter,
+            rate_limit_warning_threshold,
+            validate_services_concurrency,
+            validate_job_max_retries,
+            validate_job_retry_backoff,
+            warm_cache,
         }
     }
 
-    /// Run organization worker.
+    /// Run organization worker. The services caches are warmed up for the
+    /// organization before processing any jobs (when enabled).
     fn run(
         self,
+        org: Organization,
         mut org_jobs_rx: mpsc::UnboundedReceiver<Job>,
         cancel_token: CancellationToken,
     ) -> JoinHandle<()> {
         tokio::spawn(async move {
+            if self.warm_cache {
+                tokio::select! {
+                    biased;
+
+                    () = cancel_token.cancelled() => return,
+                    () = self.warm_up(&org) => {},
+                }
+            }
+
             loop {
                 tokio::select! {
                     biased;
 
                     // Pick next job from the queue and process it
                     Some(job) = org_jobs_rx.recv() => {
+                        let _permit = self.limiter.acquire().await;
                         match job {
                             Job::Reconcile(input) => _ = self.handle_reconcile_job(input).await,
+                            Job::RetryFailed(input) => _ = self.handle_retry_failed_job(input).await,
                             Job::Validate(input) => _ = self.handle_validate_job(input).await,
                         }
                     }
@@ -219,6 +340,19 @@ impl OrgWorker {
         })
     }
 
+    /// Prefetch some information of the organization provided from the
+    /// services, so that the first reconciliation after startup is faster.
+    /// The warm up counts as a job for the jobs limiter.
+    #[instrument(fields(org = org.name), skip_all)]
+    async fn warm_up(&self, org: &Organization) {
+        let _permit = self.limiter.acquire().await;
+        for (service_name, service_handler) in &self.services {
+            if let Err(err) = service_handler.warm_up(org).await {
+                warn!(?err, service_name, "error warming up service cache");
+            }
+        }
+    }
+
     /// Reconcile job handler.
     #[instrument(fields(org = input.org.name), skip_all, err(Debug))]
     async fn handle_reconcile_job(&self, input: ReconcileInput) -> Result<()> {
@@ -227,10 +361,37 @@ impl OrgWorker {
 
         // Reconcile services state
         for (service_name, service_handler) in &self.services {
-            debug!(service_name, "reconciling state");
-            match service_handler.reconcile(&input.org).await {
-                Ok(service_changes_applied) => {
-                    changes_applied.insert(service_name, service_changes_applied);
+            // Get snapshot of the actual state when doing an incremental reconcile
+            // (or when it's needed to track invitations declined or pending removals)
+            let track_state =
+                input.org.max_declined_invitations.is_some() || input.org.removals_grace_period_hours > 0;
+            let snapshot = if input.incremental || track_state {
+                match self.db.get_state_snapshot(&input.org.name, service_name).await {
+                    Ok(snapshot) => snapshot,
+                    Err(err) => {
+                        error!(?err, service_name, "error getting state snapshot from database");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            let incremental = input.incremental && snapshot.is_some();
+            debug!(service_name, incremental, "reconciling state");
+            match service_handler.reconcile(&input.org, snapshot.as_ref(), incremental).await {
+                Ok(output) => {
+                    // Keep snapshot of the actual state for the next reconcile
+                    if input.org.incremental_reconcile || track_state {
+                        if let Some(snapshot) = &output.snapshot {
+                            if let Err(err) =
+                                self.db.update_state_snapshot(&input.org.name, service_name, snapshot).await
+                            {
+                                error!(?err, service_name, "error updating state snapshot in database");
+                            }
+                        }
+                    }
+                    changes_applied.insert(service_name, output.changes_applied);
                 }
                 Err(err) => {
                     errors.insert(service_name, err);
@@ -238,118 +399,269 @@ impl OrgWorker {
             }
         }
 
-        // Register changes applied during reconciliation in database
+        // Register changes applied during reconciliation in database. The
+        // reconciliation is registered even when no changes were needed, so
+        // that there is a record of every run.
         if let Err(err) = self.db.register_reconciliation(&input, &changes_applied, &errors).await {
             error!(?err, "error registering reconciliation in database");
         }
 
+        // Notify reconciliation events to the organization's webhook (if any).
+        // Observe-only organizations are not notified, as no changes were
+        // actually applied.
+        if input.org.reconciliation_webhook.is_some() && !input.org.observe_only {
+            for event in ReconciliationEvent::new_from_reconciliation(
+                &input.org.name,
+                &changes_applied,
+                &errors,
+                OffsetDateTime::now_utc(),
+            ) {
+                if let Err(err) = self.notifier.notify_reconciliation(&input.org, &event).await {
+                    error!(
+                        ?err,
+                        service = event.service,
+                        "error notifying reconciliation event"
+                    );
+                }
+            }
+        }
+
         // Post reconciliation completed comment if the job was created from a PR
+        let ctx = Ctx::from(&input.org);
         if let Some(pr_number) = input.pr_number {
-            let ctx = Ctx::from(&input.org);
-            let comment_body = tmpl::ReconciliationCompleted::new(&changes_applied, &errors).render()?;
+            let comment_body =
+                tmpl::ReconciliationCompleted::new(&changes_applied, &errors, input.org.observe_only)
+                    .render()?;
             if let Err(err) = self.gh.post_comment(&ctx, pr_number, &comment_body).await {
                 error!(?err, "error posting reconciliation comment");
             }
         }
 
+        // Log GitHub API rate limit headroom after the reconciliation
+        match self.gh.get_rate_limit(&ctx).await {
+            Ok(rate_limit) => {
+                if rate_limit.remaining < self.rate_limit_warning_threshold {
+                    warn!(
+                        limit = rate_limit.limit,
+                        remaining = rate_limit.remaining,
+                        threshold = self.rate_limit_warning_threshold,
+                        "github api rate limit headroom below threshold"
+                    );
+                } else {
+                    debug!(
+                        limit = rate_limit.limit,
+                        remaining = rate_limit.remaining,
+                        "github api rate limit headroom"
+                    );
+                }
+            }
+            Err(err) => error!(?err, "error getting github api rate limit"),
+        }
+
         // Log changes applied and errors
-        for (service_name, error) in &errors {
-            debug!(?error, service = service_name, "reconciliation failed");
+        log_changes_applied(&changes_applied, &errors)
+    }
+
+    /// Retry failed job handler.
+    #[instrument(fields(org = input.org.name), skip_all, err(Debug))]
+    async fn handle_retry_failed_job(&self, input: RetryFailedInput) -> Result<()> {
+        let mut changes_applied: HashMap<ServiceName, ChangesApplied> = HashMap::new();
+        let mut errors: HashMap<ServiceName, Error> = HashMap::new();
+
+        // Apply again the changes that failed in the latest reconciliation
+        for (service_name, service_handler) in &self.services {
+            let failed = match self.db.get_failed_changes(&input.org.name, service_name).await {
+                Ok(failed) => failed,
+                Err(err) => {
+                    errors.insert(service_name, err.context("error getting failed changes"));
+                    continue;
+                }
+            };
+            if failed.is_empty() {
+                continue;
+            }
+
+            debug!(service_name, failed = failed.len(), "retrying failed changes");
+            match service_handler.retry_failed(&input.org, &failed).await {
+                Ok(service_changes_applied) => {
+                    changes_applied.insert(service_name, service_changes_applied);
+                }
+                Err(err) => {
+                    errors.insert(service_name, err);
+                }
+            }
         }
-        for (service_name, changes_applied) in &changes_applied {
-            for entry in changes_applied {
-                let msg = if entry.error.is_none() {
-                    "change applied"
-                } else {
-                    "something went wrong applying change"
-                };
-                let details = entry.change.details();
-                debug!(
-                    service = service_name,
-                    kind = details.kind,
-                    extra = serde_json::to_string(&details.extra)?,
-                    error = entry.error,
-                    "{msg}"
-                );
+
+        // Register changes applied in database, so that they are available
+        // in the audit tool as any other reconciliation
+        if !changes_applied.is_empty() || !errors.is_empty() {
+            let reconcile_input = ReconcileInput {
+                org: input.org.clone(),
+                ..Default::default()
+            };
+            if let Err(err) =
+                self.db.register_reconciliation(&reconcile_input, &changes_applied, &errors).await
+            {
+                error!(?err, "error registering reconciliation in database");
             }
         }
 
-        Ok(())
+        // Log changes applied and errors
+        log_changes_applied(&changes_applied, &errors)
     }
 
     /// Validate job handler.
     #[instrument(fields(org = input.org.name, pr_number = input.pr_number), skip_all, err(Debug))]
     async fn handle_validate_job(&self, input: ValidateInput) -> Result<()> {
-        let mut merr = MultiError::new(None);
-
-        // Prepare head configuration source
+        // Prepare head configuration source (the changes proposed are always
+        // read from the PR branch, even when the organization's configuration
+        // is deployed from a different source)
         let head_src = Source {
             inst_id: Some(input.org.installation_id),
             owner: input.pr_head_owner.unwrap_or(input.org.name.clone()),
             repo: input.pr_head_repo.unwrap_or(input.org.repository.clone()),
             ref_: input.pr_head_ref,
+            location: None,
         };
 
-        // Directory configuration validation
-        let directory_changes =
-            match Directory::get_changes_summary(self.ghc.clone(), &input.org, &head_src).await {
-                Ok(changes) => changes,
-                Err(err) => {
-                    merr.push(err);
-                    ChangesSummary {
-                        changes: vec![],
-                        base_ref_config_status: BaseRefConfigStatus::Unknown,
-                    }
-                }
-            };
+        // Get the changes summaries, retrying when the validation fails due
+        // to transient GitHub errors (a failure is only reported once the
+        // retries have been exhausted). Validation checks exempted by the PR
+        // labels are reported as warnings instead of failing.
+        let exemptions = input.org.active_validation_exemptions(&input.pr_labels);
+        let result = retry_on_transient_errors(
+            self.validate_job_max_retries,
+            self.validate_job_retry_backoff,
+            || self.get_changes_summaries(&input.org, &head_src, input.pr_created_by.as_ref(), &exemptions),
+        )
+        .await;
 
-        // Services configuration validation
-        let mut services_changes: HashMap<ServiceName, ChangesSummary> = HashMap::new();
-        if !merr.contains_errors() {
-            for (service_name, service_handler) in &self.services {
-                match service_handler.get_changes_summary(&input.org, &head_src).await {
-                    Ok(changes) => {
-                        services_changes.insert(service_name, changes);
-                    }
-                    Err(err) => merr.push(err),
-                }
-            }
-        }
-
-        // Post validation completed comment and create check run
-        let errors_found = merr.contains_errors();
-        let err = Error::from(merr);
+        // Post validation completed comment and create check run (including
+        // the legacy configuration format deprecation notice, if applicable)
         let ctx = Ctx::from(&input.org);
-        let (comment_body, check_body) = if errors_found {
-            let comment_body = tmpl::ValidationFailed::new(&err).render()?;
-            let check_body = github::new_checks_create_request(
-                input.pr_head_sha,
-                Some(JobStatus::Completed),
-                Some(ChecksCreateRequestConclusion::Failure),
-                "The configuration changes proposed are not valid",
-            );
-            (comment_body, check_body)
-        } else {
-            let comment_body =
-                tmpl::ValidationSucceeded::new(&directory_changes, &services_changes).render()?;
-            let check_body = github::new_checks_create_request(
-                input.pr_head_sha,
-                Some(JobStatus::Completed),
-                Some(ChecksCreateRequestConclusion::Success),
-                "The configuration changes proposed are valid",
-            );
-            (comment_body, check_body)
+        let notice = input.org.legacy.deprecation_notice();
+        let (comment_body, check_body) = match &result {
+            Err(err) => {
+                let comment_body = tmpl::ValidationFailed::new(err).with_notice(notice).render()?;
+                let check_body = github::new_checks_create_request(
+                    input.pr_head_sha,
+                    Some(JobStatus::Completed),
+                    Some(ChecksCreateRequestConclusion::Failure),
+                    "The configuration changes proposed are not valid",
+                );
+                (comment_body, check_body)
+            }
+            Ok((directory_changes, services_changes)) => {
+                let comment_body = tmpl::ValidationSucceeded::new(
+                    directory_changes,
+                    services_changes,
+                    input.org.high_risk_changes_reviewers.as_deref().unwrap_or_default(),
+                )
+                .with_notice(notice)
+                .render()?;
+                let check_body = github::new_checks_create_request(
+                    input.pr_head_sha,
+                    Some(JobStatus::Completed),
+                    Some(ChecksCreateRequestConclusion::Success),
+                    "The configuration changes proposed are valid",
+                );
+                (comment_body, check_body)
+            }
         };
         self.gh.post_comment(&ctx, input.pr_number, &comment_body).await?;
         self.gh.create_check_run(&ctx, &check_body).await?;
 
-        if errors_found {
-            return Err(err);
+        result.map(|_| ())
+    }
+
+    /// Get the summary of the changes proposed in the head configuration
+    /// source provided, for the directory and for each of the services.
+    async fn get_changes_summaries(
+        &self,
+        org: &Organization,
+        head_src: &Source,
+        pr_author: Option<&UserName>,
+        exemptions: &[ValidationExemption],
+    ) -> Result<(ChangesSummary, BTreeMap<ServiceName, ChangesSummary>)> {
+        // Directory configuration validation
+        let directory_changes = Directory::get_changes_summary(self.ghc.clone(), org, head_src)
+            .await
+            .map_err(MultiError::from)?;
+
+        // Services configuration validation (services are validated
+        // concurrently, as each of them builds its full head and base states)
+        let (services_changes, errors) = get_services_changes_summaries(
+            &self.services,
+            org,
+            head_src,
+            pr_author,
+            exemptions,
+            self.validate_services_concurrency,
+        )
+        .await;
+        if !errors.is_empty() {
+            let mut merr = MultiError::new(None);
+            for err in errors {
+                merr.push(err);
+            }
+            return Err(merr.into());
         }
-        Ok(())
+
+        Ok((directory_changes, services_changes))
     }
 }
 
+/// Run the operation provided, retrying it with exponential backoff while it
+/// fails due to transient GitHub errors, up to the maximum number of retries
+/// given. The result of the last attempt is returned.
+async fn retry_on_transient_errors<T, F, Fut>(max_retries: usize, backoff: Duration, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut retries = 0;
+    let mut delay = backoff;
+    loop {
+        match op().await {
+            Err(err) if retries < max_retries && is_transient_error(&err) => {
+                retries += 1;
+                warn!(?err, retries, ?delay, "transient error found, retrying");
+                sleep(delay).await;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Log the changes applied and the errors found in a reconciliation.
+fn log_changes_applied(
+    changes_applied: &HashMap<ServiceName, ChangesApplied>,
+    errors: &HashMap<ServiceName, Error>,
+) -> Result<()> {
+    for (service_name, error) in errors {
+        debug!(?error, service = service_name, "reconciliation failed");
+    }
+    for (service_name, changes_applied) in changes_applied {
+        for entry in changes_applied {
+            let msg = if entry.error.is_none() {
+                "change applied"
+            } else {
+                "something went wrong applying change"
+            };
+            let details = entry.change.details();
+            debug!(
+                service = service_name,
+                kind = details.kind,
+                extra = serde_json::to_string(&details.extra)?,
+                error = entry.error,
+                "{msg}"
+            );
+        }
+    }
+    Ok(())
+}
+
 /// A jobs scheduler is in charge of scheduling the execution of some jobs
 /// periodically.
 pub(crate) fn scheduler(
@@ -363,6 +675,14 @@ pub(crate) fn scheduler(
         let reconcile_frequency = time::Duration::from_secs(RECONCILE_FREQUENCY);
         let mut reconcile = time::interval(reconcile_frequency);
         reconcile.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut reconcile_count: u64 = 0;
+        let mut deferred_reconcile_check =
+            time::interval(time::Duration::from_secs(DEFERRED_RECONCILE_CHECK_FREQUENCY));
+        deferred_reconcile_check.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        // Reconcile jobs deferred until the organization's reconcile window
+        // opens (value indicates if the reconcile can be incremental)
+        let mut deferred: HashMap<String, bool> = HashMap::new();
 
         loop {
             tokio::select! {
@@ -373,9 +693,29 @@ pub(crate) fn scheduler(
 
                 // Schedule reconcile job for each of the registered organizations
                 _ = reconcile.tick() => {
+                    // A full reconcile is done periodically (and always on the
+                    // first run) as a safety net, as some changes may not be
+                    // noticed by incremental reconciliations
+                    let full = reconcile_count % FULL_RECONCILE_FREQUENCY == 0;
+                    reconcile_count += 1;
+
                     for org in &orgs {
+                        let incremental = org.incremental_reconcile && !full;
+
+                        // Defer reconcile if we are outside the reconcile window
+                        if !in_reconcile_window(org, OffsetDateTime::now_utc()) {
+                            debug!(org = org.name, "reconcile deferred until reconcile window opens");
+                            deferred
+                                .entry(org.name.clone())
+                                .and_modify(|deferred_incremental| *deferred_incremental &= incremental)
+                                .or_insert(incremental);
+                            continue;
+                        }
+                        let incremental = incremental && deferred.remove(&org.name).unwrap_or(true);
+
                         _ = jobs_tx.send(Job::Reconcile(ReconcileInput{
                             org: org.clone(),
+                            incremental,
                             ..Default::default()
                         }));
 
@@ -383,9 +723,303 @@ pub(crate) fn scheduler(
                         sleep(Duration::from_secs(30)).await;
                     }
                 },
+
+                // Schedule deferred reconcile jobs whose reconcile window has opened
+                _ = deferred_reconcile_check.tick() => {
+                    let now = OffsetDateTime::now_utc();
+                    for org in &orgs {
+                        if !deferred.contains_key(&org.name) || !in_reconcile_window(org, now) {
+                            continue;
+                        }
+                        let incremental = deferred.remove(&org.name).unwrap_or_default();
+                        _ = jobs_tx.send(Job::Reconcile(ReconcileInput{
+                            org: org.clone(),
+                            incremental,
+                            ..Default::default()
+                        }));
+                    }
+                },
             }
         }
     });
 
     future::join_all(vec![scheduler])
 }
+
+/// Get the changes summary of the services provided, processing up to the
+/// number of services given concurrently. Summaries and errors are returned
+/// sorted by service name, so that they are always reported in the same order.
+async fn get_services_changes_summaries(
+    services: &HashMap<ServiceName, DynServiceHandler>,
+    org: &Organization,
+    head_src: &Source,
+    pr_author: Option<&UserName>,
+    exemptions: &[ValidationExemption],
+    concurrency: usize,
+) -> (BTreeMap<ServiceName, ChangesSummary>, Vec<Error>) {
+    let mut services: Vec<(&ServiceName, &DynServiceHandler)> = services.iter().collect();
+    services.sort_by_key(|(service_name, _)| **service_name);
+
+    let results: Vec<(ServiceName, Result<ChangesSummary>)> = stream::iter(services)
+        .map(|(service_name, service_handler)| async move {
+            let summary = service_handler.get_changes_summary(org, head_src, pr_author, exemptions).await;
+            (*service_name, summary)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut services_changes = BTreeMap::new();
+    let mut errors = vec![];
+    for (service_name, result) in results {
+        match result {
+            Ok(summary) => {
+                services_changes.insert(service_name, summary);
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    (services_changes, errors)
+}
+
+/// Check if periodic reconcile jobs can be run at the moment provided for the
+/// organization given, based on its reconcile window (if any).
+fn in_reconcile_window(org: &Organization, moment: OffsetDateTime) -> bool {
+    let Some(reconcile_window) = &org.reconcile_window else {
+        return true;
+    };
+    match reconcile_window.contains(moment) {
+        Ok(in_window) => in_window,
+        Err(err) => {
+            error!(
+                ?err,
+                org = org.name,
+                "error checking reconcile window, ignoring it"
+            );
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use anyhow::format_err;
+    use clowarden_core::services::{BaseRefConfigStatus, MockServiceHandler, ReconcileOutput};
+
+    use super::*;
+    use crate::{db::MockDB, github::MockGH, notifier::MockNotifier};
+
+    #[tokio::test]
+    async fn jobs_limiter_bounds_concurrency() {
+        let limiter = JobsLimiter::new(Some(2));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<JoinHandle<()>> = (0..6)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let running = running.clone();
+                let max_running = max_running.clone();
+                tokio::spawn(async move {
+                    let _permit = limiter.acquire().await;
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now_running, Ordering::SeqCst);
+                    sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        future::join_all(tasks).await;
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn jobs_limiter_unlimited() {
+        let limiter = JobsLimiter::new(None);
+        let permits = future::join_all((0..10).map(|_| limiter.acquire())).await;
+        assert!(permits.iter().all(Option::is_none));
+    }
+
+    #[tokio::test]
+    async fn get_services_changes_summaries_collects_summaries_and_errors() {
+        let mut svc1 = MockServiceHandler::new();
+        svc1.expect_get_changes_summary().times(1).returning(|_, _, _, _| {
+            Ok(ChangesSummary {
+                changes: vec![],
+                base_ref_config_status: BaseRefConfigStatus::Valid,
+                warnings: vec![],
+                users_impact: vec![],
+            })
+        });
+        let mut svc2 = MockServiceHandler::new();
+        svc2.expect_get_changes_summary()
+            .times(1)
+            .returning(|_, _, _, _| Err(format_err!("error getting changes summary")));
+        let services: HashMap<ServiceName, DynServiceHandler> = HashMap::from([
+            ("svc2", Arc::new(svc2) as DynServiceHandler),
+            ("svc1", Arc::new(svc1) as DynServiceHandler),
+        ]);
+        let org = Organization::default();
+
+        let (services_changes, errors) =
+            get_services_changes_summaries(&services, &org, &Source::from(&org), None, &[], 2).await;
+        assert_eq!(services_changes.keys().copied().collect::<Vec<_>>(), vec!["svc1"]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "error getting changes summary");
+    }
+
+    #[tokio::test]
+    async fn retry_on_transient_errors_succeeds_on_retry() {
+        let attempts = AtomicUsize::new(0);
+        let result = retry_on_transient_errors(3, Duration::ZERO, || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(format_err!("code: 502 Bad Gateway, error: upstream unavailable"));
+            }
+            Ok("summary")
+        })
+        .await;
+        assert_eq!(result.unwrap(), "summary");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn retry_on_transient_errors_exhausted() {
+        let attempts = AtomicUsize::new(0);
+        let result: Result<()> = retry_on_transient_errors(2, Duration::ZERO, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(format_err!("API rate limit exceeded"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_on_transient_errors_config_errors_not_retried() {
+        let attempts = AtomicUsize::new(0);
+        let result: Result<()> = retry_on_transient_errors(3, Duration::ZERO, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(MultiError::from(format_err!("team[team1]: invalid maintainer")).into())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn reconcile_without_changes_is_registered() {
+        let mut db = MockDB::new();
+        db.expect_register_reconciliation()
+            .withf(|input, changes_applied, errors| {
+                input.org.name == "org1" && changes_applied["github"].is_empty() && errors.is_empty()
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let mut gh = MockGH::new();
+        gh.expect_get_rate_limit().returning(|_| Err(format_err!("rate limit not available")));
+        let mut service_handler = MockServiceHandler::new();
+        service_handler.expect_reconcile().times(1).returning(|_, _, _| {
+            Ok(ReconcileOutput {
+                changes_applied: vec![],
+                snapshot: None,
+            })
+        });
+        let services: HashMap<ServiceName, DynServiceHandler> =
+            HashMap::from([("github", Arc::new(service_handler) as DynServiceHandler)]);
+        let worker = OrgWorker::new(
+            Arc::new(db),
+            Arc::new(gh),
+            Arc::new(clowarden_core::github::MockGH::new()),
+            services,
+            Arc::new(MockNotifier::new()),
+            JobsLimiter::new(None),
+            0,
+            1,
+            0,
+            Duration::ZERO,
+            false,
+        );
+
+        let input = ReconcileInput {
+            org: Organization {
+                name: "org1".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        worker.handle_reconcile_job(input).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn org_worker_warms_up_services_caches_on_start() {
+        let (warm_up_tx, mut warm_up_rx) = mpsc::unbounded_channel();
+        let mut service_handler = MockServiceHandler::new();
+        service_handler.expect_warm_up().times(1).returning(move |org| {
+            _ = warm_up_tx.send(org.name.clone());
+            Ok(())
+        });
+        let services: HashMap<ServiceName, DynServiceHandler> =
+            HashMap::from([("github", Arc::new(service_handler) as DynServiceHandler)]);
+        let worker = OrgWorker::new(
+            Arc::new(MockDB::new()),
+            Arc::new(MockGH::new()),
+            Arc::new(clowarden_core::github::MockGH::new()),
+            services,
+            Arc::new(MockNotifier::new()),
+            JobsLimiter::new(Some(1)),
+            0,
+            1,
+            0,
+            Duration::ZERO,
+            true,
+        );
+
+        let org = Organization {
+            name: "org1".to_string(),
+            ..Default::default()
+        };
+        let (_org_jobs_tx, org_jobs_rx) = mpsc::unbounded_channel();
+        let cancel_token = CancellationToken::new();
+        let worker_handle = worker.run(org, org_jobs_rx, cancel_token.clone());
+
+        let warm_up = time::timeout(Duration::from_secs(5), warm_up_rx.recv())
+            .await
+            .expect("caches to be warmed up");
+        assert_eq!(warm_up, Some("org1".to_string()));
+        cancel_token.cancel();
+        worker_handle.await.unwrap();
+    }
+
+    #[test]
+    fn reconcile_input_actor_pr_merged() {
+        let input = ReconcileInput {
+            pr_number: Some(1),
+            pr_created_by: Some("user1".to_string()),
+            pr_merged_by: Some("user2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(input.actor(), "user2");
+    }
+
+    #[test]
+    fn reconcile_input_actor_pr_merger_not_available() {
+        let input = ReconcileInput {
+            pr_number: Some(1),
+            pr_created_by: Some("user1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(input.actor(), "user1");
+    }
+
+    #[test]
+    fn reconcile_input_actor_scheduled() {
+        let input = ReconcileInput {
+            incremental: true,
+            ..Default::default()
+        };
+        assert_eq!(input.actor(), SCHEDULER_ACTOR);
+    }
+}
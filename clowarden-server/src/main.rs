@@ -10,15 +10,17 @@ use db::DynDB;
 use deadpool_postgres::Runtime;
 use futures::future;
 use github::DynGH;
+use notifier::{DynNotifier, WebhookNotifier};
 use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
 use postgres_openssl::MakeTlsConnector;
 use tokio::{net::TcpListener, signal, sync::mpsc};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 use clowarden_core::{
     self as core,
+    github::Source,
     services::{self, DynServiceHandler, ServiceName},
 };
 
@@ -29,6 +31,7 @@ mod db;
 mod github;
 mod handlers;
 mod jobs;
+mod notifier;
 mod tmpl;
 
 #[derive(Debug, Parser)]
@@ -44,7 +47,7 @@ async fn main() -> Result<()> {
     let args = Args::parse();
 
     // Setup configuration
-    let cfg = Config::new(&args.config).context("error setting up configuration")?;
+    let mut cfg = Config::new(&args.config).context("error setting up configuration")?;
 
     // Setup logging
     if std::env::var_os("RUST_LOG").is_none() {
@@ -61,30 +64,76 @@ async fn main() -> Result<()> {
     builder.set_verify(SslVerifyMode::NONE);
     let connector = MakeTlsConnector::new(builder.build());
     let pool = cfg.db.create_pool(Some(Runtime::Tokio1), connector)?;
-    let db: DynDB = Arc::new(PgDB::new(pool));
+    let pg_db = Arc::new(PgDB::new(pool));
+    let db: DynDB = pg_db.clone();
 
     // Setup GitHub clients
     let gh_app = &cfg.server.github_app;
-    let gh: DynGH = Arc::new(github::GHApi::new(gh_app).context("error setting up github client")?);
+    let gh_client = &cfg.server.github_client;
+    let gh: DynGH =
+        Arc::new(github::GHApi::new(gh_app, gh_client).context("error setting up github client")?);
     let ghc: core::github::DynGH = Arc::new(
-        core::github::GHApi::new_with_app_creds(gh_app).context("error setting up core github client")?,
+        core::github::GHApi::new_with_app_creds(gh_app, gh_client)
+            .context("error setting up core github client")?,
     );
 
+    // Check the configuration source of each organization. Organizations with
+    // an invalid source are disabled instead of failing on each job, unless
+    // the check could not be completed due to a transient error
+    let mut orgs = vec![];
+    for org in cfg.organizations.take().unwrap_or_default() {
+        if let Err(err) = core::github::check_source_ref(&*ghc, &Source::from(&org)).await {
+            if !core::github::is_transient_error(&err) {
+                error!(
+                    ?err,
+                    org = org.name,
+                    "invalid configuration source, organization disabled"
+                );
+                continue;
+            }
+            warn!(?err, org = org.name, "error checking configuration source");
+        }
+        if org.legacy.deprecation_notice().is_some() {
+            warn!(
+                org = org.name,
+                "legacy configuration format is deprecated, please consider migrating to the native format"
+            );
+        }
+        orgs.push(org);
+    }
+    cfg.organizations = Some(orgs);
+
     // Setup services handlers
     let mut services: HashMap<ServiceName, DynServiceHandler> = HashMap::new();
     if cfg.services.github.enabled {
-        let svc = Arc::new(services::github::service::SvcApi::new_with_app_creds(gh_app)?);
+        let svc = Arc::new(services::github::service::SvcApi::new_with_app_creds(
+            gh_app, gh_client,
+        )?);
         services.insert(
             services::github::SERVICE_NAME,
-            Arc::new(services::github::Handler::new(ghc.clone(), svc)),
+            Arc::new(services::github::Handler::new(ghc.clone(), svc).with_checkpoint(pg_db.clone())),
         );
     }
 
+    // Setup reconciliation events notifier
+    let notifier: DynNotifier =
+        Arc::new(WebhookNotifier::new(gh_client).context("error setting up notifier")?);
+
     // Setup and launch jobs workers
     let orgs = cfg.organizations.clone().unwrap_or_default();
     let cancel_token = CancellationToken::new();
     let (jobs_tx, jobs_rx) = mpsc::unbounded_channel();
-    let jobs_handler = jobs::handler(&db, &gh, &ghc, &services, jobs_rx, cancel_token.clone(), &orgs);
+    let jobs_handler = jobs::handler(
+        &db,
+        &gh,
+        &ghc,
+        &services,
+        &notifier,
+        jobs_rx,
+        cancel_token.clone(),
+        &orgs,
+        gh_client,
+    );
     let jobs_scheduler = jobs::scheduler(jobs_tx.clone(), cancel_token.clone(), &orgs);
     let jobs_workers_done = future::join_all([jobs_handler, jobs_scheduler]);
 
@@ -0,0 +1,208 @@
+//! This module defines an abstraction layer used to notify external systems
+//! about the changes applied during reconciliations.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+#[cfg(test)]
+use mockall::automock;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use time::OffsetDateTime;
+
+use clowarden_core::{
+    cfg::{GitHubClient, Organization},
+    github::new_http_client,
+    services::{ChangesApplied, ServiceName},
+};
+
+/// Header used to provide the signature of the event payload (only set when
+/// a secret has been configured for the webhook).
+const SIGNATURE_HEADER: &str = "X-CLOWarden-Signature-256";
+
+/// Trait that defines some operations a Notifier implementation must support.
+#[async_trait]
+#[cfg_attr(test, automock)]
+pub(crate) trait Notifier {
+    /// Notify the reconciliation event provided to the organization's
+    /// reconciliation webhook. Nothing is sent when the organization has no
+    /// reconciliation webhook configured.
+    async fn notify_reconciliation(&self, org: &Organization, event: &ReconciliationEvent) -> Result<()>;
+}
+
+/// Type alias to represent a Notifier trait object.
+pub(crate) type DynNotifier = Arc<dyn Notifier + Send + Sync>;
+
+/// Notifier implementation that POSTs the events to a webhook. Requests that
+/// fail due to transient errors are retried with exponential backoff.
+pub(crate) struct WebhookNotifier {
+    http_client: ClientWithMiddleware,
+}
+
+impl WebhookNotifier {
+    /// Create a new WebhookNotifier instance.
+    pub(crate) fn new(gh_client: &GitHubClient) -> Result<Self> {
+        Ok(Self {
+            http_client: new_http_client(gh_client)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    /// [Notifier::notify_reconciliation]
+    async fn notify_reconciliation(&self, org: &Organization, event: &ReconciliationEvent) -> Result<()> {
+        let Some(webhook) = &org.reconciliation_webhook else {
+            return Ok(());
+        };
+
+        let body = serde_json::to_vec(event)?;
+        let mut request = self.http_client.post(&webhook.url).header("Content-Type", "application/json");
+        if let Some(secret) = &webhook.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret.as_bytes(), &body)?);
+        }
+        request.body(body).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Event describing the changes applied and the errors found when reconciling
+/// one of the services of an organization.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct ReconciliationEvent {
+    pub org: String,
+    pub service: ServiceName,
+    pub changes: Vec<ReconciliationEventChange>,
+    pub error: Option<String>,
+    pub timestamp: i64,
+}
+
+impl ReconciliationEvent {
+    /// Prepare the events for the changes applied and the errors found in a
+    /// reconciliation (one per service).
+    pub(crate) fn new_from_reconciliation(
+        org: &str,
+        changes_applied: &HashMap<ServiceName, ChangesApplied>,
+        errors: &HashMap<ServiceName, Error>,
+        timestamp: OffsetDateTime,
+    ) -> Vec<Self> {
+        let mut services: Vec<ServiceName> = changes_applied.keys().chain(errors.keys()).copied().collect();
+        services.sort_unstable();
+        services.dedup();
+
+        services
+            .into_iter()
+            .map(|service| ReconciliationEvent {
+                org: org.to_string(),
+                service,
+                changes: changes_applied
+                    .get(service)
+                    .into_iter()
+                    .flatten()
+                    .map(|entry| {
+                        let details = entry.change.details();
+                        ReconciliationEventChange {
+                            kind: details.kind,
+                            extra: details.extra,
+                            error: entry.error.clone(),
+                            applied_at: entry.applied_at.unix_timestamp(),
+                        }
+                    })
+                    .collect(),
+                error: errors.get(service).map(|err| format!("{err:#}")),
+                timestamp: timestamp.unix_timestamp(),
+            })
+            .collect()
+    }
+}
+
+/// Change applied during a reconciliation, as included in the events.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct ReconciliationEventChange {
+    pub kind: String,
+    pub extra: Value,
+    pub error: Option<String>,
+    pub applied_at: i64,
+}
+
+/// Sign the payload provided using the secret, returning the signature in
+/// the format expected in the signature header.
+fn sign(secret: &[u8], payload: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret)?;
+    mac.update(payload);
+    Ok(format!("sha256={}", hex::encode(mac.finalize().into_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::format_err;
+    use clowarden_core::services::{github::state::RepositoryChange, ChangeApplied};
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn new_from_reconciliation_events() {
+        let changes_applied = HashMap::from([(
+            "github",
+            vec![ChangeApplied {
+                change: Box::new(RepositoryChange::TeamRemoved(
+                    "repo1".to_string(),
+                    "team1".to_string(),
+                )),
+                error: Some("something went wrong".to_string()),
+                applied_at: datetime!(2024-05-01 10:00 UTC),
+            }],
+        )]);
+        let errors = HashMap::from([("other", format_err!("service unavailable"))]);
+
+        let events = ReconciliationEvent::new_from_reconciliation(
+            "org1",
+            &changes_applied,
+            &errors,
+            datetime!(2024-05-01 10:01 UTC),
+        );
+        assert_eq!(
+            serde_json::to_value(&events).unwrap(),
+            json!([
+                {
+                    "org": "org1",
+                    "service": "github",
+                    "changes": [
+                        {
+                            "kind": "repository-team-removed",
+                            "extra": {
+                                "repo_name": "repo1",
+                                "team_name": "team1"
+                            },
+                            "error": "something went wrong",
+                            "applied_at": 1_714_557_600
+                        }
+                    ],
+                    "error": null,
+                    "timestamp": 1_714_557_660
+                },
+                {
+                    "org": "org1",
+                    "service": "other",
+                    "changes": [],
+                    "error": "service unavailable",
+                    "timestamp": 1_714_557_660
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn sign_payload() {
+        assert_eq!(
+            sign(b"secret", b"{}").unwrap(),
+            "sha256=77325902caca812dc259733aacd046b73817372c777b8d95b402647474516e13"
+        );
+    }
+}
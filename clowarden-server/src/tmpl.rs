@@ -1,12 +1,16 @@
 //! This module defines the templates used to render the comments that
 //! CLOWarden will post to GitHub.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use anyhow::Error;
 use askama::Template;
 
-use clowarden_core::services::{ChangesApplied, ChangesSummary, ServiceName};
+use clowarden_core::services::{ChangeRisk, ChangesApplied, ChangesSummary, ServiceName, UserImpact};
+
+/// Number of changes above which the list of changes of a given category will
+/// be collapsed by default.
+const CHANGES_COLLAPSE_THRESHOLD: usize = 20;
 
 /// Template for the reconciliation completed comment.
 #[derive(Template)]
@@ -17,12 +21,17 @@ pub(crate) struct ReconciliationCompleted<'a> {
     some_changes_applied: bool,
     errors: &'a HashMap<ServiceName, Error>,
     errors_found: bool,
+    observe_only: bool,
+    changes_heading: &'static str,
 }
 
 impl<'a> ReconciliationCompleted<'a> {
+    /// Create a new ReconciliationCompleted instance. When the organization
+    /// is in observe-only mode, the changes provided were only planned.
     pub(crate) fn new(
         changes_applied: &'a HashMap<ServiceName, ChangesApplied>,
         errors: &'a HashMap<ServiceName, Error>,
+        observe_only: bool,
     ) -> Self {
         let services = changes_applied.keys().chain(errors.keys()).copied().collect();
         let some_changes_applied = (|| {
@@ -52,6 +61,12 @@ impl<'a> ReconciliationCompleted<'a> {
             some_changes_applied,
             errors,
             errors_found,
+            observe_only,
+            changes_heading: if observe_only {
+                "## Changes planned"
+            } else {
+                "## Changes applied"
+            },
         }
     }
 }
@@ -61,11 +76,19 @@ impl<'a> ReconciliationCompleted<'a> {
 #[template(path = "validation-failed.md")]
 pub(crate) struct ValidationFailed<'a> {
     err: &'a Error,
+    notice: Option<&'a str>,
 }
 
 impl<'a> ValidationFailed<'a> {
     pub(crate) fn new(err: &'a Error) -> Self {
-        Self { err }
+        Self { err, notice: None }
+    }
+
+    /// Add the informational notice provided to the comment.
+    #[must_use]
+    pub(crate) fn with_notice(mut self, notice: Option<&'a str>) -> Self {
+        self.notice = notice;
+        self
     }
 }
 
@@ -74,15 +97,20 @@ impl<'a> ValidationFailed<'a> {
 #[template(path = "validation-succeeded.md")]
 pub(crate) struct ValidationSucceeded<'a> {
     directory_changes: &'a ChangesSummary,
-    services_changes: &'a HashMap<ServiceName, ChangesSummary>,
+    services_changes: &'a BTreeMap<ServiceName, ChangesSummary>,
     changes_found: bool,
     invalid_base_ref_config_found: bool,
+    reviewers_mention: Option<String>,
+    users_impact: Vec<&'a UserImpact>,
+    warnings: Vec<&'a String>,
+    notice: Option<&'a str>,
 }
 
 impl<'a> ValidationSucceeded<'a> {
     pub(crate) fn new(
         directory_changes: &'a ChangesSummary,
-        services_changes: &'a HashMap<ServiceName, ChangesSummary>,
+        services_changes: &'a BTreeMap<ServiceName, ChangesSummary>,
+        high_risk_changes_reviewers: &[String],
     ) -> Self {
         let changes_found = (|| {
             if !directory_changes.changes.is_empty() {
@@ -106,19 +134,78 @@ impl<'a> ValidationSucceeded<'a> {
             }
             false
         })();
+        let high_risk_changes_found = directory_changes
+            .changes
+            .iter()
+            .chain(services_changes.values().flat_map(|summary| summary.changes.iter()))
+            .any(|change| change.risk() == ChangeRisk::High);
+        let reviewers_mention = if high_risk_changes_found && !high_risk_changes_reviewers.is_empty() {
+            let mentions: Vec<String> = high_risk_changes_reviewers
+                .iter()
+                .map(|reviewer| format!("@{}", reviewer.trim_start_matches('@')))
+                .collect();
+            Some(mentions.join(" "))
+        } else {
+            None
+        };
+        let users_impact = directory_changes
+            .users_impact
+            .iter()
+            .chain(services_changes.values().flat_map(|summary| summary.users_impact.iter()))
+            .collect();
+        let warnings = directory_changes
+            .warnings
+            .iter()
+            .chain(services_changes.values().flat_map(|summary| summary.warnings.iter()))
+            .collect();
         Self {
             directory_changes,
             services_changes,
             changes_found,
             invalid_base_ref_config_found,
+            reviewers_mention,
+            users_impact,
+            warnings,
+            notice: None,
         }
     }
+
+    /// Add the informational notice provided to the comment.
+    #[must_use]
+    pub(crate) fn with_notice(mut self, notice: Option<&'a str>) -> Self {
+        self.notice = notice;
+        self
+    }
 }
 
 mod filters {
     use anyhow::Error;
     use clowarden_core::multierror;
 
+    use super::CHANGES_COLLAPSE_THRESHOLD;
+
+    /// Template filter that returns the opening of the collapsible section
+    /// used to wrap long lists of changes (empty for short ones).
+    #[allow(clippy::trivially_copy_pass_by_ref, clippy::unnecessary_wraps)]
+    pub(crate) fn details_open(changes_count: &usize) -> askama::Result<String> {
+        if *changes_count <= CHANGES_COLLAPSE_THRESHOLD {
+            return Ok(String::new());
+        }
+        Ok(format!(
+            "\n<details>\n<summary>{changes_count} changes</summary>\n"
+        ))
+    }
+
+    /// Template filter that returns the closing of the collapsible section
+    /// used to wrap long lists of changes (empty for short ones).
+    #[allow(clippy::trivially_copy_pass_by_ref, clippy::unnecessary_wraps)]
+    pub(crate) fn details_close(changes_count: &usize) -> askama::Result<String> {
+        if *changes_count <= CHANGES_COLLAPSE_THRESHOLD {
+            return Ok(String::new());
+        }
+        Ok("\n\n</details>\n".to_string())
+    }
+
     /// Template filter that formats the error provided.
     pub(crate) fn format_error(err: &Error) -> askama::Result<String> {
         match multierror::format_error(err) {
@@ -127,3 +214,232 @@ mod filters {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clowarden_core::{
+        cfg::{Legacy, LEGACY_FORMAT_DEPRECATION_NOTICE},
+        services::{
+            github::state::{RepositoryChange, Role},
+            AccessChange, BaseRefConfigStatus, ChangeApplied, DynChange,
+        },
+    };
+
+    use super::*;
+
+    fn changes_summary(changes: Vec<DynChange>) -> ChangesSummary {
+        ChangesSummary {
+            changes,
+            base_ref_config_status: BaseRefConfigStatus::Valid,
+            warnings: vec![],
+            users_impact: vec![],
+        }
+    }
+
+    fn collaborator_added(user_name: &str, role: Role) -> DynChange {
+        Box::new(RepositoryChange::CollaboratorAdded(
+            "repo1".to_string(),
+            user_name.to_string(),
+            role,
+        ))
+    }
+
+    fn change_applied(change: DynChange) -> ChangeApplied {
+        ChangeApplied {
+            change,
+            error: None,
+            applied_at: time::OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn validation_succeeded_high_risk_changes_mention_reviewers_once() {
+        let directory_changes = changes_summary(vec![]);
+        let services_changes = BTreeMap::from([(
+            "github",
+            changes_summary(vec![
+                collaborator_added("user1", Role::Admin),
+                collaborator_added("user2", Role::Admin),
+            ]),
+        )]);
+        let reviewers = vec!["user3".to_string(), "@org/security-team".to_string()];
+
+        let output = ValidationSucceeded::new(&directory_changes, &services_changes, &reviewers)
+            .render()
+            .unwrap();
+        let golden = include_str!("testdata/validation-succeeded-reviewers-mention.golden");
+        assert_eq!(output, golden.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn validation_succeeded_low_risk_changes_do_not_mention_reviewers() {
+        let directory_changes = changes_summary(vec![]);
+        let services_changes = BTreeMap::from([(
+            "github",
+            changes_summary(vec![
+                collaborator_added("user1", Role::Write),
+                collaborator_added("user2", Role::Write),
+            ]),
+        )]);
+        let reviewers = vec!["user3".to_string(), "@org/security-team".to_string()];
+
+        let output = ValidationSucceeded::new(&directory_changes, &services_changes, &reviewers)
+            .render()
+            .unwrap();
+        let golden = include_str!("testdata/validation-succeeded-no-reviewers-mention.golden");
+        assert_eq!(output, golden.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn validation_succeeded_warnings() {
+        let directory_changes = changes_summary(vec![]);
+        let services_changes = BTreeMap::from([(
+            "github",
+            ChangesSummary {
+                warnings: vec![
+                    "repo[repo1]: warning1".to_string(),
+                    "repo[repo2]: warning2".to_string(),
+                ],
+                ..changes_summary(vec![collaborator_added("user1", Role::Write)])
+            },
+        )]);
+
+        let output = ValidationSucceeded::new(&directory_changes, &services_changes, &[]).render().unwrap();
+        let warnings = "#### ⚠️ Warnings\n\n- repo[repo1]: warning1\n- repo[repo2]: warning2\n\n***";
+        assert!(output.contains(warnings));
+    }
+
+    #[test]
+    fn validation_succeeded_legacy_deprecation_notice() {
+        let directory_changes = changes_summary(vec![]);
+        let services_changes = BTreeMap::new();
+        let notice = format!("#### ℹ️ Notice\n\n{LEGACY_FORMAT_DEPRECATION_NOTICE}\n\n***");
+
+        let legacy = Legacy {
+            enabled: true,
+            ..Default::default()
+        };
+        let output = ValidationSucceeded::new(&directory_changes, &services_changes, &[])
+            .with_notice(legacy.deprecation_notice())
+            .render()
+            .unwrap();
+        assert!(output.contains(&notice));
+
+        let output = ValidationSucceeded::new(&directory_changes, &services_changes, &[])
+            .with_notice(Legacy::default().deprecation_notice())
+            .render()
+            .unwrap();
+        assert!(!output.contains(&notice));
+    }
+
+    #[test]
+    fn validation_failed_legacy_deprecation_notice() {
+        let err = anyhow::format_err!("error1");
+        let notice = format!("ℹ️ {LEGACY_FORMAT_DEPRECATION_NOTICE}");
+
+        let legacy = Legacy {
+            enabled: true,
+            ..Default::default()
+        };
+        let output = ValidationFailed::new(&err).with_notice(legacy.deprecation_notice()).render().unwrap();
+        assert!(output.contains(&notice));
+
+        let output = ValidationFailed::new(&err)
+            .with_notice(Legacy::default().deprecation_notice())
+            .render()
+            .unwrap();
+        assert!(!output.contains(&notice));
+    }
+
+    #[test]
+    fn validation_succeeded_users_impact() {
+        let directory_changes = changes_summary(vec![]);
+        let access_change = |resource: &str, old_role: Option<Role>, new_role: Option<Role>| AccessChange {
+            resource: resource.to_string(),
+            old_role: old_role.map(|role| role.to_string()),
+            new_role: new_role.map(|role| role.to_string()),
+        };
+        let services_changes = BTreeMap::from([(
+            "github",
+            ChangesSummary {
+                users_impact: vec![
+                    UserImpact {
+                        user_name: "user1".to_string(),
+                        access_changes: vec![access_change("repo1", None, Some(Role::Write))],
+                    },
+                    UserImpact {
+                        user_name: "user2".to_string(),
+                        access_changes: vec![
+                            access_change("repo2", Some(Role::Admin), None),
+                            access_change("repo3", Some(Role::Read), Some(Role::Write)),
+                        ],
+                    },
+                ],
+                ..changes_summary(vec![collaborator_added("user1", Role::Write)])
+            },
+        )]);
+
+        let output = ValidationSucceeded::new(&directory_changes, &services_changes, &[]).render().unwrap();
+        let golden = include_str!("testdata/validation-succeeded-users-impact.golden");
+        assert_eq!(output, golden.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn validation_succeeded_many_changes_collapsed() {
+        let directory_changes = changes_summary(vec![]);
+        let services_changes = BTreeMap::from([(
+            "github",
+            changes_summary(
+                (1..=CHANGES_COLLAPSE_THRESHOLD + 1)
+                    .map(|i| collaborator_added(&format!("user{i}"), Role::Write))
+                    .collect(),
+            ),
+        )]);
+
+        let output = ValidationSucceeded::new(&directory_changes, &services_changes, &[]).render().unwrap();
+        let golden = include_str!("testdata/validation-succeeded-collapsed.golden");
+        assert_eq!(output, golden.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn reconciliation_completed_few_changes_expanded() {
+        let changes_applied = HashMap::from([(
+            "github",
+            (1..=2)
+                .map(|i| change_applied(collaborator_added(&format!("user{i}"), Role::Write)))
+                .collect(),
+        )]);
+
+        let output = ReconciliationCompleted::new(&changes_applied, &HashMap::new(), false).render().unwrap();
+        let golden = include_str!("testdata/reconciliation-completed-expanded.golden");
+        assert_eq!(output, golden.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn reconciliation_completed_many_changes_collapsed() {
+        let changes_applied = HashMap::from([(
+            "github",
+            (1..=CHANGES_COLLAPSE_THRESHOLD + 1)
+                .map(|i| change_applied(collaborator_added(&format!("user{i}"), Role::Write)))
+                .collect(),
+        )]);
+
+        let output = ReconciliationCompleted::new(&changes_applied, &HashMap::new(), false).render().unwrap();
+        let golden = include_str!("testdata/reconciliation-completed-collapsed.golden");
+        assert_eq!(output, golden.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn reconciliation_completed_observe_only() {
+        let changes_applied = HashMap::from([(
+            "github",
+            (1..=2)
+                .map(|i| change_applied(collaborator_added(&format!("user{i}"), Role::Write)))
+                .collect(),
+        )]);
+
+        let output = ReconciliationCompleted::new(&changes_applied, &HashMap::new(), true).render().unwrap();
+        let golden = include_str!("testdata/reconciliation-completed-observe-only.golden");
+        assert_eq!(output, golden.trim_end_matches('\n'));
+    }
+}
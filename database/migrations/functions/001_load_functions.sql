@@ -1,4 +1,6 @@
+{{ template "changes/get_repository_changes.sql" }}
 {{ template "changes/search_changes.sql" }}
+{{ template "reconciliations/get_last_successful_reconciliation.sql" }}
 
 ---- create above / drop below ----
 
@@ -0,0 +1,51 @@
+-- Returns all the changes applied to the repository provided in the given
+-- organization, ordered chronologically, in json format. Changes are first
+-- filtered by the repository keyword and then matched against the repository
+-- name included in their details.
+create or replace function get_repository_changes(p_organization text, p_repository text)
+returns json as $$
+    select coalesce(json_agg(json_strip_nulls(json_build_object(
+        'change_id', change_id,
+        'service', service,
+        'kind', change_kind,
+        'extra', change_extra,
+        'applied_at', change_applied_at,
+        'error', change_error,
+        'actor', change_actor,
+        'reconciliation', json_build_object(
+            'reconciliation_id', reconciliation_id,
+            'organization', organization,
+            'completed_at', reconciliation_completed_at,
+            'error', reconciliation_error,
+            'pr_number', pr_number,
+            'pr_created_by', pr_created_by,
+            'pr_merged_by', pr_merged_by,
+            'pr_merged_at', pr_merged_at,
+            'observe_only', observe_only
+        )
+    )) order by change_applied_at asc), '[]')
+    from (
+        select
+            c.change_id,
+            c.service,
+            c.kind as change_kind,
+            c.extra as change_extra,
+            extract(epoch from c.applied_at) as change_applied_at,
+            c.error as change_error,
+            c.actor as change_actor,
+            r.reconciliation_id,
+            r.organization,
+            extract(epoch from r.completed_at) as reconciliation_completed_at,
+            r.error as reconciliation_error,
+            r.pr_number,
+            r.pr_created_by,
+            r.pr_merged_by,
+            extract(epoch from r.pr_merged_at) as pr_merged_at,
+            r.observe_only
+        from change c
+        join reconciliation r using (reconciliation_id)
+        where r.organization = p_organization
+        and c.tsdoc @@ plainto_tsquery(p_repository)
+        and p_repository in (c.extra->>'repo_name', c.extra->'repo'->>'name')
+    ) rc;
+$$ language sql;
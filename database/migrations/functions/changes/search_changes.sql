@@ -58,6 +58,7 @@ begin
             c.extra as change_extra,
             extract(epoch from c.applied_at) as change_applied_at,
             c.error as change_error,
+            c.actor as change_actor,
             r.reconciliation_id,
             r.organization,
             extract(epoch from r.completed_at) as reconciliation_completed_at,
@@ -65,7 +66,8 @@ begin
             r.pr_number,
             r.pr_created_by,
             r.pr_merged_by,
-            extract(epoch from r.pr_merged_at) as pr_merged_at
+            extract(epoch from r.pr_merged_at) as pr_merged_at,
+            r.observe_only
         from change c
         join reconciliation r using (reconciliation_id)
         where
@@ -107,6 +109,7 @@ begin
                 'extra', change_extra,
                 'applied_at', change_applied_at,
                 'error', change_error,
+                'actor', change_actor,
                 'reconciliation', json_build_object(
                     'reconciliation_id', reconciliation_id,
                     'organization', organization,
@@ -115,7 +118,8 @@ begin
                     'pr_number', pr_number,
                     'pr_created_by', pr_created_by,
                     'pr_merged_by', pr_merged_by,
-                    'pr_merged_at', pr_merged_at
+                    'pr_merged_at', pr_merged_at,
+                    'observe_only', observe_only
                 )
             ))), '[]')
             from (
@@ -0,0 +1,24 @@
+-- Returns the latest reconciliation of the organization provided that
+-- completed without errors, in json format. Reconciliations where no changes
+-- were needed are included as well, so that it can be used to check that
+-- reconciliations are running. Null is returned when there is none.
+create or replace function get_last_successful_reconciliation(p_organization text)
+returns json as $$
+    select json_strip_nulls(json_build_object(
+        'reconciliation_id', r.reconciliation_id,
+        'organization', r.organization,
+        'completed_at', extract(epoch from r.completed_at),
+        'pr_number', r.pr_number,
+        'observe_only', r.observe_only,
+        'changes', (
+            select count(*)
+            from change c
+            where c.reconciliation_id = r.reconciliation_id
+        )
+    ))
+    from reconciliation r
+    where r.organization = p_organization
+    and r.error is null
+    order by r.completed_at desc
+    limit 1;
+$$ language sql;
@@ -0,0 +1,7 @@
+create table if not exists state_snapshot (
+    organization text not null check (organization <> ''),
+    service text not null check (service <> ''),
+    snapshot jsonb not null,
+    created_at timestamptz default current_timestamp not null,
+    primary key (organization, service)
+);
@@ -0,0 +1 @@
+alter table change add column actor text;
@@ -0,0 +1 @@
+alter table reconciliation add column observe_only boolean not null default false;
@@ -0,0 +1 @@
+create index reconciliation_organization_completed_at_idx on reconciliation (organization, completed_at);
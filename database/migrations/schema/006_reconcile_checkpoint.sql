@@ -0,0 +1,10 @@
+create table if not exists reconcile_checkpoint (
+    reconcile_checkpoint_id uuid primary key default gen_random_uuid(),
+    organization text not null check (organization <> ''),
+    service text not null check (service <> ''),
+    kind text not null check (kind <> ''),
+    extra jsonb,
+    applied_at timestamptz default clock_timestamp() not null
+);
+
+create index reconcile_checkpoint_organization_service_idx on reconcile_checkpoint (organization, service);
@@ -45,6 +45,10 @@ export const FILTERS: FilterSection[] = [
         { value: ChangeKind.TeamMaintainerRemoved, name: 'Maintainer removed' },
         { value: ChangeKind.TeamMemberAdded, name: 'Member added' },
         { value: ChangeKind.TeamMemberRemoved, name: 'Member removed' },
+        { value: ChangeKind.TeamNotificationsUpdated, name: 'Notifications updated' },
+        { value: ChangeKind.TeamChildTeamsReparented, name: 'Child teams reparented' },
+        { value: ChangeKind.TeamChildTeamsOrphaned, name: 'Child teams orphaned' },
+        { value: ChangeKind.TeamRetired, name: 'Team retired' },
       ],
       repository: [
         { value: ChangeKind.RepositoryAdded, name: 'Added' },
@@ -55,6 +59,44 @@ export const FILTERS: FilterSection[] = [
         { value: ChangeKind.RepositoryCollaboratorRemoved, name: 'Collaborator removed' },
         { value: ChangeKind.RepositoryCollaboratorRoleUpdated, name: 'Collaborator role updated' },
         { value: ChangeKind.RepositoryVisibilityUpdated, name: 'Visibility updated' },
+        { value: ChangeKind.RepositoryForkingPolicyUpdated, name: 'Forking policy updated' },
+        { value: ChangeKind.RepositoryTemplateStatusUpdated, name: 'Template status updated' },
+        { value: ChangeKind.RepositoryDiscussionsSettingsUpdated, name: 'Discussions settings updated' },
+        { value: ChangeKind.RepositoryMergeCommitSettingsUpdated, name: 'Merge commit settings updated' },
+        { value: ChangeKind.RepositoryCodeScanningUpdated, name: 'Code scanning updated' },
+        { value: ChangeKind.RepositoryActionsAllowListUpdated, name: 'Actions allow-list updated' },
+        { value: ChangeKind.RepositoryCodeownersUpdated, name: 'CODEOWNERS file updated' },
+        { value: ChangeKind.RepositoryDependabotConfigAdded, name: 'Dependabot config added' },
+        {
+          value: ChangeKind.RepositoryPrivateVulnerabilityReportingUpdated,
+          name: 'Private vulnerability reporting updated',
+        },
+        { value: ChangeKind.RepositoryTopicsUpdated, name: 'Topics updated' },
+        { value: ChangeKind.RepositoryWatcherAdded, name: 'Watcher added' },
+        { value: ChangeKind.RepositoryWatcherRemoved, name: 'Watcher removed' },
+        { value: ChangeKind.RepositoryArchivedChangeSkipped, name: 'Change skipped (archived)' },
+      ],
+      organization: [
+        { value: ChangeKind.OrganizationWebhookAdded, name: 'Webhook added' },
+        { value: ChangeKind.OrganizationWebhookUpdated, name: 'Webhook updated' },
+        { value: ChangeKind.OrganizationWebhookRemoved, name: 'Webhook removed' },
+        { value: ChangeKind.OrganizationCommunityHealthFileAdded, name: 'Community health file added' },
+        { value: ChangeKind.OrganizationCommunityHealthFileUpdated, name: 'Community health file updated' },
+        { value: ChangeKind.OrganizationSettingUpdated, name: 'Setting updated' },
+        { value: ChangeKind.OrganizationRequiredWorkflowAdded, name: 'Required workflow added' },
+        { value: ChangeKind.OrganizationRequiredWorkflowUpdated, name: 'Required workflow updated' },
+        { value: ChangeKind.OrganizationRequiredWorkflowRemoved, name: 'Required workflow removed' },
+        { value: ChangeKind.OrganizationSecretRepositoriesUpdated, name: 'Secret repositories updated' },
+        { value: ChangeKind.OrganizationRunnerGroupAdded, name: 'Runner group added' },
+        { value: ChangeKind.OrganizationRunnerGroupRepositoriesUpdated, name: 'Runner group repositories updated' },
+        { value: ChangeKind.OrganizationServiceAccountInvited, name: 'Service account invited' },
+        { value: ChangeKind.OrganizationServiceAccountDemoted, name: 'Service account demoted' },
+        { value: ChangeKind.OrganizationVerifiedDomainsGateUpdated, name: 'Verified domains gate updated' },
+        { value: ChangeKind.OrganizationTeamCreationPermissionUpdated, name: 'Team creation permission updated' },
+        { value: ChangeKind.OrganizationUserBlocked, name: 'User blocked' },
+        { value: ChangeKind.OrganizationUserUnblocked, name: 'User unblocked' },
+        { value: ChangeKind.OrganizationSecurityManagerTeamAdded, name: 'Security manager team added' },
+        { value: ChangeKind.OrganizationSecurityManagerTeamRemoved, name: 'Security manager team removed' },
       ],
     },
   },
@@ -514,6 +514,41 @@ const Audit = () => {
                                                   {change.extra.team_name}
                                                 </div>
                                               );
+                                            case ChangeKind.TeamChildTeamsReparented:
+                                            case ChangeKind.TeamChildTeamsOrphaned:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Team:</small>{' '}
+                                                    {change.extra.team_name}
+                                                  </div>
+                                                  <div
+                                                    className={`ms-3 text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Child teams:</small>{' '}
+                                                    {change.extra.child_teams.join(', ')}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.TeamRetired:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Team:</small>{' '}
+                                                    {change.extra.team_name}
+                                                  </div>
+                                                  <div
+                                                    className={`ms-3 text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Renamed to:</small>{' '}
+                                                    {change.extra.new_team_name}
+                                                  </div>
+                                                </>
+                                              );
                                             case ChangeKind.TeamMaintainerAdded:
                                             case ChangeKind.TeamMaintainerRemoved:
                                             case ChangeKind.TeamMemberAdded:
@@ -534,6 +569,21 @@ const Audit = () => {
                                                   </div>
                                                 </>
                                               );
+                                            case ChangeKind.TeamNotificationsUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Team:</small>{' '}
+                                                    {change.extra.team_name}
+                                                  </div>
+                                                  <div className="ms-3 text-nowrap">
+                                                    <small className="text-uppercase text-muted">Notifications:</small>{' '}
+                                                    {change.extra.notifications}
+                                                  </div>
+                                                </>
+                                              );
                                             case ChangeKind.RepositoryAdded:
                                               return (
                                                 <>
@@ -714,6 +764,307 @@ const Audit = () => {
                                                   </div>
                                                 </>
                                               );
+                                            case ChangeKind.RepositoryForkingPolicyUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div className="ms-3 text-nowrap">
+                                                    <small className="text-uppercase text-muted">Forking:</small>{' '}
+                                                    {change.extra.allow_forking ? 'allowed' : 'not allowed'}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryTemplateStatusUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div className="ms-3 text-nowrap">
+                                                    <small className="text-uppercase text-muted">Template:</small>{' '}
+                                                    {change.extra.is_template ? 'enabled' : 'disabled'}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryDiscussionsSettingsUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div
+                                                    className={`ms-3 text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Discussions:</small>{' '}
+                                                    {change.extra.discussions.enabled ? 'enabled' : 'disabled'}
+                                                    {change.extra.discussions.categories &&
+                                                      change.extra.discussions.categories.length > 0 && (
+                                                        <> ({change.extra.discussions.categories.join(', ')})</>
+                                                      )}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryMergeCommitSettingsUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  {change.extra.settings.squash_merge_commit_title && (
+                                                    <div className="ms-3 text-nowrap">
+                                                      <small className="text-uppercase text-muted">Squash title:</small>{' '}
+                                                      {change.extra.settings.squash_merge_commit_title}
+                                                    </div>
+                                                  )}
+                                                  {change.extra.settings.squash_merge_commit_message && (
+                                                    <div className="ms-3 text-nowrap">
+                                                      <small className="text-uppercase text-muted">Squash message:</small>{' '}
+                                                      {change.extra.settings.squash_merge_commit_message}
+                                                    </div>
+                                                  )}
+                                                  {change.extra.settings.merge_commit_title && (
+                                                    <div className="ms-3 text-nowrap">
+                                                      <small className="text-uppercase text-muted">Merge title:</small>{' '}
+                                                      {change.extra.settings.merge_commit_title}
+                                                    </div>
+                                                  )}
+                                                  {change.extra.settings.merge_commit_message && (
+                                                    <div className="ms-3 text-nowrap">
+                                                      <small className="text-uppercase text-muted">Merge message:</small>{' '}
+                                                      {change.extra.settings.merge_commit_message}
+                                                    </div>
+                                                  )}
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryCodeScanningUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div className="ms-3 text-nowrap">
+                                                    <small className="text-uppercase text-muted">Code scanning:</small>{' '}
+                                                    {change.extra.code_scanning.enabled ? 'enabled' : 'disabled'}
+                                                    {change.extra.code_scanning.enabled && change.extra.code_scanning.query_suite && (
+                                                      <> ({change.extra.code_scanning.query_suite})</>
+                                                    )}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryActionsAllowListUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div className="ms-3 text-nowrap">
+                                                    <small className="text-uppercase text-muted">Actions allow-list:</small>{' '}
+                                                    {change.extra.allow_list.enabled ? 'enabled' : 'disabled'}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryCodeownersUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div className="ms-3 text-nowrap">
+                                                    <small className="text-uppercase text-muted">Owners:</small>{' '}
+                                                    {change.extra.owners.length}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryDependabotConfigAdded:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                  {change.extra.repo_name}
+                                                </div>
+                                              );
+                                            case ChangeKind.RepositoryPrivateVulnerabilityReportingUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div className="ms-3 text-nowrap">
+                                                    <small className="text-uppercase text-muted">Vulnerability reporting:</small>{' '}
+                                                    {change.extra.enabled ? 'enabled' : 'disabled'}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryTopicsUpdated:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div
+                                                    className={`ms-3 text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Topics:</small>{' '}
+                                                    {change.extra.topics.length > 0 ? change.extra.topics.join(', ') : '-'}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryWatcherAdded:
+                                            case ChangeKind.RepositoryWatcherRemoved:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div
+                                                    className={`ms-3 text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Watcher:</small>{' '}
+                                                    {change.extra.user_name}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.RepositoryArchivedChangeSkipped:
+                                              return (
+                                                <>
+                                                  <div
+                                                    className={`text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Repo:</small>{' '}
+                                                    {change.extra.repo_name}
+                                                  </div>
+                                                  <div
+                                                    className={`ms-3 text-truncate text-nowrap ${styles.maxWidthTruncate2opts}`}
+                                                  >
+                                                    <small className="text-uppercase text-muted">Skipped:</small>{' '}
+                                                    {change.extra.change_kind}
+                                                  </div>
+                                                </>
+                                              );
+                                            case ChangeKind.OrganizationWebhookAdded:
+                                            case ChangeKind.OrganizationWebhookUpdated:
+                                            case ChangeKind.OrganizationWebhookRemoved:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Webhook:</small>{' '}
+                                                  {change.extra.url}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationCommunityHealthFileAdded:
+                                            case ChangeKind.OrganizationCommunityHealthFileUpdated:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">File:</small>{' '}
+                                                  {change.extra.path}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationSettingUpdated:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Setting:</small>{' '}
+                                                  {change.extra.setting}{' '}
+                                                  <small className="text-uppercase text-muted ms-2">Value:</small>{' '}
+                                                  {change.extra.value ? 'enabled' : 'disabled'}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationRequiredWorkflowAdded:
+                                            case ChangeKind.OrganizationRequiredWorkflowUpdated:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Workflow:</small>{' '}
+                                                  {change.extra.workflow.repository}/{change.extra.workflow.path}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationRequiredWorkflowRemoved:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Workflow:</small> {change.extra.name}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationSecretRepositoriesUpdated:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Secret:</small> {change.extra.name}
+                                                  <small className="text-uppercase text-muted ms-2">Repositories:</small>{' '}
+                                                  {change.extra.repositories.join(', ')}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationRunnerGroupAdded:
+                                            case ChangeKind.OrganizationRunnerGroupRepositoriesUpdated:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Runner group:</small>{' '}
+                                                  {change.extra.name}
+                                                  <small className="text-uppercase text-muted ms-2">Repositories:</small>{' '}
+                                                  {change.extra.repositories.join(', ')}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationServiceAccountInvited:
+                                            case ChangeKind.OrganizationServiceAccountDemoted:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Service account:</small> {change.extra.user_name}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationVerifiedDomainsGateUpdated:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Status:</small>{' '}
+                                                  {change.extra.enabled ? 'enabled' : 'disabled'}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationTeamCreationPermissionUpdated:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Teams can be created by:</small>{' '}
+                                                  {change.extra.permission === 'all_members' ? 'all members' : 'owners only'}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationUserBlocked:
+                                            case ChangeKind.OrganizationUserUnblocked:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">User:</small> {change.extra.user_name}
+                                                </div>
+                                              );
+                                            case ChangeKind.OrganizationSecurityManagerTeamAdded:
+                                            case ChangeKind.OrganizationSecurityManagerTeamRemoved:
+                                              return (
+                                                <div className="text-truncate text-nowrap">
+                                                  <small className="text-uppercase text-muted">Team:</small> {change.extra.team_name}
+                                                </div>
+                                              );
                                             default:
                                               return <></>;
                                           }
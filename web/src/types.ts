@@ -40,6 +40,10 @@ export enum ChangeKind {
   TeamMaintainerRemoved = 'team-maintainer-removed',
   TeamMemberAdded = 'team-member-added',
   TeamMemberRemoved = 'team-member-removed',
+  TeamNotificationsUpdated = 'team-notifications-updated',
+  TeamChildTeamsReparented = 'team-child-teams-reparented',
+  TeamChildTeamsOrphaned = 'team-child-teams-orphaned',
+  TeamRetired = 'team-retired',
   RepositoryAdded = 'repository-added',
   RepositoryTeamAdded = 'repository-team-added',
   RepositoryTeamRemoved = 'repository-team-removed',
@@ -48,6 +52,39 @@ export enum ChangeKind {
   RepositoryCollaboratorRemoved = 'repository-collaborator-removed',
   RepositoryCollaboratorRoleUpdated = 'repository-collaborator-role-updated',
   RepositoryVisibilityUpdated = 'repository-visibility-updated',
+  RepositoryForkingPolicyUpdated = 'repository-forking-policy-updated',
+  RepositoryTemplateStatusUpdated = 'repository-template-status-updated',
+  RepositoryDiscussionsSettingsUpdated = 'repository-discussions-settings-updated',
+  RepositoryMergeCommitSettingsUpdated = 'repository-merge-commit-settings-updated',
+  RepositoryCodeScanningUpdated = 'repository-code-scanning-updated',
+  RepositoryActionsAllowListUpdated = 'repository-actions-allow-list-updated',
+  RepositoryCodeownersUpdated = 'repository-codeowners-updated',
+  RepositoryDependabotConfigAdded = 'repository-dependabot-config-added',
+  RepositoryPrivateVulnerabilityReportingUpdated = 'repository-private-vulnerability-reporting-updated',
+  RepositoryTopicsUpdated = 'repository-topics-updated',
+  RepositoryWatcherAdded = 'repository-watcher-added',
+  RepositoryWatcherRemoved = 'repository-watcher-removed',
+  RepositoryArchivedChangeSkipped = 'repository-archived-change-skipped',
+  OrganizationWebhookAdded = 'organization-webhook-added',
+  OrganizationWebhookUpdated = 'organization-webhook-updated',
+  OrganizationWebhookRemoved = 'organization-webhook-removed',
+  OrganizationCommunityHealthFileAdded = 'organization-community-health-file-added',
+  OrganizationCommunityHealthFileUpdated = 'organization-community-health-file-updated',
+  OrganizationSettingUpdated = 'organization-setting-updated',
+  OrganizationRequiredWorkflowAdded = 'organization-required-workflow-added',
+  OrganizationRequiredWorkflowUpdated = 'organization-required-workflow-updated',
+  OrganizationRequiredWorkflowRemoved = 'organization-required-workflow-removed',
+  OrganizationSecretRepositoriesUpdated = 'organization-secret-repositories-updated',
+  OrganizationRunnerGroupAdded = 'organization-runner-group-added',
+  OrganizationRunnerGroupRepositoriesUpdated = 'organization-runner-group-repositories-updated',
+  OrganizationServiceAccountInvited = 'organization-service-account-invited',
+  OrganizationServiceAccountDemoted = 'organization-service-account-demoted',
+  OrganizationVerifiedDomainsGateUpdated = 'organization-verified-domains-gate-updated',
+  OrganizationTeamCreationPermissionUpdated = 'organization-team-creation-permission-updated',
+  OrganizationUserBlocked = 'organization-user-blocked',
+  OrganizationUserUnblocked = 'organization-user-unblocked',
+  OrganizationSecurityManagerTeamAdded = 'organization-security-manager-team-added',
+  OrganizationSecurityManagerTeamRemoved = 'organization-security-manager-team-removed',
 }
 
 export interface SortOption {
@@ -101,6 +138,7 @@ export interface Change {
   };
   applied_at: number;
   error?: string;
+  actor?: string;
   reconciliation: ReconciliationStatus;
 }
 
@@ -113,6 +151,7 @@ export interface ReconciliationStatus {
   pr_created_by: string;
   pr_merged_by: string;
   pr_merged_at: string;
+  observe_only: boolean;
 }
 
 export interface SearchTipItem {